@@ -0,0 +1,11 @@
+// the demo as a native activity. build with cargo-apk:
+//   cargo apk run --example android --features android
+// winit 0.27 drives android through ndk-glue, which calls main once the activity is created.
+// there are no command line arguments, so the demo starts with its defaults
+#[path = "../src/main.rs"]
+mod demo;
+
+#[cfg_attr(target_os = "android", ndk_glue::main(backtrace = "on"))]
+pub fn main() {
+    demo::main();
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use glam::{Quat, Vec3};
+use crate::transform::Transform;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+    MorphWeights,
+}
+
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pub node: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    // `width()` floats per keyframe: xyz for translations and scales, xyzw quaternions for
+    // rotations, one weight per target for morph weights.
+    // cubic splines store (in tangent, value, out tangent) per keyframe
+    pub values: Vec<f32>,
+}
+
+impl Channel {
+    fn stride(&self) -> usize {
+        match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.values.len() / (self.times.len() * self.stride()).max(1)
+    }
+
+    pub fn sample(&self, time: f32, out: &mut [f32]) {
+        let stride = self.stride();
+        let width = out.len();
+        let element = |key: usize, element: usize| &self.values[(key * stride + element) * width..][..width];
+        let value = |key: usize| element(key, stride / 2);
+
+        let last = self.times.len() - 1;
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return out.copy_from_slice(value(0));
+        }
+        if next > last {
+            return out.copy_from_slice(value(last));
+        }
+        let prev = next - 1;
+        let dt = self.times[next] - self.times[prev];
+        let t = (time - self.times[prev]) / dt;
+
+        match self.interpolation {
+            Interpolation::Step => out.copy_from_slice(value(prev)),
+            Interpolation::Linear if self.property == Property::Rotation => {
+                let a = Quat::from_slice(value(prev));
+                let b = Quat::from_slice(value(next));
+                a.slerp(b, t).write_to_slice(out);
+            }
+            Interpolation::Linear => {
+                for ((out, a), b) in out.iter_mut().zip(value(prev)).zip(value(next)) {
+                    *out = a + (b - a) * t;
+                }
+            }
+            Interpolation::CubicSpline => {
+                let t2 = t * t;
+                let t3 = t2 * t;
+                let values = value(prev).iter().zip(element(prev, 2)).zip(value(next)).zip(element(next, 0));
+                for (out, (((v0, out_tangent), v1), in_tangent)) in out.iter_mut().zip(values) {
+                    *out = v0 * (2.0 * t3 - 3.0 * t2 + 1.0)
+                        + out_tangent * dt * (t3 - 2.0 * t2 + t)
+                        + v1 * (-2.0 * t3 + 3.0 * t2)
+                        + in_tangent * dt * (t3 - t2);
+                }
+                if self.property == Property::Rotation {
+                    Quat::from_slice(out).normalize().write_to_slice(out);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl AnimationClip {
+    pub fn apply(&self, time: f32, pose: &mut Pose) {
+        for channel in &self.channels {
+            let transform = &mut pose.transforms[channel.node];
+            match channel.property {
+                Property::Translation => {
+                    let mut value = [0.0; 3];
+                    channel.sample(time, &mut value);
+                    transform.translation = Vec3::from(value);
+                }
+                Property::Rotation => {
+                    let mut value = [0.0; 4];
+                    channel.sample(time, &mut value);
+                    transform.rotation = Quat::from_array(value).normalize();
+                }
+                Property::Scale => {
+                    let mut value = [0.0; 3];
+                    channel.sample(time, &mut value);
+                    transform.scale = Vec3::from(value);
+                }
+                Property::MorphWeights => {
+                    let weights = &mut pose.weights[channel.node];
+                    weights.resize(channel.width(), 0.0);
+                    channel.sample(time, weights);
+                }
+            }
+        }
+    }
+}
+
+// local transforms and morph target weights for every node of a model
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pose {
+    pub transforms: Vec<Transform>,
+    pub weights: Vec<Vec<f32>>,
+}
+
+impl Pose {
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            transforms: self.transforms.iter().zip(&other.transforms)
+                .map(|(a, b)| a.lerp(b, t))
+                .collect(),
+            weights: self.weights.iter().zip(&other.weights)
+                .map(|(a, b)| a.iter().zip(b).map(|(a, b)| a + (b - a) * t).collect())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Playback {
+    clip: usize,
+    time: f32,
+    looping: bool,
+}
+
+impl Playback {
+    fn advance(&mut self, dt: f32, clips: &[AnimationClip]) {
+        let duration = clips[self.clip].duration;
+        self.time += dt;
+        if self.looping && duration > 0.0 {
+            self.time = self.time.rem_euclid(duration);
+        } else {
+            self.time = self.time.clamp(0.0, duration);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+    pub speed: f32,
+    paused: bool,
+    current: Option<Playback>,
+    previous: Option<Playback>,
+    fade_time: f32,
+    fade_duration: f32,
+    weight_overrides: HashMap<usize, Vec<f32>>,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self {
+            speed: 1.0,
+            paused: false,
+            current: None,
+            previous: None,
+            fade_time: 0.0,
+            fade_duration: 0.0,
+            weight_overrides: HashMap::new(),
+        }
+    }
+
+    pub fn play(&mut self, clip: usize, looping: bool) {
+        self.current = Some(Playback { clip, time: 0.0, looping });
+        self.previous = None;
+        self.paused = false;
+    }
+
+    // blends from whatever is playing now into `clip` over `duration` seconds
+    pub fn crossfade(&mut self, clip: usize, looping: bool, duration: f32) {
+        if self.current.is_none() || duration <= 0.0 {
+            return self.play(clip, looping);
+        }
+        self.previous = self.current.take();
+        self.current = Some(Playback { clip, time: 0.0, looping });
+        self.fade_time = 0.0;
+        self.fade_duration = duration;
+        self.paused = false;
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.previous = None;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_looping(&mut self, looping: bool) {
+        if let Some(current) = &mut self.current {
+            current.looping = looping;
+        }
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.current.is_some_and(|current| current.looping)
+    }
+
+    // pins the morph target weights of `node`, taking precedence over any animated weights
+    pub fn set_morph_weights(&mut self, node: usize, weights: Vec<f32>) {
+        self.weight_overrides.insert(node, weights);
+    }
+
+    pub fn clear_morph_weights(&mut self, node: usize) {
+        self.weight_overrides.remove(&node);
+    }
+
+    pub fn current_clip(&self) -> Option<usize> {
+        self.current.map(|current| current.clip)
+    }
+
+    pub fn time(&self) -> f32 {
+        self.current.map_or(0.0, |current| current.time)
+    }
+
+    pub fn update(&mut self, dt: f32, clips: &[AnimationClip]) {
+        if self.paused {
+            return;
+        }
+        let dt = dt * self.speed;
+        if let Some(current) = &mut self.current {
+            current.advance(dt, clips);
+        }
+        if let Some(previous) = &mut self.previous {
+            previous.advance(dt, clips);
+            self.fade_time += dt.abs();
+            if self.fade_time >= self.fade_duration {
+                self.previous = None;
+            }
+        }
+    }
+
+    pub fn pose(&self, clips: &[AnimationClip], rest: &Pose) -> Pose {
+        let mut pose = rest.clone();
+        if let Some(current) = self.current {
+            clips[current.clip].apply(current.time, &mut pose);
+            if let Some(previous) = self.previous {
+                let mut from = rest.clone();
+                clips[previous.clip].apply(previous.time, &mut from);
+                let weight = (self.fade_time / self.fade_duration).clamp(0.0, 1.0);
+                pose = from.lerp(&pose, weight);
+            }
+        }
+        for (&node, weights) in &self.weight_overrides {
+            if let Some(pose_weights) = pose.weights.get_mut(node) {
+                pose_weights.clone_from(weights);
+            }
+        }
+        pose
+    }
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
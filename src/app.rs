@@ -0,0 +1,278 @@
+use std::time::Instant;
+use pollster::block_on;
+use wgpu::*;
+use winit::event::{DeviceEvent, Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use crate::context::RenderContext;
+use crate::gpu_capture::GpuCapture;
+use crate::replay::{InputEvent, InputLog};
+use crate::renderer::Renderer;
+use crate::tween::{self, Tweens};
+use crate::world::{self, Entity, World};
+
+pub const DEFAULT_TICK_RATE: f64 = 60.0;
+// after a long stall the simulation drops time instead of trying to catch up all at once
+const MAX_STEPS_PER_FRAME: u32 = 8;
+// longer frames are treated as this long, e.g. after sitting at a breakpoint
+const MAX_FRAME_TIME: f64 = 0.25;
+
+// turns variable frame times into a whole number of fixed steps. the accumulator is f64 so
+// frames that are exactly one step long, like when recording, always give exactly one step
+#[derive(Copy, Clone, Debug)]
+pub struct FixedTimestep {
+    pub step: f64,
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    pub fn new(tick_rate: f64) -> Self {
+        Self {
+            step: 1.0 / tick_rate,
+            accumulator: 0.0,
+        }
+    }
+
+    // adds a frame's worth of time and returns how many steps to run
+    pub fn advance(&mut self, delta: f64) -> u32 {
+        self.accumulator += delta.min(MAX_FRAME_TIME);
+        let mut steps = 0;
+        // the epsilon keeps rounding from turning one step into zero or two
+        while self.accumulator >= self.step - 1e-9 {
+            self.accumulator = (self.accumulator - self.step).max(0.0);
+            steps += 1;
+        }
+        if steps > MAX_STEPS_PER_FRAME {
+            steps = MAX_STEPS_PER_FRAME;
+        }
+        steps
+    }
+
+    pub fn accumulator(&self) -> f64 {
+        self.accumulator
+    }
+
+    // how far the current time is between the last step and the next one, 0..1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step) as f32
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(DEFAULT_TICK_RATE)
+    }
+}
+
+// timing for one rendered frame
+#[derive(Copy, Clone, Debug)]
+pub struct FrameTime {
+    // real time since the last frame
+    pub delta: f32,
+    // the fixed simulation step
+    pub step: f32,
+    // fixed steps run this frame
+    pub steps: u32,
+    // simulated time not yet consumed by a step
+    pub accumulator: f32,
+    // accumulator / step, for interpolating between the last two steps
+    pub alpha: f32,
+}
+
+// everything an App works with
+pub struct Engine {
+    pub context: RenderContext,
+    pub renderer: Renderer,
+    pub world: World,
+    pub timestep: FixedTimestep,
+    pub gpu_capture: GpuCapture,
+    // input recorded to a file or replayed from one by run, see InputLog
+    pub input_log: Option<InputLog>,
+}
+
+impl Engine {
+    pub fn new(context: RenderContext) -> Self {
+        context.device.push_error_scope(ErrorFilter::Validation);
+        let renderer = Renderer::new(&context);
+        if let Some(error) = block_on(context.device.pop_error_scope()) {
+            panic!("failed to create renderer: {error}");
+        }
+        Self {
+            context,
+            renderer,
+            world: World::new(),
+            timestep: FixedTimestep::default(),
+            gpu_capture: GpuCapture::new(),
+            input_log: None,
+        }
+    }
+
+    // runs the fixed steps that fit into delta, then the per frame update, then syncs the
+    // renderer with the world. drawing is left to the caller
+    pub fn frame(&mut self, app: &mut impl App, delta: f64) -> FrameTime {
+        let steps = self.timestep.advance(delta);
+        let step = self.timestep.step as f32;
+        for _ in 0..steps {
+            let _span = tracing::info_span!("fixed update").entered();
+            world::snapshot_transforms(&mut self.world);
+            app.fixed_update(self, step);
+            world::advance_animations(&mut self.world, step);
+            for entity in tween::advance(&mut self.world, step) {
+                app.tween_finished(self, entity);
+            }
+        }
+        let time = FrameTime {
+            delta: delta.min(MAX_FRAME_TIME) as f32,
+            step,
+            steps,
+            accumulator: self.timestep.accumulator() as f32,
+            alpha: self.timestep.alpha(),
+        };
+        tracing::info_span!("update").in_scope(|| app.update(self, &time));
+        tracing::info_span!("renderer update").in_scope(|| self.renderer.update(&self.context, &mut self.world, &time));
+        time
+    }
+
+    // replaces whatever tweens the entity was playing
+    pub fn tween(&mut self, entity: Entity, tweens: Tweens) {
+        let _ = self.world.insert_one(entity, tweens);
+    }
+}
+
+// the callbacks an application hooks into the loop
+pub trait App {
+    // simulation at a fixed rate, e.g. physics and gameplay. animations advance right after
+    fn fixed_update(&mut self, _engine: &mut Engine, _step: f32) {}
+
+    // an entity's Tweens reached their end, e.g. to chain the next bit of scripted motion
+    fn tween_finished(&mut self, _engine: &mut Engine, _entity: Entity) {}
+
+    // once per rendered frame, for input and anything that should track the display rate
+    fn update(&mut self, _engine: &mut Engine, _time: &FrameTime) {}
+
+    // after the engine has handled resizes and the like itself
+    fn window_event(&mut self, _engine: &mut Engine, _event: &WindowEvent) {}
+
+    // raw input that isn't tied to the window, e.g. relative mouse motion
+    fn device_event(&mut self, _engine: &mut Engine, _event: &DeviceEvent) {}
+
+    // the app went to the background (android and ios), nothing is drawn until it resumes
+    fn suspended(&mut self, _engine: &mut Engine) {}
+
+    // after the surface has been recreated
+    fn resumed(&mut self, _engine: &mut Engine) {}
+}
+
+// owns the window's event loop until it's closed
+pub fn run(event_loop: EventLoop<()>, mut engine: Engine, mut app: impl App + 'static) -> ! {
+    let mut last_frame = Instant::now();
+    event_loop.run(move |event, _event_loop, flow| {
+        // runs the callbacks of finished buffer mappings, which is what wakes Readback futures
+        if let Event::MainEventsCleared = event {
+            engine.context.device.poll(Maintain::Poll);
+        }
+        match event {
+            Event::WindowEvent { event, .. } => {
+                let context = &engine.context;
+                match event {
+                    WindowEvent::Resized(size) => {
+                        context.resize(size.width, size.height);
+                        engine.renderer.resize(context, size.width, size.height);
+                    }
+                    // e.g. dragged onto a monitor with different scaling. sizes and the cursor are
+                    // all physical pixels, so only the surface and render targets need updating
+                    WindowEvent::ScaleFactorChanged { scale_factor, ref new_inner_size } => {
+                        context.set_scale_factor(scale_factor, new_inner_size.width, new_inner_size.height);
+                        engine.renderer.resize(context, new_inner_size.width, new_inner_size.height);
+                    }
+                    WindowEvent::Occluded(occluded) => {
+                        context.set_occluded(occluded);
+                    }
+                    // a grabbed cursor would stay stuck in a window that's no longer in front
+                    WindowEvent::Focused(false) => {
+                        context.set_pointer_lock(false);
+                    }
+                    WindowEvent::CloseRequested => {
+                        *flow = ControlFlow::ExitWithCode(0);
+                    }
+                    _ => {}
+                }
+                if log_input(&mut engine.input_log, InputEvent::from_window_event(&event)) {
+                    app.window_event(&mut engine, &event);
+                }
+            }
+            Event::DeviceEvent { event, .. } => {
+                if log_input(&mut engine.input_log, InputEvent::from_device_event(&event)) {
+                    app.device_event(&mut engine, &event);
+                }
+            }
+            Event::Suspended => {
+                engine.context.suspend();
+                app.suspended(&mut engine);
+            }
+            // also sent once at startup on some platforms, when there's already a surface
+            Event::Resumed => {
+                engine.context.resume();
+                let size = engine.context.physical_size();
+                engine.renderer.resize(&engine.context, size.width, size.height);
+                app.resumed(&mut engine);
+            }
+            // while hidden the loop sleeps until the next window event instead of spinning
+            Event::MainEventsCleared if engine.context.is_visible() => {
+                *flow = ControlFlow::Poll;
+                engine.context.request_redraw();
+            }
+            Event::MainEventsCleared => {
+                *flow = ControlFlow::Wait;
+            }
+            Event::RedrawRequested(..) if !engine.context.is_visible() => {}
+            Event::RedrawRequested(..) => {
+                tracing::info_span!("frame pacing").in_scope(|| engine.renderer.pacing.limit());
+                let _span = tracing::info_span!("frame").entered();
+                let now = Instant::now();
+                let mut delta = (now - last_frame).as_secs_f64();
+                last_frame = now;
+                match &mut engine.input_log {
+                    Some(InputLog::Record(recorder)) => recorder.frame(delta),
+                    Some(InputLog::Replay(replayer)) => match replayer.next_frame() {
+                        Some(frame) => {
+                            delta = frame.delta;
+                            frame.dispatch(&mut engine, &mut app);
+                        }
+                        None => {
+                            tracing::info!("replay finished, input is live again");
+                            engine.input_log = None;
+                        }
+                    },
+                    None => {}
+                }
+                engine.gpu_capture.begin_frame();
+                engine.frame(&mut app, delta);
+                if let Some(error) = block_on(engine.renderer.draw(&engine.context)) {
+                    tracing::error!("draw: {error}");
+                }
+                engine.gpu_capture.end_frame();
+            }
+            Event::LoopDestroyed => {
+                if let Some(InputLog::Record(recorder)) = &engine.input_log {
+                    match recorder.save() {
+                        Ok(()) => tracing::info!("saved input to {}", recorder.path.display()),
+                        Err(error) => tracing::error!("failed to save input to {}: {error}", recorder.path.display()),
+                    }
+                }
+            }
+            _ => {}
+        }
+    })
+}
+
+// records input, or drops it while a recording is replaying. returns whether the app gets it
+fn log_input(input_log: &mut Option<InputLog>, event: Option<InputEvent>) -> bool {
+    match (input_log, event) {
+        (Some(InputLog::Record(recorder)), Some(event)) => {
+            recorder.record(event);
+            true
+        }
+        (Some(InputLog::Replay(_)), Some(_)) => false,
+        _ => true,
+    }
+}
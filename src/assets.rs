@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use crate::animation::AnimationPlayer;
+use crate::atlas::{Atlas, AtlasBuilder, AtlasError};
+use crate::context::RenderContext;
+use crate::model::{Model, ModelError};
+use crate::primitives;
+use crate::texture::{Texture, TextureData, TextureError};
+use crate::world::{self, MeshRef, World};
+
+// how often watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// decoding is cpu bound, past a few threads it mostly fights the renderer
+const MAX_LOADER_THREADS: usize = 4;
+
+// generations count load requests, results from anything but the latest are thrown away so a
+// slow load can't overwrite a newer one
+struct ModelAsset {
+    model: Arc<Model>,
+    // the gltf file plus the buffers and images it references
+    files: Vec<PathBuf>,
+    generation: u32,
+    loaded_generation: u32,
+}
+
+struct TextureAsset {
+    texture: Arc<Texture>,
+    srgb: bool,
+    generation: u32,
+    loaded_generation: u32,
+}
+
+enum Job {
+    Model(PathBuf, u32),
+    Texture(PathBuf, u32),
+}
+
+enum Loaded {
+    Model(PathBuf, u32, Result<(Model, Vec<PathBuf>), ModelError>),
+    Texture(PathBuf, u32, Result<TextureData, TextureError>),
+}
+
+// threads that read and decode files. gpu uploads need the context so they stay on the main thread
+struct Loader {
+    jobs: Sender<Job>,
+    results: Receiver<Loaded>,
+}
+
+impl Loader {
+    fn new() -> Self {
+        let (jobs, job_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, results) = mpsc::channel();
+        let threads = thread::available_parallelism().map_or(2, |threads| threads.get()).clamp(1, MAX_LOADER_THREADS);
+        for index in 0..threads {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            thread::Builder::new()
+                .name(format!("asset loader {index}"))
+                .spawn(move || loop {
+                    // the lock is only held while waiting, not while loading. both channels close
+                    // when Assets is dropped, which ends the thread
+                    let job = job_receiver.lock().unwrap().recv();
+                    let Ok(job) = job else {
+                        break;
+                    };
+                    let loaded = match job {
+                        Job::Model(path, generation) => {
+                            let _span = tracing::info_span!("load model", path = %path.display()).entered();
+                            let model = Model::load(&path).map(|model| (model, model_files(&path)));
+                            Loaded::Model(path, generation, model)
+                        }
+                        Job::Texture(path, generation) => {
+                            let _span = tracing::info_span!("load texture", path = %path.display()).entered();
+                            let data = TextureData::read(&path);
+                            Loaded::Texture(path, generation, data)
+                        }
+                    };
+                    if result_sender.send(loaded).is_err() {
+                        break;
+                    }
+                })
+                .expect("failed to start asset loader thread");
+        }
+        Self { jobs, results }
+    }
+
+    fn request(&self, job: Job) {
+        // the threads only stop once self is gone
+        let _ = self.jobs.send(job);
+    }
+}
+
+// polls modification times. a change is only reported once the time has stayed the same for a
+// whole poll, so a file that's still being written isn't read half done
+struct FileWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    pending: HashMap<PathBuf, Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+            pending: HashMap::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    fn watch(&mut self, path: &Path) {
+        self.files.insert(path.to_path_buf(), modified(path));
+    }
+
+    fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        for (path, last) in &mut self.files {
+            let current = modified(path);
+            if current == *last {
+                self.pending.remove(path);
+                continue;
+            }
+            match self.pending.insert(path.clone(), current) {
+                Some(pending) if pending == current => {
+                    self.pending.remove(path);
+                    *last = current;
+                    changed.push(path.clone());
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// loads every model and texture once per path and hands out shared handles. `model` and
+// `texture` load in the background and return a placeholder until update swaps in the real
+// thing, the `_blocking` versions wait for it. with hot_reload on, changed files are loaded again
+// the same way
+pub struct Assets {
+    pub hot_reload: bool,
+    models: HashMap<PathBuf, ModelAsset>,
+    textures: HashMap<PathBuf, TextureAsset>,
+    // by paths and sdf spread, 0 for bitmap atlases
+    atlases: HashMap<(Vec<PathBuf>, u32), Arc<Atlas>>,
+    placeholder_model: Model,
+    loader: Loader,
+    watcher: FileWatcher,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self {
+            hot_reload: true,
+            models: HashMap::new(),
+            textures: HashMap::new(),
+            atlases: HashMap::new(),
+            placeholder_model: Model::from_mesh(primitives::cube(1.0)),
+            loader: Loader::new(),
+            watcher: FileWatcher::new(),
+        }
+    }
+
+    // a unit cube until the model has loaded. if loading fails the cube stays
+    pub fn model(&mut self, path: impl AsRef<Path>) -> Arc<Model> {
+        let path = path.as_ref();
+        if let Some(asset) = self.models.get(path) {
+            return asset.model.clone();
+        }
+        // every model gets its own placeholder, update finds what to swap by pointer
+        let model = Arc::new(self.placeholder_model.clone());
+        self.watcher.watch(path);
+        self.models.insert(path.to_path_buf(), ModelAsset {
+            model: model.clone(),
+            files: vec![path.to_path_buf()],
+            generation: 1,
+            loaded_generation: 0,
+        });
+        self.loader.request(Job::Model(path.to_path_buf(), 1));
+        model
+    }
+
+    pub fn model_blocking(&mut self, path: impl AsRef<Path>) -> Result<Arc<Model>, ModelError> {
+        let path = path.as_ref();
+        match self.models.get(path) {
+            Some(asset) if asset.loaded_generation > 0 => return Ok(asset.model.clone()),
+            // entities already have its placeholder, the background load still swaps those. this
+            // gets a copy of its own
+            Some(_) => return Ok(Arc::new(Model::load(path)?)),
+            None => {}
+        }
+        let model = Arc::new(Model::load(path)?);
+        let files = model_files(path);
+        for file in &files {
+            self.watcher.watch(file);
+        }
+        self.models.insert(path.to_path_buf(), ModelAsset {
+            model: model.clone(),
+            files,
+            generation: 1,
+            loaded_generation: 1,
+        });
+        Ok(model)
+    }
+
+    // a 1x1 grey texture until the image has loaded. whoever binds it needs to rebind once update
+    // returns its path
+    pub fn texture(&mut self, context: &RenderContext, path: impl AsRef<Path>, srgb: bool) -> Arc<Texture> {
+        let path = path.as_ref();
+        if let Some(asset) = self.textures.get(path) {
+            return asset.texture.clone();
+        }
+        let texture = Arc::new(Texture::solid(context, "placeholder", [128, 128, 128, 255], srgb));
+        self.watcher.watch(path);
+        self.textures.insert(path.to_path_buf(), TextureAsset {
+            texture: texture.clone(),
+            srgb,
+            generation: 1,
+            loaded_generation: 0,
+        });
+        self.loader.request(Job::Texture(path.to_path_buf(), 1));
+        texture
+    }
+
+    pub fn texture_blocking(&mut self, context: &RenderContext, path: impl AsRef<Path>, srgb: bool) -> Result<Arc<Texture>, TextureError> {
+        let path = path.as_ref();
+        match self.textures.get(path) {
+            Some(asset) if asset.loaded_generation > 0 => return Ok(asset.texture.clone()),
+            Some(_) => return Ok(Arc::new(Texture::load(context, path, srgb)?)),
+            None => {}
+        }
+        let texture = Arc::new(Texture::load(context, path, srgb)?);
+        self.watcher.watch(path);
+        self.textures.insert(path.to_path_buf(), TextureAsset {
+            texture: texture.clone(),
+            srgb,
+            generation: 1,
+            loaded_generation: 1,
+        });
+        Ok(texture)
+    }
+
+    // the current version of a texture, the placeholder while it's loading
+    pub fn get_texture(&self, path: impl AsRef<Path>) -> Option<Arc<Texture>> {
+        self.textures.get(path.as_ref()).map(|asset| asset.texture.clone())
+    }
+
+    // packs the images into one texture, see Atlas. regions are named by the paths as given and
+    // the same paths give back the same atlas. loads right away and isn't hot reloaded
+    pub fn atlas(&mut self, context: &RenderContext, paths: &[impl AsRef<Path>], srgb: bool) -> Result<Arc<Atlas>, AtlasError> {
+        self.load_atlas(context, paths, srgb, 0)
+    }
+
+    // the same with every image as a signed distance field, for glyphs and icons. plain atlases
+    // from atlas are the fallback for images that don't have clean edges, like photos
+    pub fn sdf_atlas(&mut self, context: &RenderContext, paths: &[impl AsRef<Path>], srgb: bool, spread: u32) -> Result<Arc<Atlas>, AtlasError> {
+        self.load_atlas(context, paths, srgb, spread.max(1))
+    }
+
+    fn load_atlas(&mut self, context: &RenderContext, paths: &[impl AsRef<Path>], srgb: bool, spread: u32) -> Result<Arc<Atlas>, AtlasError> {
+        let key = (paths.iter().map(|path| path.as_ref().to_path_buf()).collect::<Vec<_>>(), spread);
+        if let Some(atlas) = self.atlases.get(&key) {
+            return Ok(atlas.clone());
+        }
+        let mut builder = AtlasBuilder::new();
+        for path in &key.0 {
+            let image = image::open(path)?.to_rgba8();
+            let name = path.display().to_string();
+            match spread {
+                0 => builder.add(name, image),
+                spread => builder.add_sdf(name, &image, spread),
+            };
+        }
+        let atlas = Arc::new(builder.build(context, "atlas", srgb)?);
+        self.atlases.insert(key, atlas.clone());
+        Ok(atlas)
+    }
+
+    // share of assets that have finished their first load, failed ones count as finished.
+    // reloads don't count, so hot reloading never brings a loading screen back
+    pub fn progress(&self) -> f32 {
+        let total = self.models.len() + self.textures.len();
+        if total == 0 {
+            return 1.0;
+        }
+        let models = self.models.values().filter(|asset| asset.loaded_generation > 0).count();
+        let textures = self.textures.values().filter(|asset| asset.loaded_generation > 0).count();
+        (models + textures) as f32 / total as f32
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.models.values().any(|asset| asset.loaded_generation == 0)
+            || self.textures.values().any(|asset| asset.loaded_generation == 0)
+    }
+
+    // swaps in whatever finished loading and returns its paths. entities drawing a swapped model
+    // are pointed at the new one, so the renderer rebuilds their buffers on its next update.
+    // textures are only swapped here, whoever bound the old one has to rebind. call it between
+    // frames. a failed load keeps the previous version, or the placeholder
+    pub fn update(&mut self, context: &RenderContext, world: &mut World) -> Vec<PathBuf> {
+        if self.hot_reload {
+            for path in self.watcher.poll() {
+                for (model_path, asset) in &mut self.models {
+                    if asset.files.contains(&path) {
+                        asset.generation += 1;
+                        self.loader.request(Job::Model(model_path.clone(), asset.generation));
+                    }
+                }
+                if let Some(asset) = self.textures.get_mut(&path) {
+                    asset.generation += 1;
+                    self.loader.request(Job::Texture(path, asset.generation));
+                }
+            }
+        }
+
+        let mut loaded = Vec::new();
+        let _span = tracing::info_span!("upload assets").entered();
+        while let Ok(result) = self.loader.results.try_recv() {
+            match result {
+                Loaded::Model(path, generation, result) => {
+                    let Some(asset) = self.models.get_mut(&path).filter(|asset| asset.generation == generation) else {
+                        continue;
+                    };
+                    asset.loaded_generation = generation;
+                    let (model, files) = match result {
+                        Ok(result) => result,
+                        Err(error) => {
+                            tracing::error!("failed to load {}: {error}", path.display());
+                            continue;
+                        }
+                    };
+                    let model = Arc::new(model);
+                    swap_model(world, &asset.model, &model);
+                    // a gltf can start referencing different files
+                    for file in files {
+                        if !asset.files.contains(&file) {
+                            self.watcher.watch(&file);
+                            asset.files.push(file);
+                        }
+                    }
+                    asset.model = model;
+                    if generation > 1 {
+                        tracing::info!("reloaded {}", path.display());
+                    }
+                    loaded.push(path);
+                }
+                Loaded::Texture(path, generation, result) => {
+                    let Some(asset) = self.textures.get_mut(&path).filter(|asset| asset.generation == generation) else {
+                        continue;
+                    };
+                    asset.loaded_generation = generation;
+                    let texture = result.and_then(|data| Texture::from_data(context, &path.display().to_string(), &data, asset.srgb));
+                    match texture {
+                        Ok(texture) => {
+                            asset.texture = Arc::new(texture);
+                            if generation > 1 {
+                                tracing::info!("reloaded {}", path.display());
+                            }
+                            loaded.push(path);
+                        }
+                        Err(error) => tracing::error!("failed to load {}: {error}", path.display()),
+                    }
+                }
+            }
+        }
+        loaded
+    }
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn swap_model(world: &mut World, old: &Arc<Model>, new: &Arc<Model>) {
+    let mut swapped = Vec::new();
+    for (entity, mesh) in world.query_mut::<&mut MeshRef>() {
+        if Arc::ptr_eq(&mesh.0, old) {
+            mesh.0 = new.clone();
+            swapped.push(entity);
+        }
+    }
+    // clips may be gone or different now, so they restart
+    for entity in swapped {
+        let clip = world.get::<&AnimationPlayer>(entity).ok().and_then(|player| player.current_clip());
+        world::play(world, entity, clip);
+    }
+}
+
+// external buffers and images are resolved relative to the gltf, embedded ones have no file
+fn model_files(path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![path.to_path_buf()];
+    let Ok(gltf) = gltf::Gltf::open(path) else {
+        return files;
+    };
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let buffers = gltf.buffers().filter_map(|buffer| match buffer.source() {
+        gltf::buffer::Source::Uri(uri) => Some(uri),
+        gltf::buffer::Source::Bin => None,
+    });
+    let images = gltf.images().filter_map(|image| match image.source() {
+        gltf::image::Source::Uri { uri, .. } => Some(uri),
+        gltf::image::Source::View { .. } => None,
+    });
+    for uri in buffers.chain(images) {
+        if !uri.starts_with("data:") {
+            files.push(base_dir.join(uri));
+        }
+    }
+    files
+}
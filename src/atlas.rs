@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fmt;
+use glam::Vec2;
+use image::{GenericImage, RgbaImage};
+use crate::context::RenderContext;
+use crate::sdf;
+use crate::texture::Texture;
+
+// empty pixels around every image. the image's edge is repeated into them so linear filtering at
+// the border doesn't pick up its neighbours
+const PADDING: u32 = 1;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Image(image::ImageError),
+    // the images don't fit into the largest texture the device allows
+    TooLarge { max_size: u32 },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtlasError::Image(error) => error.fmt(f),
+            AtlasError::TooLarge { max_size } => write!(f, "images don't fit into a {max_size}x{max_size} atlas"),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl From<image::ImageError> for AtlasError {
+    fn from(error: image::ImageError) -> Self {
+        AtlasError::Image(error)
+    }
+}
+
+// where one image ended up, in uvs of the whole atlas
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub min: Vec2,
+    pub max: Vec2,
+    // in pixels, without the padding
+    pub width: u32,
+    pub height: u32,
+    // for images added with add_sdf, the pixels of distance field around the shape that are
+    // part of the region, so quads drawn from it need to be that much bigger. 0 for bitmaps
+    pub spread: u32,
+}
+
+impl AtlasRegion {
+    // maps a uv of the original image into the atlas
+    pub fn remap(&self, uv: Vec2) -> Vec2 {
+        self.min + (self.max - self.min) * uv
+    }
+}
+
+// collects named images to pack into one texture. sprites and glyphs drawn from the same atlas
+// share a bind group, so they can go out in a single draw
+#[derive(Default)]
+pub struct AtlasBuilder {
+    // with their sdf spread, 0 for bitmaps
+    images: Vec<(String, RgbaImage, u32)>,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // adding a name again replaces the image
+    pub fn add(&mut self, name: impl Into<String>, image: RgbaImage) -> &mut Self {
+        self.insert(name.into(), image, 0)
+    }
+
+    // adds the image as a signed distance field, for glyphs and icons that get scaled or
+    // outlined, see sdf::generate. sdf and plain images can share an atlas
+    pub fn add_sdf(&mut self, name: impl Into<String>, image: &RgbaImage, spread: u32) -> &mut Self {
+        let spread = spread.max(1);
+        self.insert(name.into(), sdf::generate(image, spread), spread)
+    }
+
+    fn insert(&mut self, name: String, image: RgbaImage, spread: u32) -> &mut Self {
+        self.images.retain(|(existing, _, _)| *existing != name);
+        self.images.push((name, image, spread));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    // shelf packing, tallest first, into the smallest power of two square that fits. no gpu
+    // needed, so it can run on a loader thread
+    pub fn pack(&self, max_size: u32) -> Result<(RgbaImage, HashMap<String, AtlasRegion>), AtlasError> {
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(self.images[index].1.height()));
+        let area: u32 = self.images.iter()
+            .map(|(_, image, _)| (image.width() + PADDING * 2) * (image.height() + PADDING * 2))
+            .sum();
+        let mut size = ((area as f32).sqrt().ceil() as u32).next_power_of_two().max(1);
+        let positions = loop {
+            if size > max_size {
+                return Err(AtlasError::TooLarge { max_size });
+            }
+            if let Some(positions) = self.place(&order, size) {
+                break positions;
+            }
+            size *= 2;
+        };
+
+        let mut atlas = RgbaImage::new(size, size);
+        let mut regions = HashMap::with_capacity(self.images.len());
+        for ((name, image, spread), (x, y)) in self.images.iter().zip(positions) {
+            let (width, height) = image.dimensions();
+            let (left, top) = (x + PADDING, y + PADDING);
+            atlas.copy_from(image, left, top)?;
+            extrude(&mut atlas, left, top, width, height);
+            regions.insert(name.clone(), AtlasRegion {
+                min: Vec2::new(left as f32, top as f32) / size as f32,
+                max: Vec2::new((left + width) as f32, (top + height) as f32) / size as f32,
+                width,
+                height,
+                spread: *spread,
+            });
+        }
+        Ok((atlas, regions))
+    }
+
+    // the top left corner of every image's padded cell, in the order the images were added.
+    // None if they don't fit
+    fn place(&self, order: &[usize], size: u32) -> Option<Vec<(u32, u32)>> {
+        let mut positions = vec![(0, 0); self.images.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for &index in order {
+            let image = &self.images[index].1;
+            let (width, height) = (image.width() + PADDING * 2, image.height() + PADDING * 2);
+            if x + width > size {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            if x + width > size || y + height > size {
+                return None;
+            }
+            positions[index] = (x, y);
+            x += width;
+            shelf_height = shelf_height.max(height);
+        }
+        Some(positions)
+    }
+
+    pub fn build(&self, context: &RenderContext, label: &str, srgb: bool) -> Result<Atlas, AtlasError> {
+        let (image, regions) = self.pack(context.limits().max_texture_dimension_2d)?;
+        let texture = Texture::from_rgba8(context, label, image.width(), image.height(), &image, srgb);
+        Ok(Atlas { texture, regions })
+    }
+}
+
+// repeats the outermost pixels of a placed image into its padding
+fn extrude(atlas: &mut RgbaImage, left: u32, top: u32, width: u32, height: u32) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let right = left + width - 1;
+    let bottom = top + height - 1;
+    for offset in 1..=PADDING {
+        for x in left..=right {
+            atlas.put_pixel(x, top - offset, *atlas.get_pixel(x, top));
+            atlas.put_pixel(x, bottom + offset, *atlas.get_pixel(x, bottom));
+        }
+        for y in top - offset..=bottom + offset {
+            atlas.put_pixel(left - offset, y, *atlas.get_pixel(left, y));
+            atlas.put_pixel(right + offset, y, *atlas.get_pixel(right, y));
+        }
+    }
+}
+
+// many small images in one texture, looked up by the name they were added under
+pub struct Atlas {
+    pub texture: Texture,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl Atlas {
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = (&str, AtlasRegion)> {
+        self.regions.iter().map(|(name, region)| (name.as_str(), *region))
+    }
+}
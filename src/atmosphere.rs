@@ -0,0 +1,134 @@
+use glam::Vec3;
+use wgpu::*;
+use crate::bindings::LayoutRegistry;
+use crate::camera::FRAME_LAYOUT;
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+#[derive(Copy, Clone, Debug)]
+pub struct AtmosphereSettings {
+    // fades lit surfaces towards fog_color with distance, thicker lower down
+    pub fog: bool,
+    // linear, ignored while the sky is on, which colors the fog instead
+    pub fog_color: Vec3,
+    // per world unit at fog_height
+    pub fog_density: f32,
+    // world height the density is given at
+    pub fog_height: f32,
+    // how quickly the fog thins out going up, 0 is the same density at every height
+    pub fog_height_falloff: f32,
+    // world units from the camera before any fog
+    pub fog_start: f32,
+    // a Preetham sky instead of the clear color behind everything, lit by the first directional
+    // light as the sun
+    pub sky: bool,
+    // haziness of the sky, 2 is very clear and 10 hazy
+    pub turbidity: f32,
+    // scales the sky's luminance, which the model gives in kcd/m²
+    pub sky_intensity: f32,
+}
+
+impl Default for AtmosphereSettings {
+    fn default() -> Self {
+        Self {
+            fog: false,
+            fog_color: Vec3::new(0.5, 0.6, 0.7),
+            fog_density: 0.02,
+            fog_height: 0.0,
+            fog_height_falloff: 0.2,
+            fog_start: 0.0,
+            sky: false,
+            turbidity: 3.0,
+            sky_intensity: 0.05,
+        }
+    }
+}
+
+// part of the lights uniform, see atmosphere.wgsl
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct AtmosphereUniform {
+    fog_color: [f32; 3],
+    fog_density: f32,
+    fog_height: f32,
+    fog_height_falloff: f32,
+    fog_start: f32,
+    fog: u32,
+    sky: u32,
+    turbidity: f32,
+    sky_intensity: f32,
+    _padding: u32,
+}
+
+impl AtmosphereUniform {
+    pub fn new(settings: &AtmosphereSettings) -> Self {
+        Self {
+            fog_color: settings.fog_color.to_array(),
+            fog_density: settings.fog_density.max(0.0),
+            fog_height: settings.fog_height,
+            fog_height_falloff: settings.fog_height_falloff.max(0.0),
+            fog_start: settings.fog_start.max(0.0),
+            fog: settings.fog as u32,
+            sky: settings.sky as u32,
+            // the model's fit only holds in about this range
+            turbidity: settings.turbidity.clamp(1.7, 10.0),
+            sky_intensity: settings.sky_intensity.max(0.0),
+            _padding: 0,
+        }
+    }
+}
+
+// the sky behind a view, drawn in place of the background with the view's frame group. it's
+// at the far plane and writes no depth, so everything after it draws over it
+pub struct Sky {
+    render_pipeline: RenderPipeline,
+}
+
+impl Sky {
+    // FRAME_LAYOUT has to be registered already, see CameraBinding
+    pub fn new(context: &RenderContext, layouts: &LayoutRegistry) -> Self {
+        let device = &context.device;
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "sky.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("sky"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("sky"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &shader_module,
+                targets: &[
+                    Some(HDR_FORMAT.into())
+                ],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        Self {
+            render_pipeline,
+        }
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        render_cmd.set_pipeline(&self.render_pipeline);
+        render_cmd.set_bind_group(0, camera_bind_group, &[]);
+        render_cmd.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,85 @@
+// see AtmosphereUniform
+struct Atmosphere {
+    fog_color: vec3<f32>,
+    fog_density: f32,
+    fog_height: f32,
+    fog_height_falloff: f32,
+    fog_start: f32,
+    fog: u32,
+    sky: u32,
+    turbidity: f32,
+    sky_intensity: f32,
+}
+
+// the Perez distribution the Preetham model is built on, theta from the zenith and gamma from
+// the sun
+fn perez(cos_theta: f32, gamma: f32, cos_gamma: f32, a: f32, b: f32, c: f32, d: f32, e: f32) -> f32 {
+    return (1.0 + a * exp(b / max(cos_theta, 0.01))) * (1.0 + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+}
+
+// linear rgb seen looking in direction, with the sun towards sun. both normalized, y is up.
+// below the horizon is the horizon
+fn preetham_sky(atmosphere: Atmosphere, direction: vec3<f32>, sun: vec3<f32>) -> vec3<f32> {
+    let t = atmosphere.turbidity;
+    // the model breaks down with the sun under the horizon, so it stays on it and dims instead
+    let sun_up = normalize(vec3<f32>(sun.x, max(sun.y, 0.01), sun.z));
+    let theta_sun = acos(sun_up.y);
+    let theta_sun2 = theta_sun * theta_sun;
+    let theta_sun3 = theta_sun2 * theta_sun;
+
+    let chi = (4.0 / 9.0 - t / 120.0) * (3.14159265 - 2.0 * theta_sun);
+    let zenith_y = (4.0453 * t - 4.9710) * tan(chi) - 0.2155 * t + 2.4192;
+    let zenith_x = t * t * (0.00166 * theta_sun3 - 0.00375 * theta_sun2 + 0.00209 * theta_sun)
+        + t * (-0.02903 * theta_sun3 + 0.06377 * theta_sun2 - 0.03202 * theta_sun + 0.00394)
+        + (0.11693 * theta_sun3 - 0.21196 * theta_sun2 + 0.06052 * theta_sun + 0.25886);
+    let zenith_chroma_y = t * t * (0.00275 * theta_sun3 - 0.00610 * theta_sun2 + 0.00317 * theta_sun)
+        + t * (-0.04214 * theta_sun3 + 0.08970 * theta_sun2 - 0.04153 * theta_sun + 0.00516)
+        + (0.15346 * theta_sun3 - 0.26756 * theta_sun2 + 0.06670 * theta_sun + 0.26688);
+
+    let cos_theta = max(direction.y, 0.0);
+    let cos_gamma = clamp(dot(direction, sun_up), -1.0, 1.0);
+    let gamma = acos(cos_gamma);
+    let cos_sun = sun_up.y;
+
+    let big_y = zenith_y
+        * perez(cos_theta, gamma, cos_gamma, 0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703)
+        / perez(1.0, theta_sun, cos_sun, 0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703);
+    let x = zenith_x
+        * perez(cos_theta, gamma, cos_gamma, -0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452)
+        / perez(1.0, theta_sun, cos_sun, -0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452);
+    let y = zenith_chroma_y
+        * perez(cos_theta, gamma, cos_gamma, -0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529)
+        / perez(1.0, theta_sun, cos_sun, -0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529);
+
+    // Yxy to XYZ to linear srgb
+    let luminance = max(big_y, 0.0) * atmosphere.sky_intensity;
+    let xyz = vec3<f32>(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+    let rgb = vec3<f32>(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    );
+    let dusk = smoothstep(-0.1, 0.05, sun.y);
+    return max(rgb, vec3<f32>(0.0)) * dusk;
+}
+
+// 0 clear to 1 fully fogged, between eye and world_position. the density falls off
+// exponentially with height, so it's integrated along the ray
+fn fog_amount(atmosphere: Atmosphere, eye: vec3<f32>, world_position: vec3<f32>) -> f32 {
+    let ray = world_position - eye;
+    let span = length(ray);
+    let fogged = max(span - atmosphere.fog_start, 0.0);
+    var density = atmosphere.fog_density;
+    if (atmosphere.fog_height_falloff > 0.0) {
+        let falloff = atmosphere.fog_height_falloff;
+        let at_eye = exp(-falloff * (eye.y - atmosphere.fog_height));
+        let rise = falloff * ray.y;
+        // the average density along the ray, its value at the eye when it's level
+        var average = at_eye;
+        if (abs(rise) > 0.0001) {
+            average = at_eye * (1.0 - exp(-rise)) / rise;
+        }
+        density = density * average;
+    }
+    return 1.0 - exp(-density * fogged);
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum AudioError {
+    Io(io::Error),
+    // built without the audio feature, or there's no output device
+    Unavailable,
+    #[cfg(feature = "audio")]
+    Decode(rodio::decoder::DecoderError),
+    #[cfg(feature = "audio")]
+    Play(rodio::PlayError),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AudioError::Io(error) => error.fmt(f),
+            AudioError::Unavailable => write!(f, "no audio output"),
+            #[cfg(feature = "audio")]
+            AudioError::Decode(error) => error.fmt(f),
+            #[cfg(feature = "audio")]
+            AudioError::Play(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+impl From<io::Error> for AudioError {
+    fn from(error: io::Error) -> Self {
+        AudioError::Io(error)
+    }
+}
+
+#[cfg(feature = "audio")]
+impl From<rodio::decoder::DecoderError> for AudioError {
+    fn from(error: rodio::decoder::DecoderError) -> Self {
+        AudioError::Decode(error)
+    }
+}
+
+#[cfg(feature = "audio")]
+impl From<rodio::PlayError> for AudioError {
+    fn from(error: rodio::PlayError) -> Self {
+        AudioError::Play(error)
+    }
+}
+
+#[cfg(feature = "audio")]
+struct Output {
+    // playback stops when the stream is dropped
+    _stream: rodio::OutputStream,
+    handle: rodio::OutputStreamHandle,
+    music: Option<rodio::Sink>,
+}
+
+// fire and forget sounds plus one looping music channel. without the audio feature, or without
+// an output device, everything returns AudioError::Unavailable so callers don't need to care
+pub struct Audio {
+    #[cfg(feature = "audio")]
+    output: Option<Output>,
+    volume: f32,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "audio")]
+            output: rodio::OutputStream::try_default()
+                .map(|(_stream, handle)| Output { _stream, handle, music: None })
+                .map_err(|error| tracing::warn!("audio unavailable: {error}"))
+                .ok(),
+            volume: 1.0,
+        }
+    }
+
+    // wav or ogg vorbis, decoded while it plays
+    pub fn play_sound(&self, path: impl AsRef<Path>) -> Result<(), AudioError> {
+        #[cfg(feature = "audio")]
+        if let Some(output) = &self.output {
+            let sink = rodio::Sink::try_new(&output.handle)?;
+            sink.set_volume(self.volume);
+            sink.append(rodio::Decoder::new(io::BufReader::new(std::fs::File::open(path)?))?);
+            sink.detach();
+            return Ok(());
+        }
+        let _ = path;
+        Err(AudioError::Unavailable)
+    }
+
+    // replaces whatever music is playing
+    pub fn play_music(&mut self, path: impl AsRef<Path>) -> Result<(), AudioError> {
+        #[cfg(feature = "audio")]
+        if let Some(output) = &mut self.output {
+            let sink = rodio::Sink::try_new(&output.handle)?;
+            sink.set_volume(self.volume);
+            sink.append(rodio::Decoder::new_looped(io::BufReader::new(std::fs::File::open(path)?))?);
+            output.music = Some(sink);
+            return Ok(());
+        }
+        let _ = path;
+        Err(AudioError::Unavailable)
+    }
+
+    pub fn stop_music(&mut self) {
+        #[cfg(feature = "audio")]
+        if let Some(output) = &mut self.output {
+            output.music = None;
+        }
+    }
+
+    pub fn set_music_paused(&self, paused: bool) {
+        #[cfg(feature = "audio")]
+        if let Some(music) = self.output.as_ref().and_then(|output| output.music.as_ref()) {
+            if paused {
+                music.pause();
+            } else {
+                music.play();
+            }
+        }
+        let _ = paused;
+    }
+
+    // 0 is silent, 1 is unchanged. applies to music and to sounds played after this
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.max(0.0);
+        #[cfg(feature = "audio")]
+        if let Some(music) = self.output.as_ref().and_then(|output| output.music.as_ref()) {
+            music.set_volume(self.volume);
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt::Write;
+use crate::context::RenderContext;
+
+// reported with every run, bump along with the dependency
+const WGPU_VERSION: &str = "0.13";
+
+// percentiles of a set of frame times, in milliseconds
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Summary {
+    pub mean: f32,
+    pub min: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Summary {
+    // all zero for no samples
+    pub fn new(samples: &[f32]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        // nearest rank, so every percentile is a time some frame actually took
+        let percentile = |p: f32| sorted[((p * sorted.len() as f32).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Self {
+            mean: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            min: sorted[0],
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+
+    fn to_json(self) -> String {
+        format!(
+            r#"{{"mean":{},"min":{},"p50":{},"p95":{},"p99":{},"max":{}}}"#,
+            self.mean, self.min, self.p50, self.p95, self.p99, self.max,
+        )
+    }
+}
+
+// the same scene timed with async compute on and off, see Renderer::async_compute. frame times
+// from the start of the update, like BenchReport::cpu
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ComputeComparison {
+    pub overlapped: Summary,
+    pub serial: Summary,
+}
+
+impl ComputeComparison {
+    // the share of the mean frame time async compute saved, negative when it cost
+    pub fn speedup(&self) -> f32 {
+        if self.serial.mean > 0.0 {
+            1.0 - self.overlapped.mean / self.serial.mean
+        } else {
+            0.0
+        }
+    }
+
+    fn to_json(self) -> String {
+        format!(
+            r#"{{"overlapped_ms":{},"serial_ms":{},"speedup":{}}}"#,
+            self.overlapped.to_json(), self.serial.to_json(), self.speedup(),
+        )
+    }
+}
+
+// what --bench prints, one json object so runs on different machines and wgpu versions can be
+// diffed or fed to a script
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+    pub frames: u32,
+    pub width: u32,
+    pub height: u32,
+    pub backend: String,
+    pub adapter: String,
+    // encoding and submitting each frame, from the start of the update
+    pub cpu: Summary,
+    // none without timestamp queries
+    pub gpu: Option<Summary>,
+    pub draw_calls: Summary,
+    // none unless async compute is on
+    pub async_compute: Option<ComputeComparison>,
+}
+
+impl BenchReport {
+    // cpu and draw_calls hold one sample per frame, gpu is FrameTimestamps::read
+    pub fn new(context: &RenderContext, cpu: &[f32], gpu: Option<&[f32]>, draw_calls: &[u32]) -> Self {
+        let size = context.physical_size();
+        let info = context.adapter_info();
+        let draw_calls: Vec<f32> = draw_calls.iter().map(|&count| count as f32).collect();
+        Self {
+            frames: cpu.len() as u32,
+            width: size.width,
+            height: size.height,
+            backend: format!("{:?}", info.backend),
+            adapter: info.name,
+            cpu: Summary::new(cpu),
+            gpu: gpu.map(Summary::new),
+            draw_calls: Summary::new(&draw_calls),
+            async_compute: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let gpu = self.gpu.map_or_else(|| "null".to_string(), Summary::to_json);
+        let async_compute = self.async_compute.map_or_else(|| "null".to_string(), ComputeComparison::to_json);
+        format!(
+            r#"{{"frames":{},"width":{},"height":{},"backend":{},"adapter":{},"wgpu":{},"cpu_ms":{},"gpu_ms":{},"draw_calls":{},"async_compute":{}}}"#,
+            self.frames,
+            self.width,
+            self.height,
+            json_string(&self.backend),
+            json_string(&self.adapter),
+            json_string(WGPU_VERSION),
+            self.cpu.to_json(),
+            gpu,
+            self.draw_calls.to_json(),
+            async_compute,
+        )
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
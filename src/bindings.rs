@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use wgpu::*;
+use crate::context::RenderContext;
+
+// the kinds of bindings the renderer uses, entries are numbered in the order they're listed
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Binding {
+    Uniform,
+    // bound size bytes at a time, at the offset passed to set_bind_group
+    DynamicUniform { size: BufferAddress },
+    Storage { read_only: bool },
+    // filterable float 2d texture
+    Texture,
+    // that many textures as one binding_array, needs Features::TEXTURE_BINDING_ARRAY
+    TextureArray { count: u32 },
+    // write only 2d texture, for compute shaders
+    StorageTexture { format: TextureFormat },
+    // depth 2d texture array, read through a ComparisonSampler
+    DepthTextureArray,
+    // filterable float cube array, needs DownlevelFlags::CUBE_ARRAY_TEXTURES
+    TextureCubeArray,
+    // filterable float 3d texture
+    Texture3d,
+    Sampler,
+    ComparisonSampler,
+}
+
+impl Binding {
+    fn ty(self) -> BindingType {
+        match self {
+            Binding::Uniform => BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            Binding::DynamicUniform { size } => BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: BufferSize::new(size),
+            },
+            Binding::Storage { read_only } => BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            Binding::Texture | Binding::TextureArray { .. } => BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            Binding::StorageTexture { format } => BindingType::StorageTexture {
+                access: StorageTextureAccess::WriteOnly,
+                format,
+                view_dimension: TextureViewDimension::D2,
+            },
+            Binding::DepthTextureArray => BindingType::Texture {
+                sample_type: TextureSampleType::Depth,
+                view_dimension: TextureViewDimension::D2Array,
+                multisampled: false,
+            },
+            Binding::TextureCubeArray => BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::CubeArray,
+                multisampled: false,
+            },
+            Binding::Texture3d => BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D3,
+                multisampled: false,
+            },
+            Binding::Sampler => BindingType::Sampler(SamplerBindingType::Filtering),
+            Binding::ComparisonSampler => BindingType::Sampler(SamplerBindingType::Comparison),
+        }
+    }
+
+    fn count(self) -> Option<NonZeroU32> {
+        match self {
+            Binding::TextureArray { count } => NonZeroU32::new(count),
+            _ => None,
+        }
+    }
+
+    fn is_buffer(self) -> bool {
+        matches!(self, Binding::Uniform | Binding::DynamicUniform { .. } | Binding::Storage { .. })
+    }
+}
+
+struct RegisteredLayout {
+    layout: BindGroupLayout,
+    bindings: Vec<Binding>,
+}
+
+// named bind group layouts shared between everything that binds the same group,
+// e.g. "frame" (camera and lights) or "object" (transform and skinning)
+#[derive(Default)]
+pub struct LayoutRegistry {
+    layouts: HashMap<&'static str, RegisteredLayout>,
+}
+
+impl LayoutRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // registering the same name again returns the existing layout
+    pub fn register(&mut self, context: &RenderContext, name: &'static str, entries: &[(Binding, ShaderStages)]) -> &BindGroupLayout {
+        let registered = self.layouts.entry(name).or_insert_with(|| {
+            let layout_entries: Vec<BindGroupLayoutEntry> = entries.iter().enumerate().map(|(index, &(binding, visibility))| {
+                BindGroupLayoutEntry {
+                    binding: index as u32,
+                    visibility,
+                    ty: binding.ty(),
+                    count: binding.count(),
+                }
+            }).collect();
+            RegisteredLayout {
+                layout: context.device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some(name),
+                    entries: &layout_entries,
+                }),
+                bindings: entries.iter().map(|&(binding, _)| binding).collect(),
+            }
+        });
+        assert!(
+            registered.bindings.iter().eq(entries.iter().map(|(binding, _)| binding)),
+            "bind group layout {name} registered twice with different bindings",
+        );
+        &registered.layout
+    }
+
+    pub fn get(&self, name: &str) -> &BindGroupLayout {
+        &self.registered(name).layout
+    }
+
+    fn registered(&self, name: &str) -> &RegisteredLayout {
+        self.layouts.get(name).unwrap_or_else(|| panic!("unknown bind group layout {name}"))
+    }
+}
+
+enum Resource<'a> {
+    Buffer(&'a Buffer),
+    Texture(&'a TextureView),
+    TextureArray(Vec<&'a TextureView>),
+    Sampler(&'a Sampler),
+}
+
+// collects resources in binding order and checks them against the registered layout
+#[derive(Default)]
+pub struct BindGroupBuilder<'a> {
+    resources: Vec<Resource<'a>>,
+}
+
+impl<'a> BindGroupBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn buffer(mut self, buffer: &'a Buffer) -> Self {
+        self.resources.push(Resource::Buffer(buffer));
+        self
+    }
+
+    pub fn texture(mut self, view: &'a TextureView) -> Self {
+        self.resources.push(Resource::Texture(view));
+        self
+    }
+
+    // exactly as many views as the binding's count
+    pub fn texture_array(mut self, views: Vec<&'a TextureView>) -> Self {
+        self.resources.push(Resource::TextureArray(views));
+        self
+    }
+
+    pub fn sampler(mut self, sampler: &'a Sampler) -> Self {
+        self.resources.push(Resource::Sampler(sampler));
+        self
+    }
+
+    pub fn build(self, context: &RenderContext, layouts: &LayoutRegistry, name: &'static str) -> BindGroup {
+        let registered = layouts.registered(name);
+        assert_eq!(registered.bindings.len(), self.resources.len(), "wrong number of resources for {name}");
+        let entries: Vec<BindGroupEntry> = self.resources.iter().zip(&registered.bindings).enumerate()
+            .map(|(index, (resource, &binding))| {
+                let resource = match *resource {
+                    Resource::Buffer(buffer) if binding.is_buffer() => match binding {
+                        Binding::DynamicUniform { size } => BindingResource::Buffer(BufferBinding { buffer, offset: 0, size: BufferSize::new(size) }),
+                        _ => buffer.as_entire_binding(),
+                    },
+                    Resource::Texture(view) if matches!(binding, Binding::Texture | Binding::StorageTexture { .. } | Binding::DepthTextureArray | Binding::TextureCubeArray | Binding::Texture3d) => {
+                        BindingResource::TextureView(view)
+                    }
+                    Resource::TextureArray(ref views) if binding == Binding::TextureArray { count: views.len() as u32 } => {
+                        BindingResource::TextureViewArray(views)
+                    }
+                    Resource::Sampler(sampler) if matches!(binding, Binding::Sampler | Binding::ComparisonSampler) => BindingResource::Sampler(sampler),
+                    _ => panic!("resource {index} of {name} doesn't match its {binding:?} binding"),
+                };
+                BindGroupEntry { binding: index as u32, resource }
+            })
+            .collect();
+        context.device.create_bind_group(&BindGroupDescriptor {
+            label: Some(name),
+            layout: &registered.layout,
+            entries: &entries,
+        })
+    }
+}
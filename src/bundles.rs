@@ -0,0 +1,108 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use wgpu::*;
+use crate::camera::CameraBinding;
+use crate::context::RenderContext;
+use crate::mesh::{MeshPipelineIds, MeshPipelines, SharedObjects};
+use crate::model::ModelInstance;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+// what the bundles are recorded with besides the meshes. the bind groups' generations stand in
+// for the bind groups, which can't be compared
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BundleInputs {
+    pub pipelines: MeshPipelineIds,
+    pub camera: u32,
+    pub shared: u32,
+    pub material_textures: u32,
+}
+
+// the static meshes of every view recorded once into a render bundle each and replayed in the
+// scene pass. transforms are read from buffers as they're drawn, so moving a mesh costs nothing,
+// while adding or removing one, or a change of lod, records them all again. one per frame in
+// flight, each view's camera bind group is
+pub struct StaticBundles {
+    key: Option<u64>,
+    bundles: Vec<RenderBundle>,
+    draw_count: u32,
+}
+
+impl StaticBundles {
+    pub fn new() -> Self {
+        Self {
+            key: None,
+            bundles: Vec::new(),
+            draw_count: 0,
+        }
+    }
+
+    // records the bundles again when anything they were recorded with changed. returns whether
+    // they were
+    #[allow(clippy::too_many_arguments)]
+    pub fn update<'a>(
+        &mut self,
+        context: &RenderContext,
+        inputs: BundleInputs,
+        pipelines: MeshPipelines,
+        camera_bindings: &[CameraBinding],
+        material_textures: &BindGroup,
+        instances: impl Iterator<Item = &'a ModelInstance> + Clone,
+        shared: &SharedObjects,
+    ) -> bool {
+        let mut hasher = DefaultHasher::new();
+        (inputs, camera_bindings.len()).hash(&mut hasher);
+        for instance in instances.clone() {
+            instance.hash_static_draws(&mut hasher);
+        }
+        let key = hasher.finish();
+        if self.key == Some(key) {
+            return false;
+        }
+        self.key = Some(key);
+        self.draw_count = instances.clone().map(|instance| instance.draw_count() - instance.dynamic_draw_count()).sum();
+        self.bundles = camera_bindings.iter().enumerate().map(|(index, camera_binding)| {
+            let label = format!("static meshes {index}");
+            let mut encoder = context.device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+                label: Some(&label),
+                color_formats: &[Some(HDR_FORMAT)],
+                depth_stencil: Some(RenderBundleDepthStencil {
+                    format: DEPTH_FORMAT,
+                    depth_read_only: false,
+                    stencil_read_only: false,
+                }),
+                sample_count: 1,
+                multiview: None,
+            });
+            encoder.set_bind_group(0, &camera_binding.bind_group, &[]);
+            encoder.set_bind_group(2, material_textures, &[]);
+            for instance in instances.clone() {
+                instance.draw_static(&mut encoder, shared, pipelines);
+            }
+            encoder.finish(&RenderBundleDescriptor { label: Some(&label) })
+        }).collect();
+        true
+    }
+
+    // frees the bundles, the next update records them again
+    pub fn clear(&mut self) {
+        self.key = None;
+        self.bundles.clear();
+        self.draw_count = 0;
+    }
+
+    // lined up with the camera bindings they were recorded with
+    pub fn get(&self, view: usize) -> Option<&RenderBundle> {
+        self.bundles.get(view)
+    }
+
+    // per bundle
+    pub fn draw_count(&self) -> u32 {
+        self.draw_count
+    }
+}
+
+impl Default for StaticBundles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
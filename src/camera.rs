@@ -0,0 +1,248 @@
+use std::mem::size_of;
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::context::RenderContext;
+use crate::light::{LightUniform, SceneLights};
+use crate::probes::{ProbeTextures, ProbeUniform};
+use crate::shadows::{ShadowTextures, ShadowUniform};
+use crate::raycast::Ray;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Camera {
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        Mat4::perspective_rh(self.fovy, aspect, self.znear, self.zfar)
+    }
+
+    pub fn view_projection(&self, aspect: f32) -> Mat4 {
+        self.projection(aspect) * self.view()
+    }
+
+    // moves the camera back until the box fits into view
+    pub fn frame(&mut self, min: Vec3, max: Vec3) {
+        let center = (min + max) * 0.5;
+        let radius = ((max - min).length() * 0.5).max(0.01);
+        let distance = radius / (self.fovy * 0.5).tan();
+        self.target = center;
+        self.eye = center + Vec3::new(0.0, 0.3, 1.0).normalize() * distance;
+        self.znear = distance * 0.01;
+        self.zfar = distance * 10.0;
+    }
+
+    // first person movement: translation is (right, up, forward) in camera space, look is
+    // (yaw right, pitch up) in radians. eye and target move together so the distance is kept
+    pub fn fly(&mut self, translation: Vec3, look: Vec2) {
+        let offset = self.target - self.eye;
+        let distance = offset.length();
+        let forward = offset / distance;
+        let right = forward.cross(self.up).normalize();
+
+        // keep the pitch away from straight up or down where look_at breaks down
+        let pitch = (forward.dot(self.up).clamp(-1.0, 1.0).asin() + look.y).clamp(-1.5, 1.5);
+        let yaw = Quat::from_axis_angle(self.up, -look.x);
+        let flat = yaw * (forward - self.up * forward.dot(self.up)).normalize_or_zero();
+        let forward = (flat * pitch.cos() + self.up * pitch.sin()).normalize();
+
+        self.eye += right * translation.x + self.up * translation.y + forward * translation.z;
+        self.target = self.eye + forward * distance;
+    }
+
+    pub fn frustum(&self, aspect: f32) -> Frustum {
+        Frustum::from_matrix(self.view_projection(aspect))
+    }
+
+    // unprojects a pixel position (origin top left) into a world space ray starting on the near plane
+    pub fn screen_to_ray(&self, pixel: Vec2, viewport: Vec2) -> Ray {
+        let ndc = Vec2::new(pixel.x / viewport.x * 2.0 - 1.0, 1.0 - pixel.y / viewport.y * 2.0);
+        let inverse = self.view_projection(viewport.x / viewport.y).inverse();
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        Ray::new(near, (far - near).normalize())
+    }
+
+    // the other way around, the pixel a world position lands on. None behind the camera, points
+    // off to the sides come back outside the viewport
+    pub fn world_to_screen(&self, position: Vec3, viewport: Vec2) -> Option<Vec2> {
+        let clip = self.view_projection(viewport.x / viewport.y) * position.extend(1.0);
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        Some(Vec2::new((ndc.x + 1.0) * 0.5 * viewport.x, (1.0 - ndc.y) * 0.5 * viewport.y))
+    }
+}
+
+// planes point inwards: a point is inside when dot(plane, (p, 1)) >= 0 for all of them
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    pub fn from_matrix(view_proj: Mat4) -> Self {
+        let row = |i| view_proj.row(i);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            // wgpu clip space depth is 0..1
+            row(2),
+            row(3) - row(2),
+        ];
+        Self {
+            planes: planes.map(|plane| plane / plane.truncate().length()),
+        }
+    }
+
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            // test the corner furthest along the plane normal
+            let corner = Vec3::select(plane.truncate().cmpge(Vec3::ZERO), max, min);
+            plane.truncate().dot(corner) + plane.w >= 0.0
+        })
+    }
+
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes.iter().all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            eye: Vec3::new(0.0, 1.5, 5.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            fovy: 60f32.to_radians(),
+            znear: 0.1,
+            zfar: 100.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+    pub view: [[f32; 4]; 4],
+    pub position: [f32; 4],
+    // unprojects clip space back into the world, e.g. for the grid's per pixel rays
+    pub inverse_view_proj: [[f32; 4]; 4],
+}
+
+impl CameraUniform {
+    pub fn new(camera: &Camera, aspect: f32) -> Self {
+        Self::from_matrices(camera.view(), camera.projection(aspect), camera.eye, camera.zfar)
+    }
+
+    // for views that aren't a perspective Camera, e.g. a shadow cascade. far ends up in
+    // position.w
+    pub fn from_matrices(view: Mat4, projection: Mat4, position: Vec3, far: f32) -> Self {
+        let view_proj = projection * view;
+        Self {
+            view_proj: view_proj.to_cols_array_2d(),
+            view: view.to_cols_array_2d(),
+            position: position.extend(far).to_array(),
+            inverse_view_proj: view_proj.inverse().to_cols_array_2d(),
+        }
+    }
+}
+
+pub const FRAME_LAYOUT: &str = "frame";
+
+// the textures bound with every camera, see ShadowMaps::textures and ReflectionProbes::textures
+#[derive(Copy, Clone)]
+pub struct FrameTextures<'a> {
+    pub occlusion: &'a TextureView,
+    pub shadows: ShadowTextures<'a>,
+    pub probes: ProbeTextures<'a>,
+}
+
+// group 0, shared by every pass: camera at binding 0, lights, shadow cascades and probe boxes at
+// binding 1, the ambient occlusion texture at binding 2, the shadow maps at 3 and 4, the point
+// light shadow faces at 5 and the reflection probes' cubes at 6 and 7
+pub struct CameraBinding {
+    pub bind_group: BindGroup,
+    buffer: Buffer,
+    light_buffer: Buffer,
+}
+
+impl CameraBinding {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, textures: FrameTextures) -> Self {
+        let buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("camera"),
+            size: size_of::<CameraUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let light_buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("lights"),
+            size: size_of::<LightUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, FRAME_LAYOUT, &[
+            (Binding::Uniform, ShaderStages::VERTEX | ShaderStages::FRAGMENT | ShaderStages::COMPUTE),
+            (Binding::Uniform, ShaderStages::FRAGMENT),
+            (Binding::Texture, ShaderStages::FRAGMENT),
+            (Binding::DepthTextureArray, ShaderStages::FRAGMENT),
+            (Binding::ComparisonSampler, ShaderStages::FRAGMENT),
+            (Binding::DepthTextureArray, ShaderStages::FRAGMENT),
+            (Binding::TextureCubeArray, ShaderStages::FRAGMENT),
+            (Binding::Sampler, ShaderStages::FRAGMENT),
+        ]);
+        let bind_group = create_bind_group(context, layouts, &buffer, &light_buffer, textures);
+        Self {
+            bind_group,
+            buffer,
+            light_buffer,
+        }
+    }
+
+    // the occlusion texture is recreated with the window size, the shadow maps with their resolution
+    pub fn set_textures(&mut self, context: &RenderContext, layouts: &LayoutRegistry, textures: FrameTextures) {
+        self.bind_group = create_bind_group(context, layouts, &self.buffer, &self.light_buffer, textures);
+    }
+
+    pub fn update(&self, context: &RenderContext, camera: &Camera, aspect: f32) {
+        self.update_uniform(context, &CameraUniform::new(camera, aspect));
+    }
+
+    pub fn update_uniform(&self, context: &RenderContext, uniform: &CameraUniform) {
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(uniform));
+    }
+
+    // shadows are the cascades fit to this camera and the point light faces, probes the captured
+    // reflection probes. NONE for either where their textures aren't bound
+    pub fn update_lights(&self, context: &RenderContext, lights: &SceneLights, shadows: &ShadowUniform, probes: &ProbeUniform) {
+        let uniform = LightUniform::new(lights, *shadows, *probes);
+        context.queue.write_buffer(&self.light_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+}
+
+fn create_bind_group(context: &RenderContext, layouts: &LayoutRegistry, buffer: &Buffer, light_buffer: &Buffer, textures: FrameTextures) -> BindGroup {
+    BindGroupBuilder::new()
+        .buffer(buffer)
+        .buffer(light_buffer)
+        .texture(textures.occlusion)
+        .texture(textures.shadows.maps)
+        .sampler(textures.shadows.sampler)
+        .texture(textures.shadows.point_maps)
+        .texture(textures.probes.maps)
+        .sampler(textures.probes.sampler)
+        .build(context, layouts, FRAME_LAYOUT)
+}
@@ -0,0 +1,16 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+// what the ssao prepass writes: view space normal in xyz, distance along the view direction in w.
+// w stays 0 where nothing was drawn
+fn view_normal_depth(normal: vec3<f32>, world_position: vec3<f32>) -> vec4<f32> {
+    let view_normal = normalize((camera.view * vec4<f32>(normal, 0.0)).xyz);
+    let view_position = camera.view * vec4<f32>(world_position, 1.0);
+    return vec4<f32>(view_normal, -view_position.z);
+}
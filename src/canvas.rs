@@ -0,0 +1,369 @@
+use std::mem::size_of;
+use std::ops::Range;
+use glam::{Affine2, Vec2};
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::context::RenderContext;
+use crate::output::CAPTURE_FORMAT;
+use crate::preprocessor::Preprocessor;
+use crate::texture::Texture;
+
+pub const CANVAS_LAYOUT: &str = "canvas";
+pub const CANVAS_IMAGE_LAYOUT: &str = "canvas image";
+
+// an image the canvas can draw, see Canvas::add_image
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CanvasImage(u32);
+
+// solid shapes sample this one
+const WHITE: CanvasImage = CanvasImage(0);
+
+// per instance, see ShapeIn in canvas.wgsl
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct CanvasShape {
+    // the quad in the shape's own space, a pixel bigger than the shape for antialiasing
+    min: [f32; 2],
+    max: [f32; 2],
+    // from the shape's space to screen pixels
+    x_axis: [f32; 2],
+    y_axis: [f32; 2],
+    translation: [f32; 2],
+    // the rectangle the shape is rounded off from
+    shape_min: [f32; 2],
+    shape_max: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    color: [f32; 4],
+    // screen pixels outside it are cut away, min then max
+    clip: [f32; 4],
+    // corner radius, and the stroke's width or 0 to fill
+    style: [f32; 2],
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct ScreenUniform {
+    size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+// immediate mode 2d shapes and images over the finished frame, in physical pixels from the top
+// left with linear colors. everything queued during the frame goes out in one pass after the
+// output pass and before text, in one draw per run of shapes sharing an image. shapes are
+// antialiased from their distance to the edge, so they stay smooth through any transform
+pub struct Canvas {
+    shapes: Vec<CanvasShape>,
+    // runs of shapes sharing an image, in the order they were queued
+    batches: Vec<(CanvasImage, Range<u32>)>,
+    drawn: Vec<(CanvasImage, Range<u32>)>,
+    transforms: Vec<Affine2>,
+    clips: Vec<[f32; 4]>,
+    shape_buffer: Buffer,
+    shape_capacity: usize,
+    screen_buffer: Buffer,
+    bind_group: BindGroup,
+    // by CanvasImage, WHITE first
+    images: Vec<BindGroup>,
+    pipelines: Vec<(TextureFormat, RenderPipeline)>,
+}
+
+impl Canvas {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry) -> Self {
+        let screen_buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("canvas screen"),
+            size: size_of::<ScreenUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, CANVAS_LAYOUT, &[
+            (Binding::Sampler, ShaderStages::FRAGMENT),
+            (Binding::Uniform, ShaderStages::VERTEX),
+        ]);
+        layouts.register(context, CANVAS_IMAGE_LAYOUT, &[
+            (Binding::Texture, ShaderStages::FRAGMENT),
+        ]);
+        let sampler = context.device.create_sampler(&SamplerDescriptor {
+            label: Some("canvas"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        let bind_group = BindGroupBuilder::new()
+            .sampler(&sampler)
+            .buffer(&screen_buffer)
+            .build(context, layouts, CANVAS_LAYOUT);
+        let white = Texture::solid(context, "canvas white", [255; 4], false);
+
+        // the same targets the output pass draws to
+        let mut formats = vec![context.format];
+        if context.is_hdr() {
+            formats.push(CAPTURE_FORMAT);
+        }
+        let pipelines = formats.into_iter()
+            .map(|format| (format, create_pipeline(context, layouts, format)))
+            .collect();
+        let mut canvas = Self {
+            shapes: Vec::new(),
+            batches: Vec::new(),
+            drawn: Vec::new(),
+            transforms: Vec::new(),
+            clips: Vec::new(),
+            shape_buffer: create_shape_buffer(context, 1024),
+            shape_capacity: 1024,
+            screen_buffer,
+            bind_group,
+            images: Vec::new(),
+            pipelines,
+        };
+        canvas.add_image(context, layouts, &white);
+        canvas
+    }
+
+    // the bind group keeps the texture alive, so it can be dropped after
+    pub fn add_image(&mut self, context: &RenderContext, layouts: &LayoutRegistry, texture: &Texture) -> CanvasImage {
+        self.images.push(BindGroupBuilder::new()
+            .texture(&texture.view)
+            .build(context, layouts, CANVAS_IMAGE_LAYOUT));
+        CanvasImage(self.images.len() as u32 - 1)
+    }
+
+    // shapes queued until the matching pop_transform go through transform, after any pushed
+    // before it
+    pub fn push_transform(&mut self, transform: Affine2) {
+        self.transforms.push(self.transform() * transform);
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.transforms.pop();
+    }
+
+    // shapes queued until the matching pop_clip only show inside the rectangle, as far as it's
+    // inside any pushed before it. it's in the current transform's space, rotated ones clip to
+    // their bounds on screen
+    pub fn push_clip(&mut self, min: Vec2, max: Vec2) {
+        let transform = self.transform();
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)].map(|corner| transform.transform_point2(corner));
+        let screen_min = corners.iter().fold(Vec2::splat(f32::MAX), |bound, corner| bound.min(*corner));
+        let screen_max = corners.iter().fold(Vec2::splat(f32::MIN), |bound, corner| bound.max(*corner));
+        let [x, y, right, bottom] = self.clip();
+        let clip = [screen_min.x.max(x), screen_min.y.max(y), screen_max.x.min(right), screen_max.y.min(bottom)];
+        self.clips.push(clip);
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    pub fn rect(&mut self, min: Vec2, max: Vec2, color: [f32; 4]) {
+        self.shape(min, max, 0.0, 0.0, color);
+    }
+
+    pub fn rounded_rect(&mut self, min: Vec2, max: Vec2, radius: f32, color: [f32; 4]) {
+        self.shape(min, max, radius, 0.0, color);
+    }
+
+    // an outline of width inside the rectangle's edge. radius 0 keeps the corners square
+    pub fn stroke_rect(&mut self, min: Vec2, max: Vec2, radius: f32, width: f32, color: [f32; 4]) {
+        self.shape(min, max, radius, width.max(0.0001), color);
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, color: [f32; 4]) {
+        self.shape(center - radius, center + radius, radius, 0.0, color);
+    }
+
+    pub fn stroke_circle(&mut self, center: Vec2, radius: f32, width: f32, color: [f32; 4]) {
+        self.shape(center - radius, center + radius, radius, width.max(0.0001), color);
+    }
+
+    // with square ends at from and to
+    pub fn line(&mut self, from: Vec2, to: Vec2, width: f32, color: [f32; 4]) {
+        let direction = to - from;
+        let length = direction.length();
+        if length <= 0.0 {
+            return;
+        }
+        // a rectangle along the x axis, turned onto the line
+        self.push_transform(Affine2::from_angle_translation(direction.y.atan2(direction.x), from));
+        self.rect(Vec2::new(0.0, -width * 0.5), Vec2::new(length, width * 0.5), color);
+        self.pop_transform();
+    }
+
+    // stretched over the rectangle and multiplied with tint
+    pub fn image(&mut self, image: CanvasImage, min: Vec2, max: Vec2, tint: [f32; 4]) {
+        self.push(image, min, max, Vec2::ZERO, Vec2::ONE, 0.0, 0.0, tint);
+    }
+
+    // part of the image, in uvs
+    pub fn image_region(&mut self, image: CanvasImage, min: Vec2, max: Vec2, uv_min: Vec2, uv_max: Vec2, tint: [f32; 4]) {
+        self.push(image, min, max, uv_min, uv_max, 0.0, 0.0, tint);
+    }
+
+    fn shape(&mut self, min: Vec2, max: Vec2, radius: f32, stroke: f32, color: [f32; 4]) {
+        self.push(WHITE, min, max, Vec2::ZERO, Vec2::ONE, radius, stroke, color);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push(&mut self, image: CanvasImage, min: Vec2, max: Vec2, uv_min: Vec2, uv_max: Vec2, radius: f32, stroke: f32, color: [f32; 4]) {
+        let transform = self.transform();
+        let clip = self.clip();
+        let scale = transform.matrix2.determinant().abs().sqrt();
+        if scale <= 0.0 || clip[0] >= clip[2] || clip[1] >= clip[3] {
+            return;
+        }
+        let (min, max) = (min.min(max), min.max(max));
+        let margin = Vec2::splat(1.0 / scale);
+        let index = self.shapes.len() as u32;
+        self.shapes.push(CanvasShape {
+            min: (min - margin).to_array(),
+            max: (max + margin).to_array(),
+            x_axis: transform.matrix2.x_axis.to_array(),
+            y_axis: transform.matrix2.y_axis.to_array(),
+            translation: transform.translation.to_array(),
+            shape_min: min.to_array(),
+            shape_max: max.to_array(),
+            uv_min: uv_min.to_array(),
+            uv_max: uv_max.to_array(),
+            color,
+            clip,
+            style: [radius.max(0.0), stroke],
+        });
+        match self.batches.last_mut() {
+            Some((batch_image, range)) if *batch_image == image => range.end = index + 1,
+            _ => self.batches.push((image, index..index + 1)),
+        }
+    }
+
+    fn transform(&self) -> Affine2 {
+        self.transforms.last().copied().unwrap_or(Affine2::IDENTITY)
+    }
+
+    fn clip(&self) -> [f32; 4] {
+        self.clips.last().copied().unwrap_or([f32::MIN, f32::MIN, f32::MAX, f32::MAX])
+    }
+
+    // uploads everything queued since the last update and starts a new batch. transforms and
+    // clips left pushed are dropped
+    pub fn update(&mut self, context: &RenderContext) {
+        if self.shapes.len() > self.shape_capacity {
+            self.shape_capacity = self.shapes.len().next_power_of_two();
+            self.shape_buffer = create_shape_buffer(context, self.shape_capacity);
+        }
+        if !self.shapes.is_empty() {
+            context.queue.write_buffer(&self.shape_buffer, 0, bytemuck::cast_slice(&self.shapes));
+            let size = context.physical_size();
+            let screen = ScreenUniform {
+                size: [size.width.max(1) as f32, size.height.max(1) as f32],
+                _padding: [0.0; 2],
+            };
+            context.queue.write_buffer(&self.screen_buffer, 0, bytemuck::bytes_of(&screen));
+        }
+        self.drawn = std::mem::take(&mut self.batches);
+        self.shapes.clear();
+        self.transforms.clear();
+        self.clips.clear();
+    }
+
+    // one draw per run of shapes sharing an image
+    pub fn draw_count(&self) -> u32 {
+        self.drawn.len() as u32
+    }
+
+    // over what's already in target, format is one the output pass draws to
+    pub fn draw(&self, cmd: &mut CommandEncoder, target: &TextureView, format: TextureFormat) {
+        if self.drawn.is_empty() {
+            return;
+        }
+        let pipeline = self.pipelines.iter()
+            .find(|(pipeline_format, _)| *pipeline_format == format)
+            .map(|(_, pipeline)| pipeline)
+            .unwrap_or_else(|| panic!("no canvas pipeline for {format:?}"));
+        let mut render_cmd = cmd.begin_render_pass(&RenderPassDescriptor {
+            label: Some("canvas"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                    view: target,
+                    resolve_target: None,
+                })
+            ],
+            depth_stencil_attachment: None,
+        });
+        render_cmd.set_pipeline(pipeline);
+        render_cmd.set_bind_group(0, &self.bind_group, &[]);
+        render_cmd.set_vertex_buffer(0, self.shape_buffer.slice(..));
+        for (image, shapes) in &self.drawn {
+            render_cmd.set_bind_group(1, &self.images[image.0 as usize], &[]);
+            render_cmd.draw(0..4, shapes.clone());
+        }
+    }
+}
+
+fn create_pipeline(context: &RenderContext, layouts: &LayoutRegistry, format: TextureFormat) -> RenderPipeline {
+    let device = &context.device;
+    let shader_module = Preprocessor::new().target(format).create_module(context, "canvas.wgsl");
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("canvas"),
+        bind_group_layouts: &[layouts.get(CANVAS_LAYOUT), layouts.get(CANVAS_IMAGE_LAYOUT)],
+        push_constant_ranges: &[],
+    });
+    let label = format!("canvas {format:?}");
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(&label),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            entry_point: "vertex",
+            module: &shader_module,
+            buffers: &[
+                VertexBufferLayout {
+                    array_stride: size_of::<CanvasShape>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32x2,
+                        3 => Float32x2,
+                        4 => Float32x2,
+                        5 => Float32x2,
+                        6 => Float32x2,
+                        7 => Float32x2,
+                        8 => Float32x2,
+                        9 => Float32x4,
+                        10 => Float32x4,
+                        11 => Float32x2,
+                    ],
+                },
+            ],
+        },
+        fragment: Some(FragmentState {
+            entry_point: "fragment",
+            module: &shader_module,
+            targets: &[
+                Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })
+            ],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleStrip,
+            ..PrimitiveState::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+fn create_shape_buffer(context: &RenderContext, capacity: usize) -> Buffer {
+    context.device.create_buffer(&BufferDescriptor {
+        label: Some("canvas shapes"),
+        size: (capacity * size_of::<CanvasShape>()) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
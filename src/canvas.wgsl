@@ -0,0 +1,84 @@
+#include "color.wgsl"
+
+struct Screen {
+    // physical pixels
+    size: vec2<f32>,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0) var image_sampler: sampler;
+@group(0) @binding(1) var<uniform> screen: Screen;
+@group(1) @binding(0) var image: texture_2d<f32>;
+
+// see CanvasShape
+struct ShapeIn {
+    @location(0) min: vec2<f32>,
+    @location(1) max: vec2<f32>,
+    @location(2) x_axis: vec2<f32>,
+    @location(3) y_axis: vec2<f32>,
+    @location(4) translation: vec2<f32>,
+    @location(5) shape_min: vec2<f32>,
+    @location(6) shape_max: vec2<f32>,
+    @location(7) uv_min: vec2<f32>,
+    @location(8) uv_max: vec2<f32>,
+    @location(9) color: vec4<f32>,
+    @location(10) clip: vec4<f32>,
+    // corner radius, stroke width or 0 to fill
+    @location(11) style: vec2<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    // in the shape's own space
+    @location(0) shape_position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) clip: vec4<f32>,
+    @location(4) shape_min: vec2<f32>,
+    @location(5) shape_max: vec2<f32>,
+    @location(6) style: vec2<f32>,
+    // screen pixels, which the clip rectangle is in whatever size the target is
+    @location(7) pixel: vec2<f32>,
+}
+
+// a triangle strip of 4 vertices per shape
+@vertex
+fn vertex(@builtin(vertex_index) index: u32, shape: ShapeIn) -> VertexOut {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    let shape_position = mix(shape.min, shape.max, corner);
+    let pixel = shape.x_axis * shape_position.x + shape.y_axis * shape_position.y + shape.translation;
+    var out: VertexOut;
+    out.pos = vec4<f32>(pixel / screen.size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.shape_position = shape_position;
+    // the quad is bigger than the shape, the uvs carry on past its edges
+    out.uv = mix(shape.uv_min, shape.uv_max, (shape_position - shape.shape_min) / max(shape.shape_max - shape.shape_min, vec2<f32>(0.0001)));
+    out.color = shape.color;
+    out.clip = shape.clip;
+    out.shape_min = shape.shape_min;
+    out.shape_max = shape.shape_max;
+    out.style = shape.style;
+    out.pixel = pixel;
+    return out;
+}
+
+// how far outside the rounded rectangle position is, negative inside
+fn rounded_rect_distance(position: vec2<f32>, min_corner: vec2<f32>, max_corner: vec2<f32>, radius: f32) -> f32 {
+    let half_size = (max_corner - min_corner) * 0.5;
+    let corner_radius = min(radius, min(half_size.x, half_size.y));
+    let q = abs(position - (min_corner + half_size)) - half_size + corner_radius;
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - corner_radius;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let texel = textureSample(image, image_sampler, in.uv);
+    var edge = rounded_rect_distance(in.shape_position, in.shape_min, in.shape_max, in.style.x);
+    // a stroke is the band just inside the edge
+    let stroke = in.style.y;
+    edge = select(edge, abs(edge + stroke * 0.5) - stroke * 0.5, stroke > 0.0);
+    // a pixel's worth of distance, whatever the shape is scaled by
+    let coverage = clamp(0.5 - edge / max(fwidth(edge), 0.0001), 0.0, 1.0);
+    let clipped = all(in.pixel >= in.clip.xy) && all(in.pixel < in.clip.zw);
+    let color = in.color * texel;
+    return output_color(vec4<f32>(color.rgb, color.a * select(0.0, coverage, clipped)));
+}
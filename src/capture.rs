@@ -0,0 +1,73 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use image::{ImageResult, RgbaImage};
+use wgpu::*;
+use crate::context::RenderContext;
+
+// copies a whole 4 byte per pixel texture into an image, blocking until the gpu is done
+pub fn read_texture(context: &RenderContext, texture: &Texture, format: TextureFormat, width: u32, height: u32) -> RgbaImage {
+    let size = Extent3d { width, height, depth_or_array_layers: 1 };
+    let mut pixels = context.read_texture(texture.as_image_copy(), format, size)
+        .wait(context)
+        .unwrap_or_else(|error| panic!("{error}"));
+    if matches!(format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb) {
+        pixels.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+    }
+    RgbaImage::from_raw(width, height, pixels).expect("readback size mismatch")
+}
+
+enum Sink {
+    Png(PathBuf),
+    Ffmpeg(Child),
+}
+
+// writes frames either as numbered pngs into a directory, or to ffmpeg when the path looks like a video file
+pub struct Recorder {
+    sink: Sink,
+    frame: u32,
+}
+
+impl Recorder {
+    pub fn new(path: impl AsRef<Path>, width: u32, height: u32, fps: u32) -> ImageResult<Self> {
+        let path = path.as_ref();
+        let is_video = path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| matches!(extension, "mp4" | "mkv" | "mov" | "webm" | "gif"));
+        let sink = if is_video {
+            let child = Command::new("ffmpeg")
+                .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+                .args(["-s", &format!("{width}x{height}"), "-r", &fps.to_string(), "-i", "-"])
+                .arg(path)
+                .stdin(Stdio::piped())
+                .spawn()?;
+            Sink::Ffmpeg(child)
+        } else {
+            fs::create_dir_all(path)?;
+            Sink::Png(path.to_path_buf())
+        };
+        Ok(Self { sink, frame: 0 })
+    }
+
+    pub fn write(&mut self, image: &RgbaImage) -> ImageResult<()> {
+        match &mut self.sink {
+            Sink::Png(dir) => image.save(dir.join(format!("frame_{:05}.png", self.frame)))?,
+            Sink::Ffmpeg(child) => child.stdin.as_mut().expect("ffmpeg stdin is piped").write_all(image.as_raw())?,
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    // closes the ffmpeg pipe and waits for the encoder to finish
+    pub fn finish(self) -> ImageResult<()> {
+        if let Sink::Ffmpeg(mut child) = self.sink {
+            drop(child.stdin.take());
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(std::io::Error::other(format!("ffmpeg exited with {status}")).into());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ClipboardError {
+    // built without the clipboard feature, or there's no clipboard to connect to
+    Unavailable,
+    #[cfg(feature = "clipboard")]
+    Clipboard(arboard::Error),
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipboardError::Unavailable => write!(f, "no clipboard"),
+            #[cfg(feature = "clipboard")]
+            ClipboardError::Clipboard(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ClipboardError {}
+
+#[cfg(feature = "clipboard")]
+impl From<arboard::Error> for ClipboardError {
+    fn from(error: arboard::Error) -> Self {
+        ClipboardError::Clipboard(error)
+    }
+}
+
+// rgba8, rows top to bottom
+pub struct ClipboardImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+// reads the system clipboard. without the clipboard feature, or without a clipboard, everything
+// returns ClipboardError::Unavailable
+pub struct Clipboard {
+    #[cfg(feature = "clipboard")]
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "clipboard")]
+            clipboard: arboard::Clipboard::new()
+                .map_err(|error| tracing::warn!("clipboard unavailable: {error}"))
+                .ok(),
+        }
+    }
+
+    // fails when there's no image on the clipboard, e.g. it holds text
+    pub fn image(&mut self) -> Result<ClipboardImage, ClipboardError> {
+        #[cfg(feature = "clipboard")]
+        if let Some(clipboard) = &mut self.clipboard {
+            let image = clipboard.get_image()?;
+            return Ok(ClipboardImage {
+                width: image.width as u32,
+                height: image.height as u32,
+                rgba: image.bytes.into_owned(),
+            });
+        }
+        Err(ClipboardError::Unavailable)
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
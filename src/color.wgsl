@@ -0,0 +1,22 @@
+fn srgb_encode(color: vec3<f32>) -> vec3<f32> {
+    let rgb = max(color, vec3<f32>(0.0));
+    let low = rgb * 12.92;
+    let high = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, rgb <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(color: vec3<f32>) -> vec3<f32> {
+    let rgb = max(color, vec3<f32>(0.0));
+    let low = rgb / 12.92;
+    let high = pow((rgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, rgb <= vec3<f32>(0.04045));
+}
+
+// shaders work in linear color. srgb targets encode on write, anything else is encoded here
+fn output_color(color: vec4<f32>) -> vec4<f32> {
+#ifdef ENCODE_SRGB
+    return vec4<f32>(srgb_encode(color.rgb), color.a);
+#else
+    return color;
+#endif
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::num::NonZeroU32;
+use std::path::Path;
+use wgpu::*;
+use crate::context::RenderContext;
+
+// the common size, and what the identity lut is made at
+pub const DEFAULT_LUT_SIZE: u32 = 32;
+// wgpu's downlevel limit for 3d textures
+const MAX_LUT_SIZE: u32 = 256;
+
+#[derive(Debug)]
+pub enum LutError {
+    Io(io::Error),
+    Image(image::ImageError),
+    // a strip that isn't size * size wide and size high, or a .cube file that doesn't parse
+    Invalid(String),
+}
+
+impl fmt::Display for LutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LutError::Io(error) => error.fmt(f),
+            LutError::Image(error) => error.fmt(f),
+            LutError::Invalid(message) => write!(f, "invalid lut: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LutError {}
+
+impl From<io::Error> for LutError {
+    fn from(error: io::Error) -> Self {
+        LutError::Io(error)
+    }
+}
+
+impl From<image::ImageError> for LutError {
+    fn from(error: image::ImageError) -> Self {
+        LutError::Image(error)
+    }
+}
+
+// a 3d color lookup table read without a device. it maps srgb encoded colors to srgb encoded
+// colors, red along x, green along y and blue along z, as rgba8
+pub struct LutData {
+    pub size: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl LutData {
+    // leaves every color as it is
+    pub fn identity(size: u32) -> Self {
+        let size = size.clamp(2, MAX_LUT_SIZE);
+        let level = |index: u32| (index * 255 / (size - 1)) as u8;
+        let mut rgba = Vec::with_capacity((size * size * size * 4) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    rgba.extend_from_slice(&[level(r), level(g), level(b), 255]);
+                }
+            }
+        }
+        Self {
+            size,
+            rgba,
+        }
+    }
+
+    // .cube files as written by resolve and most grading tools, anything else as an image strip
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, LutError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("cube") => Self::from_cube(&fs::read_to_string(path)?),
+            _ => Self::from_strip(&image::open(path)?),
+        }
+    }
+
+    // size slices of size by size side by side, blue going up from left to right, red along
+    // each slice and green down it. a graded screenshot of the identity strip is one of these
+    pub fn from_strip(image: &image::DynamicImage) -> Result<Self, LutError> {
+        let strip = image.to_rgba8();
+        let size = strip.height();
+        if !(2..=MAX_LUT_SIZE).contains(&size) || strip.width() != size * size {
+            return Err(LutError::Invalid(format!("a {}x{} strip isn't size * size by size", strip.width(), size)));
+        }
+        let mut rgba = Vec::with_capacity((size * size * size * 4) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    rgba.extend_from_slice(&strip.get_pixel(b * size + r, g).0);
+                }
+            }
+        }
+        Ok(Self {
+            size,
+            rgba,
+        })
+    }
+
+    // the adobe cube format: keywords, then size³ lines of r g b with red changing fastest.
+    // values are scaled from the domain to 0..1 and then stored at 8 bits
+    pub fn from_cube(text: &str) -> Result<Self, LutError> {
+        let mut size = None;
+        let mut domain_min = [0.0f32; 3];
+        let mut domain_max = [1.0f32; 3];
+        let mut values = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let invalid = |what: &str| LutError::Invalid(format!("line {}: {what}", number + 1));
+            let floats = |words: std::str::SplitWhitespace| -> Result<[f32; 3], LutError> {
+                let floats: Vec<f32> = words.map(str::parse).collect::<Result<_, _>>().map_err(|_| invalid("expected numbers"))?;
+                floats.try_into().map_err(|_| invalid("expected three numbers"))
+            };
+            match words.next() {
+                Some("TITLE") => {}
+                Some("LUT_1D_SIZE") => return Err(invalid("1d luts aren't supported")),
+                Some("LUT_3D_SIZE") => {
+                    let parsed = words.next().and_then(|word| word.parse::<u32>().ok())
+                        .filter(|size| (2..=MAX_LUT_SIZE).contains(size))
+                        .ok_or_else(|| invalid(&format!("the size has to be 2 to {MAX_LUT_SIZE}")))?;
+                    size = Some(parsed);
+                }
+                Some("DOMAIN_MIN") => domain_min = floats(words)?,
+                Some("DOMAIN_MAX") => domain_max = floats(words)?,
+                Some(word) if word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => {
+                    values.push(floats(line.split_whitespace())?);
+                }
+                // other keywords, e.g. LUT_IN_VIDEO_RANGE, don't change the table
+                _ => {}
+            }
+        }
+        let size = size.ok_or_else(|| LutError::Invalid("no LUT_3D_SIZE".into()))?;
+        if values.len() != (size * size * size) as usize {
+            return Err(LutError::Invalid(format!("{} entries for a size of {size}", values.len())));
+        }
+        let mut rgba = Vec::with_capacity(values.len() * 4);
+        for value in values {
+            for channel in 0..3 {
+                let range = (domain_max[channel] - domain_min[channel]).max(f32::EPSILON);
+                let unit = ((value[channel] - domain_min[channel]) / range).clamp(0.0, 1.0);
+                rgba.push((unit * 255.0).round() as u8);
+            }
+            rgba.push(255);
+        }
+        Ok(Self {
+            size,
+            rgba,
+        })
+    }
+}
+
+// a lut uploaded as a 3d texture, sampled by the output pass
+pub struct ColorLut {
+    pub view: TextureView,
+    pub size: u32,
+}
+
+impl ColorLut {
+    pub fn new(context: &RenderContext, label: &str, data: &LutData) -> Self {
+        let size = Extent3d {
+            width: data.size,
+            height: data.size,
+            depth_or_array_layers: data.size,
+        };
+        let texture = context.device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        context.queue.write_texture(
+            texture.as_image_copy(),
+            &data.rgba,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(data.size * 4),
+                rows_per_image: NonZeroU32::new(data.size),
+            },
+            size,
+        );
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some(label),
+            ..TextureViewDescriptor::default()
+        });
+        Self {
+            view,
+            size: data.size,
+        }
+    }
+
+    pub fn load(context: &RenderContext, path: impl AsRef<Path>) -> Result<Self, LutError> {
+        let path = path.as_ref();
+        Ok(Self::new(context, &path.display().to_string(), &LutData::read(path)?))
+    }
+}
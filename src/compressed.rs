@@ -0,0 +1,274 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use wgpu::TextureFormat;
+use crate::embedded;
+
+const KTX2_IDENTIFIER: [u8; 12] = [0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n'];
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+// DDS_HEADER.dwCaps2
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+#[derive(Debug)]
+pub enum CompressedError {
+    Io(io::Error),
+    // truncated, or not a ktx2 or dds file at all
+    Invalid(String),
+    // a valid file this doesn't handle, e.g. a cubemap or an uncompressed pixel format
+    Unsupported(String),
+    Decode(String),
+}
+
+impl fmt::Display for CompressedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressedError::Io(error) => error.fmt(f),
+            CompressedError::Invalid(message) => write!(f, "invalid texture file: {message}"),
+            CompressedError::Unsupported(message) => write!(f, "unsupported texture: {message}"),
+            CompressedError::Decode(message) => write!(f, "failed to decompress texture: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for CompressedError {}
+
+impl From<io::Error> for CompressedError {
+    fn from(error: io::Error) -> Self {
+        CompressedError::Io(error)
+    }
+}
+
+// a 2d texture with its mip chain as stored in a ktx2 or dds file, still block compressed.
+// whether it's srgb is part of the format, the file decides that rather than whoever loads it
+pub struct CompressedImage {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    // largest first
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CompressedError> {
+        let bytes = embedded::read(path)?;
+        if bytes.starts_with(&KTX2_IDENTIFIER) {
+            Self::from_ktx2(&bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            Self::from_dds(&bytes)
+        } else {
+            Err(CompressedError::Invalid("neither ktx2 nor dds".into()))
+        }
+    }
+
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, CompressedError> {
+        let reader = Reader(bytes);
+        if !bytes.starts_with(&KTX2_IDENTIFIER) {
+            return Err(CompressedError::Invalid("missing ktx2 identifier".into()));
+        }
+        let vk_format = reader.u32(12)?;
+        let width = reader.u32(20)?;
+        let height = reader.u32(24)?.max(1);
+        let (depth, layers, faces) = (reader.u32(28)?, reader.u32(32)?, reader.u32(36)?);
+        let level_count = reader.u32(40)?.clamp(1, max_levels(width, height));
+        let supercompression = reader.u32(44)?;
+        if width == 0 {
+            return Err(CompressedError::Invalid("zero width".into()));
+        }
+        if depth > 1 || layers > 1 || faces > 1 {
+            return Err(CompressedError::Unsupported("only single 2d textures are supported".into()));
+        }
+        if supercompression != 0 {
+            return Err(CompressedError::Unsupported(format!("supercompression scheme {supercompression}")));
+        }
+        let format = vk_format_to_wgpu(vk_format)
+            .ok_or_else(|| CompressedError::Unsupported(format!("vulkan format {vk_format}")))?;
+
+        // the level index follows the 80 byte header, 3 u64s per level
+        let levels = (0..level_count).map(|level| {
+            let entry = 80 + level as usize * 24;
+            let offset = reader.u64(entry)? as usize;
+            let length = reader.u64(entry + 8)? as usize;
+            let expected = level_byte_size(format, width, height, level);
+            if length < expected {
+                return Err(CompressedError::Invalid(format!("level {level} is {length} bytes, expected {expected}")));
+            }
+            Ok(reader.bytes(offset, expected)?.to_vec())
+        }).collect::<Result<_, _>>()?;
+        Ok(Self { format, width, height, levels })
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, CompressedError> {
+        let reader = Reader(bytes);
+        if !bytes.starts_with(DDS_MAGIC) {
+            return Err(CompressedError::Invalid("missing dds magic".into()));
+        }
+        // offsets are from the start of the file, the header starts after the 4 byte magic
+        let height = reader.u32(12)?;
+        let width = reader.u32(16)?;
+        if width == 0 || height == 0 {
+            return Err(CompressedError::Invalid("zero size".into()));
+        }
+        let level_count = reader.u32(28)?.clamp(1, max_levels(width, height));
+        let four_cc = reader.bytes(84, 4)?;
+        let caps2 = reader.u32(112)?;
+        if caps2 & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 {
+            return Err(CompressedError::Unsupported("only single 2d textures are supported".into()));
+        }
+
+        let (format, mut offset) = if four_cc == b"DX10" {
+            let dxgi_format = reader.u32(128)?;
+            let array_size = reader.u32(140)?;
+            if array_size > 1 {
+                return Err(CompressedError::Unsupported("only single 2d textures are supported".into()));
+            }
+            let format = dxgi_format_to_wgpu(dxgi_format)
+                .ok_or_else(|| CompressedError::Unsupported(format!("dxgi format {dxgi_format}")))?;
+            (format, 148)
+        } else {
+            let format = four_cc_to_wgpu(four_cc)
+                .ok_or_else(|| CompressedError::Unsupported(format!("four cc {}", String::from_utf8_lossy(four_cc))))?;
+            (format, 128)
+        };
+
+        let levels = (0..level_count).map(|level| {
+            let size = level_byte_size(format, width, height, level);
+            let data = reader.bytes(offset, size)?.to_vec();
+            offset += size;
+            Ok(data)
+        }).collect::<Result<_, CompressedError>>()?;
+        Ok(Self { format, width, height, levels })
+    }
+
+    pub fn level_size(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    // what decompress produces
+    pub fn decompressed_format(&self) -> TextureFormat {
+        if self.format.describe().srgb {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        }
+    }
+
+    // every level as rgba8, for devices without bc support. bc6h is hdr and gets clamped to 0..1,
+    // snorm formats come out as unorm
+    pub fn decompress(&self) -> Result<Vec<Vec<u8>>, CompressedError> {
+        self.levels.iter().enumerate().map(|(level, data)| {
+            let (width, height) = self.level_size(level as u32);
+            let (width, height) = (width as usize, height as usize);
+            let mut pixels = vec![0u32; width * height];
+            let result = match self.format {
+                TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb => texture2ddecoder::decode_bc1a(data, width, height, &mut pixels),
+                TextureFormat::Bc2RgbaUnorm | TextureFormat::Bc2RgbaUnormSrgb => texture2ddecoder::decode_bc2(data, width, height, &mut pixels),
+                TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb => texture2ddecoder::decode_bc3(data, width, height, &mut pixels),
+                TextureFormat::Bc4RUnorm | TextureFormat::Bc4RSnorm => texture2ddecoder::decode_bc4(data, width, height, &mut pixels),
+                TextureFormat::Bc5RgUnorm | TextureFormat::Bc5RgSnorm => texture2ddecoder::decode_bc5(data, width, height, &mut pixels),
+                TextureFormat::Bc6hRgbUfloat => texture2ddecoder::decode_bc6_unsigned(data, width, height, &mut pixels),
+                TextureFormat::Bc6hRgbSfloat => texture2ddecoder::decode_bc6_signed(data, width, height, &mut pixels),
+                TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb => texture2ddecoder::decode_bc7(data, width, height, &mut pixels),
+                TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => return Ok(data.clone()),
+                format => return Err(CompressedError::Unsupported(format!("{format:?}"))),
+            };
+            result.map_err(|error| CompressedError::Decode(error.into()))?;
+            // the decoder packs pixels as 0xaarrggbb
+            Ok(pixels.iter().flat_map(|pixel| {
+                let [b, g, r, a] = pixel.to_le_bytes();
+                [r, g, b, a]
+            }).collect())
+        }).collect()
+    }
+}
+
+// bytes a level takes up, whole blocks only
+pub fn level_byte_size(format: TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let info = format.describe();
+    let (block_width, block_height) = (info.block_dimensions.0 as u32, info.block_dimensions.1 as u32);
+    let width = (width >> level).max(1).div_ceil(block_width);
+    let height = (height >> level).max(1).div_ceil(block_height);
+    (width * height) as usize * info.block_size as usize
+}
+
+// some exporters write a level count that goes past 1x1
+fn max_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// bounds checked little endian reads
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: usize, length: usize) -> Result<&'a [u8], CompressedError> {
+        offset.checked_add(length)
+            .and_then(|end| self.0.get(offset..end))
+            .ok_or_else(|| CompressedError::Invalid(format!("truncated at byte {offset}")))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, CompressedError> {
+        Ok(u32::from_le_bytes(self.bytes(offset, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64, CompressedError> {
+        Ok(u64::from_le_bytes(self.bytes(offset, 8)?.try_into().unwrap()))
+    }
+}
+
+// VkFormat values. bc1 without alpha decodes the same as with, wgpu only has the rgba variant
+fn vk_format_to_wgpu(format: u32) -> Option<TextureFormat> {
+    Some(match format {
+        37 => TextureFormat::Rgba8Unorm,
+        43 => TextureFormat::Rgba8UnormSrgb,
+        131 | 133 => TextureFormat::Bc1RgbaUnorm,
+        132 | 134 => TextureFormat::Bc1RgbaUnormSrgb,
+        135 => TextureFormat::Bc2RgbaUnorm,
+        136 => TextureFormat::Bc2RgbaUnormSrgb,
+        137 => TextureFormat::Bc3RgbaUnorm,
+        138 => TextureFormat::Bc3RgbaUnormSrgb,
+        139 => TextureFormat::Bc4RUnorm,
+        140 => TextureFormat::Bc4RSnorm,
+        141 => TextureFormat::Bc5RgUnorm,
+        142 => TextureFormat::Bc5RgSnorm,
+        143 => TextureFormat::Bc6hRgbUfloat,
+        144 => TextureFormat::Bc6hRgbSfloat,
+        145 => TextureFormat::Bc7RgbaUnorm,
+        146 => TextureFormat::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+// DXGI_FORMAT values, the typeless ones are read as unorm
+fn dxgi_format_to_wgpu(format: u32) -> Option<TextureFormat> {
+    Some(match format {
+        70 | 71 => TextureFormat::Bc1RgbaUnorm,
+        72 => TextureFormat::Bc1RgbaUnormSrgb,
+        73 | 74 => TextureFormat::Bc2RgbaUnorm,
+        75 => TextureFormat::Bc2RgbaUnormSrgb,
+        76 | 77 => TextureFormat::Bc3RgbaUnorm,
+        78 => TextureFormat::Bc3RgbaUnormSrgb,
+        79 | 80 => TextureFormat::Bc4RUnorm,
+        81 => TextureFormat::Bc4RSnorm,
+        82 | 83 => TextureFormat::Bc5RgUnorm,
+        84 => TextureFormat::Bc5RgSnorm,
+        94 | 95 => TextureFormat::Bc6hRgbUfloat,
+        96 => TextureFormat::Bc6hRgbSfloat,
+        97 | 98 => TextureFormat::Bc7RgbaUnorm,
+        99 => TextureFormat::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+// the pre-dx10 way of naming formats, no srgb variants
+fn four_cc_to_wgpu(four_cc: &[u8]) -> Option<TextureFormat> {
+    Some(match four_cc {
+        b"DXT1" => TextureFormat::Bc1RgbaUnorm,
+        b"DXT2" | b"DXT3" => TextureFormat::Bc2RgbaUnorm,
+        b"DXT4" | b"DXT5" => TextureFormat::Bc3RgbaUnorm,
+        b"ATI1" | b"BC4U" => TextureFormat::Bc4RUnorm,
+        b"BC4S" => TextureFormat::Bc4RSnorm,
+        b"ATI2" | b"BC5U" => TextureFormat::Bc5RgUnorm,
+        b"BC5S" => TextureFormat::Bc5RgSnorm,
+        _ => return None,
+    })
+}
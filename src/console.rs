@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use glam::Vec2;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+use crate::app::Engine;
+use crate::logging;
+use crate::text::{TextRenderer, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+// of the window height, from the top
+const HEIGHT: f32 = 0.45;
+// around the text, in font pixels
+const PADDING: f32 = 4.0;
+const MAX_INPUT: usize = 200;
+// entered lines kept for up and down
+const MAX_ENTERED: usize = 64;
+const BACKGROUND: [f32; 4] = [0.01, 0.01, 0.015, 0.85];
+const LOG_COLOR: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+const WARN_COLOR: [f32; 4] = [1.0, 0.7, 0.1, 1.0];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.15, 0.1, 1.0];
+const INPUT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// gets the app the console belongs to and the words after the command's name. the Ok text is
+// logged if there is any, errors are logged as warnings
+pub type Command<A> = fn(&mut A, &mut Engine, &[&str]) -> Result<String, String>;
+
+struct Registered<A> {
+    run: Command<A>,
+    // the arguments it takes, for help
+    usage: &'static str,
+}
+
+// a drop-down over the top of the window showing the log, see logging::history, with a line to
+// type commands into. toggle it from the app's input, while it's open feed it window events
+// before anything else and run what was entered with execute. command output goes to the log,
+// so it shows up in order with everything else
+pub struct Console<A> {
+    pub open: bool,
+    input: String,
+    // oldest first
+    entered: Vec<String>,
+    // the entered line being shown instead of the input, by up and down
+    browsing: Option<usize>,
+    pending: Vec<String>,
+    commands: BTreeMap<&'static str, Registered<A>>,
+}
+
+impl<A> Console<A> {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            input: String::new(),
+            entered: Vec::new(),
+            browsing: None,
+            pending: Vec::new(),
+            commands: BTreeMap::new(),
+        }
+    }
+
+    // registering a name again replaces the command. "help" is built in and lists them
+    pub fn register(&mut self, name: &'static str, usage: &'static str, run: Command<A>) -> &mut Self {
+        self.commands.insert(name, Registered { run, usage });
+        self
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    // returns whether the event was the console's, which it is for every key while it's open.
+    // ` and escape close it
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        if !self.open {
+            return false;
+        }
+        match *event {
+            WindowEvent::ReceivedCharacter(character) => {
+                match character {
+                    '\r' | '\n' => self.submit(),
+                    // backspace
+                    '\u{8}' => {
+                        self.take_browsed();
+                        self.input.pop();
+                    }
+                    // the toggle key types these too
+                    '`' | '~' => {}
+                    character if !character.is_control() && self.input.len() < MAX_INPUT => {
+                        self.take_browsed();
+                        self.input.push(character);
+                    }
+                    _ => {}
+                }
+                true
+            }
+            WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode, .. }, .. } => {
+                if state == ElementState::Pressed {
+                    match virtual_keycode {
+                        Some(VirtualKeyCode::Grave | VirtualKeyCode::Escape) => self.open = false,
+                        Some(VirtualKeyCode::Up) => self.browse(-1),
+                        Some(VirtualKeyCode::Down) => self.browse(1),
+                        _ => {}
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // runs what was entered since the last call. commands need the app the console is part of,
+    // `console` finds it in there again
+    pub fn execute(app: &mut A, engine: &mut Engine, console: fn(&mut A) -> &mut Console<A>) {
+        for line in std::mem::take(&mut console(app).pending) {
+            tracing::info!("> {line}");
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let Some((&name, args)) = words.split_first() else {
+                continue;
+            };
+            let commands = &console(app).commands;
+            if name == "help" {
+                for (name, command) in commands {
+                    tracing::info!("{name} {}", command.usage);
+                }
+                continue;
+            }
+            let Some(run) = commands.get(name).map(|command| command.run) else {
+                tracing::warn!("unknown command {name}, try help");
+                continue;
+            };
+            match run(app, engine, args) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => tracing::info!("{output}"),
+                Err(error) => tracing::warn!("{name}: {error}"),
+            }
+        }
+    }
+
+    // queues the console for this frame, nothing while it's closed. scale is font pixels per
+    // screen pixel
+    pub fn draw(&self, text: &mut TextRenderer, width: u32, height: u32, scale: f32) {
+        if !self.open {
+            return;
+        }
+        let bottom = (height as f32 * HEIGHT).round();
+        text.rect(Vec2::ZERO, Vec2::new(width as f32, bottom), BACKGROUND);
+        let padding = PADDING * scale;
+        let line_height = TextRenderer::line_height(scale);
+        let columns = ((width as f32 - padding * 2.0) / TextRenderer::advance(scale)).max(0.0) as usize;
+
+        let mut y = bottom - padding - line_height;
+        let input = format!("> {}", self.shown_input());
+        // the end of the line when it's longer than the console is wide
+        let input: String = input.chars().skip(input.chars().count().saturating_sub(columns.saturating_sub(1))).collect();
+        let end = text.text(Vec2::new(padding, y), scale, INPUT_COLOR, &input);
+        text.rect(end, end + Vec2::new(GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32) * scale, INPUT_COLOR);
+
+        for line in logging::history().iter().rev() {
+            y -= line_height;
+            if y < padding {
+                break;
+            }
+            let line: String = line.chars().take(columns).collect();
+            text.text(Vec2::new(padding, y), scale, log_color(&line), &line);
+        }
+    }
+
+    fn shown_input(&self) -> &str {
+        match self.browsing {
+            Some(index) => &self.entered[index],
+            None => &self.input,
+        }
+    }
+
+    // editing a line from the history makes it the input
+    fn take_browsed(&mut self) {
+        if let Some(index) = self.browsing.take() {
+            self.input = self.entered[index].clone();
+        }
+    }
+
+    // -1 goes back to older lines, past the newest is the input again
+    fn browse(&mut self, direction: i32) {
+        if self.entered.is_empty() {
+            return;
+        }
+        let newest = self.entered.len() - 1;
+        self.browsing = match (self.browsing, direction < 0) {
+            (None, true) => Some(newest),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index < newest => Some(index + 1),
+            (Some(_), false) => None,
+        };
+    }
+
+    fn submit(&mut self) {
+        self.take_browsed();
+        let line = std::mem::take(&mut self.input);
+        if line.trim().is_empty() {
+            return;
+        }
+        if self.entered.last() != Some(&line) {
+            if self.entered.len() == MAX_ENTERED {
+                self.entered.remove(0);
+            }
+            self.entered.push(line.clone());
+        }
+        self.pending.push(line);
+    }
+}
+
+impl<A> Default for Console<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// by the level the log line starts with
+fn log_color(line: &str) -> [f32; 4] {
+    match line.split_whitespace().next() {
+        Some("ERROR") => ERROR_COLOR,
+        Some("WARN") => WARN_COLOR,
+        _ => LOG_COLOR,
+    }
+}
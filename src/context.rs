@@ -0,0 +1,486 @@
+use std::cell::Cell;
+use std::ops::Range;
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use wgpu::*;
+use winit::dpi::{LogicalSize, PhysicalSize};
+use winit::event_loop::EventLoop;
+use winit::window::{CursorGrabMode, Icon, Window, WindowBuilder};
+use crate::readback::Readback;
+use crate::window_ext::{default_icon, fullscreen, FullscreenRequest};
+
+// used when the adapter has them. code that depends on one checks context.features() first
+pub const OPTIONAL_FEATURES: Features = Features::PUSH_CONSTANTS
+    .union(Features::TIMESTAMP_QUERY)
+    .union(Features::TEXTURE_COMPRESSION_BC)
+    .union(Features::TEXTURE_COMPRESSION_ETC2)
+    .union(Features::TEXTURE_COMPRESSION_ASTC_LDR)
+    .union(Features::POLYGON_MODE_LINE)
+    .union(Features::SPIRV_SHADER_PASSTHROUGH)
+    .union(Features::INDIRECT_FIRST_INSTANCE)
+    .union(Features::MULTI_DRAW_INDIRECT)
+    .union(Features::MULTI_DRAW_INDIRECT_COUNT)
+    .union(Features::TEXTURE_BINDING_ARRAY);
+
+// android has vulkan, or gles on older devices. apple platforms only have metal
+const BACKENDS: Backends = if cfg!(target_os = "android") {
+    Backends::VULKAN.union(Backends::GL)
+} else if cfg!(any(target_os = "ios", target_os = "macos")) {
+    Backends::METAL
+} else {
+    Backends::DX12
+};
+
+// the most push constant space wgpu lets backends offer
+const MAX_PUSH_CONSTANT_SIZE: u32 = 128;
+// more than any adapter without binding arrays offers, clamped like every other limit
+const MAX_SAMPLED_TEXTURES: u32 = 1024;
+
+#[derive(Clone, Debug)]
+pub struct ContextConfig {
+    // prefer a surface format that encodes to srgb on write. shaders work in linear color
+    // either way, this only decides whether the hardware or output_color does the encoding
+    pub srgb: bool,
+    // use a float surface for hdr output if there is one. off by default since with hdr turned
+    // off in the os, anything past paper white just clips
+    pub hdr: bool,
+    // device creation fails without these
+    pub required_features: Features,
+    // enabled where the adapter has them
+    pub optional_features: Features,
+    // asked for, then clamped to what the adapter supports
+    pub limits: Limits,
+    // off presents as fast as frames are drawn, e.g. to benchmark
+    pub vsync: bool,
+    // the rest are for the window with_config creates, from_raw_handle has nothing to apply them to
+    // a hidden window still gets a device and surface, for drawing offscreen
+    pub visible: bool,
+    // the window's size in physical pixels, left to the platform when none
+    pub size: Option<PhysicalSize<u32>>,
+    pub title: String,
+    // window_ext::ICON_PNG unless replaced, see window_ext::icon_from_png
+    pub icon: Option<Icon>,
+    // windowed when none
+    pub fullscreen: Option<FullscreenRequest>,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            srgb: true,
+            hdr: false,
+            required_features: Features::empty(),
+            optional_features: OPTIONAL_FEATURES,
+            limits: Limits {
+                max_push_constant_size: MAX_PUSH_CONSTANT_SIZE,
+                // room for the material texture array, see MaterialTextures
+                max_sampled_textures_per_shader_stage: MAX_SAMPLED_TEXTURES,
+                ..Limits::default()
+            },
+            vsync: true,
+            visible: true,
+            size: None,
+            title: "dumb wgpu example".into(),
+            icon: default_icon(),
+            fullscreen: None,
+        }
+    }
+}
+
+pub struct RenderContext {
+    pub device: Device,
+    pub queue: Queue,
+
+    target: SurfaceTarget,
+    // kept to recreate the surface on resume
+    instance: Instance,
+    adapter: Adapter,
+    // none while suspended. android destroys the native window when the app is backgrounded and
+    // the surface can't outlive it, and there's no native window before the first Resumed event
+    surface: Option<Surface>,
+    pub format: TextureFormat,
+    present_mode: PresentMode,
+    // the surface is sized in physical pixels. tracked here rather than asking the window since
+    // inner_size lags behind while a scale factor change is being handled
+    size: Cell<PhysicalSize<u32>>,
+    scale_factor: Cell<f64>,
+    occluded: Cell<bool>,
+    pointer_locked: Cell<bool>,
+    suspended: bool,
+}
+
+impl RenderContext {
+    pub async fn new(event_loop: &EventLoop<()>) -> Self {
+        Self::with_config(event_loop, ContextConfig::default()).await
+    }
+
+    pub async fn with_config(event_loop: &EventLoop<()>, config: ContextConfig) -> Self {
+        let mut builder = window_builder()
+            .with_visible(config.visible)
+            .with_title(&config.title)
+            .with_window_icon(config.icon.clone());
+        if let Some(size) = config.size {
+            builder = builder.with_inner_size(size);
+        }
+        if let Some(request) = &config.fullscreen {
+            builder = builder.with_fullscreen(Some(fullscreen(request, event_loop.available_monitors(), event_loop.primary_monitor())));
+        }
+        let window = builder.build(event_loop).expect("failed to create window");
+        let size = window.inner_size();
+        let scale_factor = window.scale_factor();
+        Self::create(SurfaceTarget::Window(window), size, scale_factor, &config).await
+    }
+
+    // renders into a native window owned by a host application, e.g. a widget in a qt or gtk
+    // editor. the host tells the context about resizes and scale factor changes through resize
+    // and set_scale_factor, and asks for redraws itself. the pointer can't be locked
+    //
+    // safety: the handle has to be a valid window for as long as the context lives
+    pub async unsafe fn from_raw_handle(handle: RawWindowHandle, size: PhysicalSize<u32>) -> Self {
+        Self::from_raw_handle_with_config(handle, size, ContextConfig::default()).await
+    }
+
+    // safety: as from_raw_handle
+    pub async unsafe fn from_raw_handle_with_config(handle: RawWindowHandle, size: PhysicalSize<u32>, config: ContextConfig) -> Self {
+        Self::create(SurfaceTarget::External(ExternalWindow(handle)), size, 1.0, &config).await
+    }
+
+    async fn create(target: SurfaceTarget, size: PhysicalSize<u32>, scale_factor: f64, config: &ContextConfig) -> Self {
+        let instance = Instance::new(BACKENDS);
+        // our android window has no native window before the first Resumed event, a host's
+        // window is there already
+        let has_native_window = matches!(target, SurfaceTarget::External(_)) || !cfg!(target_os = "android");
+        let surface = has_native_window.then(|| unsafe { target.create_surface(&instance) });
+        let adapter = instance.request_adapter(&RequestAdapterOptions {
+            power_preference: PowerPreference::LowPower,
+            force_fallback_adapter: false,
+            compatible_surface: surface.as_ref(),
+        }).await.expect("failed to request adapter");
+        // pipelines are built for the format before the surface exists on android. every android
+        // vulkan driver supports rgba8 surfaces
+        let format = match &surface {
+            Some(surface) => select_format(&surface.get_supported_formats(&adapter), config),
+            None if config.srgb => TextureFormat::Rgba8UnormSrgb,
+            None => TextureFormat::Rgba8Unorm,
+        };
+
+        let features = negotiate_features(&adapter, config);
+        let limits = clamp_limits(&config.limits, &adapter.limits());
+        tracing::info!("device features: {features:?}");
+        let (device, queue) = adapter.request_device(
+            &DeviceDescriptor {
+                label: Some("device"),
+                features,
+                limits,
+            },
+            None,
+        ).await.expect("failed to request device");
+
+        let context = Self {
+            device,
+            queue,
+
+            target,
+            instance,
+            adapter,
+            surface,
+            format,
+            present_mode: if config.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync },
+            size: Cell::new(size),
+            scale_factor: Cell::new(scale_factor),
+            occluded: Cell::new(false),
+            pointer_locked: Cell::new(false),
+            suspended: false,
+        };
+        context.configure();
+        context
+    }
+
+    // a minimized window reports a size of 0, which the surface can't be configured with. device
+    // rotation on ios and android also arrives as a resize, reconfiguring is what resizes the
+    // drawables (on ios wgpu resizes its CAMetalLayer to the view here too)
+    pub fn resize(&self, width: u32, height: u32) {
+        self.size.set(PhysicalSize::new(width, height));
+        self.configure();
+        // required for MacOS
+        self.request_redraw();
+    }
+
+    // none for a context made from a raw handle
+    pub fn window(&self) -> Option<&Window> {
+        match &self.target {
+            SurfaceTarget::Window(window) => Some(window),
+            SurfaceTarget::External(_) => None,
+        }
+    }
+
+    // a host application with its own window schedules its own redraws
+    pub fn request_redraw(&self) {
+        if let Some(window) = self.window() {
+            window.request_redraw();
+        }
+    }
+
+    // for Event::Suspended, nothing is drawn until resume. android also drops the surface. ios
+    // keeps it, recreating it would add another metal layer to the view each time
+    pub fn suspend(&mut self) {
+        self.suspended = true;
+        if cfg!(target_os = "android") {
+            self.surface = None;
+        }
+    }
+
+    // for Event::Resumed. recreates the surface for the window's new native window if it was
+    // dropped, and picks up the window's size, which may have changed while suspended (e.g. the
+    // device was rotated) or not have been known yet when the window was created on ios
+    pub fn resume(&mut self) {
+        self.suspended = false;
+        if self.surface.is_none() {
+            let surface = unsafe { self.target.create_surface(&self.instance) };
+            if !surface.get_supported_formats(&self.adapter).contains(&self.format) {
+                panic!("surface doesn't support {:?}", self.format);
+            }
+            self.surface = Some(surface);
+        }
+        if let Some(window) = self.window() {
+            self.size.set(window.inner_size());
+        }
+        self.configure();
+    }
+
+    // for SurfaceError::Lost and Outdated, e.g. right after a resume or a rotation, at the size
+    // the surface was last given
+    pub fn reconfigure(&self) {
+        self.configure();
+    }
+
+    fn configure(&self) {
+        let size = self.size.get();
+        let Some(surface) = &self.surface else { return };
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        surface.configure(&self.device, &SurfaceConfiguration {
+            format: self.format,
+            width: size.width,
+            height: size.height,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            present_mode: self.present_mode,
+        });
+    }
+
+    // none while suspended
+    pub fn surface(&self) -> Option<&Surface> {
+        self.surface.as_ref().filter(|_| !self.suspended)
+    }
+
+    // for WindowEvent::ScaleFactorChanged, the new size comes with the event
+    pub fn set_scale_factor(&self, scale_factor: f64, width: u32, height: u32) {
+        self.scale_factor.set(scale_factor);
+        self.resize(width, height);
+    }
+
+    // for WindowEvent::Occluded, e.g. fully covered by another window or on another desktop
+    pub fn set_occluded(&self, occluded: bool) {
+        self.occluded.set(occluded);
+    }
+
+    // grabs and hides the cursor, for mouse look through DeviceEvent::MouseMotion. windows and
+    // x11 can't lock the cursor in place, so it's confined to the window there instead. returns
+    // whether it's locked now
+    pub fn set_pointer_lock(&self, locked: bool) -> bool {
+        if locked == self.pointer_locked.get() {
+            return locked;
+        }
+        let Some(window) = self.window() else {
+            tracing::warn!("can't grab the cursor of a window the context doesn't own");
+            return false;
+        };
+        if locked {
+            let grabbed = window.set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(error) = grabbed {
+                tracing::warn!("can't grab the cursor: {error}");
+                return false;
+            }
+        } else {
+            let _ = window.set_cursor_grab(CursorGrabMode::None);
+        }
+        window.set_cursor_visible(!locked);
+        self.pointer_locked.set(locked);
+        locked
+    }
+
+    pub fn is_pointer_locked(&self) -> bool {
+        self.pointer_locked.get()
+    }
+
+    // nothing drawn would be seen, so there's no point drawing
+    pub fn is_visible(&self) -> bool {
+        let size = self.size.get();
+        size.width > 0 && size.height > 0 && !self.occluded.get() && self.surface().is_some()
+    }
+
+    pub fn aspect(&self) -> f32 {
+        let size = self.size.get();
+        size.width.max(1) as f32 / size.height.max(1) as f32
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor.get()
+    }
+
+    pub fn physical_size(&self) -> PhysicalSize<u32> {
+        self.size.get()
+    }
+
+    // what the device was created with, see OPTIONAL_FEATURES
+    pub fn features(&self) -> Features {
+        self.device.features()
+    }
+
+    // the requested limits after clamping to the adapter's
+    pub fn limits(&self) -> Limits {
+        self.device.limits()
+    }
+
+    // copies part of a buffer back to the cpu, see Readback
+    pub fn read_buffer(&self, buffer: &Buffer, range: Range<BufferAddress>) -> Readback {
+        Readback::buffer(self, buffer, range)
+    }
+
+    // copies a region of a texture back to the cpu with the row padding taken out, see Readback
+    pub fn read_texture(&self, texture: ImageCopyTexture, format: TextureFormat, size: Extent3d) -> Readback {
+        Readback::texture(self, texture, format, size)
+    }
+
+    // which gpu and backend we ended up on
+    pub fn adapter_info(&self) -> AdapterInfo {
+        self.adapter.get_info()
+    }
+
+    // the surface is scrgb, see is_hdr_format
+    pub fn is_hdr(&self) -> bool {
+        is_hdr_format(self.format)
+    }
+
+    // what ui and text should lay out in, so they keep their size on hidpi monitors
+    pub fn logical_size(&self) -> LogicalSize<f32> {
+        self.size.get().to_logical(self.scale_factor())
+    }
+}
+
+// what the surface is made for, recreated from on resume
+enum SurfaceTarget {
+    Window(Window),
+    External(ExternalWindow),
+}
+
+impl SurfaceTarget {
+    unsafe fn create_surface(&self, instance: &Instance) -> Surface {
+        match self {
+            SurfaceTarget::Window(window) => instance.create_surface(window),
+            SurfaceTarget::External(window) => instance.create_surface(window),
+        }
+    }
+}
+
+// a host application's window, see RenderContext::from_raw_handle
+struct ExternalWindow(RawWindowHandle);
+
+unsafe impl HasRawWindowHandle for ExternalWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.0
+    }
+}
+
+// float surfaces are presented as scrgb: linear, with 1.0 at 80 nits and no upper limit. wgpu
+// doesn't let us pick hdr10 or another color space, so that's the only hdr output there is
+pub fn is_hdr_format(format: TextureFormat) -> bool {
+    format == TextureFormat::Rgba16Float
+}
+
+// with the ios feature the view takes the whole screen in any orientation, under the status bar
+// and home indicator
+fn window_builder() -> WindowBuilder {
+    let builder = WindowBuilder::new();
+    #[cfg(all(target_os = "ios", feature = "ios"))]
+    let builder = {
+        use winit::platform::ios::{ValidOrientations, WindowBuilderExtIOS};
+        builder
+            .with_valid_orientations(ValidOrientations::LandscapeAndPortrait)
+            .with_prefers_status_bar_hidden(true)
+            .with_prefers_home_indicator_hidden(true)
+    };
+    builder
+}
+
+fn negotiate_features(adapter: &Adapter, config: &ContextConfig) -> Features {
+    let supported = adapter.features();
+    let missing = config.required_features - supported;
+    if !missing.is_empty() {
+        panic!("adapter is missing required features {missing:?}");
+    }
+    config.required_features | (config.optional_features & supported)
+}
+
+// maximums can't go past the adapter's and alignments can't be finer than it needs. asking for
+// more than the adapter has fails device creation, so weaker adapters get what they have
+fn clamp_limits(requested: &Limits, supported: &Limits) -> Limits {
+    let mut limits = requested.clone();
+    macro_rules! at_most {
+        ($($field:ident),* $(,)?) => {
+            $(limits.$field = limits.$field.min(supported.$field);)*
+        };
+    }
+    macro_rules! at_least {
+        ($($field:ident),* $(,)?) => {
+            $(limits.$field = limits.$field.max(supported.$field);)*
+        };
+    }
+    at_most!(
+        max_texture_dimension_1d,
+        max_texture_dimension_2d,
+        max_texture_dimension_3d,
+        max_texture_array_layers,
+        max_bind_groups,
+        max_dynamic_uniform_buffers_per_pipeline_layout,
+        max_dynamic_storage_buffers_per_pipeline_layout,
+        max_sampled_textures_per_shader_stage,
+        max_samplers_per_shader_stage,
+        max_storage_buffers_per_shader_stage,
+        max_storage_textures_per_shader_stage,
+        max_uniform_buffers_per_shader_stage,
+        max_uniform_buffer_binding_size,
+        max_storage_buffer_binding_size,
+        max_vertex_buffers,
+        max_vertex_attributes,
+        max_vertex_buffer_array_stride,
+        max_push_constant_size,
+        max_inter_stage_shader_components,
+        max_compute_workgroup_storage_size,
+        max_compute_invocations_per_workgroup,
+        max_compute_workgroup_size_x,
+        max_compute_workgroup_size_y,
+        max_compute_workgroup_size_z,
+        max_compute_workgroups_per_dimension,
+        max_buffer_size,
+    );
+    at_least!(
+        min_uniform_buffer_offset_alignment,
+        min_storage_buffer_offset_alignment,
+    );
+    limits
+}
+
+// the first format is whatever the driver lists first, which varies between machines
+fn select_format(formats: &[TextureFormat], config: &ContextConfig) -> TextureFormat {
+    if config.hdr {
+        if let Some(&format) = formats.iter().find(|&&format| is_hdr_format(format)) {
+            return format;
+        }
+        tracing::warn!("no hdr surface format, falling back to sdr");
+    }
+    formats.iter().copied()
+        .find(|&format| !is_hdr_format(format) && format.describe().srgb == config.srgb)
+        .unwrap_or(formats[0])
+}
@@ -0,0 +1,13 @@
+// matches CulledObject in culling.rs
+struct CulledObject {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    base_color: vec4<f32>,
+    // local bounds
+    min: vec4<f32>,
+    max: vec4<f32>,
+    index_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    id: u32,
+}
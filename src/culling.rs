@@ -0,0 +1,373 @@
+use std::mem::size_of;
+use std::sync::Arc;
+use glam::Vec3;
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::{Frustum, FRAME_LAYOUT};
+use crate::context::RenderContext;
+use crate::mesh::Vertex;
+use crate::model::{Model, ModelInstance};
+use crate::pipeline_cache::{DepthKey, PipelineCache, PipelineId, PipelineKey};
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+use crate::ssao::NORMAL_DEPTH_FORMAT;
+
+pub const CULLING_LAYOUT: &str = "culling";
+pub const CULLED_OBJECTS_LAYOUT: &str = "culled objects";
+const WORKGROUP_SIZE: u32 = 64;
+// matches DrawIndexedIndirect in culling.wgsl
+const DRAW_SIZE: BufferAddress = 20;
+
+// matches CulledObject in culled_object.wgsl
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct CulledObject {
+    model: [[f32; 4]; 4],
+    normal: [[f32; 4]; 4],
+    base_color: [f32; 4],
+    min: [f32; 4],
+    max: [f32; 4],
+    index_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    id: u32,
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct CullParams {
+    planes: [[f32; 4]; 6],
+    object_count: u32,
+    _padding: [u32; 3],
+}
+
+#[derive(Clone)]
+struct PooledMesh {
+    // first index and count of the base mesh and then each lod
+    lods: Vec<(u32, u32)>,
+    base_vertex: i32,
+    bounds: (Vec3, Vec3),
+}
+
+// the meshes of every model gpu culling draws, in one vertex and one index buffer so a single
+// indirect draw reaches all of them
+struct GeometryPool {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    models: Vec<(Arc<Model>, Vec<PooledMesh>)>,
+}
+
+impl GeometryPool {
+    fn new(context: &RenderContext, models: Vec<Arc<Model>>) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let models = models.into_iter().map(|model| {
+            let meshes = model.meshes.iter().map(|mesh| {
+                let base_vertex = vertices.len() as i32;
+                vertices.extend_from_slice(&mesh.vertices);
+                let lods = std::iter::once(&mesh.indices).chain(mesh.lods.iter().map(|lod| &lod.indices)).map(|lod| {
+                    let first_index = indices.len() as u32;
+                    indices.extend_from_slice(lod);
+                    (first_index, lod.len() as u32)
+                }).collect();
+                PooledMesh {
+                    lods,
+                    base_vertex,
+                    bounds: mesh.bounds(),
+                }
+            }).collect();
+            (model, meshes)
+        }).collect();
+        // buffers can't be empty
+        if vertices.is_empty() {
+            vertices.push(Vertex::default());
+            indices.push(0);
+        }
+        Self {
+            vertex_buffer: context.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("culled vertices"),
+                usage: BufferUsages::VERTEX,
+                contents: bytemuck::cast_slice(&vertices),
+            }),
+            index_buffer: context.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("culled indices"),
+                usage: BufferUsages::INDEX,
+                contents: bytemuck::cast_slice(&indices),
+            }),
+            models,
+        }
+    }
+
+    fn meshes(&self, model: &Arc<Model>) -> Option<&[PooledMesh]> {
+        self.models.iter().find(|(pooled, _)| Arc::ptr_eq(pooled, model)).map(|(_, meshes)| meshes.as_slice())
+    }
+}
+
+// the draws one view keeps after culling, and how many there are
+struct CullView {
+    params_buffer: Buffer,
+    draw_buffer: Buffer,
+    count_buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl CullView {
+    fn new(context: &RenderContext, layouts: &LayoutRegistry, object_buffer: &Buffer, capacity: u32) -> Self {
+        let device = &context.device;
+        let params_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("culling params"),
+            size: size_of::<CullParams>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // cleared every frame, so draws past the count are empty for multi_draw_indexed_indirect
+        let draw_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("culled draws"),
+            size: capacity as BufferAddress * DRAW_SIZE,
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let count_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("culled draw count"),
+            size: size_of::<u32>() as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = BindGroupBuilder::new()
+            .buffer(&params_buffer)
+            .buffer(object_buffer)
+            .buffer(&draw_buffer)
+            .buffer(&count_buffer)
+            .build(context, layouts, CULLING_LAYOUT);
+        Self {
+            params_buffer,
+            draw_buffer,
+            count_buffer,
+            bind_group,
+        }
+    }
+}
+
+// gpu driven drawing of static meshes: a compute pass tests every object's bounds against each
+// view's frustum and compacts the visible ones into an indirect buffer, which is drawn with a
+// single multi_draw_indexed_indirect. skinned and morphed meshes still draw one by one
+pub struct GpuCulling {
+    pub render_pipeline: PipelineId,
+    pub normal_pipeline: PipelineId,
+    features: Features,
+    pool: GeometryPool,
+    capacity: u32,
+    object_count: u32,
+    object_buffer: Buffer,
+    objects_bind_group: BindGroup,
+    // lined up with the frustums passed to update
+    views: Vec<CullView>,
+    view_count: usize,
+    cull_pipeline: ComputePipeline,
+}
+
+impl GpuCulling {
+    // the vertex shader finds its object through the instance index, which needs a first
+    // instance in indirect draws
+    pub fn is_supported(context: &RenderContext) -> bool {
+        context.features().contains(Features::INDIRECT_FIRST_INSTANCE)
+    }
+
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, cache: &mut PipelineCache) -> Self {
+        let device = &context.device;
+        layouts.register(context, CULLING_LAYOUT, &[
+            (Binding::Uniform, ShaderStages::COMPUTE),
+            (Binding::Storage { read_only: true }, ShaderStages::COMPUTE),
+            (Binding::Storage { read_only: false }, ShaderStages::COMPUTE),
+            (Binding::Storage { read_only: false }, ShaderStages::COMPUTE),
+        ]);
+        layouts.register(context, CULLED_OBJECTS_LAYOUT, &[
+            (Binding::Storage { read_only: true }, ShaderStages::VERTEX | ShaderStages::FRAGMENT),
+        ]);
+
+        let compute_module = Preprocessor::new().create_module(context, "culling.wgsl");
+        let compute_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("culling"),
+            bind_group_layouts: &[layouts.get(CULLING_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let cull_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("culling"),
+            layout: Some(&compute_layout),
+            module: &compute_module,
+            entry_point: "cull",
+        });
+
+        cache.add_shader("mesh culled", Preprocessor::new().target(HDR_FORMAT).create_module(context, "mesh_culled.wgsl"));
+        cache.add_layout("mesh culled", device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("mesh culled"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT), layouts.get(CULLED_OBJECTS_LAYOUT)],
+            push_constant_ranges: &[],
+        }));
+
+        let capacity = 64;
+        let object_buffer = create_object_buffer(context, capacity);
+        Self {
+            render_pipeline: cache.get_or_create(context, &pipeline_key("fragment", HDR_FORMAT.into())),
+            normal_pipeline: cache.get_or_create(context, &pipeline_key("fragment_normal", NORMAL_DEPTH_FORMAT.into())),
+            features: context.features(),
+            pool: GeometryPool::new(context, Vec::new()),
+            capacity,
+            object_count: 0,
+            objects_bind_group: create_objects_bind_group(context, layouts, &object_buffer),
+            object_buffer,
+            views: Vec::new(),
+            view_count: 0,
+            cull_pipeline,
+        }
+    }
+
+    // gathers the static draws of every instance, the pool is rebuilt when a model shows up that
+    // isn't in it yet
+    pub fn update<'a>(&mut self, context: &RenderContext, layouts: &LayoutRegistry, instances: impl Iterator<Item = &'a ModelInstance> + Clone, frustums: &[Frustum]) {
+        let instances = instances.filter(|instance| instance.static_draws().next().is_some());
+        if instances.clone().any(|instance| self.pool.meshes(&instance.model).is_none()) {
+            let mut models: Vec<Arc<Model>> = Vec::new();
+            for instance in instances.clone() {
+                if !models.iter().any(|model| Arc::ptr_eq(model, &instance.model)) {
+                    models.push(instance.model.clone());
+                }
+            }
+            self.pool = GeometryPool::new(context, models);
+        }
+
+        let mut objects = Vec::new();
+        for instance in instances {
+            let Some(meshes) = self.pool.meshes(&instance.model) else {
+                continue;
+            };
+            for (mesh, lod, model) in instance.static_draws() {
+                let pooled = &meshes[mesh];
+                let (first_index, index_count) = pooled.lods[lod.min(pooled.lods.len() - 1)];
+                objects.push(CulledObject {
+                    model: model.to_cols_array_2d(),
+                    normal: model.inverse().transpose().to_cols_array_2d(),
+                    base_color: instance.material.base_color,
+                    min: pooled.bounds.0.extend(1.0).to_array(),
+                    max: pooled.bounds.1.extend(1.0).to_array(),
+                    index_count,
+                    first_index,
+                    base_vertex: pooled.base_vertex,
+                    id: instance.pick_id,
+                });
+            }
+        }
+        self.object_count = objects.len() as u32;
+
+        if self.object_count > self.capacity {
+            self.capacity = self.object_count.next_power_of_two();
+            self.object_buffer = create_object_buffer(context, self.capacity);
+            self.objects_bind_group = create_objects_bind_group(context, layouts, &self.object_buffer);
+            self.views.clear();
+        }
+        while self.views.len() < frustums.len() {
+            self.views.push(CullView::new(context, layouts, &self.object_buffer, self.capacity));
+        }
+        self.view_count = frustums.len();
+        if !objects.is_empty() {
+            context.queue.write_buffer(&self.object_buffer, 0, bytemuck::cast_slice(&objects));
+        }
+        for (view, frustum) in self.views.iter().zip(frustums) {
+            let params = CullParams {
+                planes: frustum.planes.map(|plane| plane.to_array()),
+                object_count: self.object_count,
+                _padding: [0; 3],
+            };
+            context.queue.write_buffer(&view.params_buffer, 0, bytemuck::bytes_of(&params));
+        }
+    }
+
+    // fills every view's indirect buffer, has to run before any of them are drawn
+    pub fn cull(&self, cmd: &mut CommandEncoder) {
+        if self.object_count == 0 {
+            return;
+        }
+        let views = &self.views[..self.view_count];
+        for view in views {
+            cmd.clear_buffer(&view.draw_buffer, 0, None);
+            cmd.clear_buffer(&view.count_buffer, 0, None);
+        }
+        let mut compute_cmd = cmd.begin_compute_pass(&ComputePassDescriptor { label: Some("culling") });
+        compute_cmd.set_pipeline(&self.cull_pipeline);
+        for view in views {
+            compute_cmd.set_bind_group(0, &view.bind_group, &[]);
+            compute_cmd.dispatch_workgroups(self.object_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+
+    // what draw issues for the view, one multi draw or a draw per object
+    pub fn draw_count(&self, view: usize) -> u32 {
+        if view >= self.view_count || self.object_count == 0 {
+            0
+        } else if self.features.intersects(Features::MULTI_DRAW_INDIRECT_COUNT | Features::MULTI_DRAW_INDIRECT) {
+            1
+        } else {
+            self.object_count
+        }
+    }
+
+    // the pipeline is render_pipeline or normal_pipeline, with the view's frame group at 0.
+    // without multi draw support the draws are issued one by one, empty ones included
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, pipeline: &'a RenderPipeline, view: usize) {
+        let Some(view) = self.views[..self.view_count].get(view).filter(|_| self.object_count > 0) else {
+            return;
+        };
+        render_cmd.set_pipeline(pipeline);
+        render_cmd.set_bind_group(1, &self.objects_bind_group, &[]);
+        render_cmd.set_vertex_buffer(0, self.pool.vertex_buffer.slice(..));
+        render_cmd.set_index_buffer(self.pool.index_buffer.slice(..), IndexFormat::Uint32);
+        if self.features.contains(Features::MULTI_DRAW_INDIRECT_COUNT) {
+            render_cmd.multi_draw_indexed_indirect_count(&view.draw_buffer, 0, &view.count_buffer, 0, self.object_count);
+        } else if self.features.contains(Features::MULTI_DRAW_INDIRECT) {
+            render_cmd.multi_draw_indexed_indirect(&view.draw_buffer, 0, self.object_count);
+        } else {
+            for index in 0..self.object_count {
+                render_cmd.draw_indexed_indirect(&view.draw_buffer, index as BufferAddress * DRAW_SIZE);
+            }
+        }
+    }
+}
+
+fn create_object_buffer(context: &RenderContext, capacity: u32) -> Buffer {
+    context.device.create_buffer(&BufferDescriptor {
+        label: Some("culled objects"),
+        size: (capacity as usize * size_of::<CulledObject>()) as BufferAddress,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_objects_bind_group(context: &RenderContext, layouts: &LayoutRegistry, object_buffer: &Buffer) -> BindGroup {
+    BindGroupBuilder::new()
+        .buffer(object_buffer)
+        .build(context, layouts, CULLED_OBJECTS_LAYOUT)
+}
+
+// like mesh::pipeline_key, for "fragment" and "fragment_normal" in mesh_culled.wgsl
+pub fn pipeline_key(fragment_entry: &'static str, target: ColorTargetState) -> PipelineKey {
+    PipelineKey {
+        shader: "mesh culled",
+        layout: "mesh culled",
+        vertex_entry: "vertex",
+        fragment_entry: Some(fragment_entry),
+        vertex_layouts: vec![(&Vertex::LAYOUT).into()],
+        targets: vec![target],
+        primitive: PrimitiveState {
+            cull_mode: Some(Face::Back),
+            ..PrimitiveState::default()
+        },
+        depth: Some(DepthKey {
+            format: DEPTH_FORMAT,
+            write_enabled: true,
+            compare: CompareFunction::Less,
+            stencil: StencilState::default(),
+        }),
+        multisample: MultisampleState::default(),
+    }
+}
@@ -0,0 +1,49 @@
+#include "culled_object.wgsl"
+
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+struct DrawCount {
+    count: atomic<u32>,
+}
+
+struct Params {
+    // inward facing, normalized
+    planes: array<vec4<f32>, 6>,
+    object_count: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> objects: array<CulledObject>;
+@group(0) @binding(2) var<storage, read_write> draws: array<DrawIndexedIndirect>;
+@group(0) @binding(3) var<storage, read_write> draw_count: DrawCount;
+
+// the local bounds are moved into world space as a box around the transformed box, then tested
+// against every plane using its extent along the plane normal
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.object_count) {
+        return;
+    }
+    let object = objects[index];
+    let local_center = (object.min.xyz + object.max.xyz) * 0.5;
+    let local_extent = (object.max.xyz - object.min.xyz) * 0.5;
+    let center = (object.model * vec4<f32>(local_center, 1.0)).xyz;
+    let axes = mat3x3<f32>(abs(object.model[0].xyz), abs(object.model[1].xyz), abs(object.model[2].xyz));
+    let extent = axes * local_extent;
+    for (var i = 0; i < 6; i = i + 1) {
+        let plane = params.planes[i];
+        if (dot(plane.xyz, center) + plane.w < -dot(abs(plane.xyz), extent)) {
+            return;
+        }
+    }
+
+    let slot = atomicAdd(&draw_count.count, 1u);
+    draws[slot] = DrawIndexedIndirect(object.index_count, 1u, object.first_index, object.base_vertex, index);
+}
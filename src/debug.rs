@@ -0,0 +1,187 @@
+use std::f32::consts::TAU;
+use std::mem::size_of;
+use glam::{Mat4, Vec3};
+use wgpu::*;
+use crate::bindings::LayoutRegistry;
+use crate::camera::FRAME_LAYOUT;
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+const SPHERE_SEGMENTS: u32 = 24;
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+// immediate mode lines, shapes are queued during the frame and drawn on top of the scene
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+    vertex_buffer: Buffer,
+    vertex_capacity: usize,
+    vertex_count: u32,
+    render_pipeline: RenderPipeline,
+}
+
+impl DebugDraw {
+    pub fn new(context: &RenderContext, layouts: &LayoutRegistry) -> Self {
+        let device = &context.device;
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "debug.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("debug lines"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("debug lines"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[
+                    VertexBufferLayout {
+                        array_stride: size_of::<DebugVertex>() as BufferAddress,
+                        step_mode: VertexStepMode::Vertex,
+                        attributes: &vertex_attr_array![
+                            0 => Float32x3,
+                            1 => Float32x4,
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &shader_module,
+                targets: &[
+                    Some(ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                ..PrimitiveState::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            vertices: Vec::new(),
+            vertex_buffer: create_vertex_buffer(context, 1024),
+            vertex_capacity: 1024,
+            vertex_count: 0,
+            render_pipeline,
+        }
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: [f32; 4]) {
+        self.vertices.push(DebugVertex { position: a.to_array(), color });
+        self.vertices.push(DebugVertex { position: b.to_array(), color });
+    }
+
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: [f32; 4]) {
+        self.aabb_transformed(Mat4::IDENTITY, min, max, color);
+    }
+
+    // the box is transformed as a whole, so it stays tight around rotated objects
+    pub fn aabb_transformed(&mut self, transform: Mat4, min: Vec3, max: Vec3, color: [f32; 4]) {
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i: u32| {
+            let corner = Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            transform.transform_point3(corner)
+        });
+        self.box_edges(&corners, color);
+    }
+
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: [f32; 4]) {
+        // one circle around each axis
+        let axes = [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)];
+        for (u, v) in axes {
+            let point = |i: u32| {
+                let angle = i as f32 / SPHERE_SEGMENTS as f32 * TAU;
+                center + (u * angle.cos() + v * angle.sin()) * radius
+            };
+            for i in 0..SPHERE_SEGMENTS {
+                self.line(point(i), point(i + 1), color);
+            }
+        }
+    }
+
+    // outlines the volume covered by a view projection matrix, e.g. a camera or light frustum
+    pub fn frustum(&mut self, view_proj: Mat4, color: [f32; 4]) {
+        let inverse = view_proj.inverse();
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i: u32| {
+            let ndc = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { 0.0 } else { 1.0 },
+            );
+            inverse.project_point3(ndc)
+        });
+        self.box_edges(&corners, color);
+    }
+
+    // corners are indexed by bits x, y, z
+    fn box_edges(&mut self, corners: &[Vec3; 8], color: [f32; 4]) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    // uploads everything queued since the last update and starts a new batch
+    pub fn update(&mut self, context: &RenderContext) {
+        if self.vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = create_vertex_buffer(context, self.vertex_capacity);
+        }
+        if !self.vertices.is_empty() {
+            context.queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        }
+        self.vertex_count = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+
+    // all lines go out in one draw, none when there are none
+    pub fn draw_count(&self) -> u32 {
+        (self.vertex_count > 0) as u32
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        render_cmd.set_pipeline(&self.render_pipeline);
+        render_cmd.set_bind_group(0, camera_bind_group, &[]);
+        render_cmd.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_cmd.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+fn create_vertex_buffer(context: &RenderContext, capacity: usize) -> Buffer {
+    context.device.create_buffer(&BufferDescriptor {
+        label: Some("debug lines"),
+        size: (capacity * size_of::<DebugVertex>()) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
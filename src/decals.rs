@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use glam::{Mat4, Quat, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::{Camera, FRAME_LAYOUT};
+use crate::context::RenderContext;
+use crate::material_textures::MaterialTextures;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+pub const DECAL_LAYOUT: &str = "decals";
+pub const DECAL_TEXTURE_LAYOUT: &str = "decal texture";
+// the unit box's triangles facing out, corners indexed by bits x, y, z
+const BOX_INDICES: [u16; 36] = [0, 6, 2, 0, 4, 6, 1, 3, 7, 1, 7, 5, 0, 1, 5, 0, 5, 4, 2, 7, 3, 2, 6, 7, 0, 3, 1, 0, 2, 3, 4, 5, 7, 4, 7, 6];
+
+// a texture projected down a box onto whatever's inside it, e.g. a stain on the floor or a
+// bullet hole in a wall. it's lit like the surface it lands on, in front of meshes and terrain
+// but behind the grid, particles and debug lines. only drawn with a single view, as it reads
+// the ssao prepass
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Decal {
+    pub position: Vec3,
+    // the box projects down its local y axis
+    pub rotation: Quat,
+    // the box's full size along each of its axes
+    pub size: Vec3,
+    // linear, multiplied with the texture. alpha is the decal's opacity
+    pub color: Vec4,
+    // a material texture id, see Renderer::add_material_texture. None is the color alone
+    pub texture: Option<u32>,
+    // higher draws over lower where decals overlap, equal ones further from the camera first
+    pub order: i32,
+    // of the box's height, towards its top and bottom, where the decal fades out instead of
+    // stopping at a hard line
+    pub fade: f32,
+    // radians between a surface's normal and the box's up axis past which the decal is gone,
+    // it fades in over the last quarter before that
+    pub max_angle: f32,
+}
+
+impl Default for Decal {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            size: Vec3::ONE,
+            color: Vec4::ONE,
+            texture: None,
+            order: 0,
+            fade: 0.2,
+            max_angle: 60f32.to_radians(),
+        }
+    }
+}
+
+impl Decal {
+    // from the unit box around the origin to world space
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.size.max(Vec3::splat(0.0001)), self.rotation, self.position)
+    }
+}
+
+// per instance, see VertexIn in decals.wgsl
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GpuDecal {
+    model: [[f32; 4]; 4],
+    inverse: [[f32; 4]; 4],
+    color: [f32; 4],
+    params: [f32; 4],
+}
+
+// a run of sorted decals sharing a texture, drawn at once
+struct Batch {
+    texture: Option<u32>,
+    instances: std::ops::Range<u32>,
+}
+
+// the decals in the world, drawn as boxes whose pixels look up the surface the prepass saw there
+pub struct Decals {
+    index_buffer: Buffer,
+    instance_buffer: Buffer,
+    instance_capacity: usize,
+    batches: Vec<Batch>,
+    normal_depth_bind_group: BindGroup,
+    // per material texture the decals use, made as they're needed. None is white, also for
+    // ids that have no texture yet
+    texture_bind_groups: HashMap<Option<u32>, BindGroup>,
+    sampler: Sampler,
+    render_pipeline: RenderPipeline,
+}
+
+impl Decals {
+    // normal_depth is the ssao prepass, replaced with set_normal_depth when it's recreated.
+    // FRAME_LAYOUT has to be registered already, see CameraBinding
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, normal_depth: &TextureView) -> Self {
+        let device = &context.device;
+        layouts.register(context, DECAL_LAYOUT, &[
+            (Binding::Texture, ShaderStages::FRAGMENT),
+        ]);
+        layouts.register(context, DECAL_TEXTURE_LAYOUT, &[
+            (Binding::Texture, ShaderStages::FRAGMENT),
+            (Binding::Sampler, ShaderStages::FRAGMENT),
+        ]);
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "decals.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("decals"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT), layouts.get(DECAL_LAYOUT), layouts.get(DECAL_TEXTURE_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("decals"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[
+                    VertexBufferLayout {
+                        array_stride: size_of::<GpuDecal>() as BufferAddress,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &vertex_attr_array![
+                            0 => Float32x4,
+                            1 => Float32x4,
+                            2 => Float32x4,
+                            3 => Float32x4,
+                            4 => Float32x4,
+                            5 => Float32x4,
+                            6 => Float32x4,
+                            7 => Float32x4,
+                            8 => Float32x4,
+                            9 => Float32x4,
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &shader_module,
+                targets: &[
+                    Some(ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })
+                ],
+            }),
+            // the back faces, so the box still draws with the camera inside it. which pixels
+            // it covers is decided by the box test in the shader, not by depth
+            primitive: PrimitiveState {
+                cull_mode: Some(Face::Front),
+                ..PrimitiveState::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("decal box"),
+            contents: bytemuck::cast_slice(&BOX_INDICES),
+            usage: BufferUsages::INDEX,
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("decals"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        Self {
+            index_buffer,
+            instance_buffer: create_instance_buffer(context, 64),
+            instance_capacity: 64,
+            batches: Vec::new(),
+            normal_depth_bind_group: create_normal_depth_bind_group(context, layouts, normal_depth),
+            texture_bind_groups: HashMap::new(),
+            sampler,
+            render_pipeline,
+        }
+    }
+
+    pub fn set_normal_depth(&mut self, context: &RenderContext, layouts: &LayoutRegistry, normal_depth: &TextureView) {
+        self.normal_depth_bind_group = create_normal_depth_bind_group(context, layouts, normal_depth);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    // sorted by order, then back to front from camera. retextured are the material texture ids
+    // replaced since the last update, white stands in for ids without a texture
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(&mut self, context: &RenderContext, layouts: &LayoutRegistry, decals: &[Decal], camera: &Camera, textures: &MaterialTextures, retextured: &[u32], white: &TextureView) {
+        for id in retextured {
+            self.texture_bind_groups.remove(&Some(*id));
+        }
+        let mut decals = decals.to_vec();
+        let distance = |decal: &Decal| decal.position.distance_squared(camera.eye);
+        decals.sort_by(|a, b| a.order.cmp(&b.order).then(distance(b).total_cmp(&distance(a))));
+
+        self.batches.clear();
+        for (index, decal) in decals.iter().enumerate() {
+            let index = index as u32;
+            let texture = decal.texture.filter(|&id| textures.get(id).is_some());
+            match self.batches.last_mut() {
+                Some(batch) if batch.texture == texture => batch.instances.end = index + 1,
+                _ => self.batches.push(Batch { texture, instances: index..index + 1 }),
+            }
+            if !self.texture_bind_groups.contains_key(&texture) {
+                let view = texture.and_then(|id| textures.get(id)).map_or(white, |texture| &texture.view);
+                let bind_group = BindGroupBuilder::new()
+                    .texture(view)
+                    .sampler(&self.sampler)
+                    .build(context, layouts, DECAL_TEXTURE_LAYOUT);
+                self.texture_bind_groups.insert(texture, bind_group);
+            }
+        }
+        if decals.is_empty() {
+            return;
+        }
+
+        let instances: Vec<GpuDecal> = decals.iter()
+            .map(|decal| {
+                let model = decal.matrix();
+                GpuDecal {
+                    model: model.to_cols_array_2d(),
+                    inverse: model.inverse().to_cols_array_2d(),
+                    color: decal.color.to_array(),
+                    params: [decal.fade.clamp(0.0, 1.0), decal.max_angle.clamp(0.0, std::f32::consts::PI).cos(), 0.0, 0.0],
+                }
+            })
+            .collect();
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = create_instance_buffer(context, self.instance_capacity);
+        }
+        context.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    // one draw per texture the decals switch between
+    pub fn draw_count(&self) -> u32 {
+        self.batches.len() as u32
+    }
+
+    // after the meshes and terrain of the view the prepass was drawn for
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        if self.batches.is_empty() {
+            return;
+        }
+        render_cmd.set_pipeline(&self.render_pipeline);
+        render_cmd.set_bind_group(0, camera_bind_group, &[]);
+        render_cmd.set_bind_group(1, &self.normal_depth_bind_group, &[]);
+        render_cmd.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_cmd.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint16);
+        for batch in &self.batches {
+            render_cmd.set_bind_group(2, &self.texture_bind_groups[&batch.texture], &[]);
+            render_cmd.draw_indexed(0..BOX_INDICES.len() as u32, 0, batch.instances.clone());
+        }
+    }
+}
+
+fn create_normal_depth_bind_group(context: &RenderContext, layouts: &LayoutRegistry, normal_depth: &TextureView) -> BindGroup {
+    BindGroupBuilder::new()
+        .texture(normal_depth)
+        .build(context, layouts, DECAL_LAYOUT)
+}
+
+fn create_instance_buffer(context: &RenderContext, capacity: usize) -> Buffer {
+    context.device.create_buffer(&BufferDescriptor {
+        label: Some("decals"),
+        size: (capacity * size_of::<GpuDecal>()) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,91 @@
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "color.wgsl"
+
+// the ssao prepass, see view_normal_depth
+@group(1) @binding(0) var normal_depth_map: texture_2d<f32>;
+@group(2) @binding(0) var decal_texture: texture_2d<f32>;
+@group(2) @binding(1) var decal_sampler: sampler;
+
+// see GpuDecal
+struct VertexIn {
+    @builtin(vertex_index) index: u32,
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(4) inverse_0: vec4<f32>,
+    @location(5) inverse_1: vec4<f32>,
+    @location(6) inverse_2: vec4<f32>,
+    @location(7) inverse_3: vec4<f32>,
+    @location(8) color: vec4<f32>,
+    // fade, cos of max_angle
+    @location(9) params: vec4<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) inverse_0: vec4<f32>,
+    @location(1) inverse_1: vec4<f32>,
+    @location(2) inverse_2: vec4<f32>,
+    @location(3) inverse_3: vec4<f32>,
+    // the box's up axis in world space, what surfaces are compared against for the angle fade
+    @location(4) up: vec3<f32>,
+    @location(5) color: vec4<f32>,
+    @location(6) params: vec4<f32>,
+}
+
+// the unit box, corners indexed by bits x, y, z like the index buffer expects
+@vertex
+fn vertex(in: VertexIn) -> VertexOut {
+    let corner = vec3<f32>(f32(in.index & 1u), f32((in.index >> 1u) & 1u), f32((in.index >> 2u) & 1u)) - 0.5;
+    let model = mat4x4<f32>(in.model_0, in.model_1, in.model_2, in.model_3);
+    var out: VertexOut;
+    out.pos = camera.view_proj * model * vec4<f32>(corner, 1.0);
+    out.inverse_0 = in.inverse_0;
+    out.inverse_1 = in.inverse_1;
+    out.inverse_2 = in.inverse_2;
+    out.inverse_3 = in.inverse_3;
+    out.up = normalize(in.model_1.xyz);
+    out.color = in.color;
+    out.params = in.params;
+    return out;
+}
+
+// where the prepass saw a surface at pixel, from its depth along the view direction
+fn surface_position(pixel: vec2<f32>, depth: f32) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(normal_depth_map));
+    let ndc = vec2<f32>(pixel.x / size.x * 2.0 - 1.0, 1.0 - pixel.y / size.y * 2.0);
+    let unprojected = camera.inverse_view_proj * vec4<f32>(ndc, 0.5, 1.0);
+    let ray = normalize(unprojected.xyz / unprojected.w - camera.position.xyz);
+    let forward = -vec3<f32>(camera.view[0].z, camera.view[1].z, camera.view[2].z);
+    return camera.position.xyz + ray * (depth / dot(ray, forward));
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let normal_depth = textureLoad(normal_depth_map, vec2<i32>(in.pos.xy), 0);
+    let world_position = surface_position(in.pos.xy, normal_depth.w);
+    let world_to_decal = mat4x4<f32>(in.inverse_0, in.inverse_1, in.inverse_2, in.inverse_3);
+    let box_position = (world_to_decal * vec4<f32>(world_position, 1.0)).xyz;
+    // projected down the box's y axis, the texture's top towards -z
+    let texel = textureSample(decal_texture, decal_sampler, box_position.xz + 0.5);
+
+    // nothing drawn there, or the surface is outside the box
+    if (normal_depth.w <= 0.0 || any(abs(box_position) > vec3<f32>(0.5))) {
+        discard;
+    }
+    let view_rotation = mat3x3<f32>(camera.view[0].xyz, camera.view[1].xyz, camera.view[2].xyz);
+    let normal = normalize(transpose(view_rotation) * normal_depth.xyz);
+    // towards the top and bottom of the box, and on surfaces turned away from its up axis
+    let fade = in.params.x;
+    let max_cos = in.params.y;
+    let height_fade = clamp((0.5 - abs(box_position.y)) / max(fade * 0.5, 0.0001), 0.0, 1.0);
+    let angle_fade = smoothstep(max_cos, mix(max_cos, 1.0, 0.25), dot(normal, in.up));
+    let alpha = in.color.a * texel.a * height_fade * angle_fade;
+    if (alpha <= 0.0) {
+        discard;
+    }
+    let color = shade(in.color.rgb * texel.rgb, normal, world_position, ambient_occlusion(in.pos.xy));
+    return output_color(vec4<f32>(color, alpha));
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// files baked into the binary with the embedded-assets feature, by their path from the crate
+// root. shaders, the font and the window icon are always built in, and the demo's meshes are
+// generated, so the scenes are all that's left to read at runtime
+#[cfg(feature = "embedded-assets")]
+const FILES: &[(&str, &[u8])] = &[
+    ("scenes/demo.ron", include_bytes!("../scenes/demo.ron")),
+    ("scenes/physics.ron", include_bytes!("../scenes/physics.ron")),
+];
+#[cfg(not(feature = "embedded-assets"))]
+const FILES: &[(&str, &[u8])] = &[];
+
+// an embedded file by its path from the crate root, or its absolute path on the machine it was
+// built on, which is what env!("CARGO_MANIFEST_DIR") paths resolve to
+pub fn get(path: impl AsRef<Path>) -> Option<&'static [u8]> {
+    let path = path.as_ref();
+    let path = path.strip_prefix(env!("CARGO_MANIFEST_DIR")).unwrap_or(path);
+    FILES.iter().find(|(name, _)| Path::new(name) == path).map(|&(_, bytes)| bytes)
+}
+
+// the embedded copy of a file that isn't on disk. files on disk win, so they can still be
+// edited and hot reloaded next to a binary that has them built in
+pub fn fallback(path: impl AsRef<Path>) -> Option<&'static [u8]> {
+    let path = path.as_ref();
+    if FILES.is_empty() || path.exists() {
+        return None;
+    }
+    get(path)
+}
+
+// fs::read, falling back to the embedded copy
+pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    match fallback(&path) {
+        Some(bytes) => Ok(bytes.to_vec()),
+        None => fs::read(path),
+    }
+}
+
+// fs::read_to_string, falling back to the embedded copy
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    match fallback(&path) {
+        Some(bytes) => String::from_utf8(bytes.to_vec()).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+        None => fs::read_to_string(path),
+    }
+}
@@ -0,0 +1,198 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageResult, RgbaImage};
+use wgpu::*;
+use crate::capture::Recorder;
+use crate::context::RenderContext;
+use crate::readback::Readback;
+
+// copies still on their way back, frames past this are skipped rather than queued up behind a
+// slow gpu
+const MAX_PENDING: usize = 4;
+
+// the last few seconds of output kept on the cpu at a lower rate and size, to save after
+// something went wrong on screen
+#[derive(Copy, Clone, Debug)]
+pub struct FrameHistorySettings {
+    pub enabled: bool,
+    pub seconds: f32,
+    // frames kept per second
+    pub fps: f32,
+    // of the window size, each frame is width * height * scale² * 4 bytes
+    pub scale: f32,
+}
+
+impl Default for FrameHistorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seconds: 10.0,
+            fps: 10.0,
+            scale: 0.25,
+        }
+    }
+}
+
+impl FrameHistorySettings {
+    fn capacity(&self) -> usize {
+        (self.seconds * self.fps).ceil().max(1.0) as usize
+    }
+}
+
+struct Target {
+    texture: Texture,
+    view: TextureView,
+    width: u32,
+    height: u32,
+}
+
+// the output pass draws a downscaled copy of the frame into target every 1 / fps seconds, which
+// is read back on the next update and lands in the ring a frame or two later
+pub struct FrameHistory {
+    target: Option<Target>,
+    format: TextureFormat,
+    frames: VecDeque<RgbaImage>,
+    pending: VecDeque<Readback>,
+    since_capture: f32,
+    // decided on update, for the frame encoded after it
+    due: bool,
+    // set when encode drew into the target
+    written: Cell<bool>,
+}
+
+impl FrameHistory {
+    // format is what the output pass can draw into besides the surface, see CAPTURE_FORMAT
+    pub fn new(format: TextureFormat) -> Self {
+        Self {
+            target: None,
+            format,
+            frames: VecDeque::new(),
+            pending: VecDeque::new(),
+            since_capture: 0.0,
+            due: false,
+            written: Cell::new(false),
+        }
+    }
+
+    // window_size is physical pixels. turning it off frees everything
+    pub fn update(&mut self, context: &RenderContext, settings: &FrameHistorySettings, window_size: (u32, u32), delta: f32) {
+        if !settings.enabled {
+            self.target = None;
+            self.frames.clear();
+            self.pending.clear();
+            self.due = false;
+            self.written.set(false);
+            return;
+        }
+        if self.written.take() {
+            if let Some(target) = &self.target {
+                let size = Extent3d { width: target.width, height: target.height, depth_or_array_layers: 1 };
+                self.pending.push_back(context.read_texture(target.texture.as_image_copy(), self.format, size));
+            }
+        }
+        // readbacks finish in order, the device is polled by the event loop
+        while let Some(readback) = self.pending.front_mut() {
+            let Some(result) = readback.try_take() else { break };
+            self.pending.pop_front();
+            let Some(target) = &self.target else { continue };
+            match result {
+                Ok(mut pixels) => {
+                    if matches!(self.format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb) {
+                        pixels.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+                    }
+                    // anything read back from before a resize has the old size
+                    if let Some(image) = RgbaImage::from_raw(target.width, target.height, pixels) {
+                        self.frames.push_back(image);
+                    }
+                }
+                Err(error) => tracing::warn!("failed to read back a history frame: {error}"),
+            }
+        }
+        while self.frames.len() > settings.capacity() {
+            self.frames.pop_front();
+        }
+
+        let scaled = |size: u32| ((size as f32 * settings.scale).round() as u32).max(1);
+        let (width, height) = (scaled(window_size.0), scaled(window_size.1));
+        if self.target.as_ref().is_none_or(|target| (target.width, target.height) != (width, height)) {
+            // frames of different sizes can't go into one gif
+            self.frames.clear();
+            self.pending.clear();
+            self.target = Some(create_target(context, self.format, width, height));
+        }
+        self.since_capture += delta;
+        self.due = self.since_capture >= 1.0 / settings.fps.max(0.01) && self.pending.len() < MAX_PENDING;
+        if self.due {
+            self.since_capture = 0.0;
+        }
+    }
+
+    // where the frame encoded next should be drawn too, none when this frame isn't kept
+    pub fn target(&self) -> Option<(&TextureView, TextureFormat)> {
+        let target = self.target.as_ref().filter(|_| self.due)?;
+        self.written.set(true);
+        Some((&target.view, self.format))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // a copy of what's kept so far, oldest first, to save without holding up rendering
+    pub fn snapshot(&self, settings: &FrameHistorySettings) -> FrameDump {
+        FrameDump {
+            frames: self.frames.iter().cloned().collect(),
+            fps: settings.fps,
+        }
+    }
+}
+
+pub struct FrameDump {
+    pub frames: Vec<RgbaImage>,
+    pub fps: f32,
+}
+
+impl FrameDump {
+    // a looping gif for .gif paths, numbered pngs in a directory otherwise. gifs are quantized
+    // frame by frame, which takes a while, so this is best called off the main thread
+    pub fn save(self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let path = path.as_ref();
+        let is_gif = path.extension().and_then(|extension| extension.to_str()).is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+        if is_gif {
+            let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+            encoder.set_repeat(Repeat::Infinite)?;
+            let delay = Delay::from_numer_denom_ms(1000, self.fps.round().max(1.0) as u32);
+            return encoder.encode_frames(self.frames.into_iter().map(|image| Frame::from_parts(image, 0, 0, delay)));
+        }
+        let Some(first) = self.frames.first() else {
+            return Ok(());
+        };
+        let mut recorder = Recorder::new(path, first.width(), first.height(), self.fps.round() as u32)?;
+        for image in &self.frames {
+            recorder.write(image)?;
+        }
+        recorder.finish()
+    }
+}
+
+fn create_target(context: &RenderContext, format: TextureFormat, width: u32, height: u32) -> Target {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some("frame history"),
+        size: Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    Target { texture, view, width, height }
+}
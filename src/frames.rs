@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use wgpu::*;
+
+// per-frame resources are created this many times, so the cpu can fill one while the gpu still
+// reads the others
+pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
+
+// one copy of something per frame in flight, indexed by FramePacer::slot
+pub struct FrameRing<T> {
+    items: Vec<T>,
+}
+
+impl<T> FrameRing<T> {
+    pub fn new(mut create: impl FnMut() -> T) -> Self {
+        Self {
+            items: (0..MAX_FRAMES_IN_FLIGHT).map(|_| create()).collect(),
+        }
+    }
+
+    pub fn get(&self, slot: usize) -> &T {
+        &self.items[slot]
+    }
+
+    pub fn get_mut(&mut self, slot: usize) -> &mut T {
+        &mut self.items[slot]
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.items.iter_mut()
+    }
+}
+
+// keeps the cpu at most max_in_flight frames ahead of the gpu, and optionally caps the frame rate
+pub struct FramePacer {
+    // 1 is lowest latency, more keeps the gpu busier. at most MAX_FRAMES_IN_FLIGHT
+    pub max_in_flight: usize,
+    // frames per second, None to run as fast as the present mode allows
+    pub max_fps: Option<f32>,
+    frame: u64,
+    // decremented from on_submitted_work_done, so it only changes while the device is polled
+    in_flight: Arc<AtomicUsize>,
+    last_frame: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self {
+            max_in_flight: 2,
+            max_fps: None,
+            frame: 0,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            last_frame: None,
+        }
+    }
+
+    // which FrameRing entry the current frame uses
+    pub fn slot(&self) -> usize {
+        (self.frame % MAX_FRAMES_IN_FLIGHT as u64) as usize
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    // blocks until a frame slot is free and moves on to it
+    pub fn begin_frame(&mut self, device: &Device) {
+        let max = self.max_in_flight.clamp(1, MAX_FRAMES_IN_FLIGHT);
+        device.poll(Maintain::Poll);
+        while self.in_flight() >= max {
+            thread::sleep(Duration::from_micros(100));
+            device.poll(Maintain::Poll);
+        }
+        self.frame += 1;
+    }
+
+    // call right after submitting a frame's last command buffers. anything submitted earlier in
+    // the frame, like async compute, is done by the time they are, so it's counted with them
+    pub fn submitted(&self, queue: &Queue) {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        let in_flight = self.in_flight.clone();
+        queue.on_submitted_work_done(move || {
+            in_flight.fetch_sub(1, Ordering::AcqRel);
+        });
+    }
+
+    // sleeps out whatever is left of the frame when max_fps is set
+    pub fn limit(&mut self) {
+        if let (Some(fps), Some(last_frame)) = (self.max_fps, self.last_frame) {
+            let frame_time = Duration::from_secs_f32(1.0 / fps.max(1.0));
+            if let Some(remaining) = frame_time.checked_sub(last_frame.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+        self.last_frame = Some(Instant::now());
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
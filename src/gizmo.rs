@@ -0,0 +1,204 @@
+use std::f32::consts::TAU;
+use glam::{Quat, Vec3};
+use crate::debug::DebugDraw;
+use crate::raycast::Ray;
+use crate::transform::Transform;
+
+// the handles' length as a fraction of their distance from the camera, so they keep their size
+// on screen
+const SCREEN_SCALE: f32 = 0.15;
+// how close a ray has to pass a handle to grab it, as a fraction of the handle length
+const GRAB_TOLERANCE: f32 = 0.08;
+const RING_SEGMENTS: u32 = 48;
+const AXES: [Vec3; 3] = [Vec3::X, Vec3::Y, Vec3::Z];
+const AXIS_COLORS: [[f32; 4]; 3] = [[1.0, 0.2, 0.2, 1.0], [0.2, 1.0, 0.2, 1.0], [0.2, 0.2, 1.0, 1.0]];
+const ACTIVE_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub fn next(self) -> Self {
+        match self {
+            GizmoMode::Translate => GizmoMode::Rotate,
+            GizmoMode::Rotate => GizmoMode::Scale,
+            GizmoMode::Scale => GizmoMode::Translate,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Drag {
+    axis: usize,
+    start: Transform,
+    // kept from the start, so the handles don't change size under the cursor
+    size: f32,
+    // where along the axis it was grabbed for translate and scale, the direction from the
+    // center to the grabbed point on the ring for rotate
+    grabbed: Vec3,
+}
+
+// handles along the world axes at an object's origin: arrows to move it, rings to turn it
+// and boxes to scale it. rays come from Camera::screen_to_ray, the same as ray casting
+#[derive(Default)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    hovered: Option<usize>,
+    drag: Option<Drag>,
+}
+
+impl Gizmo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    // highlights the handle under the ray
+    pub fn hover(&mut self, transform: &Transform, eye: Vec3, ray: &Ray) {
+        if self.drag.is_none() {
+            self.hovered = self.handle_at(transform, size(transform, eye), ray);
+        }
+    }
+
+    // starts dragging the handle under the ray. false if there's none, the click is free to
+    // pick something else then
+    pub fn begin_drag(&mut self, transform: &Transform, eye: Vec3, ray: &Ray) -> bool {
+        let size = size(transform, eye);
+        let Some(axis) = self.handle_at(transform, size, ray) else {
+            return false;
+        };
+        let grabbed = match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => closest_on_axis(ray, transform.translation, AXES[axis]).map(|along| AXES[axis] * along),
+            GizmoMode::Rotate => intersect_plane(ray, transform.translation, AXES[axis]).map(|point| point - transform.translation),
+        };
+        let Some(grabbed) = grabbed else {
+            return false;
+        };
+        self.drag = Some(Drag { axis, start: *transform, size, grabbed });
+        self.hovered = Some(axis);
+        true
+    }
+
+    // the transform the drag has moved to, None while not dragging or when the ray runs
+    // parallel to what the handle moves along
+    pub fn drag(&self, ray: &Ray) -> Option<Transform> {
+        let drag = self.drag?;
+        let axis = AXES[drag.axis];
+        let center = drag.start.translation;
+        let mut transform = drag.start;
+        match self.mode {
+            GizmoMode::Translate => {
+                let along = closest_on_axis(ray, center, axis)?;
+                transform.translation = center + axis * along - drag.grabbed;
+            }
+            GizmoMode::Rotate => {
+                let to = intersect_plane(ray, center, axis)? - center;
+                let from = drag.grabbed;
+                let angle = axis.dot(from.cross(to)).atan2(from.dot(to));
+                transform.rotation = Quat::from_axis_angle(axis, angle) * drag.start.rotation;
+            }
+            GizmoMode::Scale => {
+                let along = closest_on_axis(ray, center, axis)?;
+                let grabbed = drag.grabbed.dot(axis);
+                if grabbed.abs() < f32::EPSILON {
+                    return None;
+                }
+                transform.scale[drag.axis] = (drag.start.scale[drag.axis] * along / grabbed).max(0.01);
+            }
+        }
+        Some(transform)
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn draw(&self, debug: &mut DebugDraw, transform: &Transform, eye: Vec3) {
+        let center = transform.translation;
+        let size = self.drag.map_or_else(|| size(transform, eye), |drag| drag.size);
+        for (index, axis) in AXES.into_iter().enumerate() {
+            let color = if self.hovered == Some(index) { ACTIVE_COLOR } else { AXIS_COLORS[index] };
+            let end = center + axis * size;
+            match self.mode {
+                GizmoMode::Translate => {
+                    debug.line(center, end, color);
+                    // a cone of four lines for the tip
+                    let (u, v) = axis.any_orthonormal_pair();
+                    for side in [u, -u, v, -v] {
+                        debug.line(end, end - axis * size * 0.15 + side * size * 0.05, color);
+                    }
+                }
+                GizmoMode::Rotate => {
+                    let (u, v) = axis.any_orthonormal_pair();
+                    let point = |i: u32| {
+                        let angle = i as f32 / RING_SEGMENTS as f32 * TAU;
+                        center + (u * angle.cos() + v * angle.sin()) * size
+                    };
+                    for i in 0..RING_SEGMENTS {
+                        debug.line(point(i), point(i + 1), color);
+                    }
+                }
+                GizmoMode::Scale => {
+                    debug.line(center, end, color);
+                    let half = Vec3::splat(size * 0.05);
+                    debug.aabb(end - half, end + half, color);
+                }
+            }
+        }
+    }
+
+    fn handle_at(&self, transform: &Transform, size: f32, ray: &Ray) -> Option<usize> {
+        let center = transform.translation;
+        let tolerance = size * GRAB_TOLERANCE;
+        AXES.into_iter().enumerate()
+            .filter_map(|(index, axis)| {
+                let distance = match self.mode {
+                    GizmoMode::Translate | GizmoMode::Scale => {
+                        let along = closest_on_axis(ray, center, axis)?.clamp(0.0, size);
+                        distance_to_ray(ray, center + axis * along)
+                    }
+                    GizmoMode::Rotate => {
+                        let point = intersect_plane(ray, center, axis)?;
+                        (point.distance(center) - size).abs()
+                    }
+                };
+                (distance < tolerance).then_some((index, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+}
+
+fn size(transform: &Transform, eye: Vec3) -> f32 {
+    (transform.translation.distance(eye) * SCREEN_SCALE).max(f32::EPSILON)
+}
+
+// how far along the axis through origin its closest point to the ray is
+fn closest_on_axis(ray: &Ray, origin: Vec3, axis: Vec3) -> Option<f32> {
+    let direction = ray.direction.normalize();
+    let offset = origin - ray.origin;
+    let b = axis.dot(direction);
+    let denominator = 1.0 - b * b;
+    if denominator < 1e-6 {
+        return None;
+    }
+    Some((b * direction.dot(offset) - axis.dot(offset)) / denominator)
+}
+
+fn distance_to_ray(ray: &Ray, point: Vec3) -> f32 {
+    let direction = ray.direction.normalize();
+    let along = direction.dot(point - ray.origin).max(0.0);
+    point.distance(ray.origin + direction * along)
+}
+
+fn intersect_plane(ray: &Ray, origin: Vec3, normal: Vec3) -> Option<Vec3> {
+    ray.intersect_plane(origin, normal).map(|distance| ray.at(distance))
+}
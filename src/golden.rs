@@ -0,0 +1,151 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::{Rgba, RgbaImage};
+
+// the largest yiq delta there is, between black and white
+const MAX_DELTA: f32 = 35215.0;
+const DIFF_COLOR: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Image(image::ImageError),
+    // nothing to compare against yet
+    Missing(PathBuf),
+    SizeMismatch { expected: (u32, u32), actual: (u32, u32) },
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GoldenError::Image(error) => error.fmt(f),
+            GoldenError::Missing(path) => write!(f, "no reference image at {}", path.display()),
+            GoldenError::SizeMismatch { expected, actual } => {
+                write!(f, "expected {}x{}, rendered {}x{}", expected.0, expected.1, actual.0, actual.1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<image::ImageError> for GoldenError {
+    fn from(error: image::ImageError) -> Self {
+        GoldenError::Image(error)
+    }
+}
+
+// every scene in scenes/, in order. each is checked against golden/<name>.png
+pub fn scenes() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut scenes: Vec<PathBuf> = fs::read_dir(root.join("scenes"))
+        .expect("failed to list scenes")
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .collect();
+    scenes.sort();
+    scenes
+}
+
+// how different a frame may be from its reference and still pass. drivers round and filter a
+// little differently, so exact matches only hold on the machine the reference came from
+#[derive(Copy, Clone, Debug)]
+pub struct Tolerance {
+    // per pixel, 0..1 of the largest perceptual difference there is. 0.1 ignores antialiasing
+    // and filtering noise but not a changed color
+    pub threshold: f32,
+    // the fraction of pixels past the threshold a frame may have
+    pub max_differing: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            threshold: 0.1,
+            max_differing: 0.001,
+        }
+    }
+}
+
+pub struct Diff {
+    pub differing: u32,
+    pub total: u32,
+    // the reference faded out with differing pixels in red
+    pub image: RgbaImage,
+}
+
+impl Diff {
+    pub fn fraction(&self) -> f32 {
+        self.differing as f32 / self.total.max(1) as f32
+    }
+
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.fraction() <= tolerance.max_differing
+    }
+}
+
+// pixels are compared in yiq, weighted the way the eye weighs brightness against hue, like
+// pixelmatch does
+pub fn compare(expected: &RgbaImage, actual: &RgbaImage, tolerance: &Tolerance) -> Result<Diff, GoldenError> {
+    if expected.dimensions() != actual.dimensions() {
+        return Err(GoldenError::SizeMismatch { expected: expected.dimensions(), actual: actual.dimensions() });
+    }
+    let limit = MAX_DELTA * tolerance.threshold * tolerance.threshold;
+    let mut image = RgbaImage::new(expected.width(), expected.height());
+    let mut differing = 0;
+    for ((a, b), out) in expected.pixels().zip(actual.pixels()).zip(image.pixels_mut()) {
+        if delta(a, b) > limit {
+            differing += 1;
+            *out = DIFF_COLOR;
+        } else {
+            let gray = (255.0 - (255.0 - yiq(a)[0]) * 0.1) as u8;
+            *out = Rgba([gray, gray, gray, 255]);
+        }
+    }
+    Ok(Diff {
+        differing,
+        total: expected.width() * expected.height(),
+        image,
+    })
+}
+
+// loads path and compares against it
+pub fn compare_to_file(path: impl AsRef<Path>, actual: &RgbaImage, tolerance: &Tolerance) -> Result<Diff, GoldenError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(GoldenError::Missing(path.to_path_buf()));
+    }
+    let expected = image::open(path)?.to_rgba8();
+    compare(&expected, actual, tolerance)
+}
+
+// writes the rendered frame as the new reference
+pub fn update(path: impl AsRef<Path>, actual: &RgbaImage) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(image::ImageError::IoError)?;
+    }
+    actual.save(path)?;
+    Ok(())
+}
+
+// alpha is blended onto white first, so transparent pixels compare by how they'd look
+fn yiq(pixel: &Rgba<u8>) -> [f32; 3] {
+    let [r, g, b, a] = pixel.0.map(f32::from);
+    let blend = |c: f32| 255.0 + (c - 255.0) * a / 255.0;
+    let (r, g, b) = (blend(r), blend(g), blend(b));
+    [
+        r * 0.298_895_3 + g * 0.586_622_47 + b * 0.114_482_23,
+        r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_89,
+        r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_94,
+    ]
+}
+
+fn delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    if a == b {
+        return 0.0;
+    }
+    let (a, b) = (yiq(a), yiq(b));
+    let [y, i, q] = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
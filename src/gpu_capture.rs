@@ -0,0 +1,95 @@
+#[cfg(feature = "renderdoc")]
+use renderdoc::{RenderDoc, V110};
+
+// renderdoc frame captures. needs the renderdoc feature and the app started from renderdoc (or
+// with its library injected), otherwise requests are ignored
+pub struct GpuCapture {
+    #[cfg(feature = "renderdoc")]
+    api: Option<RenderDoc<V110>>,
+    // frames begun so far, the first one is frame 1
+    frame: u64,
+    // capture the frame with this number
+    at_frame: Option<u64>,
+    requested: bool,
+    capturing: bool,
+}
+
+impl GpuCapture {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "renderdoc")]
+            api: RenderDoc::new()
+                .map_err(|error| tracing::debug!("renderdoc not attached: {error}"))
+                .ok(),
+            frame: 0,
+            at_frame: None,
+            requested: false,
+            capturing: false,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        #[cfg(feature = "renderdoc")]
+        if self.api.is_some() {
+            return true;
+        }
+        false
+    }
+
+    // captures the next frame
+    pub fn request(&mut self) {
+        if !self.is_available() {
+            tracing::warn!("can't capture, not running under renderdoc with the renderdoc feature");
+            return;
+        }
+        self.requested = true;
+    }
+
+    // captures frame number `frame`, counting from 1
+    pub fn capture_frame(&mut self, frame: u64) {
+        self.at_frame = Some(frame);
+    }
+
+    // call before any of the frame's gpu work is recorded
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+        if self.at_frame == Some(self.frame) {
+            self.at_frame = None;
+            self.request();
+        }
+        if std::mem::take(&mut self.requested) {
+            self.start();
+        }
+    }
+
+    // call once the frame has been submitted
+    pub fn end_frame(&mut self) {
+        if self.capturing {
+            self.capturing = false;
+            self.end();
+        }
+    }
+
+    fn start(&mut self) {
+        #[cfg(feature = "renderdoc")]
+        if let Some(api) = &mut self.api {
+            // null device and window capture whatever the app renders to
+            api.start_frame_capture(std::ptr::null(), std::ptr::null());
+            self.capturing = true;
+            tracing::info!("capturing frame {}", self.frame);
+        }
+    }
+
+    fn end(&mut self) {
+        #[cfg(feature = "renderdoc")]
+        if let Some(api) = &mut self.api {
+            api.end_frame_capture(std::ptr::null(), std::ptr::null());
+        }
+    }
+}
+
+impl Default for GpuCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
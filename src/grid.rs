@@ -0,0 +1,129 @@
+use std::mem::size_of;
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::FRAME_LAYOUT;
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+pub const GRID_LAYOUT: &str = "grid";
+
+#[derive(Copy, Clone, Debug)]
+pub struct GridSettings {
+    pub enabled: bool,
+    // linear, the alpha of major lines, minor ones get half
+    pub color: [f32; 4],
+    // world units between minor lines
+    pub spacing: f32,
+    // every that many minor lines is a major one
+    pub major: u32,
+    // lines fade out towards this distance from the camera
+    pub fade_distance: f32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: [0.6, 0.6, 0.6, 0.8],
+            spacing: 1.0,
+            major: 10,
+            fade_distance: 50.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GridUniform {
+    color: [f32; 4],
+    spacing: f32,
+    major: f32,
+    fade_distance: f32,
+    _padding: f32,
+}
+
+// an endless ground grid on y = 0 with the x and z axes drawn in, for editing. one fullscreen
+// triangle whose pixels find the plane by unprojecting, so there's no mesh to run out of
+pub struct Grid {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    render_pipeline: RenderPipeline,
+}
+
+impl Grid {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry) -> Self {
+        let device = &context.device;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("grid"),
+            size: size_of::<GridUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, GRID_LAYOUT, &[(Binding::Uniform, ShaderStages::FRAGMENT)]);
+        let bind_group = BindGroupBuilder::new()
+            .buffer(&buffer)
+            .build(context, layouts, GRID_LAYOUT);
+
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "grid.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("grid"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT), layouts.get(GRID_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("grid"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &shader_module,
+                targets: &[
+                    Some(ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: PrimitiveState::default(),
+            // tested against the meshes, but transparent, so it doesn't write depth
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        Self {
+            buffer,
+            bind_group,
+            render_pipeline,
+        }
+    }
+
+    pub fn update(&self, context: &RenderContext, settings: &GridSettings) {
+        let uniform = GridUniform {
+            color: settings.color,
+            spacing: settings.spacing.max(f32::EPSILON),
+            major: settings.major.max(1) as f32,
+            fade_distance: settings.fade_distance.max(f32::EPSILON),
+            _padding: 0.0,
+        };
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        render_cmd.set_pipeline(&self.render_pipeline);
+        render_cmd.set_bind_group(0, camera_bind_group, &[]);
+        render_cmd.set_bind_group(1, &self.bind_group, &[]);
+        render_cmd.draw(0..3, 0..1);
+    }
+}
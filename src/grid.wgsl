@@ -0,0 +1,80 @@
+#include "camera.wgsl"
+#include "color.wgsl"
+
+struct Grid {
+    color: vec4<f32>,
+    // world units between minor lines
+    spacing: f32,
+    // every that many minor lines is a major one
+    major: f32,
+    // lines fade out towards this distance from the camera
+    fade_distance: f32,
+}
+
+@group(1) @binding(0) var<uniform> grid: Grid;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+struct FragmentOut {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+// a single triangle that covers the viewport
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+    var out: VertexOut;
+    out.pos = vec4<f32>(corner, 0.0, 1.0);
+    out.ndc = corner;
+    return out;
+}
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let world = camera.inverse_view_proj * vec4<f32>(ndc, 1.0);
+    return world.xyz / world.w;
+}
+
+// 1 on a line, fading to 0 within about a pixel of it
+fn lines(position: vec2<f32>, spacing: f32) -> f32 {
+    let coord = position / spacing;
+    let width = fwidth(coord);
+    let distance = abs(fract(coord - 0.5) - 0.5) / width;
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+// intersects each pixel's ray with the y = 0 plane, depth is written so meshes cover the grid.
+// pixels whose ray misses the plane are discarded last, derivatives need every pixel until then
+@fragment
+fn fragment(in: VertexOut) -> FragmentOut {
+    let near = unproject(vec3<f32>(in.ndc, 0.0));
+    let far = unproject(vec3<f32>(in.ndc, 1.0));
+    let t = -near.y / (far.y - near.y);
+    let position = near + (far - near) * t;
+    let clip = camera.view_proj * vec4<f32>(position, 1.0);
+
+    let minor = lines(position.xz, grid.spacing);
+    let major = lines(position.xz, grid.spacing * grid.major);
+    var color = vec4<f32>(grid.color.rgb, grid.color.a * max(minor * 0.5, major));
+    // the x axis runs along z = 0, the z axis along x = 0
+    let axis_width = fwidth(position.xz);
+    if (abs(position.z) < axis_width.y) {
+        color = vec4<f32>(1.0, 0.2, 0.2, 1.0);
+    }
+    if (abs(position.x) < axis_width.x) {
+        color = vec4<f32>(0.2, 0.2, 1.0, 1.0);
+    }
+    let fade = 1.0 - clamp(distance(position, camera.position.xyz) / grid.fade_distance, 0.0, 1.0);
+    color.a = color.a * fade;
+    if (t <= 0.0 || t >= 1.0) {
+        discard;
+    }
+
+    var out: FragmentOut;
+    out.color = output_color(color);
+    out.depth = clip.z / clip.w;
+    return out;
+}
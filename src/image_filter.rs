@@ -0,0 +1,159 @@
+use std::str::FromStr;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::texture::Texture;
+
+pub const IMAGE_FILTER_LAYOUT: &str = "image filter";
+// what filtered images come out as. float so filtering linear colors doesn't band, and every
+// device can write it as a storage texture, which srgb formats can't be
+pub const FILTERED_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+// the workgroups in image_filter.wgsl are this many pixels square, 64 invocations
+const WORKGROUP_SIZE: u32 = 8;
+const MAX_BLUR_RADIUS: i32 = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageFilter {
+    // sigma in pixels, taps reach out to 3 sigma
+    GaussianBlur { sigma: f32 },
+    // the luminance's edges, white on black
+    Sobel,
+    Grayscale,
+}
+
+// for --filter, blur takes its sigma after a colon, e.g. blur:4
+impl FromStr for ImageFilter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.split_once(':') {
+            Some(("blur", sigma)) => sigma.parse()
+                .map(|sigma| ImageFilter::GaussianBlur { sigma })
+                .map_err(|_| format!("bad blur sigma {sigma}")),
+            None if text == "blur" => Ok(ImageFilter::GaussianBlur { sigma: 2.0 }),
+            None if text == "sobel" => Ok(ImageFilter::Sobel),
+            None if text == "grayscale" => Ok(ImageFilter::Grayscale),
+            _ => Err(format!("unknown filter {text}, expected blur, sobel or grayscale")),
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct FilterParams {
+    direction: [i32; 2],
+    radius: i32,
+    sigma: f32,
+}
+
+// compute filters from one texture into a new one, reading the source as a sampled texture and
+// writing the result as a storage texture. meant for images as they're loaded, e.g. before
+// they're added as material textures, so nothing is kept between runs
+pub struct ImageFilters {
+    blur_pipeline: ComputePipeline,
+    sobel_pipeline: ComputePipeline,
+    grayscale_pipeline: ComputePipeline,
+}
+
+impl ImageFilters {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry) -> Self {
+        let device = &context.device;
+        layouts.register(context, IMAGE_FILTER_LAYOUT, &[
+            (Binding::Texture, ShaderStages::COMPUTE),
+            (Binding::StorageTexture { format: FILTERED_FORMAT }, ShaderStages::COMPUTE),
+            (Binding::Uniform, ShaderStages::COMPUTE),
+        ]);
+        let module = Preprocessor::new().create_module(context, "image_filter.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("image filter"),
+            bind_group_layouts: &[layouts.get(IMAGE_FILTER_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = |entry_point| device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point,
+        });
+        Self {
+            blur_pipeline: compute_pipeline("blur"),
+            sobel_pipeline: compute_pipeline("sobel"),
+            grayscale_pipeline: compute_pipeline("grayscale"),
+        }
+    }
+
+    // a new texture the size of source in FILTERED_FORMAT. srgb sources read back as linear, so
+    // the filters work on linear colors either way
+    pub fn apply(&self, context: &RenderContext, layouts: &LayoutRegistry, source: &Texture, filter: ImageFilter) -> Texture {
+        let mut cmd = context.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("image filter") });
+        let output = create_texture(context, source.size, "filtered");
+        let mut params = FilterParams { direction: [0, 0], radius: 0, sigma: 0.0 };
+        match filter {
+            // separable, so each pixel takes 2 * (2r + 1) taps instead of (2r + 1)^2
+            ImageFilter::GaussianBlur { sigma } => {
+                let sigma = sigma.max(0.1);
+                params.sigma = sigma;
+                params.radius = ((sigma * 3.0).ceil() as i32).clamp(1, MAX_BLUR_RADIUS);
+                let horizontal = create_texture(context, source.size, "blurred horizontally");
+                params.direction = [1, 0];
+                dispatch(context, layouts, &mut cmd, &self.blur_pipeline, &source.view, &horizontal, params);
+                params.direction = [0, 1];
+                dispatch(context, layouts, &mut cmd, &self.blur_pipeline, &horizontal.view, &output, params);
+            }
+            ImageFilter::Sobel => dispatch(context, layouts, &mut cmd, &self.sobel_pipeline, &source.view, &output, params),
+            ImageFilter::Grayscale => dispatch(context, layouts, &mut cmd, &self.grayscale_pipeline, &source.view, &output, params),
+        }
+        context.queue.submit([cmd.finish()]);
+        output
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn dispatch(
+    context: &RenderContext,
+    layouts: &LayoutRegistry,
+    cmd: &mut CommandEncoder,
+    pipeline: &ComputePipeline,
+    source: &TextureView,
+    destination: &Texture,
+    params: FilterParams,
+) {
+    let buffer = context.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("image filter"),
+        contents: bytemuck::bytes_of(&params),
+        usage: BufferUsages::UNIFORM,
+    });
+    let bind_group = BindGroupBuilder::new()
+        .texture(source)
+        .texture(&destination.view)
+        .buffer(&buffer)
+        .build(context, layouts, IMAGE_FILTER_LAYOUT);
+    let size = destination.size;
+    let mut compute_cmd = cmd.begin_compute_pass(&ComputePassDescriptor { label: Some("image filter") });
+    compute_cmd.set_pipeline(pipeline);
+    compute_cmd.set_bind_group(0, &bind_group, &[]);
+    compute_cmd.dispatch_workgroups(size.width.div_ceil(WORKGROUP_SIZE), size.height.div_ceil(WORKGROUP_SIZE), 1);
+}
+
+fn create_texture(context: &RenderContext, size: Extent3d, label: &str) -> Texture {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: FILTERED_FORMAT,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+    });
+    let view = texture.create_view(&TextureViewDescriptor {
+        label: Some(label),
+        ..TextureViewDescriptor::default()
+    });
+    Texture {
+        texture,
+        view,
+        size,
+    }
+}
@@ -0,0 +1,81 @@
+// filters for ImageFilters, one invocation per pixel. the workgroup size has to match
+// WORKGROUP_SIZE in image_filter.rs
+
+struct Params {
+    // from one blur tap to the next, a pixel along x or y
+    direction: vec2<i32>,
+    radius: i32,
+    sigma: f32,
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var destination: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+// taps past the edges repeat the border
+fn load(pixel: vec2<i32>) -> vec4<f32> {
+    let last = vec2<i32>(textureDimensions(source)) - 1;
+    return textureLoad(source, clamp(pixel, vec2<i32>(0), last), 0);
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn luminance_at(pixel: vec2<i32>, x: i32, y: i32) -> f32 {
+    return luminance(load(pixel + vec2<i32>(x, y)).rgb);
+}
+
+// the last workgroups hang over the edges of images that aren't a multiple of their size
+fn outside(id: vec3<u32>) -> bool {
+    let size = vec2<u32>(textureDimensions(source));
+    return id.x >= size.x || id.y >= size.y;
+}
+
+// one direction of a separable gaussian, run once along x and once along y
+@compute @workgroup_size(8, 8)
+fn blur(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (outside(id)) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+    var sum = vec4<f32>(0.0);
+    var weights = 0.0;
+    for (var i: i32 = -params.radius; i <= params.radius; i = i + 1) {
+        let weight = exp(-f32(i * i) / (2.0 * params.sigma * params.sigma));
+        sum = sum + load(pixel + params.direction * i) * weight;
+        weights = weights + weight;
+    }
+    textureStore(destination, pixel, sum / weights);
+}
+
+// gradient magnitude of the luminance, white edges on black
+@compute @workgroup_size(8, 8)
+fn sobel(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (outside(id)) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+    let top_left = luminance_at(pixel, -1, -1);
+    let top = luminance_at(pixel, 0, -1);
+    let top_right = luminance_at(pixel, 1, -1);
+    let left = luminance_at(pixel, -1, 0);
+    let right = luminance_at(pixel, 1, 0);
+    let bottom_left = luminance_at(pixel, -1, 1);
+    let bottom = luminance_at(pixel, 0, 1);
+    let bottom_right = luminance_at(pixel, 1, 1);
+    let x = (top_right + 2.0 * right + bottom_right) - (top_left + 2.0 * left + bottom_left);
+    let y = (bottom_left + 2.0 * bottom + bottom_right) - (top_left + 2.0 * top + top_right);
+    let edge = min(length(vec2<f32>(x, y)), 1.0);
+    textureStore(destination, pixel, vec4<f32>(vec3<f32>(edge), 1.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn grayscale(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (outside(id)) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+    let color = load(pixel);
+    textureStore(destination, pixel, vec4<f32>(vec3<f32>(luminance(color.rgb)), color.a));
+}
@@ -0,0 +1,132 @@
+use std::mem::size_of;
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+use crate::texture::Texture;
+
+pub const IMAGE_VIEW_LAYOUT: &str = "image view";
+// the share of the window the image can take up
+const FILL: f32 = 0.9;
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct ImageViewUniform {
+    half_size: [f32; 2],
+}
+
+struct Shown {
+    bind_group: BindGroup,
+    // width / height
+    aspect: f32,
+}
+
+// shows a texture on top of the scene, as large as fits while keeping its aspect ratio
+pub struct ImageView {
+    buffer: Buffer,
+    sampler: Sampler,
+    render_pipeline: RenderPipeline,
+    shown: Option<Shown>,
+}
+
+impl ImageView {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry) -> Self {
+        let device = &context.device;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("image view"),
+            size: size_of::<ImageViewUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, IMAGE_VIEW_LAYOUT, &[
+            (Binding::Uniform, ShaderStages::VERTEX),
+            (Binding::Texture, ShaderStages::FRAGMENT),
+            (Binding::Sampler, ShaderStages::FRAGMENT),
+        ]);
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("image view"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "image_view.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("image view"),
+            bind_group_layouts: &[layouts.get(IMAGE_VIEW_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("image view"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &shader_module,
+                targets: &[
+                    Some(ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                ..PrimitiveState::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            buffer,
+            sampler,
+            render_pipeline,
+            shown: None,
+        }
+    }
+
+    // None hides it. the bind group holds onto the texture, so it stays alive while shown
+    pub fn set_texture(&mut self, context: &RenderContext, layouts: &LayoutRegistry, texture: Option<&Texture>) {
+        self.shown = texture.map(|texture| Shown {
+            bind_group: BindGroupBuilder::new()
+                .buffer(&self.buffer)
+                .texture(&texture.view)
+                .sampler(&self.sampler)
+                .build(context, layouts, IMAGE_VIEW_LAYOUT),
+            aspect: texture.size.width.max(1) as f32 / texture.size.height.max(1) as f32,
+        });
+    }
+
+    pub fn update(&self, context: &RenderContext) {
+        let Some(shown) = &self.shown else {
+            return;
+        };
+        // wider than the window fills its width, taller fills its height
+        let ratio = shown.aspect / context.aspect();
+        let half_size = if ratio > 1.0 { [FILL, FILL / ratio] } else { [FILL * ratio, FILL] };
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&ImageViewUniform { half_size }));
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>) {
+        let Some(shown) = &self.shown else {
+            return;
+        };
+        render_cmd.set_pipeline(&self.render_pipeline);
+        render_cmd.set_bind_group(0, &shown.bind_group, &[]);
+        render_cmd.draw(0..4, 0..1);
+    }
+}
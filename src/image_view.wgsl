@@ -0,0 +1,33 @@
+#include "color.wgsl"
+
+struct ImageView {
+    // half the image's size in clip space
+    half_size: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> view: ImageView;
+@group(0) @binding(1)
+var image: texture_2d<f32>;
+@group(0) @binding(2)
+var image_sampler: sampler;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a quad drawn as a 4 vertex triangle strip
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    var out: VertexOut;
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    out.pos = vec4<f32>((corner * 2.0 - 1.0) * view.half_size, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    return output_color(textureSample(image, image_sampler, in.uv));
+}
@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+use glam::Vec2;
+use winit::event::{DeviceEvent, ElementState, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent};
+
+// sticks rest slightly off center, anything inside this is treated as 0
+const STICK_DEADZONE: f32 = 0.15;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    ToggleBounds,
+    ReloadScene,
+    PauseAnimation,
+    ToggleLoop,
+    NextClip,
+    ToggleMusic,
+    ToggleAntialiasing,
+    ToggleSsao,
+    CaptureFrame,
+    PasteImage,
+    // releases the pointer if it's locked, otherwise hides the image
+    Cancel,
+    TogglePointerLock,
+    LogLods,
+    ToggleVertexPulling,
+    // shows the grid and the selected object's gizmo
+    ToggleEditor,
+    NextGizmoMode,
+    SaveScene,
+    LoadScene,
+    // eases the camera over to frame the selected object
+    FocusSelected,
+    // drops a marker where the pointer's ray meets the ground
+    PlaceMarker,
+    // writes out the last seconds of frames, see FrameHistorySettings
+    SaveFrameHistory,
+    // the console takes the keyboard while it's open, see Console
+    ToggleConsole,
+}
+
+// which keys and buttons trigger which actions
+pub struct InputMap {
+    pub keys: HashMap<VirtualKeyCode, Action>,
+    // pressed while ctrl is held, these win over keys
+    pub ctrl_keys: HashMap<VirtualKeyCode, Action>,
+    #[cfg(feature = "gamepad")]
+    pub buttons: HashMap<gilrs::Button, Action>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::from([
+                (VirtualKeyCode::B, Action::ToggleBounds),
+                (VirtualKeyCode::F5, Action::ReloadScene),
+                (VirtualKeyCode::Space, Action::PauseAnimation),
+                (VirtualKeyCode::L, Action::ToggleLoop),
+                (VirtualKeyCode::N, Action::NextClip),
+                (VirtualKeyCode::M, Action::ToggleMusic),
+                (VirtualKeyCode::F, Action::ToggleAntialiasing),
+                (VirtualKeyCode::O, Action::ToggleSsao),
+                (VirtualKeyCode::F12, Action::CaptureFrame),
+                (VirtualKeyCode::V, Action::PasteImage),
+                (VirtualKeyCode::Escape, Action::Cancel),
+                (VirtualKeyCode::Tab, Action::TogglePointerLock),
+                (VirtualKeyCode::I, Action::LogLods),
+                (VirtualKeyCode::P, Action::ToggleVertexPulling),
+                (VirtualKeyCode::G, Action::ToggleEditor),
+                (VirtualKeyCode::T, Action::NextGizmoMode),
+                (VirtualKeyCode::C, Action::FocusSelected),
+                (VirtualKeyCode::X, Action::PlaceMarker),
+                (VirtualKeyCode::F9, Action::SaveFrameHistory),
+                (VirtualKeyCode::Grave, Action::ToggleConsole),
+            ]),
+            ctrl_keys: HashMap::from([
+                (VirtualKeyCode::S, Action::SaveScene),
+                (VirtualKeyCode::O, Action::LoadScene),
+            ]),
+            #[cfg(feature = "gamepad")]
+            buttons: HashMap::from([
+                (gilrs::Button::North, Action::ToggleBounds),
+                (gilrs::Button::Select, Action::ReloadScene),
+                (gilrs::Button::South, Action::PauseAnimation),
+                (gilrs::Button::West, Action::ToggleLoop),
+                (gilrs::Button::East, Action::NextClip),
+                (gilrs::Button::Start, Action::ToggleMusic),
+            ]),
+        }
+    }
+}
+
+// keyboard and gamepad state, turned into actions and the move/look axes. feed it window events
+// and call poll once per frame
+pub struct Input {
+    pub map: InputMap,
+    keys_down: HashSet<VirtualKeyCode>,
+    modifiers: ModifiersState,
+    actions: Vec<Action>,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gilrs::Gilrs>,
+    left_stick: Vec2,
+    right_stick: Vec2,
+    // raw mouse motion since the last take_mouse_delta
+    mouse_delta: Vec2,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            map: InputMap::default(),
+            keys_down: HashSet::new(),
+            modifiers: ModifiersState::empty(),
+            actions: Vec::new(),
+            // no gamepad support isn't worth failing over, e.g. without permission to read devices
+            #[cfg(feature = "gamepad")]
+            gamepads: gilrs::Gilrs::new().map_err(|error| tracing::warn!("gamepads unavailable: {error}")).ok(),
+            left_stick: Vec2::ZERO,
+            right_stick: Vec2::ZERO,
+            mouse_delta: Vec2::ZERO,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        // releases while unfocused never arrive
+        if let WindowEvent::Focused(false) = event {
+            self.release_all();
+        }
+        if let WindowEvent::ModifiersChanged(modifiers) = *event {
+            self.modifiers = modifiers;
+        }
+        if let WindowEvent::KeyboardInput {
+            input: KeyboardInput { state, virtual_keycode: Some(key), .. },
+            ..
+        } = *event {
+            match state {
+                // held keys repeat their pressed events, only the first one counts
+                ElementState::Pressed if self.keys_down.insert(key) => {
+                    let ctrl_action = self.map.ctrl_keys.get(&key).filter(|_| self.modifiers.ctrl());
+                    self.actions.extend(ctrl_action.or_else(|| self.map.keys.get(&key)));
+                }
+                ElementState::Pressed => {}
+                ElementState::Released => {
+                    self.keys_down.remove(&key);
+                }
+            }
+        }
+    }
+
+    // as if every key was let go, e.g. when something else takes the keyboard
+    pub fn release_all(&mut self) {
+        self.keys_down.clear();
+        self.modifiers = ModifiersState::empty();
+    }
+
+    // only the raw mouse motion is used, it keeps coming while the cursor is locked in place
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = *event {
+            self.mouse_delta += Vec2::new(x as f32, y as f32);
+        }
+    }
+
+    // in unaccelerated device units, roughly pixels. x is right, y is down
+    pub fn take_mouse_delta(&mut self) -> Vec2 {
+        std::mem::take(&mut self.mouse_delta)
+    }
+
+    // reads buttons and sticks from every connected gamepad
+    pub fn poll(&mut self) {
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepads) = &mut self.gamepads {
+            use gilrs::{Axis, EventType};
+
+            while let Some(event) = gamepads.next_event() {
+                if let EventType::ButtonPressed(button, _) = event.event {
+                    self.actions.extend(self.map.buttons.get(&button));
+                }
+            }
+            self.left_stick = Vec2::ZERO;
+            self.right_stick = Vec2::ZERO;
+            for (_, gamepad) in gamepads.gamepads() {
+                self.left_stick += Vec2::new(gamepad.value(Axis::LeftStickX), gamepad.value(Axis::LeftStickY));
+                self.right_stick += Vec2::new(gamepad.value(Axis::RightStickX), gamepad.value(Axis::RightStickY));
+            }
+        }
+    }
+
+    // actions triggered since the last call
+    pub fn drain_actions(&mut self) -> impl Iterator<Item = Action> + '_ {
+        self.actions.drain(..)
+    }
+
+    pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    // x is right, y is forward, length at most 1. wasd or the left stick. with ctrl held the
+    // keys are shortcuts instead, see InputMap::ctrl_keys
+    pub fn move_axis(&self) -> Vec2 {
+        let keys = if self.modifiers.ctrl() {
+            Vec2::ZERO
+        } else {
+            self.key_axis(VirtualKeyCode::A, VirtualKeyCode::D, VirtualKeyCode::S, VirtualKeyCode::W)
+        };
+        (keys + deadzone(self.left_stick)).clamp_length_max(1.0)
+    }
+
+    // x turns right, y looks up, length at most 1. arrow keys or the right stick
+    pub fn look_axis(&self) -> Vec2 {
+        let keys = self.key_axis(VirtualKeyCode::Left, VirtualKeyCode::Right, VirtualKeyCode::Down, VirtualKeyCode::Up);
+        (keys + deadzone(self.right_stick)).clamp_length_max(1.0)
+    }
+
+    fn key_axis(&self, left: VirtualKeyCode, right: VirtualKeyCode, down: VirtualKeyCode, up: VirtualKeyCode) -> Vec2 {
+        let axis = |negative, positive| self.is_key_down(positive) as i32 as f32 - self.is_key_down(negative) as i32 as f32;
+        Vec2::new(axis(left, right), axis(down, up))
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// rescales so the stick still covers 0..1 outside the deadzone
+fn deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length <= STICK_DEADZONE {
+        return Vec2::ZERO;
+    }
+    stick / length * ((length - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0)
+}
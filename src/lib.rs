@@ -0,0 +1,73 @@
+pub mod animation;
+pub mod assets;
+pub mod atlas;
+pub mod atmosphere;
+pub mod app;
+pub mod audio;
+pub mod bench;
+pub mod bindings;
+pub mod bundles;
+pub mod camera;
+pub mod canvas;
+pub mod capture;
+pub mod clipboard;
+pub mod color_grading;
+pub mod compressed;
+pub mod console;
+pub mod context;
+pub mod culling;
+pub mod debug;
+pub mod decals;
+pub mod embedded;
+pub mod frame_history;
+pub mod frames;
+pub mod gizmo;
+pub mod golden;
+pub mod gpu_capture;
+pub mod grid;
+pub mod image_filter;
+pub mod image_view;
+pub mod input;
+pub mod light;
+pub mod loading;
+pub mod logging;
+pub mod material_textures;
+pub mod mesh;
+pub mod model;
+pub mod outline;
+pub mod output;
+pub mod parallel_recording;
+pub mod particles;
+pub mod physics;
+pub mod picking;
+pub mod pipeline_cache;
+pub mod preprocessor;
+pub mod primitives;
+pub mod probes;
+pub mod raycast;
+pub mod readback;
+pub mod render_scale;
+pub mod render_target;
+pub mod renderer;
+pub mod replay;
+pub mod scene;
+pub mod sdf;
+pub mod shader;
+pub mod shader_check;
+pub mod shadows;
+pub mod ssao;
+pub mod ssr;
+pub mod terrain;
+pub mod text;
+pub mod texture;
+pub mod tilemap;
+pub mod timestamps;
+pub mod transform;
+pub mod tween;
+pub mod vertex_compression;
+pub mod video;
+pub mod viewport;
+pub mod voxels;
+pub mod water;
+pub mod window_ext;
+pub mod world;
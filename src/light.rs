@@ -0,0 +1,137 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use crate::atmosphere::{AtmosphereSettings, AtmosphereUniform};
+use crate::probes::ProbeUniform;
+use crate::shadows::ShadowUniform;
+
+// match the array sizes in the shaders
+pub const MAX_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectionalLight {
+    // the direction the light travels in
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            direction: -Vec3::new(0.4, 1.0, 0.6).normalize(),
+            color: Vec3::ONE,
+            intensity: 1.0,
+        }
+    }
+}
+
+// shines in every direction from position, fading out towards range
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    // world units, nothing further away is lit or shadowed
+    pub range: f32,
+    // gets a cube of shadow maps while there are some left, see ShadowSettings::point_lights
+    pub cast_shadows: bool,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: 10.0,
+            cast_shadows: false,
+        }
+    }
+}
+
+// everything the lighting reads, gathered from the world once per update
+#[derive(Clone, Debug, Default)]
+pub struct SceneLights {
+    pub directional: Vec<DirectionalLight>,
+    pub point: Vec<PointLight>,
+    // indices into point of the lights with shadow maps, in the order of their maps
+    pub shadowed: Vec<usize>,
+    // fog over everything lit, and the sky it takes its color from
+    pub atmosphere: AtmosphereSettings,
+}
+
+#[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GpuLight {
+    direction: [f32; 3],
+    intensity: f32,
+    color: [f32; 4],
+}
+
+#[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GpuPointLight {
+    position: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    intensity: f32,
+    // into the shadow maps' cubes, -1 without one
+    shadow: i32,
+    _pad: [u32; 3],
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct LightUniform {
+    lights: [GpuLight; MAX_LIGHTS],
+    count: u32,
+    _pad: [u32; 3],
+    // the first light's cascades and the shadowed point lights' faces, see ShadowMaps
+    shadows: ShadowUniform,
+    point_lights: [GpuPointLight; MAX_POINT_LIGHTS],
+    point_count: u32,
+    _point_pad: [u32; 3],
+    // boxes of the captured reflection probes, see ReflectionProbes
+    probes: ProbeUniform,
+    atmosphere: AtmosphereUniform,
+}
+
+impl LightUniform {
+    // lights past MAX_LIGHTS and MAX_POINT_LIGHTS are dropped
+    pub fn new(scene_lights: &SceneLights, shadows: ShadowUniform, probes: ProbeUniform) -> Self {
+        let lights = &scene_lights.directional;
+        let mut gpu_lights = [GpuLight::default(); MAX_LIGHTS];
+        for (light, gpu) in lights.iter().zip(&mut gpu_lights) {
+            *gpu = GpuLight {
+                direction: light.direction.normalize_or_zero().to_array(),
+                intensity: light.intensity,
+                color: light.color.extend(1.0).to_array(),
+            };
+        }
+        let mut point_lights = [GpuPointLight::default(); MAX_POINT_LIGHTS];
+        for (index, (light, gpu)) in scene_lights.point.iter().zip(&mut point_lights).enumerate() {
+            *gpu = GpuPointLight {
+                position: light.position.to_array(),
+                range: light.range.max(0.001),
+                color: light.color.to_array(),
+                intensity: light.intensity,
+                shadow: scene_lights.shadowed.iter().position(|&shadowed| shadowed == index).map_or(-1, |cube| cube as i32),
+                _pad: [0; 3],
+            };
+        }
+        Self {
+            lights: gpu_lights,
+            count: lights.len().min(MAX_LIGHTS) as u32,
+            _pad: [0; 3],
+            shadows,
+            point_lights,
+            point_count: scene_lights.point.len().min(MAX_POINT_LIGHTS) as u32,
+            _point_pad: [0; 3],
+            probes,
+            atmosphere: AtmosphereUniform::new(&scene_lights.atmosphere),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+#include "atmosphere.wgsl"
+
+struct Light {
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec4<f32>,
+}
+
+// see ShadowUniform
+struct Shadows {
+    matrices: array<mat4x4<f32>, MAX_CASCADES>,
+    splits: vec4<f32>,
+    texel_sizes: vec4<f32>,
+    count: u32,
+    bias: f32,
+    normal_bias: f32,
+    blend: f32,
+    debug: u32,
+    // six faces for each shadowed point light, +x, -x, +y, -y, +z and -z
+    point_matrices: array<mat4x4<f32>, POINT_SHADOW_FACES>,
+    point_count: u32,
+    point_bias: f32,
+}
+
+struct PointLight {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    // the cube in the point shadow faces, -1 without one
+    shadow: i32,
+}
+
+// see ProbeUniform, the box is extents around position
+struct Probe {
+    position: vec4<f32>,
+    extents: vec4<f32>,
+}
+
+struct Probes {
+    probes: array<Probe, MAX_PROBES>,
+    count: u32,
+    strength: f32,
+}
+
+struct Lights {
+    lights: array<Light, MAX_LIGHTS>,
+    count: u32,
+    // cast by the first light and the shadowed point lights, none where nothing is bound
+    shadows: Shadows,
+    point_lights: array<PointLight, MAX_POINT_LIGHTS>,
+    point_count: u32,
+    // captured reflection probes, none where nothing is bound
+    probes: Probes,
+    atmosphere: Atmosphere,
+}
+
+@group(0) @binding(1) var<uniform> lights: Lights;
+// screen space ambient occlusion at the window size, plain white when it's off
+@group(0) @binding(2) var occlusion_map: texture_2d<f32>;
+// a layer per cascade
+@group(0) @binding(3) var shadow_maps: texture_depth_2d_array;
+@group(0) @binding(4) var shadow_sampler: sampler_comparison;
+// six layers per shadowed point light, holding the distance to the light over its range
+@group(0) @binding(5) var point_shadow_maps: texture_depth_2d_array;
+// a cube per reflection probe
+@group(0) @binding(6) var probe_maps: texture_cube_array<f32>;
+@group(0) @binding(7) var probe_sampler: sampler;
+
+fn ambient_occlusion(pixel: vec2<f32>) -> f32 {
+    let last = vec2<i32>(textureDimensions(occlusion_map)) - 1;
+    return textureLoad(occlusion_map, min(vec2<i32>(pixel), last), 0).r;
+}
+
+// 1 lit to 0 in shadow, a 3x3 filter over one cascade. outside of it counts as lit
+fn cascade_visibility(cascade: u32, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let offset = world_position + normal * lights.shadows.normal_bias * lights.shadows.texel_sizes[cascade];
+    let clip = lights.shadows.matrices[cascade] * vec4<f32>(offset, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z < 0.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_maps));
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let sample_uv = uv + vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompareLevel(shadow_maps, shadow_sampler, sample_uv, i32(cascade), ndc.z - lights.shadows.bias);
+        }
+    }
+    return lit / 9.0;
+}
+
+// the first cascade whose split is past the point, count when it's past all of them
+fn cascade_index(world_position: vec3<f32>) -> u32 {
+    let depth = -(camera.view * vec4<f32>(world_position, 1.0)).z;
+    var cascade = 0u;
+    loop {
+        if (cascade >= lights.shadows.count || depth <= lights.shadows.splits[cascade]) {
+            break;
+        }
+        cascade = cascade + 1u;
+    }
+    return cascade;
+}
+
+// over the last blend of each cascade it fades into the next one, the last fades out to lit
+fn shadow_visibility(world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let cascade = cascade_index(world_position);
+    if (cascade >= lights.shadows.count) {
+        return 1.0;
+    }
+    let depth = -(camera.view * vec4<f32>(world_position, 1.0)).z;
+    var start = 0.0;
+    if (cascade > 0u) {
+        start = lights.shadows.splits[cascade - 1u];
+    }
+    let end = lights.shadows.splits[cascade];
+    let fade_start = end - (end - start) * lights.shadows.blend;
+    let visibility = cascade_visibility(cascade, world_position, normal);
+    if (depth <= fade_start) {
+        return visibility;
+    }
+    let fade = (depth - fade_start) / max(end - fade_start, 0.0001);
+    var next = 1.0;
+    if (cascade + 1u < lights.shadows.count) {
+        next = cascade_visibility(cascade + 1u, world_position, normal);
+    }
+    return mix(visibility, next, fade);
+}
+
+// for ShadowSettings::debug_cascades, red, green, blue and yellow from near to far
+fn cascade_tint(world_position: vec3<f32>) -> vec3<f32> {
+    var tints = array<vec3<f32>, MAX_CASCADES>(
+        vec3<f32>(1.0, 0.4, 0.4),
+        vec3<f32>(0.4, 1.0, 0.4),
+        vec3<f32>(0.4, 0.6, 1.0),
+        vec3<f32>(1.0, 1.0, 0.4),
+    );
+    let cascade = cascade_index(world_position);
+    if (cascade >= lights.shadows.count) {
+        return vec3<f32>(1.0);
+    }
+    return tints[cascade];
+}
+
+// 1 lit to 0 in shadow, compared by distance on the face of the cube the point is in
+fn point_visibility(light: PointLight, world_position: vec3<f32>) -> f32 {
+    if (light.shadow < 0 || u32(light.shadow) >= lights.shadows.point_count) {
+        return 1.0;
+    }
+    let to_point = world_position - light.position;
+    let axes = abs(to_point);
+    var face = 0;
+    if (axes.x >= axes.y && axes.x >= axes.z) {
+        face = select(1, 0, to_point.x > 0.0);
+    } else if (axes.y >= axes.z) {
+        face = select(3, 2, to_point.y > 0.0);
+    } else {
+        face = select(5, 4, to_point.z > 0.0);
+    }
+    let layer = light.shadow * 6 + face;
+    let clip = lights.shadows.point_matrices[layer] * vec4<f32>(world_position, 1.0);
+    let ndc = clip.xy / clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let reference = length(to_point) / light.range - lights.shadows.point_bias;
+    return textureSampleCompareLevel(point_shadow_maps, shadow_sampler, uv, layer, reference);
+}
+
+// fades to nothing at the light's range
+fn point_light(light: PointLight, normal: vec3<f32>, world_position: vec3<f32>) -> vec3<f32> {
+    let to_light = light.position - world_position;
+    let span = length(to_light);
+    if (span >= light.range) {
+        return vec3<f32>(0.0);
+    }
+    let falloff = 1.0 - span / light.range;
+    let diffuse = max(dot(normal, to_light / max(span, 0.0001)), 0.0);
+    let direct = light.color * light.intensity * diffuse * falloff * falloff;
+    return direct * point_visibility(light, world_position);
+}
+
+// the first probe whose box holds the point, count when none do
+fn probe_index(world_position: vec3<f32>) -> u32 {
+    var index = 0u;
+    loop {
+        if (index >= lights.probes.count) {
+            break;
+        }
+        let probe = lights.probes.probes[index];
+        if (all(abs(world_position - probe.position.xyz) <= probe.extents.xyz)) {
+            break;
+        }
+        index = index + 1u;
+    }
+    return index;
+}
+
+// color with the surroundings of the probe the point is in reflected over it. the reflected ray
+// is followed to the probe's box, as if what was captured were painted on its walls
+fn probe_reflection(color: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>) -> vec3<f32> {
+    let index = probe_index(world_position);
+    if (index >= lights.probes.count) {
+        return color;
+    }
+    let probe = lights.probes.probes[index];
+    let incident = normalize(world_position - camera.position.xyz);
+    let reflected = reflect(incident, normal);
+    let box_min = probe.position.xyz - probe.extents.xyz;
+    let box_max = probe.position.xyz + probe.extents.xyz;
+    let exits = max((box_max - world_position) / reflected, (box_min - world_position) / reflected);
+    let travel = min(min(exits.x, exits.y), exits.z);
+    let direction = world_position + reflected * travel - probe.position.xyz;
+    // the faces were drawn with z flipped, see ReflectionProbes
+    let captured = textureSampleLevel(probe_maps, probe_sampler, direction * vec3<f32>(1.0, 1.0, -1.0), i32(index), 0.0).rgb;
+    let facing = clamp(dot(-incident, normal), 0.0, 1.0);
+    let fresnel = mix(lights.probes.strength, 1.0, pow(1.0 - facing, 5.0));
+    return mix(color, captured, fresnel);
+}
+
+// the first light is the sun
+fn sky(direction: vec3<f32>) -> vec3<f32> {
+    return preetham_sky(lights.atmosphere, direction, -normalize(lights.lights[0].direction));
+}
+
+// the sky behind the point colors the fog while it's on
+fn fog(color: vec3<f32>, world_position: vec3<f32>) -> vec3<f32> {
+    if (lights.atmosphere.fog == 0u) {
+        return color;
+    }
+    var fog_color = lights.atmosphere.fog_color;
+    if (lights.atmosphere.sky != 0u) {
+        fog_color = sky(normalize(world_position - camera.position.xyz));
+    }
+    return mix(color, fog_color, fog_amount(lights.atmosphere, camera.position.xyz, world_position));
+}
+
+// what a white surface is lit with. occlusion only darkens the ambient term, direct light from
+// the first light and shadowed point lights is shadowed
+fn surface_light(normal: vec3<f32>, world_position: vec3<f32>, occlusion: f32) -> vec3<f32> {
+    var light = vec3<f32>(0.15, 0.15, 0.15) * occlusion;
+    for (var i = 0u; i < lights.count; i = i + 1u) {
+        let l = lights.lights[i];
+        var direct = l.color.rgb * l.intensity * 0.85 * max(dot(normal, -l.direction), 0.0);
+        if (i == 0u && lights.shadows.count > 0u) {
+            direct = direct * shadow_visibility(world_position, normal);
+        }
+        light = light + direct;
+    }
+    for (var i = 0u; i < lights.point_count; i = i + 1u) {
+        light = light + point_light(lights.point_lights[i], normal, world_position);
+    }
+    return light;
+}
+
+fn shade(albedo: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, occlusion: f32) -> vec3<f32> {
+    let light = surface_light(normal, world_position, occlusion);
+    var color = fog(probe_reflection(albedo * light, normal, world_position), world_position);
+    if (lights.shadows.debug != 0u) {
+        color = color * cascade_tint(world_position);
+    }
+    return color;
+}
@@ -0,0 +1,91 @@
+use std::mem::size_of;
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+pub const LOADING_LAYOUT: &str = "loading";
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct LoadingUniform {
+    progress: f32,
+    aspect: f32,
+}
+
+// a progress bar on a blank screen, drawn instead of the scene while assets are loading
+pub struct LoadingScreen {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    render_pipeline: RenderPipeline,
+}
+
+impl LoadingScreen {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry) -> Self {
+        let device = &context.device;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("loading"),
+            size: size_of::<LoadingUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, LOADING_LAYOUT, &[(Binding::Uniform, ShaderStages::FRAGMENT)]);
+        let bind_group = BindGroupBuilder::new()
+            .buffer(&buffer)
+            .build(context, layouts, LOADING_LAYOUT);
+
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "loading.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("loading"),
+            bind_group_layouts: &[layouts.get(LOADING_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("loading"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &shader_module,
+                targets: &[
+                    Some(HDR_FORMAT.into())
+                ],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            buffer,
+            bind_group,
+            render_pipeline,
+        }
+    }
+
+    pub fn update(&self, context: &RenderContext, progress: f32) {
+        let uniform = LoadingUniform {
+            progress: progress.clamp(0.0, 1.0),
+            aspect: context.aspect(),
+        };
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>) {
+        render_cmd.set_pipeline(&self.render_pipeline);
+        render_cmd.set_bind_group(0, &self.bind_group, &[]);
+        render_cmd.draw(0..3, 0..1);
+    }
+}
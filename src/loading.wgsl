@@ -0,0 +1,46 @@
+#include "color.wgsl"
+
+struct Loading {
+    // 0..1
+    progress: f32,
+    // width / height
+    aspect: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> loading: Loading;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a single triangle that covers the screen
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    var out: VertexOut;
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.pos = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    // the bar's height is scaled by the aspect so it's the same number of pixels as a fraction
+    // of the width at any window shape
+    let half_size = vec2<f32>(0.2, 0.008 * loading.aspect);
+    let border = vec2<f32>(0.003, 0.003 * loading.aspect);
+    let offset = abs(in.uv - vec2<f32>(0.5, 0.5));
+    var color = vec3<f32>(0.02, 0.02, 0.025);
+    if (all(offset <= half_size + border)) {
+        color = vec3<f32>(0.3, 0.3, 0.3);
+    }
+    if (all(offset <= half_size)) {
+        color = vec3<f32>(0.05, 0.05, 0.05);
+        if (in.uv.x <= 0.5 - half_size.x + 2.0 * half_size.x * loading.progress) {
+            color = vec3<f32>(0.8, 0.8, 0.85);
+        }
+    }
+    return output_color(vec4<f32>(color, 1.0));
+}
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+pub use tracing_chrome::FlushGuard;
+
+// used when RUST_LOG isn't set. wgpu logs a lot at info
+const DEFAULT_FILTER: &str = "info,wgpu_core=warn,wgpu_hal=warn,naga=warn";
+// lines kept for history, older ones are dropped
+const HISTORY_LINES: usize = 256;
+
+static HISTORY: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// logs to stderr filtered by RUST_LOG and keeps the last lines, see history. also writes the
+// spans that pass the filter to a chrome trace (load it in chrome://tracing or perfetto) when a
+// path is given. the trace is only complete once the guard is dropped, so keep it alive until exit
+pub fn init(chrome_trace: Option<&Path>) -> Option<FlushGuard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let fmt = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    let history = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .without_time()
+        .with_target(false)
+        .with_writer(|| HistoryWriter(Vec::new()));
+    let (chrome, guard) = match chrome_trace {
+        Some(path) => {
+            let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+                .file(path)
+                .include_args(true)
+                .build();
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt)
+        .with(history)
+        .with(chrome)
+        .init();
+    guard
+}
+
+// the last lines logged, oldest first, e.g. for the console. empty unless init was called
+pub fn history() -> Vec<String> {
+    HISTORY.lock().unwrap().iter().cloned().collect()
+}
+
+// collects one event and adds its lines to the history once the layer is done writing it
+struct HistoryWriter(Vec<u8>);
+
+impl io::Write for HistoryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for HistoryWriter {
+    fn drop(&mut self) {
+        let text = String::from_utf8_lossy(&self.0);
+        let mut history = HISTORY.lock().unwrap();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if history.len() == HISTORY_LINES {
+                history.pop_front();
+            }
+            history.push_back(line.to_owned());
+        }
+    }
+}
@@ -1,202 +1,1737 @@
-use std::mem::size_of;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use glam::{Affine2, BVec3, Vec2, Vec3, Vec4};
 use pollster::block_on;
-use wgpu::*;
-use wgpu::util::{BufferInitDescriptor, DeviceExt};
-use winit::event::{Event, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::Window;
-
-struct RenderContext {
-    instance: Instance,
-    device: Device,
-    queue: Queue,
-
-    window: Window,
-    surface: Surface,
-    format: TextureFormat,
-}
-
-impl RenderContext {
-    async fn new(event_loop: &EventLoop<()>) -> Self {
-        let window = Window::new(&event_loop).expect("failed to create window");
-        let instance = Instance::new(Backends::DX12);
-        let surface = unsafe { instance.create_surface(&window) };
-        let adapter = instance.request_adapter(&RequestAdapterOptions {
-            power_preference: PowerPreference::LowPower,
-            force_fallback_adapter: false,
-            compatible_surface: Some(&surface),
-        }).await.expect("failed to request adapter");
-        let format = surface.get_supported_formats(&adapter)[0];
-
-        let (device, queue) = adapter.request_device(
-            &DeviceDescriptor::default(),
-            None,
-        ).await.expect("failed to request device");
-
-        let size = window.inner_size();
-        surface.configure(&device, &SurfaceConfiguration {
-            format,
-            width: size.width,
-            height: size.height,
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            present_mode: PresentMode::AutoVsync,
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+use wgpu::{Color, Features};
+use winit::event::{DeviceEvent, ElementState, MouseButton, WindowEvent};
+use winit::event_loop::EventLoop;
+use dumb_wgpu_example::animation::AnimationPlayer;
+use dumb_wgpu_example::app::{self, App, Engine, FixedTimestep, FrameTime};
+use dumb_wgpu_example::assets::Assets;
+use dumb_wgpu_example::atmosphere::AtmosphereSettings;
+use dumb_wgpu_example::audio::Audio;
+use dumb_wgpu_example::bench::{BenchReport, ComputeComparison, Summary};
+use dumb_wgpu_example::camera::Camera;
+use dumb_wgpu_example::canvas::Canvas;
+use dumb_wgpu_example::capture::Recorder;
+use dumb_wgpu_example::clipboard::Clipboard;
+use dumb_wgpu_example::color_grading::ColorLut;
+use dumb_wgpu_example::console::Console;
+use dumb_wgpu_example::context::{ContextConfig, RenderContext};
+use dumb_wgpu_example::decals::Decal;
+use dumb_wgpu_example::frame_history::FrameHistorySettings;
+use dumb_wgpu_example::frames::FramePacer;
+use dumb_wgpu_example::gizmo::Gizmo;
+use dumb_wgpu_example::golden::{self, Tolerance};
+use dumb_wgpu_example::image_filter::ImageFilter;
+use dumb_wgpu_example::input::{Action, Input};
+use dumb_wgpu_example::mesh::Material;
+use dumb_wgpu_example::light::{DirectionalLight, PointLight};
+use dumb_wgpu_example::logging::{self, FlushGuard};
+use dumb_wgpu_example::output::{Antialiasing, OutputSettings};
+use dumb_wgpu_example::parallel_recording::ParallelRecording;
+use dumb_wgpu_example::particles::ParticleCollision;
+use dumb_wgpu_example::physics::Physics;
+use dumb_wgpu_example::primitives;
+use dumb_wgpu_example::probes::{ProbeSettings, ReflectionProbe};
+use dumb_wgpu_example::raycast::Ray;
+use dumb_wgpu_example::terrain::{Heightmap, TerrainConfig};
+use dumb_wgpu_example::text::TextRenderer;
+use dumb_wgpu_example::texture::Texture;
+use dumb_wgpu_example::tilemap::TiledMap;
+use dumb_wgpu_example::timestamps::FrameTimestamps;
+use dumb_wgpu_example::transform::Transform;
+use dumb_wgpu_example::tween::{Ease, Property, Tween, Tweens};
+use dumb_wgpu_example::video::VideoTexture;
+use dumb_wgpu_example::world::{self, Entity, MeshRef, Name, World};
+use dumb_wgpu_example::render_scale::RenderScale;
+use dumb_wgpu_example::renderer::{RenderEvent, Renderer};
+use dumb_wgpu_example::replay::{InputLog, InputRecorder, InputRecording, InputReplayer};
+use dumb_wgpu_example::scene::{self, MeshSource, Scene, SceneDesc};
+use dumb_wgpu_example::shader_check;
+use dumb_wgpu_example::shadows::ShadowSettings;
+use dumb_wgpu_example::ssao::SsaoSettings;
+use dumb_wgpu_example::ssr::SsrSettings;
+use dumb_wgpu_example::viewport::Viewport;
+use dumb_wgpu_example::voxels::VoxelSettings;
+use dumb_wgpu_example::water::WaterSettings;
+use dumb_wgpu_example::window_ext::{self, icon_from_png, monitor_report, FpsTitle, FullscreenRequest, Taskbar, TaskbarProgress};
+
+const RECORD_FPS: u32 = 60;
+// golden images are rendered this size whatever the screen, so references compare anywhere
+const GOLDEN_SIZE: (u32, u32) = (640, 360);
+// frames simulated before a golden image is taken, so animations and physics are always at the
+// same point
+const GOLDEN_FRAMES: u32 = 30;
+// frames drawn before a benchmark starts timing, so pipelines and buffers have settled
+const BENCH_WARMUP_FRAMES: u32 = 10;
+// frames timed in one async compute mode before switching to the other, so neither gets the
+// warmer run
+const BENCH_COMPARE_BLOCK: u32 = 10;
+// radians per second at full stick deflection
+const LOOK_SPEED: f32 = 1.5;
+// radians per unit of mouse motion while the pointer is locked
+const MOUSE_SENSITIVITY: f32 = 0.003;
+// seconds the camera takes to move over to the selected object
+const FOCUS_TIME: f32 = 0.6;
+// screen pixels per font pixel in the console, at a scale factor of 1
+const CONSOLE_SCALE: f32 = 2.0;
+// frames in the hud's frame time graph
+const HUD_FRAMES: usize = 120;
+// top right corner, drawn over the main view
+const PIP_VIEWPORT: Viewport = Viewport { x: 0.72, y: 0.03, width: 0.25, height: 0.25, order: 1 };
+
+// pub(crate) so examples/android.rs can run it from its entry point
+pub(crate) fn main() {
+    // runs before any window or device exists so it works headless, e.g. in ci.
+    // any other arguments are extra .wgsl or .spv files to check
+    if std::env::args().any(|arg| arg == "--check-shaders") {
+        let mut errors = shader_check::check_all();
+        let files = std::env::args().skip(1).filter(|arg| !arg.starts_with("--"));
+        errors.extend(files.filter_map(|path| shader_check::check_file(path).err()));
+        for error in &errors {
+            eprintln!("{error}");
+        }
+        if !errors.is_empty() {
+            std::process::exit(1);
+        }
+        println!("all shaders ok");
+        return;
+    }
+
+    let mut model_path = None;
+    let mut terrain_path = None;
+    let mut blend_map_path = None;
+    let mut show_primitives = false;
+    let mut texture_path = None;
+    let mut video_path = None;
+    let mut lut_path = None;
+    let mut tilemap_path = None;
+    let mut filter = None;
+    let mut scene_path = None;
+    let mut session_path = PathBuf::from("session.ron");
+    let mut music_path = None;
+    let mut sound_path = None;
+    let mut record_path = None;
+    let mut record_input_path = None;
+    let mut replay_input = None;
+    // how many frames --record and --bench run for
+    let mut record_frames = 120;
+    let mut bench = false;
+    let mut golden = false;
+    let mut update_golden = false;
+    let mut golden_scene = None;
+    let mut headless = false;
+    let mut trace_path = None;
+    let mut capture_frame = None;
+    let mut split_screen = false;
+    let mut gpu_culling = false;
+    let mut vertex_pulling = false;
+    let mut render_bundles = false;
+    let mut vertex_compression = false;
+    let mut async_compute = false;
+    let mut lod_bias = 1.0;
+    let mut pip = false;
+    let mut hud = false;
+    let mut markers = false;
+    let mut list_monitors = false;
+    let mut context_config = ContextConfig::default();
+    let mut output = OutputSettings::default();
+    let mut ssao = SsaoSettings::default();
+    let mut ssr = SsrSettings::default();
+    let mut shadows = ShadowSettings::default();
+    let mut probes = ProbeSettings::default();
+    let mut atmosphere = AtmosphereSettings::default();
+    let mut water = WaterSettings::default();
+    let mut voxels = VoxelSettings::default();
+    let mut frame_history = FrameHistorySettings::default();
+    let mut frame_history_path = PathBuf::from("frame_history.gif");
+    let mut particle_collision = ParticleCollision::default();
+    let mut render_scale = RenderScale::default();
+    let mut pacing = FramePacer::new();
+    let mut parallel_recording = ParallelRecording::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--terrain" => terrain_path = args.next(),
+            "--blend-map" => blend_map_path = args.next(),
+            "--primitives" => show_primitives = true,
+            "--texture" => texture_path = args.next(),
+            "--video" => video_path = args.next(),
+            "--filter" => filter = Some(args.next().expect("--filter expects blur, sobel or grayscale").parse().unwrap_or_else(|error: String| panic!("{error}"))),
+            "--scene" => scene_path = args.next(),
+            "--session" => session_path = args.next().map(PathBuf::from).expect("--session expects a path"),
+            "--music" => music_path = args.next(),
+            "--sound" => sound_path = args.next(),
+            "--record" => record_path = args.next(),
+            "--record-input" => record_input_path = Some(args.next().expect("--record-input expects a path")),
+            "--replay-input" => {
+                let path = args.next().expect("--replay-input expects a path");
+                replay_input = Some(InputRecording::load(&path).unwrap_or_else(|error| panic!("failed to load {path}: {error}")));
+            }
+            "--bench" => bench = true,
+            "--golden" => golden = true,
+            // one scene instead of all of them, what tests/golden.rs runs
+            "--golden-scene" => {
+                golden = true;
+                golden_scene = Some(args.next().map(PathBuf::from).expect("--golden-scene expects a path"));
+            }
+            "--update-golden" => {
+                golden = true;
+                update_golden = true;
+            }
+            "--headless" => headless = true,
+            "--trace-chrome" => trace_path = args.next(),
+            "--capture-frame" => capture_frame = Some(args.next().and_then(|frame| frame.parse().ok()).expect("--capture-frame expects a frame number")),
+            "--linear" => context_config.srgb = false,
+            "--hdr" => context_config.hdr = true,
+            "--title" => context_config.title = args.next().expect("--title expects some text"),
+            "--fullscreen" => {
+                let mode = args.next().expect("--fullscreen expects borderless, exclusive or <width>x<height>[@<hz>]");
+                let monitor = context_config.fullscreen.take().and_then(|request| request.monitor);
+                let request = mode.parse::<FullscreenRequest>().unwrap_or_else(|error| panic!("{error}"));
+                context_config.fullscreen = Some(FullscreenRequest { monitor, ..request });
+            }
+            "--monitor" => {
+                let monitor = args.next().and_then(|monitor| monitor.parse().ok()).expect("--monitor expects a number, see --list-monitors");
+                context_config.fullscreen.get_or_insert_with(FullscreenRequest::default).monitor = Some(monitor);
+            }
+            "--list-monitors" => list_monitors = true,
+            "--icon" => {
+                let path = args.next().expect("--icon expects a png");
+                let png = fs::read(&path).unwrap_or_else(|error| panic!("failed to read {path}: {error}"));
+                context_config.icon = Some(icon_from_png(&png).unwrap_or_else(|error| panic!("failed to load {path}: {error}")));
+            }
+            "--fxaa" => output.antialiasing = Antialiasing::Fxaa,
+            "--lut" => lut_path = args.next(),
+            "--tilemap" => tilemap_path = args.next(),
+            "--ssao" => ssao.enabled = true,
+            "--ssr" => ssr.enabled = true,
+            "--shadows" => shadows.enabled = true,
+            "--shadow-debug" => shadows.debug_cascades = true,
+            "--probes" => probes.enabled = true,
+            "--fog" => atmosphere.fog = true,
+            "--fog-density" => atmosphere.fog_density = args.next().and_then(|density| density.parse().ok()).expect("--fog-density expects a number"),
+            "--sky" => atmosphere.sky = true,
+            // the water showcase: waves under a sky, reflecting the primitives unless something
+            // else is loaded
+            "--water" => {
+                water.enabled = true;
+                atmosphere.sky = true;
+                ssr.enabled = true;
+            }
+            "--frame-history" => {
+                frame_history.enabled = true;
+                frame_history.seconds = args.next().and_then(|seconds| seconds.parse().ok()).expect("--frame-history expects a number of seconds");
+            }
+            "--frame-history-path" => frame_history_path = args.next().expect("--frame-history-path expects a path").into(),
+            // fog hides chunks streaming in at the edge of the view distance
+            "--voxels" => {
+                voxels.enabled = true;
+                atmosphere.fog = true;
+            }
+            "--voxel-distance" => voxels.view_distance = args.next().and_then(|distance| distance.parse().ok()).expect("--voxel-distance expects a number"),
+            "--particle-collision" => particle_collision.enabled = true,
+            "--probe-resolution" => probes.resolution = args.next().and_then(|resolution| resolution.parse().ok()).expect("--probe-resolution expects a number"),
+            "--gpu-culling" => gpu_culling = true,
+            "--vertex-pulling" => vertex_pulling = true,
+            "--render-bundles" => render_bundles = true,
+            "--vertex-compression" => vertex_compression = true,
+            "--async-compute" => async_compute = true,
+            // 0 is one thread per core
+            "--record-threads" => {
+                parallel_recording.enabled = true;
+                parallel_recording.threads = args.next().and_then(|threads| threads.parse().ok()).expect("--record-threads expects a number");
+            }
+            "--lod-bias" => lod_bias = args.next().and_then(|bias| bias.parse().ok()).expect("--lod-bias expects a number"),
+            "--split-screen" => split_screen = true,
+            "--pip" => pip = true,
+            "--hud" => hud = true,
+            "--markers" => markers = true,
+            "--render-scale" => render_scale.scale = args.next().and_then(|scale| scale.parse().ok()).expect("--render-scale expects a number"),
+            "--max-fps" => pacing.max_fps = Some(args.next().and_then(|fps| fps.parse().ok()).expect("--max-fps expects a number")),
+            "--frames-in-flight" => pacing.max_in_flight = args.next().and_then(|frames| frames.parse().ok()).expect("--frames-in-flight expects a number"),
+            "--target-fps" => {
+                let fps: f32 = args.next().and_then(|fps| fps.parse().ok()).expect("--target-fps expects a number");
+                render_scale.dynamic = true;
+                render_scale.target_frame_time = 1.0 / fps;
+            }
+            "--ssao-radius" => ssao.radius = args.next().and_then(|radius| radius.parse().ok()).expect("--ssao-radius expects a number"),
+            "--ssao-bias" => ssao.bias = args.next().and_then(|bias| bias.parse().ok()).expect("--ssao-bias expects a number"),
+            "--ssao-intensity" => ssao.intensity = args.next().and_then(|intensity| intensity.parse().ok()).expect("--ssao-intensity expects a number"),
+            "--ssr-steps" => ssr.steps = args.next().and_then(|steps| steps.parse().ok()).expect("--ssr-steps expects a number"),
+            "--ssr-thickness" => ssr.thickness = args.next().and_then(|thickness| thickness.parse().ok()).expect("--ssr-thickness expects a number"),
+            "--ssr-fade" => ssr.fade = args.next().and_then(|fade| fade.parse().ok()).expect("--ssr-fade expects a number"),
+            "--cascades" => shadows.cascades = args.next().and_then(|cascades| cascades.parse().ok()).expect("--cascades expects a number"),
+            "--shadow-distance" => shadows.distance = args.next().and_then(|distance| distance.parse().ok()).expect("--shadow-distance expects a number"),
+            "--shadow-resolution" => shadows.resolution = args.next().and_then(|resolution| resolution.parse().ok()).expect("--shadow-resolution expects a number"),
+            "--paper-white" => output.paper_white = args.next().and_then(|nits| nits.parse().ok()).expect("--paper-white expects a number"),
+            "--max-nits" => output.max_nits = args.next().and_then(|nits| nits.parse().ok()).expect("--max-nits expects a number"),
+            "--frames" => record_frames = args.next().and_then(|frames| frames.parse().ok()).expect("--frames expects a number"),
+            _ => model_path = Some(arg),
+        }
+    }
+
+    // recorded cursor positions only line up in a window of the same size
+    if let Some(recording) = &replay_input {
+        context_config.size = Some(PhysicalSize::new(recording.size.0, recording.size.1));
+    }
+    if golden {
+        context_config.visible = false;
+        context_config.size = Some(PhysicalSize::new(GOLDEN_SIZE.0, GOLDEN_SIZE.1));
+    }
+    if water.enabled && model_path.is_none() && scene_path.is_none() && terrain_path.is_none() {
+        show_primitives = true;
+    }
+    if bench {
+        context_config.vsync = false;
+        context_config.visible = !headless;
+        // the same scene every run unless told otherwise, so results compare
+        if model_path.is_none() && scene_path.is_none() && terrain_path.is_none() && !show_primitives {
+            scene_path = Some(concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/demo.ron").to_string());
+        }
+    }
+
+    let trace = logging::init(trace_path.as_deref().map(Path::new));
+
+    let event_loop = EventLoop::new();
+    if list_monitors {
+        print!("{}", monitor_report(&event_loop));
+        return;
+    }
+    let title = context_config.title.clone();
+    let context = block_on(RenderContext::with_config(&event_loop, context_config));
+    let mut engine = Engine::new(context);
+    engine.renderer.output = output;
+    engine.renderer.ssao = ssao;
+    engine.renderer.ssr = ssr;
+    engine.renderer.shadows = shadows;
+    engine.renderer.probes = probes;
+    engine.renderer.atmosphere = atmosphere;
+    engine.renderer.water = water;
+    engine.renderer.voxels = voxels;
+    engine.renderer.frame_history = frame_history;
+    let size = engine.context.physical_size();
+    if let Some(path) = record_input_path {
+        engine.input_log = Some(InputLog::Record(InputRecorder::new(path, (size.width, size.height))));
+    } else if let Some(recording) = replay_input {
+        if recording.size != (size.width, size.height) {
+            tracing::warn!("input was recorded at {}x{}, the window is {}x{}", recording.size.0, recording.size.1, size.width, size.height);
+        }
+        engine.input_log = Some(InputLog::Replay(InputReplayer::new(recording)));
+    }
+    engine.renderer.particles.collision = particle_collision;
+    engine.renderer.gpu_culling = gpu_culling;
+    engine.renderer.vertex_pulling = vertex_pulling;
+    engine.renderer.parallel_recording = parallel_recording;
+    engine.renderer.render_bundles = render_bundles;
+    engine.renderer.vertex_compression = vertex_compression;
+    engine.renderer.async_compute = async_compute;
+    engine.renderer.lod_bias = lod_bias;
+    engine.renderer.render_scale = render_scale;
+    engine.renderer.pacing = pacing;
+    if let Some(frame) = capture_frame {
+        engine.gpu_capture.capture_frame(frame);
+    }
+    let world = &mut engine.world;
+    let camera = world.spawn((Camera::default(),));
+    // the other quarters look at the main camera's target from the side, above and behind
+    let mut follow_cameras = Vec::new();
+    if split_screen {
+        let _ = world.insert_one(camera, Viewport::quarter(0));
+        let directions = [Vec3::X, Vec3::Y, Vec3::new(-1.0, 1.0, -1.0).normalize()];
+        for (i, direction) in directions.into_iter().enumerate() {
+            follow_cameras.push((world.spawn((Camera::default(), Viewport::quarter(i + 1))), direction));
+        }
+    }
+    if pip {
+        follow_cameras.push((world.spawn((Camera::default(), PIP_VIEWPORT)), Vec3::Y));
+    }
+
+    let mut assets = Assets::new();
+    let terrain = terrain_path.map(|path| {
+        let heightmap = Heightmap::load(&path).unwrap_or_else(|error| panic!("failed to load {path}: {error}"));
+        let blend_map = blend_map_path.map(|path| {
+            assets.texture(&engine.context, &path, false);
+            PathBuf::from(path)
         });
-        Self {
-            instance,
-            device,
-            queue,
+        let config = TerrainConfig::default();
+        let mut camera = world.get::<&mut Camera>(camera).unwrap();
+        camera.eye = Vec3::new(0.0, config.height_scale * 1.5, config.size * 0.5);
+        camera.zfar = config.size * 2.0;
+        drop(camera);
+        let terrain = TerrainSource { heightmap, blend_map, config };
+        terrain.apply(&mut engine.renderer, &engine.context, &assets);
+        terrain
+    });
+
+    if let Some(path) = model_path {
+        // waits for it, since the camera is framed around it
+        let model = assets.model_blocking(&path).unwrap_or_else(|error| panic!("failed to load {path}: {error}"));
+        let (min, max) = model.bounds();
+        world.get::<&mut Camera>(camera).unwrap().frame(min, max);
+        let entity = world::spawn_model(world, MeshRef(model), Transform::IDENTITY, Material::default());
+        let _ = world.insert(entity, (name_of(Path::new(&path)), MeshSource::Gltf(PathBuf::from(&path))));
+        world::play(world, entity, Some(0));
+    }
+
+    // put on the primitives
+    let material_texture = texture_path.map(|path| {
+        let texture = assets.texture(&engine.context, &path, true);
+        (PathBuf::from(path), engine.renderer.add_material_texture(texture))
+    });
+
+    if let Some(path) = tilemap_path {
+        let map = TiledMap::load(&path).unwrap_or_else(|error| panic!("failed to load {path}: {error}"));
+        engine.renderer.set_tilemap(&engine.context, Some(&map)).unwrap_or_else(|error| panic!("failed to load {path}: {error}"));
+    }
+
+    if let Some(path) = lut_path {
+        let lut = ColorLut::load(&engine.context, &path).unwrap_or_else(|error| panic!("failed to load {path}: {error}"));
+        engine.renderer.set_color_lut(&engine.context, Some(lut));
+    }
+
+    // an animated gif or png, put on the primitives instead of --texture
+    let video = video_path.map(|path| {
+        VideoTexture::open(&engine.context, &path, true).unwrap_or_else(|error| panic!("failed to open {path}: {error}"))
+    });
+    let video_texture = video.as_ref().map(|video| engine.renderer.add_material_texture(video.texture.clone()));
+
+    if show_primitives {
+        let texture = video_texture.or(material_texture.as_ref().map(|(_, id)| *id));
+        let material = Material { texture, ..Material::default() };
+        // built from mesh sources so sessions can save them
+        let sources = primitive_sources();
+        let spacing = 1.75;
+        let offset = (sources.len() - 1) as f32 * spacing * 0.5;
+        for (i, (name, source)) in sources.into_iter().enumerate() {
+            let transform = Transform::from_translation(Vec3::new(i as f32 * spacing - offset, 0.0, 0.0));
+            let model = source.build(Path::new(".")).expect("primitives don't load anything");
+            let entity = world::spawn_model(world, MeshRef::new(model), transform, material);
+            let _ = world.insert(entity, (Name(name.to_string()), source));
+        }
+        world.get::<&mut Camera>(camera).unwrap().frame(Vec3::new(-offset - 1.0, -1.0, -1.0), Vec3::new(offset + 1.0, 1.0, 1.0));
+    }
+
+    let scene = scene_path.map(|path| {
+        Scene::load(world, &mut assets, &path).unwrap_or_else(|error| panic!("failed to load {path}: {error}"))
+    });
+    if scene.iter().flat_map(|scene| &scene.desc().entities).any(|entity| entity.body.is_some()) && !Physics::is_available() {
+        tracing::warn!("built without the physics feature, bodies won't move");
+    }
+
+    let mut audio = Audio::new();
+    if let Some(path) = music_path {
+        if let Err(error) = audio.play_music(&path) {
+            tracing::error!("failed to play {path}: {error}");
+        }
+    }
+    let mut demo = Demo {
+        camera,
+        follow_cameras,
+        scene,
+        assets,
+        terrain,
+        physics: Physics::new(),
+        input: Input::new(),
+        audio,
+        sound_path,
+        music_paused: false,
+        show_bounds: false,
+        cursor: (0, 0),
+        selected: None,
+        last_selected: None,
+        editor: false,
+        gizmo: Gizmo::new(),
+        clipboard: Clipboard::new(),
+        dropped_model: None,
+        dropped_image: None,
+        material_texture,
+        filter,
+        video,
+        session_path,
+        console: demo_console(),
+        hud: hud.then(VecDeque::new),
+        markers,
+        placed_markers: Vec::new(),
+        frame_history_path,
+        fps_title: FpsTitle::new(title),
+        taskbar: Taskbar::new(),
+        _trace: trace,
+    };
+
+    // renders at a fixed timestep without presenting, then exits
+    if let Some(path) = record_path {
+        let size = engine.context.physical_size();
+        let mut recorder = Recorder::new(&path, size.width, size.height, RECORD_FPS)
+            .unwrap_or_else(|error| panic!("failed to start recording to {path}: {error}"));
+        for frame in 0..record_frames {
+            engine.gpu_capture.begin_frame();
+            engine.frame(&mut demo, 1.0 / RECORD_FPS as f64);
+            let image = engine.renderer.capture(&engine.context);
+            engine.gpu_capture.end_frame();
+            if let Err(error) = recorder.write(&image) {
+                panic!("failed to write frame {frame}: {error}");
+            }
+        }
+        recorder.finish().unwrap_or_else(|error| panic!("failed to finish recording: {error}"));
+        return;
+    }
+
+    if bench {
+        run_bench(&mut engine, &mut demo, record_frames, headless);
+        return;
+    }
 
-            window,
-            surface,
-            format,
+    // renders every scene in scenes/ in a hidden window and compares it with its reference in
+    // golden/, e.g. in ci. exits with 1 if any differ
+    if golden {
+        if !run_golden(&mut engine, &mut demo, update_golden, golden_scene.as_deref()) {
+            std::process::exit(1);
         }
+        return;
     }
 
-    fn resize(&self, width: u32, height: u32) {
-        self.surface.configure(&self.device, &SurfaceConfiguration {
-            format: self.format,
-            width,
-            height,
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            present_mode: PresentMode::Fifo,
+    app::run(event_loop, engine, demo);
+}
+
+// draws frames as fast as they go at a fixed timestep, then prints cpu and gpu frame times and
+// draw calls as json. headless draws into a texture of the surface's format instead of
+// presenting, the window stays hidden
+fn run_bench(engine: &mut Engine, demo: &mut Demo, frames: u32, headless: bool) {
+    let delta = 1.0 / RECORD_FPS as f64;
+    let size = engine.context.physical_size();
+    let target = headless.then(|| {
+        let texture = engine.context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("bench"),
+            size: wgpu::Extent3d { width: size.width.max(1), height: size.height.max(1), depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: engine.context.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         });
-        // required for MacOS
-        self.window.request_redraw();
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    });
+    let draw = |engine: &Engine| {
+        let error = match &target {
+            Some(view) => block_on(engine.renderer.draw_offscreen(&engine.context, view, engine.context.format)),
+            None => block_on(engine.renderer.draw(&engine.context)),
+        };
+        if let Some(error) = error {
+            panic!("failed to draw benchmark frame: {error}");
+        }
+    };
+
+    while demo.assets.is_loading() {
+        engine.frame(demo, delta);
+        draw(engine);
     }
+    for _ in 0..BENCH_WARMUP_FRAMES {
+        engine.frame(demo, delta);
+        draw(engine);
+    }
+
+    if FrameTimestamps::is_supported(&engine.context) {
+        engine.renderer.timestamps = Some(FrameTimestamps::new(&engine.context, frames));
+    } else {
+        tracing::warn!("no timestamp queries, gpu times won't be reported");
+    }
+    let mut cpu = Vec::with_capacity(frames as usize);
+    let mut draw_calls = Vec::with_capacity(frames as usize);
+    for _ in 0..frames {
+        let start = Instant::now();
+        engine.frame(demo, delta);
+        draw(engine);
+        cpu.push(start.elapsed().as_secs_f32() * 1000.0);
+        draw_calls.push(engine.renderer.draw_calls());
+    }
+    let gpu = engine.renderer.timestamps.take().map(|timestamps| timestamps.read(&engine.context));
+    let mut report = BenchReport::new(&engine.context, &cpu, gpu.as_deref(), &draw_calls);
+    // as many frames again on one thread, to compare recording times
+    if engine.renderer.parallel_recording.enabled {
+        engine.renderer.parallel_recording.enabled = false;
+        for _ in 0..frames {
+            engine.frame(demo, delta);
+            draw(engine);
+        }
+        engine.renderer.parallel_recording.enabled = true;
+        tracing::info!("{}", engine.renderer.recording_stats());
+    }
+    // as many frames again in each mode, alternating in blocks after warming up the serial one
+    // too. bench frames wait on the gpu once enough are in flight, so their time shows what
+    // overlapping won
+    if engine.renderer.async_compute {
+        engine.renderer.async_compute = false;
+        for _ in 0..BENCH_WARMUP_FRAMES {
+            engine.frame(demo, delta);
+            draw(engine);
+        }
+        let mut overlapped = Vec::with_capacity(frames as usize);
+        let mut serial = Vec::with_capacity(frames as usize);
+        while overlapped.len() < frames as usize {
+            for (on, samples) in [(false, &mut serial), (true, &mut overlapped)] {
+                engine.renderer.async_compute = on;
+                for _ in 0..BENCH_COMPARE_BLOCK.min(frames - samples.len() as u32) {
+                    let start = Instant::now();
+                    engine.frame(demo, delta);
+                    draw(engine);
+                    samples.push(start.elapsed().as_secs_f32() * 1000.0);
+                }
+            }
+        }
+        let comparison = ComputeComparison { overlapped: Summary::new(&overlapped), serial: Summary::new(&serial) };
+        tracing::info!("async compute: {:.3} ms a frame, {:.3} ms submitted with the frame, {:.1}% faster", comparison.overlapped.mean, comparison.serial.mean, comparison.speedup() * 100.0);
+        report.async_compute = Some(comparison);
+    }
+    println!("{}", report.to_json());
 }
 
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-#[repr(C)]
-struct Vertex {
-    pos: [f32; 2],
+// failed frames are written to target/golden with an image of where they differ
+fn run_golden(engine: &mut Engine, demo: &mut Demo, update: bool, only: Option<&Path>) -> bool {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let scenes = match only {
+        Some(path) => vec![path.to_path_buf()],
+        None => golden::scenes(),
+    };
+    let tolerance = Tolerance::default();
+    let failed_dir = root.join("target").join("golden");
+    let mut passed = true;
+    for path in scenes {
+        let Name(name) = name_of(&path);
+        let reference = root.join("golden").join(format!("{name}.png"));
+        // input recorded with --record-input is played over the scene before the usual frames
+        let input_path = root.join("golden").join(format!("{name}.input.ron"));
+        let input = input_path.exists().then(|| {
+            InputRecording::load(&input_path).unwrap_or_else(|error| panic!("failed to load {}: {error}", input_path.display()))
+        });
+        let image = render_golden(engine, demo, &path, input.as_ref());
+        if update {
+            match golden::update(&reference, &image) {
+                Ok(()) => tracing::info!("{name}: updated {}", reference.display()),
+                Err(error) => {
+                    tracing::error!("{name}: failed to write {}: {error}", reference.display());
+                    passed = false;
+                }
+            }
+            continue;
+        }
+        match golden::compare_to_file(&reference, &image, &tolerance) {
+            Ok(diff) if diff.passes(&tolerance) => tracing::info!("{name}: ok, {:.3}% of pixels differ", diff.fraction() * 100.0),
+            Ok(diff) => {
+                passed = false;
+                let _ = fs::create_dir_all(&failed_dir);
+                let _ = image.save(failed_dir.join(format!("{name}.png")));
+                let _ = diff.image.save(failed_dir.join(format!("{name}.diff.png")));
+                tracing::error!("{name}: {:.3}% of pixels differ, see {}", diff.fraction() * 100.0, failed_dir.display());
+            }
+            Err(error) => {
+                passed = false;
+                tracing::error!("{name}: {error}");
+            }
+        }
+    }
+    passed
 }
 
-const VERTEX_SIZE: BufferAddress = size_of::<Vertex>() as BufferAddress;
+// loads the scene in place of the last one and, once its assets are in, replays the input if
+// there is any and runs it for GOLDEN_FRAMES fixed steps
+fn render_golden(engine: &mut Engine, demo: &mut Demo, path: &Path, input: Option<&InputRecording>) -> RgbaImage {
+    let delta = 1.0 / RECORD_FPS as f64;
+    let world = &mut engine.world;
+    scene::clear(world);
+    if let Ok(mut camera) = world.get::<&mut Camera>(demo.camera) {
+        *camera = Camera::default();
+    }
+    let scene = Scene::load(world, &mut demo.assets, path).unwrap_or_else(|error| panic!("failed to load {}: {error}", path.display()));
+    demo.scene = Some(scene);
+    engine.timestep = FixedTimestep::default();
+    while demo.assets.is_loading() {
+        demo.assets.update(&engine.context, &mut engine.world);
+        std::thread::yield_now();
+    }
+    for frame in input.iter().flat_map(|input| &input.frames) {
+        frame.dispatch(engine, demo);
+        engine.frame(demo, frame.delta);
+    }
+    for _ in 0..GOLDEN_FRAMES {
+        engine.frame(demo, delta);
+    }
+    engine.renderer.capture(&engine.context)
+}
 
-struct Renderer {
-    render_pipeline: RenderPipeline,
-    vertex_buffer: Buffer,
+// scene entities are named after the file they were loaded from
+fn name_of(path: &Path) -> Name {
+    Name(path.file_stem().map_or_else(|| path.display().to_string(), |stem| stem.to_string_lossy().into_owned()))
 }
 
-impl Renderer {
-    fn new(context: &RenderContext) -> Self {
-        let shader_module = context.device.create_shader_module(include_wgsl!("shader.wgsl"));
+// what --primitives shows and the console's spawn makes
+fn primitive_sources() -> [(&'static str, MeshSource); 5] {
+    [
+        ("plane", MeshSource::Plane { size: 1.5, subdivisions: 4 }),
+        ("cube", MeshSource::Cube { size: 1.0 }),
+        ("sphere", MeshSource::Sphere { radius: 0.6, sectors: 32, stacks: 16 }),
+        ("cylinder", MeshSource::Cylinder { radius: 0.5, height: 1.2, sectors: 32 }),
+        ("torus", MeshSource::Torus { major_radius: 0.5, minor_radius: 0.2, major_segments: 32, minor_segments: 16 }),
+    ]
+}
 
-        let pipeline_layout = context.device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[],
-            push_constant_ranges: &[],
-        });
+fn demo_console() -> Console<Demo> {
+    let mut console = Console::new();
+    console
+        .register("clear", "<r> <g> <b>, linear 0..1", clear_command)
+        .register("water", "[on|off] or <height|size|resolution|reflectivity|steepness> <value>", water_command)
+        .register("voxels", "[on|off] or <distance|seed> <value>", voxels_command)
+        .register("hud", "[on|off], a frame time graph", hud_command)
+        .register("fullscreen", "[off|borderless|exclusive|<width>x<height>[@<hz>]] [monitor]", fullscreen_command)
+        .register("threads", "[on|off] or <threads|min> <value>, meshes recorded on several threads, with recording times", threads_command)
+        .register("history", "[on|off|save [path]] or <seconds|fps|scale> <value>, the last seconds of frames kept to save", history_command)
+        .register("markers", "[on|off|clear], marks the ground under the pointer, x drops one there", markers_command)
+        .register("tilemap", "<path.tmx>|off or zoom <value>", tilemap_command)
+        .register("collision", "[on|off] or <restitution|friction|thickness> <value>, particles bouncing off the scene", collision_command)
+        .register("wireframe", "[on|off]", wireframe_command)
+        .register("bundles", "[on|off], static meshes replayed from render bundles while gpu culling is off", bundles_command)
+        .register("compute", "[on|off], culling and particles submitted ahead of the frame", compute_command)
+        .register("compression", "[on|off], quantized vertices, shows what the meshes' vertices take up", compression_command)
+        .register("decal", "[x y z] [size], a marker projected down onto what's below", decal_command)
+        .register("fog", "[on|off], <density|height|falloff|start> <value> or color <r> <g> <b>", fog_command)
+        .register("load", "<path>, a model or an image", load_command)
+        .register("lut", "<path> or off, a .cube file or a strip png to color grade with, or strength <value>", lut_command)
+        .register("light", "[x y z] [range], a shadow casting point light", light_command)
+        .register("probe", "[x y z] [extent], a reflection probe with a box that big each way", probe_command)
+        .register("probes", "[on|off|capture] or <resolution|strength|distance> <value>", probes_command)
+        .register("shadows", "[on|off|debug] or <cascades|resolution|distance|lambda|blend|bias|normal-bias|point-lights|point-resolution|point-bias> <value>", shadows_command)
+        .register("ssr", "[on|off] or <steps|thickness|distance|fade|roughness|strength> <value>", ssr_command)
+        .register("sky", "[on|off] or <turbidity|intensity> <value>", sky_command)
+        .register("spawn", "<plane|cube|sphere|cylinder|torus> [x y z]", spawn_command)
+        .register("sun", "<elevation> [azimuth], in degrees, turns the first directional light", sun_command);
+    console
+}
 
-        let render_pipeline = context.device.create_render_pipeline(
-            &RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: VertexState {
-                    entry_point: "vertex",
-                    module: &shader_module,
-                    buffers: &[
-                        VertexBufferLayout {
-                            array_stride: VERTEX_SIZE,
-                            step_mode: VertexStepMode::Vertex,
-                            attributes: &vertex_attr_array![
-                                0 => Float32x2,
-                            ],
-                        },
-                    ],
-                },
-                fragment: Some(FragmentState {
-                    entry_point: "fragment",
-                    module: &shader_module,
-                    targets: &[
-                        Some(context.format.into())
-                    ],
-                }),
-                primitive: PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: MultisampleState::default(),
-                multiview: None,
-            }
-        );
-
-        let vertex_buffer = context.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            usage: BufferUsages::VERTEX,
-            contents: bytemuck::cast_slice(&[
-                Vertex { pos: [-1.0, -1.0] },
-                Vertex { pos: [0.0, 1.0] },
-                Vertex { pos: [1.0, -1.0] },
-            ]),
-        });
+fn parse_floats(args: &[&str]) -> Result<Vec<f32>, String> {
+    args.iter().map(|arg| arg.parse().map_err(|_| format!("{arg} isn't a number"))).collect()
+}
+
+fn clear_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let [r, g, b] = parse_floats(args)?[..] else {
+        return Err("expected r g b".into());
+    };
+    engine.renderer.clear_color = Color { r: r as f64, g: g as f64, b: b as f64, a: 1.0 };
+    Ok(String::new())
+}
+
+fn bundles_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let renderer = &mut engine.renderer;
+    renderer.render_bundles = match args {
+        [] => !renderer.render_bundles,
+        ["on"] => true,
+        ["off"] => false,
+        _ => return Err("expected on or off".into()),
+    };
+    Ok(format!("render bundles: {}", if renderer.render_bundles { "on" } else { "off" }))
+}
 
-        Self {
-            render_pipeline,
-            vertex_buffer,
+fn compute_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let renderer = &mut engine.renderer;
+    renderer.async_compute = match args {
+        [] => !renderer.async_compute,
+        ["on"] => true,
+        ["off"] => false,
+        _ => return Err("expected on or off".into()),
+    };
+    Ok(format!("async compute: {}", if renderer.async_compute { "on" } else { "off" }))
+}
+
+fn compression_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let renderer = &mut engine.renderer;
+    renderer.vertex_compression = match args {
+        [] => !renderer.vertex_compression,
+        ["on"] => true,
+        ["off"] => false,
+        _ => return Err("expected on or off".into()),
+    };
+    // the meshes are uploaded again on the next update, so this is still the old size
+    let mib = renderer.vertex_bytes() as f32 / (1024.0 * 1024.0);
+    Ok(format!("vertex compression: {}, vertices take {mib:.2} MiB before switching", if renderer.vertex_compression { "on" } else { "off" }))
+}
+
+fn wireframe_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    if !engine.context.features().contains(Features::POLYGON_MODE_LINE) {
+        return Err("not supported on this device".into());
+    }
+    let renderer = &mut engine.renderer;
+    renderer.wireframe = match args {
+        [] => !renderer.wireframe,
+        ["on"] => true,
+        ["off"] => false,
+        _ => return Err("expected on or off".into()),
+    };
+    Ok(format!("wireframe: {}", if renderer.wireframe { "on" } else { "off" }))
+}
+
+fn shadows_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let shadows = &mut engine.renderer.shadows;
+    match args {
+        [] => shadows.enabled = !shadows.enabled,
+        ["on"] => shadows.enabled = true,
+        ["off"] => shadows.enabled = false,
+        ["debug"] => shadows.debug_cascades = !shadows.debug_cascades,
+        ["cascades", value] => shadows.cascades = value.parse().map_err(|_| format!("{value} isn't a whole number"))?,
+        ["resolution", value] => shadows.resolution = value.parse().map_err(|_| format!("{value} isn't a whole number"))?,
+        ["point-lights", value] => shadows.point_lights = value.parse().map_err(|_| format!("{value} isn't a whole number"))?,
+        ["point-resolution", value] => shadows.point_resolution = value.parse().map_err(|_| format!("{value} isn't a whole number"))?,
+        [setting, value] => {
+            let value: f32 = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+            match *setting {
+                "distance" => shadows.distance = value,
+                "lambda" => shadows.split_lambda = value,
+                "blend" => shadows.blend = value,
+                "bias" => shadows.bias = value,
+                "normal-bias" => shadows.normal_bias = value,
+                "point-bias" => shadows.point_bias = value,
+                _ => return Err(format!("no setting {setting}")),
+            }
         }
+        _ => return Err("expected on, off, debug or a setting and its value".into()),
     }
+    Ok(format!("{shadows:?}"))
+}
 
-    async fn draw(&self, context: &RenderContext) -> Option<Error> {
-        println!("draw");
-        context.device.push_error_scope(ErrorFilter::Validation);
+fn fog_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let atmosphere = &mut engine.renderer.atmosphere;
+    match args {
+        [] => atmosphere.fog = !atmosphere.fog,
+        ["on"] => atmosphere.fog = true,
+        ["off"] => atmosphere.fog = false,
+        ["color", rgb @ ..] => {
+            let [r, g, b] = parse_floats(rgb)?[..] else {
+                return Err("expected r g b".into());
+            };
+            atmosphere.fog_color = Vec3::new(r, g, b);
+        }
+        [setting, value] => {
+            let value: f32 = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+            match *setting {
+                "density" => atmosphere.fog_density = value,
+                "height" => atmosphere.fog_height = value,
+                "falloff" => atmosphere.fog_height_falloff = value,
+                "start" => atmosphere.fog_start = value,
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off, color or a setting and its value".into()),
+    }
+    Ok(format!("{atmosphere:?}"))
+}
 
-        let surface_texture = context.surface.get_current_texture().expect("couldn't get next surface texture");
-        let surface_view = surface_texture.texture.create_view(&TextureViewDescriptor::default());
+fn sky_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let atmosphere = &mut engine.renderer.atmosphere;
+    match args {
+        [] => atmosphere.sky = !atmosphere.sky,
+        ["on"] => atmosphere.sky = true,
+        ["off"] => atmosphere.sky = false,
+        [setting, value] => {
+            let value: f32 = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+            match *setting {
+                "turbidity" => atmosphere.turbidity = value,
+                "intensity" => atmosphere.sky_intensity = value,
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off or a setting and its value".into()),
+    }
+    Ok(format!("sky: {}, turbidity {}, intensity {}", if atmosphere.sky { "on" } else { "off" }, atmosphere.turbidity, atmosphere.sky_intensity))
+}
 
-        let mut cmd = context.device.create_command_encoder(&CommandEncoderDescriptor::default());
-        let mut render_cmd = cmd.begin_render_pass(&RenderPassDescriptor {
-            label: None,
-            color_attachments: &[
-                Some(RenderPassColorAttachment {
-                    ops: Operations {
-                        load: LoadOp::Clear(Color::RED),
-                        store: true,
-                    },
-                    view: &surface_view,
-                    resolve_target: None,
-                })
-            ],
-            depth_stencil_attachment: None,
-        });
-        render_cmd.set_pipeline(&self.render_pipeline);
-        render_cmd.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_cmd.draw(0..3, 0..1);
-        drop(render_cmd);
-        let cmd = cmd.finish();
-        context.queue.submit([cmd]);
-        surface_texture.present();
+fn water_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let water = &mut engine.renderer.water;
+    match args {
+        [] => water.enabled = !water.enabled,
+        ["on"] => water.enabled = true,
+        ["off"] => water.enabled = false,
+        ["resolution", value] => water.resolution = value.parse().map_err(|_| format!("{value} isn't a whole number"))?,
+        [setting, value] => {
+            let value: f32 = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+            match *setting {
+                "height" => water.height = value,
+                "size" => water.size = value,
+                "reflectivity" => water.reflectivity = value,
+                // the waves' total, keeping them in proportion
+                "steepness" => {
+                    let total: f32 = water.waves.iter().map(|wave| wave.steepness).sum();
+                    for wave in &mut water.waves {
+                        wave.steepness *= value / total.max(0.0001);
+                    }
+                }
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off or a setting and its value".into()),
+    }
+    let steepness: f32 = water.waves.iter().map(|wave| wave.steepness).sum();
+    Ok(format!("water: {}, height {}, size {}, resolution {}, steepness {steepness}", if water.enabled { "on" } else { "off" }, water.height, water.size, water.resolution))
+}
 
-        context.device.pop_error_scope().await
+fn voxels_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let voxels = &mut engine.renderer.voxels;
+    match args {
+        [] => voxels.enabled = !voxels.enabled,
+        ["on"] => voxels.enabled = true,
+        ["off"] => voxels.enabled = false,
+        [setting, value] => {
+            let value: u32 = value.parse().map_err(|_| format!("{value} isn't a whole number"))?;
+            match *setting {
+                "distance" => voxels.view_distance = value,
+                "seed" => voxels.seed = value,
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off or a setting and its value".into()),
     }
+    Ok(format!("voxels: {}, distance {}, seed {}", if voxels.enabled { "on" } else { "off" }, voxels.view_distance, voxels.seed))
 }
 
-fn main() {
-    let event_loop = EventLoop::new();
-    let context = block_on(RenderContext::new(&event_loop));
-    context.device.push_error_scope(ErrorFilter::Validation);
-    let renderer = Renderer::new(&context);
-    if let Some(error) = block_on(context.device.pop_error_scope()) {
-        panic!("failed to create renderer: {error}");
-    }
-
-    event_loop.run(move |event, _event_loop, flow| {
-        match event {
-            Event::WindowEvent { event, .. } => {
-                match event {
-                    WindowEvent::Resized(size) => {
-                        context.resize(size.width, size.height);
+fn collision_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let collision = &mut engine.renderer.particles.collision;
+    match args {
+        [] => collision.enabled = !collision.enabled,
+        ["on"] => collision.enabled = true,
+        ["off"] => collision.enabled = false,
+        [setting, value] => {
+            let value: f32 = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+            match *setting {
+                "restitution" => collision.restitution = value,
+                "friction" => collision.friction = value,
+                "thickness" => collision.thickness = value,
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off or a setting and its value".into()),
+    }
+    Ok(format!("particle collision: {}, restitution {}, friction {}, thickness {}", if collision.enabled { "on" } else { "off" }, collision.restitution, collision.friction, collision.thickness))
+}
+
+// the sky's sun follows the first directional light, one is spawned if there's none
+fn sun_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let (elevation, azimuth) = match parse_floats(args)?[..] {
+        [elevation] => (elevation, 0.0),
+        [elevation, azimuth] => (elevation, azimuth),
+        _ => return Err("expected an elevation and maybe an azimuth".into()),
+    };
+    let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+    let towards_sun = Vec3::new(elevation.cos() * azimuth.sin(), elevation.sin(), elevation.cos() * azimuth.cos());
+    let world = &mut engine.world;
+    let light = world.query_mut::<&DirectionalLight>().into_iter().next().map(|(entity, _)| entity);
+    let light = light.unwrap_or_else(|| world.spawn((DirectionalLight::default(),)));
+    if let Ok(mut light) = world.get::<&mut DirectionalLight>(light) {
+        light.direction = -towards_sun;
+    }
+    Ok(format!("sun towards {towards_sun}"))
+}
+
+fn ssr_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let ssr = &mut engine.renderer.ssr;
+    match args {
+        [] => ssr.enabled = !ssr.enabled,
+        ["on"] => ssr.enabled = true,
+        ["off"] => ssr.enabled = false,
+        ["steps", value] => ssr.steps = value.parse().map_err(|_| format!("{value} isn't a whole number"))?,
+        [setting, value] => {
+            let value: f32 = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+            match *setting {
+                "thickness" => ssr.thickness = value,
+                "distance" => ssr.max_distance = value,
+                "fade" => ssr.fade = value,
+                "roughness" => ssr.roughness = value,
+                "strength" => ssr.strength = value,
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off or a setting and its value".into()),
+    }
+    Ok(format!("{ssr:?}"))
+}
+
+fn hud_command(demo: &mut Demo, _engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let show = match args {
+        [] => demo.hud.is_none(),
+        ["on"] => true,
+        ["off"] => false,
+        _ => return Err("expected on or off".into()),
+    };
+    demo.hud = show.then(VecDeque::new);
+    Ok(String::new())
+}
+
+// the last frames' times as bars against the 60fps budget, on a panel in the top right corner
+fn draw_hud(canvas: &mut Canvas, frame_times: &VecDeque<f32>, width: u32, scale: f32) {
+    let bar = 2.0 * scale;
+    let padding = 4.0 * scale;
+    let graph = Vec2::new(HUD_FRAMES as f32 * bar, 40.0 * scale);
+    let min = Vec2::new(width as f32 - graph.x - padding * 3.0, padding);
+    canvas.rounded_rect(min, min + graph + padding * 2.0, padding, [0.0, 0.0, 0.0, 0.6]);
+    // the graph's space has y going up from its bottom left, bars past the top are cut off
+    canvas.push_transform(Affine2::from_translation(min + Vec2::new(padding, padding + graph.y)) * Affine2::from_scale(Vec2::new(1.0, -1.0)));
+    canvas.push_clip(Vec2::ZERO, graph);
+    let budget = 1.0 / 60.0;
+    // two budgets tall
+    let pixels_per_second = graph.y / (budget * 2.0);
+    for (index, &frame_time) in frame_times.iter().enumerate() {
+        let color = if frame_time > budget { [1.0, 0.3, 0.2, 1.0] } else { [0.3, 1.0, 0.4, 1.0] };
+        let x = index as f32 * bar;
+        canvas.rect(Vec2::new(x, 0.0), Vec2::new(x + bar * 0.75, frame_time * pixels_per_second), color);
+    }
+    let budget_height = budget * pixels_per_second;
+    canvas.line(Vec2::new(0.0, budget_height), Vec2::new(graph.x, budget_height), scale, [1.0, 1.0, 1.0, 0.5]);
+    canvas.pop_clip();
+    canvas.pop_transform();
+}
+
+fn fullscreen_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let window = engine.context.window().ok_or("there's no window")?;
+    let (mode, monitor) = match args {
+        [] if window.fullscreen().is_some() => ("off", None),
+        [] => ("borderless", None),
+        [mode] => (*mode, None),
+        [mode, monitor] => (*mode, Some(monitor.parse().map_err(|_| format!("{monitor} isn't a monitor number"))?)),
+        _ => return Err("expected a mode and maybe a monitor".into()),
+    };
+    let fullscreen = match mode {
+        "off" => None,
+        _ => {
+            let request: FullscreenRequest = mode.parse().map_err(|error| format!("{error}"))?;
+            let request = FullscreenRequest { monitor, ..request };
+            Some(window_ext::fullscreen(&request, window.available_monitors(), window.primary_monitor()))
+        }
+    };
+    window.set_fullscreen(fullscreen);
+    Ok(String::new())
+}
+
+fn threads_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let recording = &mut engine.renderer.parallel_recording;
+    match args {
+        [] => return Ok(format!("{recording:?}\n{}", engine.renderer.recording_stats())),
+        ["on"] => recording.enabled = true,
+        ["off"] => recording.enabled = false,
+        [setting, value] => {
+            let value: usize = value.parse().map_err(|_| format!("{value} isn't a whole number"))?;
+            match *setting {
+                "threads" => recording.threads = value,
+                "min" => recording.min_per_thread = value,
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off or a setting and its value".into()),
+    }
+    Ok(format!("{recording:?}"))
+}
+
+fn history_command(demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let history = &mut engine.renderer.frame_history;
+    match args {
+        [] => history.enabled = !history.enabled,
+        ["on"] => history.enabled = true,
+        ["off"] => history.enabled = false,
+        ["save"] => return save_frame_history(&engine.renderer, &demo.frame_history_path),
+        ["save", ..] => return save_frame_history(&engine.renderer, Path::new(&args[1..].join(" "))),
+        [setting, value] => {
+            let value: f32 = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+            match *setting {
+                "seconds" => history.seconds = value,
+                "fps" => history.fps = value,
+                "scale" => history.scale = value.clamp(0.01, 1.0),
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off, save or a setting and its value".into()),
+    }
+    Ok(format!("{history:?}"))
+}
+
+// encoded on another thread, a gif takes a while
+fn save_frame_history(renderer: &Renderer, path: &Path) -> Result<String, String> {
+    if !renderer.frame_history.enabled {
+        return Err("the frame history is off, turn it on with --frame-history or history on".into());
+    }
+    let dump = renderer.save_frame_history();
+    let message = format!("saving {} frames to {}", dump.frames.len(), path.display());
+    let path = path.to_path_buf();
+    thread::spawn(move || match dump.save(&path) {
+        Ok(()) => tracing::info!("saved {}", path.display()),
+        Err(error) => tracing::error!("failed to save {}: {error}", path.display()),
+    });
+    Ok(message)
+}
+
+fn markers_command(demo: &mut Demo, _engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    match args {
+        [] => demo.markers = !demo.markers,
+        ["on"] => demo.markers = true,
+        ["off"] => demo.markers = false,
+        ["clear"] => demo.placed_markers.clear(),
+        _ => return Err("expected on, off or clear".into()),
+    }
+    Ok(format!("markers: {}, {} placed", if demo.markers { "on" } else { "off" }, demo.placed_markers.len()))
+}
+
+// paths can have spaces, everything after the command is the path
+fn tilemap_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    match args {
+        ["off"] => {
+            engine.renderer.set_tilemap(&engine.context, None).map_err(|error| error.to_string())?;
+        }
+        ["zoom", value] => {
+            let tilemap = engine.renderer.tilemap.as_mut().ok_or("no tilemap")?;
+            tilemap.view.zoom = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+        }
+        [] => return Err("expected a path, off or zoom".into()),
+        _ => {
+            let path = args.join(" ");
+            let map = TiledMap::load(&path).map_err(|error| format!("failed to load {path}: {error}"))?;
+            engine.renderer.set_tilemap(&engine.context, Some(&map)).map_err(|error| format!("failed to load {path}: {error}"))?;
+            return Ok(format!("tilemap: {}x{} tiles, {} layers", map.width, map.height, map.layers.len()));
+        }
+    }
+    Ok(String::new())
+}
+
+fn lut_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    match args {
+        ["off"] => engine.renderer.set_color_lut(&engine.context, None),
+        ["strength", value] => engine.renderer.output.grading = value.parse().map_err(|_| format!("{value} isn't a number"))?,
+        [] => return Err("expected a path, off or strength".into()),
+        _ => {
+            let path = args.join(" ");
+            let lut = ColorLut::load(&engine.context, &path).map_err(|error| format!("failed to load {path}: {error}"))?;
+            let size = lut.size;
+            engine.renderer.set_color_lut(&engine.context, Some(lut));
+            return Ok(format!("lut: {size}³"));
+        }
+    }
+    Ok(String::new())
+}
+
+fn load_command(demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let path = PathBuf::from(args.join(" "));
+    if !path.is_file() {
+        return Err(format!("no file at {}", path.display()));
+    }
+    demo.open_file(engine, &path);
+    Ok(String::new())
+}
+
+// at the main camera's target unless a position is given
+fn spawn_command(demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let Some((&kind, position)) = args.split_first() else {
+        return Err("expected a primitive".into());
+    };
+    let Some((name, source)) = primitive_sources().into_iter().find(|(name, _)| *name == kind) else {
+        return Err(format!("unknown primitive {kind}"));
+    };
+    let world = &mut engine.world;
+    let translation = match parse_floats(position)?[..] {
+        [x, y, z] => Vec3::new(x, y, z),
+        [] => world.get::<&Camera>(demo.camera).map_or(Vec3::ZERO, |camera| camera.target),
+        _ => return Err("expected x y z".into()),
+    };
+    let model = source.build(Path::new(".")).map_err(|error| error.to_string())?;
+    let entity = world::spawn_model(world, MeshRef::new(model), Transform::from_translation(translation), Material::default());
+    let _ = world.insert(entity, (Name(name.to_string()), source));
+    Ok(format!("spawned {name} {entity:?} at {translation}"))
+}
+
+// at the camera's target, raised a little so it isn't inside the ground
+fn light_command(demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let world = &mut engine.world;
+    let target = world.get::<&Camera>(demo.camera).map_or(Vec3::ZERO, |camera| camera.target) + Vec3::Y;
+    let (position, range) = match parse_floats(args)?[..] {
+        [] => (target, None),
+        [range] => (target, Some(range)),
+        [x, y, z] => (Vec3::new(x, y, z), None),
+        [x, y, z, range] => (Vec3::new(x, y, z), Some(range)),
+        _ => return Err("expected x y z and a range".into()),
+    };
+    let light = PointLight {
+        position,
+        range: range.unwrap_or(PointLight::default().range),
+        cast_shadows: true,
+        ..PointLight::default()
+    };
+    let entity = world.spawn((light,));
+    Ok(format!("spawned point light {entity:?} at {position}"))
+}
+
+// at the camera's target, the box as big as given or the default
+fn probe_command(demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let world = &mut engine.world;
+    let target = world.get::<&Camera>(demo.camera).map_or(Vec3::ZERO, |camera| camera.target);
+    let (position, extent) = match parse_floats(args)?[..] {
+        [] => (target, None),
+        [extent] => (target, Some(extent)),
+        [x, y, z] => (Vec3::new(x, y, z), None),
+        [x, y, z, extent] => (Vec3::new(x, y, z), Some(extent)),
+        _ => return Err("expected x y z and an extent".into()),
+    };
+    let probe = ReflectionProbe {
+        position,
+        extents: extent.map_or(ReflectionProbe::default().extents, Vec3::splat),
+    };
+    let entity = world.spawn((probe,));
+    let note = if engine.renderer.probes.enabled { "" } else { ", turn probes on to see it" };
+    Ok(format!("spawned reflection probe {entity:?} at {position}{note}"))
+}
+
+fn decal_command(demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let world = &mut engine.world;
+    let target = world.get::<&Camera>(demo.camera).map_or(Vec3::ZERO, |camera| camera.target);
+    let (position, size) = match parse_floats(args)?[..] {
+        [] => (target, 1.0),
+        [size] => (target, size),
+        [x, y, z] => (Vec3::new(x, y, z), 1.0),
+        [x, y, z, size] => (Vec3::new(x, y, z), size),
+        _ => return Err("expected x y z and a size".into()),
+    };
+    let decal = Decal {
+        position,
+        size: Vec3::new(size, size.max(1.0), size),
+        color: Vec4::new(0.8, 0.1, 0.1, 0.9),
+        ..Decal::default()
+    };
+    let entity = world.spawn((decal,));
+    Ok(format!("spawned decal {entity:?} at {position}"))
+}
+
+fn probes_command(_demo: &mut Demo, engine: &mut Engine, args: &[&str]) -> Result<String, String> {
+    let renderer = &mut engine.renderer;
+    let probes = &mut renderer.probes;
+    match args {
+        [] => probes.enabled = !probes.enabled,
+        ["on"] => probes.enabled = true,
+        ["off"] => probes.enabled = false,
+        ["capture"] => {
+            renderer.capture_probes();
+            return Ok("capturing reflection probes".into());
+        }
+        ["resolution", value] => probes.resolution = value.parse().map_err(|_| format!("{value} isn't a whole number"))?,
+        [setting, value] => {
+            let value: f32 = value.parse().map_err(|_| format!("{value} isn't a number"))?;
+            match *setting {
+                "strength" => probes.strength = value,
+                "distance" => probes.distance = value,
+                _ => return Err(format!("no setting {setting}")),
+            }
+        }
+        _ => return Err("expected on, off, capture or a setting and its value".into()),
+    }
+    Ok(format!("{probes:?}"))
+}
+
+struct TerrainSource {
+    heightmap: Heightmap,
+    blend_map: Option<PathBuf>,
+    config: TerrainConfig,
+}
+
+impl TerrainSource {
+    fn apply(&self, renderer: &mut Renderer, context: &RenderContext, assets: &Assets) {
+        let blend_map = self.blend_map.as_ref().and_then(|path| assets.get_texture(path));
+        renderer.set_terrain(context, &self.heightmap, blend_map.as_deref(), self.config);
+    }
+}
+
+struct Demo {
+    camera: Entity,
+    // extra views that orbit the main camera's target, with the direction they look from
+    follow_cameras: Vec<(Entity, Vec3)>,
+    scene: Option<Scene>,
+    assets: Assets,
+    terrain: Option<TerrainSource>,
+    physics: Physics,
+    input: Input,
+    audio: Audio,
+    sound_path: Option<String>,
+    music_paused: bool,
+    show_bounds: bool,
+    cursor: (u32, u32),
+    selected: Option<Entity>,
+    last_selected: Option<Entity>,
+    // grid and gizmo shown, left clicks on a gizmo handle drag it instead of picking
+    editor: bool,
+    gizmo: Gizmo,
+    clipboard: Clipboard,
+    // the last model dropped onto the window, replaced by the next one
+    dropped_model: Option<(PathBuf, Entity)>,
+    // shown until hidden or replaced, rebound once it's loaded
+    dropped_image: Option<PathBuf>,
+    // the --texture file and its material texture id, replaced once it's loaded
+    material_texture: Option<(PathBuf, u32)>,
+    // run over the --texture file once it's loaded
+    filter: Option<ImageFilter>,
+    // the --video file, played every frame
+    video: Option<VideoTexture>,
+    // where ctrl+s saves the world as a scene and ctrl+o loads it from
+    session_path: PathBuf,
+    console: Console<Demo>,
+    // the last frame times, graphed in the corner while Some
+    hud: Option<VecDeque<f32>>,
+    // the point on the ground under the pointer is marked, with where it is
+    markers: bool,
+    // dropped with PlaceMarker, labelled with their number
+    placed_markers: Vec<Vec3>,
+    // where SaveFrameHistory writes to, a .gif or a directory of pngs
+    frame_history_path: PathBuf,
+    fps_title: FpsTitle,
+    // shows asset loading progress and flashes once it's done
+    taskbar: Taskbar,
+    // flushes the chrome trace when the demo is dropped on exit
+    _trace: Option<FlushGuard>,
+}
+
+impl Demo {
+    // models replace the last dropped model, images are shown over the scene
+    fn open_file(&mut self, engine: &mut Engine, path: &Path) {
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("gltf" | "glb" | "obj") => {
+                let world = &mut engine.world;
+                if let Some((_, entity)) = self.dropped_model.take() {
+                    world::despawn(world, entity);
+                }
+                let model = self.assets.model(path);
+                let entity = world::spawn_model(world, MeshRef(model), Transform::IDENTITY, Material::default());
+                let _ = world.insert(entity, (name_of(path), MeshSource::Gltf(path.to_path_buf())));
+                world::play(world, entity, Some(0));
+                // framed around the placeholder for now, and again once it has loaded
+                self.frame_model(world, entity);
+                self.dropped_model = Some((path.to_path_buf(), entity));
+                engine.renderer.set_image(&engine.context, None);
+                self.dropped_image = None;
+            }
+            Some("png" | "jpg" | "jpeg" | "ktx2" | "dds") => {
+                let texture = self.assets.texture(&engine.context, path, true);
+                engine.renderer.set_image(&engine.context, Some(&texture));
+                self.dropped_image = Some(path.to_path_buf());
+            }
+            _ => tracing::warn!("don't know how to open {}", path.display()),
+        }
+    }
+
+    // the middle of the window while the pointer is locked, the cursor isn't shown then
+    fn pointer(&self, engine: &Engine) -> (u32, u32) {
+        if engine.context.is_pointer_locked() {
+            let size = engine.context.physical_size();
+            return (size.width / 2, size.height / 2);
+        }
+        self.cursor
+    }
+
+    // through whichever view is under the pointer, the topmost one if they overlap. also
+    // returns that view's eye
+    fn pointer_ray(&self, engine: &Engine) -> Option<(Ray, Vec3)> {
+        let size = engine.context.physical_size();
+        let (x, y) = self.pointer(engine);
+        let (u, v) = (x as f32 / size.width as f32, y as f32 / size.height as f32);
+        let view = engine.renderer.views().iter().rev().find(|view| view.viewport.contains(u, v))?;
+        let (left, top, width, height) = view.viewport.rect(size.width, size.height);
+        let pixel = Vec2::new(x as f32 - left as f32, y as f32 - top as f32);
+        Some((view.camera.screen_to_ray(pixel, Vec2::new(width as f32, height as f32)), view.camera.eye))
+    }
+
+    // where the pointer's ray meets the y = 0 plane
+    fn ground_point(&self, engine: &Engine) -> Option<Vec3> {
+        let (ray, _) = self.pointer_ray(engine)?;
+        ray.intersect_plane(Vec3::ZERO, Vec3::Y).map(|distance| ray.at(distance))
+    }
+
+    // a cross on the ground with a post sticking up, sized by the distance so it reads the same
+    // anywhere on screen
+    fn draw_markers(&self, renderer: &mut Renderer, ground: Option<Vec3>, size: (u32, u32), scale: f32) {
+        let camera = renderer.views()[0].camera;
+        let (left, top, width, height) = renderer.views()[0].viewport.rect(size.0, size.1);
+        let viewport = Vec2::new(width as f32, height as f32);
+        let offset = Vec2::new(left as f32, top as f32);
+        let mut labels = Vec::new();
+        let hovered = ground.filter(|_| self.markers).map(|point| (point, [1.0, 0.8, 0.2, 1.0], format!("{:.2}, {:.2}", point.x, point.z)));
+        let placed = self.placed_markers.iter().enumerate().map(|(index, &point)| (point, [0.2, 0.8, 1.0, 1.0], (index + 1).to_string()));
+        for (point, color, label) in hovered.into_iter().chain(placed) {
+            let extent = point.distance(camera.eye) * 0.02;
+            let debug = &mut renderer.debug;
+            debug.line(point - Vec3::X * extent, point + Vec3::X * extent, color);
+            debug.line(point - Vec3::Z * extent, point + Vec3::Z * extent, color);
+            debug.line(point, point + Vec3::Y * extent * 2.0, color);
+            debug.sphere(point + Vec3::Y * extent * 2.0, extent * 0.25, color);
+            if let Some(pixel) = camera.world_to_screen(point + Vec3::Y * extent * 2.0, viewport) {
+                labels.push((offset + pixel, color, label));
+            }
+        }
+        for (pixel, color, label) in labels {
+            let width = TextRenderer::measure(&label, scale).x;
+            renderer.text.text(pixel - Vec2::new(width * 0.5, TextRenderer::line_height(scale) * 1.5), scale, color, &label);
+        }
+    }
+
+    fn selected_transform(&self, world: &World) -> Option<(Entity, Transform)> {
+        let entity = self.selected?;
+        let transform = *world.get::<&Transform>(entity).ok()?;
+        Some((entity, transform))
+    }
+
+    // like frame_model for the selected object where it is now, tweened instead of jumping
+    fn focus_selected(&self, world: &mut World) {
+        let Some((entity, transform)) = self.selected_transform(world) else {
+            return;
+        };
+        let Ok(model) = world.get::<&MeshRef>(entity).map(|mesh| mesh.0.clone()) else {
+            return;
+        };
+        let Ok(mut camera) = world.get::<&mut Camera>(self.camera) else {
+            return;
+        };
+        let (min, max) = model.bounds();
+        let matrix = transform.matrix();
+        let (min, max) = (0..8)
+            .map(|corner| Vec3::select(BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0), max, min))
+            .map(|corner| matrix.transform_point3(corner))
+            .fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(min, max), corner| (min.min(corner), max.max(corner)));
+        let mut framed = *camera;
+        framed.frame(min, max);
+        // the clip planes cover both ends of the move right away
+        camera.znear = camera.znear.min(framed.znear);
+        camera.zfar = camera.zfar.max(framed.zfar);
+        drop(camera);
+        let tweens = Tweens::new()
+            .then(Tween::to(Property::CameraEye(framed.eye), FOCUS_TIME).ease(Ease::CubicInOut))
+            .with(Tween::to(Property::CameraTarget(framed.target), FOCUS_TIME).ease(Ease::CubicInOut));
+        let _ = world.insert_one(self.camera, tweens);
+    }
+
+    fn frame_model(&self, world: &mut World, entity: Entity) {
+        let Ok(model) = world.get::<&MeshRef>(entity).map(|mesh| mesh.0.clone()) else {
+            return;
+        };
+        if let Ok(mut camera) = world.get::<&mut Camera>(self.camera) {
+            let (min, max) = model.bounds();
+            camera.frame(min, max);
+        }
+    }
+}
+
+impl App for Demo {
+    fn fixed_update(&mut self, engine: &mut Engine, step: f32) {
+        self.physics.step(&mut engine.world, step);
+    }
+
+    fn update(&mut self, engine: &mut Engine, time: &FrameTime) {
+        Console::execute(self, engine, |demo| &mut demo.console);
+        let ground = self.ground_point(engine);
+        let world = &mut engine.world;
+        self.input.poll();
+        for action in self.input.drain_actions() {
+            match action {
+                Action::ToggleBounds => self.show_bounds = !self.show_bounds,
+                Action::ToggleConsole => {
+                    self.console.toggle();
+                    self.input.release_all();
+                }
+                Action::ToggleAntialiasing => {
+                    let output = &mut engine.renderer.output;
+                    output.antialiasing = match output.antialiasing {
+                        Antialiasing::None => Antialiasing::Fxaa,
+                        Antialiasing::Fxaa => Antialiasing::None,
+                    };
+                    tracing::info!("antialiasing: {:?}", output.antialiasing);
+                }
+                Action::CaptureFrame => engine.gpu_capture.request(),
+                Action::PasteImage => match self.clipboard.image() {
+                    Ok(image) => {
+                        let texture = Texture::from_rgba8(&engine.context, "clipboard", image.width, image.height, &image.rgba, true);
+                        engine.renderer.set_image(&engine.context, Some(&texture));
+                        self.dropped_image = None;
+                    }
+                    Err(error) => tracing::warn!("can't paste an image: {error}"),
+                }
+                Action::Cancel if engine.context.is_pointer_locked() => {
+                    engine.context.set_pointer_lock(false);
+                }
+                Action::Cancel => {
+                    engine.renderer.set_image(&engine.context, None);
+                    self.dropped_image = None;
+                }
+                Action::TogglePointerLock => {
+                    let locked = !engine.context.is_pointer_locked();
+                    engine.context.set_pointer_lock(locked);
+                }
+                Action::ToggleSsao => {
+                    let ssao = &mut engine.renderer.ssao;
+                    ssao.enabled = !ssao.enabled;
+                    tracing::info!("ssao: {}", if ssao.enabled { "on" } else { "off" });
+                }
+                Action::ToggleVertexPulling => {
+                    let renderer = &mut engine.renderer;
+                    renderer.vertex_pulling = !renderer.vertex_pulling;
+                    tracing::info!("vertex pulling: {}", if renderer.vertex_pulling { "on" } else { "off" });
+                }
+                Action::ToggleEditor => {
+                    self.editor = !self.editor;
+                    self.gizmo.end_drag();
+                    engine.renderer.grid.enabled = self.editor;
+                    tracing::info!("editor: {}", if self.editor { "on" } else { "off" });
+                }
+                Action::FocusSelected => self.focus_selected(world),
+                Action::PlaceMarker => match ground {
+                    Some(point) => {
+                        self.placed_markers.push(point);
+                        tracing::info!("marker {} at {point:?}", self.placed_markers.len());
+                    }
+                    None => tracing::info!("the pointer isn't over the ground"),
+                }
+                Action::SaveFrameHistory => match save_frame_history(&engine.renderer, &self.frame_history_path) {
+                    Ok(message) => tracing::info!("{message}"),
+                    Err(error) => tracing::warn!("{error}"),
+                }
+                Action::NextGizmoMode => {
+                    self.gizmo.mode = self.gizmo.mode.next();
+                    tracing::info!("gizmo: {:?}", self.gizmo.mode);
+                }
+                Action::SaveScene => {
+                    let path = &self.session_path;
+                    match SceneDesc::capture(world, path).save(path) {
+                        Ok(()) => tracing::info!("saved {}", path.display()),
+                        Err(error) => tracing::error!("failed to save {}: {error}", path.display()),
+                    }
+                }
+                // read first, a missing or broken file leaves the world as it is
+                Action::LoadScene => match SceneDesc::load(&self.session_path) {
+                    Ok(desc) => {
+                        scene::clear(world);
+                        self.scene = Some(Scene::spawn(world, &mut self.assets, &self.session_path, desc));
+                        self.selected = None;
+                        self.dropped_model = None;
+                        self.gizmo.end_drag();
+                        tracing::info!("loaded {}", self.session_path.display());
                     }
-                    WindowEvent::CloseRequested => {
-                        *flow = ControlFlow::ExitWithCode(0);
+                    Err(error) => tracing::error!("failed to load {}: {error}", self.session_path.display()),
+                }
+                Action::LogLods => {
+                    let counts = engine.renderer.lod_counts();
+                    let counts: Vec<String> = counts.iter().enumerate().map(|(lod, count)| format!("{lod}: {count}")).collect();
+                    tracing::info!("meshes per lod: {}", counts.join(", "));
+                }
+                Action::ToggleMusic => {
+                    self.music_paused = !self.music_paused;
+                    self.audio.set_music_paused(self.music_paused);
+                }
+                Action::ReloadScene => if let Some(scene) = &mut self.scene {
+                    if let Err(error) = scene.reload(world, &mut self.assets) {
+                        tracing::error!("failed to reload {}: {error}", scene.path.display());
+                    }
+                    self.selected = None;
+                }
+                _ => {}
+            }
+            for (_, (mesh, player)) in world.query_mut::<(&MeshRef, &mut AnimationPlayer)>() {
+                let clip_count = mesh.0.animations.len();
+                match action {
+                    Action::PauseAnimation if player.is_paused() => player.resume(),
+                    Action::PauseAnimation => player.pause(),
+                    Action::ToggleLoop => player.set_looping(!player.is_looping()),
+                    Action::NextClip if clip_count > 0 => {
+                        let next = player.current_clip().map_or(0, |clip| (clip + 1) % clip_count);
+                        player.crossfade(next, true, 0.3);
                     }
                     _ => {}
                 }
             }
-            Event::RedrawRequested(..) => {
-                if let Some(error) = block_on(renderer.draw(&context)) {
-                    eprintln!("draw: {error}");
+        }
+
+        // models swap themselves, the terrain has to be rebuilt to pick up a new blend map
+        let loaded = self.assets.update(&engine.context, world);
+        if let Some(terrain) = &self.terrain {
+            if terrain.blend_map.as_ref().is_some_and(|path| loaded.contains(path)) {
+                terrain.apply(&mut engine.renderer, &engine.context, &self.assets);
+            }
+        }
+        if let Some(path) = self.dropped_image.as_ref().filter(|path| loaded.contains(path)) {
+            engine.renderer.set_image(&engine.context, self.assets.get_texture(path).as_deref());
+        }
+        if let Some((path, id)) = self.material_texture.as_ref().filter(|(path, _)| loaded.contains(path)) {
+            if let Some(texture) = self.assets.get_texture(path) {
+                let texture = match self.filter {
+                    Some(filter) => Arc::new(engine.renderer.filter_image(&engine.context, &texture, filter)),
+                    None => texture,
+                };
+                engine.renderer.set_material_texture(*id, texture);
+            }
+        }
+        if let Some((_, entity)) = self.dropped_model.as_ref().filter(|(path, _)| loaded.contains(path)) {
+            self.frame_model(world, *entity);
+        }
+        let progress = self.assets.is_loading().then(|| self.assets.progress());
+        engine.renderer.set_loading(progress);
+        if let Some(window) = engine.context.window() {
+            if progress.is_none() && self.taskbar.progress() != TaskbarProgress::None {
+                self.taskbar.flash(window);
+            }
+            self.taskbar.set_progress(window, progress.map_or(TaskbarProgress::None, TaskbarProgress::Normal));
+            self.fps_title.update(window, time.delta);
+        }
+        if let Some(video) = &mut self.video {
+            video.update(&engine.context, time.delta);
+        }
+
+        // the camera follows input every frame rather than every step so it never lags behind.
+        // speeds scale with the distance to the target so small and large scenes both work
+        // mouse motion is already a distance, so it isn't scaled by the frame time. it's only
+        // taken while locked, any other time it's the cursor moving around
+        let mouse_delta = self.input.take_mouse_delta();
+        let mouse_look = if engine.context.is_pointer_locked() {
+            Vec2::new(mouse_delta.x, -mouse_delta.y) * MOUSE_SENSITIVITY
+        } else {
+            Vec2::ZERO
+        };
+        // a tilemap takes the movement keys to scroll, a screen a second at full tilt
+        if let Some(tilemap) = &mut engine.renderer.tilemap {
+            let size = engine.context.physical_size();
+            let movement = self.input.move_axis() * Vec2::new(size.width as f32, size.height as f32) * time.delta / tilemap.view.zoom.max(0.01);
+            tilemap.view.center += Vec2::new(movement.x, -movement.y);
+        } else if let Ok(mut camera) = world.get::<&mut Camera>(self.camera) {
+            let speed = (camera.target - camera.eye).length() * time.delta;
+            let movement = self.input.move_axis() * speed;
+            camera.fly(Vec3::new(movement.x, 0.0, movement.y), self.input.look_axis() * LOOK_SPEED * time.delta + mouse_look);
+        }
+        let main = world.get::<&Camera>(self.camera).map(|camera| *camera);
+        if let Ok(main) = main {
+            let distance = (main.target - main.eye).length();
+            for &(entity, direction) in &self.follow_cameras {
+                if let Ok(mut camera) = world.get::<&mut Camera>(entity) {
+                    camera.eye = main.target + direction * distance;
+                    camera.target = main.target;
+                    // straight down has no usable y up
+                    camera.up = if direction.cross(Vec3::Y).length_squared() < 1e-6 { Vec3::NEG_Z } else { Vec3::Y };
+                    camera.zfar = main.zfar;
                 }
             }
+        }
+
+        let renderer = &mut engine.renderer;
+        for event in renderer.drain_events() {
+            match event {
+                RenderEvent::Picked(picked) => self.selected = picked,
+            }
+        }
+        // plays whenever something new gets selected, by picking or ray casting
+        if let (Some(_), Some(path)) = (self.selected, &self.sound_path) {
+            if self.selected != self.last_selected {
+                if let Err(error) = self.audio.play_sound(path) {
+                    tracing::error!("failed to play {path}: {error}");
+                }
+            }
+        }
+        self.last_selected = self.selected;
+        renderer.selected = self.selected.into_iter().collect();
+
+        if self.show_bounds {
+            let boxes: Vec<_> = renderer.instances()
+                .map(|(_, instance)| {
+                    let (min, max) = instance.bounds();
+                    (instance.transform, min, max)
+                })
+                .collect();
+            for (transform, min, max) in boxes {
+                renderer.debug.aabb_transformed(transform, min, max, [0.0, 1.0, 0.0, 1.0]);
+            }
+        }
+        if self.selected.is_none() {
+            self.gizmo.end_drag();
+        }
+        if self.editor {
+            if let Some((_, transform)) = self.selected_transform(&engine.world) {
+                let eye = renderer.views()[0].camera.eye;
+                self.gizmo.draw(&mut renderer.debug, &transform, eye);
+            }
+        }
+        let size = engine.context.physical_size();
+        let scale = (CONSOLE_SCALE * engine.context.scale_factor() as f32).round().max(1.0);
+        if self.markers || !self.placed_markers.is_empty() {
+            self.draw_markers(renderer, ground, (size.width, size.height), scale);
+        }
+        if let Some(frame_times) = &mut self.hud {
+            if frame_times.len() == HUD_FRAMES {
+                frame_times.pop_front();
+            }
+            frame_times.push_back(time.delta);
+            draw_hud(&mut renderer.canvas, frame_times, size.width, scale);
+        }
+        self.console.draw(&mut renderer.text, size.width, size.height, scale);
+    }
+
+    fn window_event(&mut self, engine: &mut Engine, event: &WindowEvent) {
+        if self.console.handle_event(event) {
+            return;
+        }
+        self.input.handle_event(event);
+        match *event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x as u32, position.y as u32);
+                if !self.editor {
+                    return;
+                }
+                let (Some((entity, transform)), Some((ray, eye))) = (self.selected_transform(&engine.world), self.pointer_ray(engine)) else {
+                    return;
+                };
+                if !self.gizmo.is_dragging() {
+                    self.gizmo.hover(&transform, eye, &ray);
+                } else if let Some(dragged) = self.gizmo.drag(&ray) {
+                    if let Ok(mut transform) = engine.world.get::<&mut Transform>(entity) {
+                        *transform = dragged;
+                    }
+                }
+            }
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                if self.editor {
+                    if let (Some((_, transform)), Some((ray, eye))) = (self.selected_transform(&engine.world), self.pointer_ray(engine)) {
+                        if self.gizmo.begin_drag(&transform, eye, &ray) {
+                            return;
+                        }
+                    }
+                }
+                let (x, y) = self.pointer(engine);
+                engine.renderer.pick(x, y);
+            }
+            WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+                self.gizmo.end_drag();
+            }
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Right, .. } => {
+                let Some((ray, _)) = self.pointer_ray(engine) else {
+                    return;
+                };
+                self.selected = engine.renderer.raycast(&ray).map(|(entity, hit)| {
+                    tracing::info!("hit {entity:?} at {:?}, distance {}, uv {:?}", hit.position, hit.distance, hit.uv);
+                    entity
+                });
+            }
+            WindowEvent::DroppedFile(ref path) => self.open_file(engine, path),
             _ => {}
         }
-    });
+    }
+
+    fn device_event(&mut self, _engine: &mut Engine, event: &DeviceEvent) {
+        self.input.handle_device_event(event);
+    }
+
+    // keeps the music from playing on in the background
+    fn suspended(&mut self, _engine: &mut Engine) {
+        self.audio.set_music_paused(true);
+    }
+
+    fn resumed(&mut self, _engine: &mut Engine) {
+        self.audio.set_music_paused(self.music_paused);
+    }
 }
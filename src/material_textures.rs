@@ -0,0 +1,121 @@
+use std::sync::Arc;
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::context::RenderContext;
+use crate::texture::Texture;
+
+pub const MATERIAL_TEXTURES_LAYOUT: &str = "material textures";
+// the size of the binding array, slots past the last added texture are bound to a fallback
+pub const MAX_MATERIAL_TEXTURES: u32 = 256;
+// what ObjectUniform::texture holds for objects without a material texture
+pub const NO_TEXTURE: u32 = u32::MAX;
+// sampled textures the mesh shader binds besides the array, the occlusion and base color maps,
+// with some room to spare
+const RESERVED_TEXTURES: u32 = 8;
+
+// textures materials refer to by id, see Material::texture. where the device has binding arrays
+// they're all bound at once in group 2 and the mesh shader indexes them by the object's texture
+// id, so drawing doesn't rebind anything. otherwise each object's own bind group gets its
+// material's texture instead, and group 2 is left empty
+pub struct MaterialTextures {
+    textures: Vec<Arc<Texture>>,
+    bindless: bool,
+    bind_group: BindGroup,
+    // ids whose texture was replaced since the last update
+    changed: Vec<u32>,
+    dirty: bool,
+    // bumped whenever the bind group is recreated, see generation
+    generation: u32,
+}
+
+impl MaterialTextures {
+    pub fn is_supported(context: &RenderContext) -> bool {
+        context.features().contains(Features::TEXTURE_BINDING_ARRAY)
+            && context.limits().max_sampled_textures_per_shader_stage >= MAX_MATERIAL_TEXTURES + RESERVED_TEXTURES
+    }
+
+    // fallback fills the array's slots until they have a texture
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, fallback: &TextureView) -> Self {
+        let bindless = Self::is_supported(context);
+        if bindless {
+            layouts.register(context, MATERIAL_TEXTURES_LAYOUT, &[
+                (Binding::TextureArray { count: MAX_MATERIAL_TEXTURES }, ShaderStages::FRAGMENT),
+            ]);
+        } else {
+            tracing::info!("no texture binding arrays, material textures are bound per object");
+            layouts.register(context, MATERIAL_TEXTURES_LAYOUT, &[]);
+        }
+        let textures = Vec::new();
+        let bind_group = create_bind_group(context, layouts, &textures, bindless, fallback);
+        Self {
+            textures,
+            bindless,
+            bind_group,
+            changed: Vec::new(),
+            dirty: false,
+            generation: 0,
+        }
+    }
+
+    // whether the textures are bound as one array, see MaterialTextures
+    pub fn is_bindless(&self) -> bool {
+        self.bindless
+    }
+
+    // returns the id to put in Material::texture. ids are never reused. past
+    // MAX_MATERIAL_TEXTURES only the per object path can show them
+    pub fn add(&mut self, texture: Arc<Texture>) -> u32 {
+        let id = self.textures.len() as u32;
+        if self.bindless && id >= MAX_MATERIAL_TEXTURES {
+            tracing::warn!("more than {MAX_MATERIAL_TEXTURES} material textures, texture {id} won't show");
+        }
+        self.textures.push(texture);
+        self.dirty = true;
+        id
+    }
+
+    // e.g. once an asset has been reloaded
+    pub fn set(&mut self, id: u32, texture: Arc<Texture>) {
+        if let Some(slot) = self.textures.get_mut(id as usize) {
+            *slot = texture;
+            self.changed.push(id);
+            self.dirty = true;
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Arc<Texture>> {
+        self.textures.get(id as usize)
+    }
+
+    // rebuilds the array's bind group after textures were added or replaced. returns the ids
+    // that were replaced, objects binding them on their own need to rebind
+    pub fn update(&mut self, context: &RenderContext, layouts: &LayoutRegistry, fallback: &TextureView) -> Vec<u32> {
+        if self.dirty && self.bindless {
+            self.bind_group = create_bind_group(context, layouts, &self.textures, self.bindless, fallback);
+            self.generation += 1;
+        }
+        self.dirty = false;
+        std::mem::take(&mut self.changed)
+    }
+
+    // group 2 of the mesh pipelines, empty unless bindless
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+
+    // changes when bind_group is recreated, anything recorded with it must be recorded again
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+fn create_bind_group(context: &RenderContext, layouts: &LayoutRegistry, textures: &[Arc<Texture>], bindless: bool, fallback: &TextureView) -> BindGroup {
+    let mut builder = BindGroupBuilder::new();
+    if bindless {
+        let views = (0..MAX_MATERIAL_TEXTURES as usize)
+            .map(|index| textures.get(index).map_or(fallback, |texture| &texture.view))
+            .collect();
+        builder = builder.texture_array(views);
+    }
+    builder.build(context, layouts, MATERIAL_TEXTURES_LAYOUT)
+}
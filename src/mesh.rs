@@ -0,0 +1,617 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ops::Range;
+use std::sync::OnceLock;
+use bytemuck::Zeroable;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt, RenderEncoder};
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::FRAME_LAYOUT;
+use crate::context::RenderContext;
+use crate::material_textures::{MATERIAL_TEXTURES_LAYOUT, NO_TEXTURE};
+use crate::pipeline_cache::{DepthKey, PipelineCache, PipelineId, PipelineKey};
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+use crate::shadows::SHADOW_FORMAT;
+use crate::vertex_compression::{self, Quantization, VertexEncoding};
+use self::process::Bounds;
+
+pub mod process;
+
+#[derive(Copy, Clone, Default, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+    // xyz along +u, w is the bitangent sign
+    pub tangent: [f32; 4],
+}
+
+// what the pulling vertex shader reads a vertex as, see pipeline_key
+pub const VERTEX_WORDS: usize = size_of::<Vertex>() / size_of::<u32>();
+
+impl Vertex {
+    pub const LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: size_of::<Vertex>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &vertex_attr_array![
+            0 => Float32x3,
+            1 => Float32x3,
+            2 => Float32x2,
+            3 => Uint32x4,
+            4 => Float32x4,
+            5 => Float32x4,
+        ],
+    };
+}
+
+// per-vertex offsets relative to the base mesh
+#[derive(Clone, Default, Debug)]
+pub struct MorphTarget {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+}
+
+// a coarser set of triangles over the same vertices, drawn once the mesh covers less than
+// screen_size of the screen height
+#[derive(Clone, Default, Debug)]
+pub struct Lod {
+    pub indices: Vec<u32>,
+    pub screen_size: f32,
+}
+
+impl Lod {
+    // halves with every level: 0.5, 0.25, 0.125...
+    pub fn default_screen_size(level: usize) -> f32 {
+        0.5f32.powi(level as i32)
+    }
+}
+
+// meshes with fewer triangles than this don't get generated lods
+const MIN_LOD_TRIANGLES: usize = 256;
+// a generated level is dropped unless it has at most this share of the previous level's triangles
+const MAX_LOD_RATIO: f32 = 0.75;
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct MorphDelta {
+    position: [f32; 4],
+    normal: [f32; 4],
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Mesh {
+    // shows up in the gpu buffer labels
+    pub name: Option<String>,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub morph_targets: Vec<MorphTarget>,
+    // coarsest last, see Lod
+    pub lods: Vec<Lod>,
+    // worked out on first use, see invalidate_bounds
+    pub(crate) cached_bounds: OnceLock<Bounds>,
+}
+
+impl Mesh {
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        let bounds = self.bounding();
+        (bounds.min, bounds.max)
+    }
+
+    // the box and sphere around the vertices, cached so loaders, culling and physics all share
+    // the one pass over them
+    pub fn bounding(&self) -> Bounds {
+        *self.cached_bounds.get_or_init(|| process::bounds(&self.vertices))
+    }
+
+    // after moving vertices by hand, the methods here that change them call it themselves
+    pub fn invalidate_bounds(&mut self) {
+        self.cached_bounds = OnceLock::new();
+    }
+
+    // smooth normals for meshes that come without any, see process::smooth_normals
+    pub fn compute_normals(&mut self) {
+        process::smooth_normals(&mut self.vertices, &self.indices);
+    }
+
+    // for meshes that come without tangents, from their normals and uvs
+    pub fn compute_tangents(&mut self) {
+        process::tangents(&mut self.vertices, &self.indices);
+    }
+
+    // adds a separately modelled lod, its vertices are appended so every level shares one
+    // vertex buffer. morph targets don't move them
+    pub fn add_lod(&mut self, lod: &Mesh, screen_size: f32) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&lod.vertices);
+        self.invalidate_bounds();
+        for target in &mut self.morph_targets {
+            target.positions.resize(self.vertices.len(), [0.0; 3]);
+            target.normals.resize(self.vertices.len(), [0.0; 3]);
+        }
+        self.lods.push(Lod {
+            indices: lod.indices.iter().map(|index| base + index).collect(),
+            screen_size,
+        });
+    }
+
+    // decimates by vertex clustering, each level on a grid half as fine as the one before, for
+    // meshes big enough to be worth it that don't have lods yet
+    pub fn generate_lods(&mut self, levels: usize) {
+        if !self.lods.is_empty() || self.indices.len() / 3 < MIN_LOD_TRIANGLES {
+            return;
+        }
+        let mut triangles = self.indices.len() / 3;
+        for level in 1..=levels {
+            let cells = 64 >> level;
+            if cells < 2 {
+                break;
+            }
+            let indices = self.simplify(cells);
+            if indices.len() / 3 > (triangles as f32 * MAX_LOD_RATIO) as usize {
+                continue;
+            }
+            triangles = indices.len() / 3;
+            self.lods.push(Lod {
+                indices,
+                screen_size: Lod::default_screen_size(self.lods.len() + 1),
+            });
+        }
+    }
+
+    // vertices in the same grid cell, out of `cells` along the longest side, collapse onto the
+    // first of them and triangles that lose an edge go. vertices facing different ways stay
+    // apart, so hard edges survive
+    pub fn simplify(&self, cells: u32) -> Vec<u32> {
+        let (min, max) = self.bounds();
+        let cell_size = ((max - min).max_element() / cells as f32).max(f32::EPSILON);
+        let mut representatives: HashMap<([i32; 3], usize), u32> = HashMap::new();
+        let remap: Vec<u32> = self.vertices.iter().enumerate().map(|(index, vertex)| {
+            let cell = ((Vec3::from(vertex.position) - min) / cell_size).floor().as_ivec3().to_array();
+            let normal = Vec3::from(vertex.normal);
+            let axis = normal.abs().to_array().iter().enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map_or(0, |(axis, _)| axis * 2 + (normal[axis] < 0.0) as usize);
+            *representatives.entry((cell, axis)).or_insert(index as u32)
+        }).collect();
+        self.indices.chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| remap[triangle[i] as usize]))
+            .filter(|[a, b, c]| a != b && b != c && a != c)
+            .flatten()
+            .collect()
+    }
+
+    pub fn upload(&self, context: &RenderContext, layouts: &LayoutRegistry, encoding: VertexEncoding) -> GpuMesh {
+        let name = self.name.as_deref().unwrap_or("mesh");
+        let (quantization, contents) = match encoding {
+            VertexEncoding::Full => (Quantization::IDENTITY, bytemuck::cast_slice(&self.vertices).to_vec()),
+            VertexEncoding::Compressed => {
+                let quantization = Quantization::new(self.bounds());
+                (quantization, bytemuck::cast_slice(&vertex_compression::compress(&self.vertices, &quantization)).to_vec())
+            }
+        };
+        // bound either way, see GpuMesh::draw
+        let vertex_buffer = context.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{name} vertices")),
+            usage: BufferUsages::VERTEX | BufferUsages::STORAGE,
+            contents: &contents,
+        });
+        let quantization_buffer = context.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{name} quantization")),
+            usage: BufferUsages::UNIFORM,
+            contents: bytemuck::bytes_of(&quantization.uniform()),
+        });
+        let vertex_bind_group = BindGroupBuilder::new()
+            .buffer(&vertex_buffer)
+            .buffer(&quantization_buffer)
+            .build(context, layouts, VERTICES_LAYOUT);
+        // every lod's indices follow the base ones in the same buffer
+        let mut indices = self.indices.clone();
+        let mut lods = Vec::with_capacity(self.lods.len() + 1);
+        lods.push(0..self.indices.len() as u32);
+        for lod in &self.lods {
+            let start = indices.len() as u32;
+            indices.extend_from_slice(&lod.indices);
+            lods.push(start..indices.len() as u32);
+        }
+        let index_buffer = context.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{name} indices")),
+            usage: BufferUsages::INDEX,
+            contents: bytemuck::cast_slice(&indices),
+        });
+
+        // laid out target-major so the shader can index with target * vertex_count + vertex
+        let mut deltas: Vec<MorphDelta> = self.morph_targets.iter().flat_map(|target| {
+            (0..self.vertices.len()).map(|i| MorphDelta {
+                position: target.positions.get(i).map_or([0.0; 4], |p| [p[0], p[1], p[2], 0.0]),
+                normal: target.normals.get(i).map_or([0.0; 4], |n| [n[0], n[1], n[2], 0.0]),
+            })
+        }).collect();
+        if deltas.is_empty() {
+            deltas.push(MorphDelta::zeroed());
+        }
+        let morph_buffer = context.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{name} morph targets")),
+            usage: BufferUsages::STORAGE,
+            contents: bytemuck::cast_slice(&deltas),
+        });
+
+        GpuMesh {
+            vertex_buffer,
+            vertex_bind_group,
+            index_buffer,
+            morph_buffer,
+            lods,
+            vertex_count: self.vertices.len() as u32,
+            morph_target_count: self.morph_targets.len() as u32,
+            encoding,
+        }
+    }
+}
+
+pub struct GpuMesh {
+    pub vertex_buffer: Buffer,
+    // the same buffer as storage and the quantization, group 3 of the mesh pipelines
+    pub vertex_bind_group: BindGroup,
+    pub index_buffer: Buffer,
+    pub morph_buffer: Buffer,
+    // index ranges of the base mesh and then each lod
+    pub lods: Vec<Range<u32>>,
+    pub vertex_count: u32,
+    pub morph_target_count: u32,
+    pub encoding: VertexEncoding,
+}
+
+impl GpuMesh {
+    // 0 is the base mesh, past the last lod draws the last. the vertices are bound both as a
+    // vertex buffer and for pulling, whichever of the pipelines for the mesh's encoding is used
+    pub fn draw<'a>(&'a self, render_cmd: &mut impl RenderEncoder<'a>, pipelines: MeshPipelines<'a>, lod: usize) {
+        render_cmd.set_pipeline(pipelines.get(self.encoding));
+        render_cmd.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_cmd.set_bind_group(3, &self.vertex_bind_group, &[]);
+        render_cmd.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
+        render_cmd.draw_indexed(self.lods[lod.min(self.lods.len() - 1)].clone(), 0, 0..1);
+    }
+
+    // as uploaded, the index buffer aside
+    pub fn vertex_bytes(&self) -> usize {
+        self.vertex_count as usize * self.encoding.stride()
+    }
+}
+
+// a mesh pipeline for either vertex encoding, from the same key
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MeshPipelineIds {
+    pub full: PipelineId,
+    pub compressed: PipelineId,
+}
+
+impl MeshPipelineIds {
+    // compressed_shader is the key's shader built with COMPRESSED_VERTICES defined
+    pub fn get_or_create(context: &RenderContext, cache: &mut PipelineCache, key: PipelineKey, compressed_shader: &'static str) -> Self {
+        Self {
+            full: cache.get_or_create(context, &key),
+            compressed: cache.get_or_create(context, &vertex_compression::compressed_key(key, compressed_shader)),
+        }
+    }
+
+    pub fn get<'a>(&self, cache: &'a PipelineCache) -> MeshPipelines<'a> {
+        MeshPipelines {
+            full: cache.get(self.full),
+            compressed: cache.get(self.compressed),
+        }
+    }
+}
+
+// what meshes are drawn with in a pass, each mesh sets the one for its encoding. setting the one
+// already set costs next to nothing
+#[derive(Copy, Clone)]
+pub struct MeshPipelines<'a> {
+    full: &'a RenderPipeline,
+    compressed: &'a RenderPipeline,
+}
+
+impl<'a> MeshPipelines<'a> {
+    pub fn get(&self, encoding: VertexEncoding) -> &'a RenderPipeline {
+        match encoding {
+            VertexEncoding::Full => self.full,
+            VertexEncoding::Compressed => self.compressed,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    // linear, not srgb. colors taken from an srgb color picker need converting first
+    pub base_color: [f32; 4],
+    // multiplies the base color, an id from MaterialTextures. only known at runtime, so scenes
+    // can't set it
+    #[serde(skip)]
+    pub texture: Option<u32>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: [0.8, 0.8, 0.8, 1.0],
+            texture: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ObjectUniform {
+    pub model: [[f32; 4]; 4],
+    pub normal: [[f32; 4]; 4],
+    pub joint_count: u32,
+    pub morph_target_count: u32,
+    pub vertex_count: u32,
+    pub id: u32,
+    pub base_color: [f32; 4],
+    // into the material texture array, NO_TEXTURE for none
+    pub texture: u32,
+    pub _padding: [u32; 3],
+}
+
+impl ObjectUniform {
+    pub fn new(model: Mat4, joint_count: u32, mesh: &GpuMesh, id: u32, material: &Material) -> Self {
+        Self {
+            model: model.to_cols_array_2d(),
+            normal: model.inverse().transpose().to_cols_array_2d(),
+            joint_count,
+            morph_target_count: mesh.morph_target_count,
+            vertex_count: mesh.vertex_count,
+            id,
+            base_color: material.base_color,
+            texture: material.texture.unwrap_or(NO_TEXTURE),
+            _padding: [0; 3],
+        }
+    }
+}
+
+pub const OBJECT_LAYOUT: &str = "object";
+pub const VERTICES_LAYOUT: &str = "mesh vertices";
+const OBJECT_UNIFORM_SIZE: BufferAddress = size_of::<ObjectUniform>() as BufferAddress;
+
+// per-object data (transform, joint palette, morph targets and the base color texture) lives in
+// group 1. the uniform is bound with a dynamic offset, always 0 for objects with their own
+// binding, see SharedObjects for the rest
+pub struct ObjectBinding {
+    pub bind_group: BindGroup,
+    uniform_buffer: Buffer,
+    joint_buffer: Buffer,
+    weight_buffer: Buffer,
+}
+
+impl ObjectBinding {
+    pub fn new(context: &RenderContext, layouts: &LayoutRegistry, mesh: &GpuMesh, max_joints: usize, texture: &TextureView, sampler: &Sampler) -> Self {
+        let uniform_buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("object"),
+            size: OBJECT_UNIFORM_SIZE,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let joint_buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("object joints"),
+            size: (max_joints.max(1) * size_of::<Mat4>()) as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let weight_buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("object morph weights"),
+            size: (mesh.morph_target_count.max(1) as usize * size_of::<f32>()) as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = create_object_bind_group(context, layouts, mesh, [&uniform_buffer, &joint_buffer, &weight_buffer], texture, sampler);
+        Self {
+            bind_group,
+            uniform_buffer,
+            joint_buffer,
+            weight_buffer,
+        }
+    }
+
+    // e.g. a render target the object shows, see Screen
+    pub fn set_texture(&mut self, context: &RenderContext, layouts: &LayoutRegistry, mesh: &GpuMesh, texture: &TextureView, sampler: &Sampler) {
+        let buffers = [&self.uniform_buffer, &self.joint_buffer, &self.weight_buffer];
+        self.bind_group = create_object_bind_group(context, layouts, mesh, buffers, texture, sampler);
+    }
+
+    pub fn bind<'a>(&'a self, render_cmd: &mut impl RenderEncoder<'a>) {
+        render_cmd.set_bind_group(1, &self.bind_group, &[0]);
+    }
+
+    pub fn update(&self, context: &RenderContext, uniform: &ObjectUniform, joints: &[Mat4], weights: &[f32]) {
+        context.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniform));
+        if !joints.is_empty() {
+            context.queue.write_buffer(&self.joint_buffer, 0, bytemuck::cast_slice(joints));
+        }
+        let weights = &weights[..weights.len().min(uniform.morph_target_count as usize)];
+        if !weights.is_empty() {
+            context.queue.write_buffer(&self.weight_buffer, 0, bytemuck::cast_slice(weights));
+        }
+    }
+}
+
+fn create_object_bind_group(context: &RenderContext, layouts: &LayoutRegistry, mesh: &GpuMesh, [uniform, joints, weights]: [&Buffer; 3], texture: &TextureView, sampler: &Sampler) -> BindGroup {
+    BindGroupBuilder::new()
+        .buffer(uniform)
+        .buffer(joints)
+        .buffer(&mesh.morph_buffer)
+        .buffer(weights)
+        .texture(texture)
+        .sampler(sampler)
+        .build(context, layouts, OBJECT_LAYOUT)
+}
+
+// objects that aren't skinned or morphed and show the default texture only differ by their
+// uniform, so theirs all go into one buffer, each in a slice aligned to
+// min_uniform_buffer_offset_alignment. they share a single bind group and are drawn at their
+// slice's offset, rather than every one of them having a bind group and buffer of its own
+pub struct SharedObjects {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    // placeholders for the joints, morph targets and weights they don't have
+    empty_buffers: [Buffer; 3],
+    stride: BufferAddress,
+    capacity: u32,
+    // bumped whenever the bind group is recreated, see generation
+    generation: u32,
+}
+
+impl SharedObjects {
+    pub fn new(context: &RenderContext, layouts: &LayoutRegistry, texture: &TextureView, sampler: &Sampler) -> Self {
+        let alignment = context.limits().min_uniform_buffer_offset_alignment as BufferAddress;
+        let stride = OBJECT_UNIFORM_SIZE.next_multiple_of(alignment);
+        let empty_buffers = ["shared object joints", "shared object morph targets", "shared object morph weights"].map(|label| {
+            context.device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                // one joint matrix, the largest single element of the three
+                size: size_of::<Mat4>() as BufferAddress,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            })
+        });
+        let capacity = 64;
+        let buffer = create_shared_buffer(context, stride, capacity);
+        let bind_group = create_shared_bind_group(context, layouts, &buffer, &empty_buffers, texture, sampler);
+        Self {
+            buffer,
+            bind_group,
+            empty_buffers,
+            stride,
+            capacity,
+            generation: 0,
+        }
+    }
+
+    // changes when what bind binds is recreated, anything recorded with it must be recorded again
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    // writes uniforms[slot] for every slot handed out this frame, growing the buffer if it has to
+    pub fn update(&mut self, context: &RenderContext, layouts: &LayoutRegistry, uniforms: &[ObjectUniform], texture: &TextureView, sampler: &Sampler) {
+        if uniforms.len() as u32 > self.capacity {
+            self.capacity = (uniforms.len() as u32).next_power_of_two();
+            self.buffer = create_shared_buffer(context, self.stride, self.capacity);
+            self.bind_group = create_shared_bind_group(context, layouts, &self.buffer, &self.empty_buffers, texture, sampler);
+            self.generation += 1;
+        }
+        if uniforms.is_empty() {
+            return;
+        }
+        let mut data = vec![0; uniforms.len() * self.stride as usize];
+        for (slice, uniform) in data.chunks_exact_mut(self.stride as usize).zip(uniforms) {
+            slice[..OBJECT_UNIFORM_SIZE as usize].copy_from_slice(bytemuck::bytes_of(uniform));
+        }
+        context.queue.write_buffer(&self.buffer, 0, &data);
+    }
+
+    pub fn bind<'a>(&'a self, render_cmd: &mut impl RenderEncoder<'a>, slot: u32) {
+        render_cmd.set_bind_group(1, &self.bind_group, &[(slot as BufferAddress * self.stride) as DynamicOffset]);
+    }
+}
+
+fn create_shared_buffer(context: &RenderContext, stride: BufferAddress, capacity: u32) -> Buffer {
+    context.device.create_buffer(&BufferDescriptor {
+        label: Some("shared objects"),
+        size: stride * capacity as BufferAddress,
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_shared_bind_group(context: &RenderContext, layouts: &LayoutRegistry, buffer: &Buffer, [joints, morph, weights]: &[Buffer; 3], texture: &TextureView, sampler: &Sampler) -> BindGroup {
+    BindGroupBuilder::new()
+        .buffer(buffer)
+        .buffer(joints)
+        .buffer(morph)
+        .buffer(weights)
+        .texture(texture)
+        .sampler(sampler)
+        .build(context, layouts, OBJECT_LAYOUT)
+}
+
+// registers the object and vertex layouts and the mesh shader, pipeline variants are created from
+// `pipeline_key`. group 2 is MaterialTextures, with the array only when bindless
+pub fn register_pipeline(context: &RenderContext, layouts: &mut LayoutRegistry, cache: &mut PipelineCache, bindless: bool) {
+    let storage = (Binding::Storage { read_only: true }, ShaderStages::VERTEX);
+    layouts.register(context, OBJECT_LAYOUT, &[
+        (Binding::DynamicUniform { size: OBJECT_UNIFORM_SIZE }, ShaderStages::VERTEX | ShaderStages::FRAGMENT),
+        storage,
+        storage,
+        storage,
+        (Binding::Texture, ShaderStages::FRAGMENT),
+        (Binding::Sampler, ShaderStages::FRAGMENT),
+    ]);
+    layouts.register(context, VERTICES_LAYOUT, &[storage, (Binding::Uniform, ShaderStages::VERTEX)]);
+
+    let device = &context.device;
+    let mut preprocessor = Preprocessor::new().target(HDR_FORMAT);
+    if bindless {
+        preprocessor = preprocessor.define("MATERIAL_TEXTURES", 1);
+    }
+    cache.add_shader("mesh", preprocessor.create_module(context, "mesh.wgsl"));
+    cache.add_shader("mesh_compressed", preprocessor.define("COMPRESSED_VERTICES", 1).create_module(context, "mesh.wgsl"));
+    cache.add_layout("mesh", device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("mesh"),
+        bind_group_layouts: &[
+            layouts.get(FRAME_LAYOUT),
+            layouts.get(OBJECT_LAYOUT),
+            layouts.get(MATERIAL_TEXTURES_LAYOUT),
+            layouts.get(VERTICES_LAYOUT),
+        ],
+        push_constant_ranges: &[],
+    }));
+}
+
+// "fragment" shades, "fragment_normal" writes normals and depth for ssao, "fragment_id" writes
+// object ids for picking. pulled pipelines have no vertex buffers, "vertex_pulled" reads the
+// vertices from group 3 by index instead
+pub fn pipeline_key(fragment_entry: &'static str, target: ColorTargetState, pulled: bool) -> PipelineKey {
+    PipelineKey {
+        shader: "mesh",
+        layout: "mesh",
+        vertex_entry: if pulled { "vertex_pulled" } else { "vertex" },
+        fragment_entry: Some(fragment_entry),
+        vertex_layouts: if pulled { Vec::new() } else { vec![(&Vertex::LAYOUT).into()] },
+        targets: vec![target],
+        primitive: PrimitiveState {
+            cull_mode: Some(Face::Back),
+            ..PrimitiveState::default()
+        },
+        depth: Some(DepthKey {
+            format: DEPTH_FORMAT,
+            write_enabled: true,
+            compare: CompareFunction::Less,
+            stencil: StencilState::default(),
+        }),
+        multisample: MultisampleState::default(),
+    }
+}
+
+// pipeline_key's pipelines, or shadow_pipeline_key's, for both vertex encodings
+pub fn pipeline_ids(context: &RenderContext, cache: &mut PipelineCache, key: PipelineKey) -> MeshPipelineIds {
+    MeshPipelineIds::get_or_create(context, cache, key, "mesh_compressed")
+}
+
+// depth only from a shadow cascade's light, or "fragment_point_shadow" for a point light's face,
+// see ShadowMaps
+pub fn shadow_pipeline_key(fragment_entry: Option<&'static str>, pulled: bool) -> PipelineKey {
+    let mut key = pipeline_key("fragment", HDR_FORMAT.into(), pulled);
+    key.fragment_entry = fragment_entry;
+    key.targets.clear();
+    if let Some(depth) = &mut key.depth {
+        depth.format = SHADOW_FORMAT;
+    }
+    key
+}
@@ -0,0 +1,40 @@
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "color.wgsl"
+#include "object.wgsl"
+
+#ifdef MATERIAL_TEXTURES
+// indexed by object.texture, see MaterialTextures
+@group(2) @binding(0) var material_textures: binding_array<texture_2d<f32>, MAX_MATERIAL_TEXTURES>;
+#endif
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    var texel = textureSample(base_color_map, base_color_sampler, in.uv);
+#ifdef MATERIAL_TEXTURES
+    // the same for the whole draw, so the index is uniform
+    if (object.texture != NO_TEXTURE) {
+        texel = textureSample(material_textures[object.texture], base_color_sampler, in.uv);
+    }
+#endif
+    let base_color = object.base_color * texel;
+    let color = shade(base_color.rgb, normalize(in.normal), in.world_position, ambient_occlusion(in.pos.xy));
+    return output_color(vec4<f32>(color, base_color.a));
+}
+
+@fragment
+fn fragment_normal(in: VertexOut) -> @location(0) vec4<f32> {
+    return view_normal_depth(in.normal, in.world_position);
+}
+
+// the distance to the light over its range, for a point light's shadow face. the face camera
+// holds the light's position and range, see ShadowMaps
+@fragment
+fn fragment_point_shadow(in: VertexOut) -> @builtin(frag_depth) f32 {
+    return distance(in.world_position, camera.position.xyz) / camera.position.w;
+}
+
+@fragment
+fn fragment_id(in: VertexOut) -> @location(0) u32 {
+    return object.id;
+}
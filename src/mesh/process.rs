@@ -0,0 +1,94 @@
+use glam::{Vec2, Vec3};
+use crate::mesh::Vertex;
+
+// a box and a sphere around the same vertices. the sphere is centered on the box, so it's a
+// little looser than the tightest one but cheap and stable as vertices change
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl Bounds {
+    // a sphere of radius 0 at the origin when there are no vertices
+    pub const EMPTY: Self = Self {
+        min: Vec3::ZERO,
+        max: Vec3::ZERO,
+        center: Vec3::ZERO,
+        radius: 0.0,
+    };
+}
+
+pub fn bounds(vertices: &[Vertex]) -> Bounds {
+    if vertices.is_empty() {
+        return Bounds::EMPTY;
+    }
+    let (min, max) = vertices.iter().fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(min, max), v| {
+        let p = Vec3::from(v.position);
+        (min.min(p), max.max(p))
+    });
+    let center = (min + max) * 0.5;
+    let radius = vertices.iter()
+        .map(|v| Vec3::from(v.position).distance_squared(center))
+        .fold(0.0, f32::max)
+        .sqrt();
+    Bounds { min, max, center, radius }
+}
+
+// weighted by triangle area, so small slivers along an edge don't tip the normal over. vertices
+// only share a normal when they're the same vertex, split ones keep their hard edge
+pub fn smooth_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(vertices[triangle[i] as usize].position));
+        let normal = (b - a).cross(c - a);
+        for &index in triangle {
+            normals[index as usize] += normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normal.normalize_or_zero().to_array();
+    }
+}
+
+// per-vertex tangents from uv 0 the way mikktspace goes about it: each triangle's uv gradients
+// are weighted by the corner angle at the vertex, then made orthogonal to the normal, with the
+// bitangent sign in w so that bitangent = cross(normal, tangent) * w. needs normals first.
+// vertices without usable uvs get some tangent orthogonal to their normal
+pub fn tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|i| &vertices[triangle[i] as usize]);
+        let [p0, p1, p2] = corners.map(|v| Vec3::from(v.position));
+        let [uv0, uv1, uv2] = corners.map(|v| Vec2::from(v.uv));
+        let (edge1, edge2) = (p1 - p0, p2 - p0);
+        let (duv1, duv2) = (uv1 - uv0, uv2 - uv0);
+        let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+        if determinant.abs() <= f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / determinant;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / determinant;
+        let positions = [p0, p1, p2];
+        for (corner, &index) in triangle.iter().enumerate() {
+            let p = positions[corner];
+            let angle = (positions[(corner + 1) % 3] - p).angle_between(positions[(corner + 2) % 3] - p);
+            if angle.is_finite() {
+                tangents[index as usize] += tangent * angle;
+                bitangents[index as usize] += bitangent * angle;
+            }
+        }
+    }
+    for ((vertex, tangent), bitangent) in vertices.iter_mut().zip(tangents).zip(bitangents) {
+        let normal = Vec3::from(vertex.normal).normalize_or_zero();
+        let mut orthogonal = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+        if orthogonal == Vec3::ZERO {
+            orthogonal = if normal == Vec3::ZERO { Vec3::X } else { normal.any_orthonormal_vector() };
+        }
+        let sign = if normal.cross(orthogonal).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+        vertex.tangent = orthogonal.extend(sign).to_array();
+    }
+}
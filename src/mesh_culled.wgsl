@@ -0,0 +1,46 @@
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "color.wgsl"
+#include "culled_object.wgsl"
+
+// indexed by instance, the culling pass puts the object index in first_instance
+@group(1) @binding(0) var<storage, read> objects: array<CulledObject>;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @builtin(instance_index) instance: u32,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) @interpolate(flat) instance: u32,
+}
+
+@vertex
+fn vertex(in: VertexIn) -> VertexOut {
+    let object = objects[in.instance];
+    let world = object.model * vec4<f32>(in.position, 1.0);
+
+    var out: VertexOut;
+    out.pos = camera.view_proj * world;
+    out.world_position = world.xyz;
+    out.normal = (object.normal * vec4<f32>(in.normal, 0.0)).xyz;
+    out.instance = in.instance;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let base_color = objects[in.instance].base_color;
+    let color = shade(base_color.rgb, normalize(in.normal), in.world_position, ambient_occlusion(in.pos.xy));
+    return output_color(vec4<f32>(color, base_color.a));
+}
+
+@fragment
+fn fragment_normal(in: VertexOut) -> @location(0) vec4<f32> {
+    return view_normal_depth(in.normal, in.world_position);
+}
@@ -0,0 +1,665 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::Arc;
+use bytemuck::Zeroable;
+use gltf::animation::util::ReadOutputs;
+use glam::{Mat4, Quat, Vec2, Vec3};
+use wgpu::*;
+use wgpu::util::RenderEncoder;
+use crate::animation::{AnimationClip, AnimationPlayer, Channel, Interpolation, Pose, Property};
+use crate::bindings::LayoutRegistry;
+use crate::context::RenderContext;
+use crate::camera::Camera;
+use crate::embedded;
+use crate::mesh::{process, GpuMesh, Lod, Material, Mesh, MeshPipelines, MorphTarget, ObjectBinding, ObjectUniform, SharedObjects, Vertex};
+use crate::raycast::{Hit, Ray};
+use crate::transform::Transform;
+use crate::vertex_compression::VertexEncoding;
+use crate::world::Entity;
+
+// levels generated for meshes that weren't loaded with any, see Mesh::generate_lods
+pub const GENERATED_LODS: usize = 3;
+// a finer lod is only switched back to once the mesh is this much bigger than where the coarser
+// one took over, so a mesh sitting right at a threshold doesn't flicker between them
+const LOD_HYSTERESIS: f32 = 0.15;
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: Option<String>,
+    pub parent: Option<usize>,
+    pub transform: Transform,
+    pub mesh: Option<usize>,
+    pub skin: Option<usize>,
+    // default morph target weights
+    pub weights: Vec<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Skin {
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Model {
+    pub nodes: Vec<Node>,
+    pub meshes: Vec<Mesh>,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<AnimationClip>,
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    Gltf(gltf::Error),
+    Obj(tobj::LoadError),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::Gltf(error) => error.fmt(f),
+            ModelError::Obj(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+impl From<gltf::Error> for ModelError {
+    fn from(error: gltf::Error) -> Self {
+        ModelError::Gltf(error)
+    }
+}
+
+impl From<tobj::LoadError> for ModelError {
+    fn from(error: tobj::LoadError) -> Self {
+        ModelError::Obj(error)
+    }
+}
+
+impl Model {
+    // .obj through tobj, anything else as gltf. meshes that came without lods get generated ones
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+        let mut model = match extension.as_deref() {
+            Some("obj") => Self::load_obj(path)?,
+            _ => Self::load_gltf(path)?,
+        };
+        model.generate_lods(GENERATED_LODS);
+        Ok(model)
+    }
+
+    pub fn generate_lods(&mut self, levels: usize) {
+        for mesh in &mut self.meshes {
+            mesh.generate_lods(levels);
+        }
+    }
+
+    // every object in the file becomes a mesh under its own root node. materials are ignored
+    pub fn load_obj(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        let (models, _materials) = match embedded::fallback(&path) {
+            // materials are ignored, so there's no need to find them
+            Some(bytes) => tobj::load_obj_buf(&mut &bytes[..], &tobj::GPU_LOAD_OPTIONS, |_| Err(tobj::LoadError::OpenFileFailed))?,
+            None => tobj::load_obj(path.as_ref(), &tobj::GPU_LOAD_OPTIONS)?,
+        };
+        let mut model = Self::default();
+        for (index, object) in models.into_iter().enumerate() {
+            let source = object.mesh;
+            let mut mesh = Mesh {
+                name: Some(object.name.clone()),
+                indices: source.indices,
+                ..Mesh::default()
+            };
+            mesh.vertices = source.positions.chunks_exact(3).enumerate().map(|(i, position)| Vertex {
+                position: [position[0], position[1], position[2]],
+                normal: source.normals.get(i * 3..i * 3 + 3).map_or([0.0; 3], |n| [n[0], n[1], n[2]]),
+                // obj puts v = 0 at the bottom
+                uv: source.texcoords.get(i * 2..i * 2 + 2).map_or([0.0; 2], |uv| [uv[0], 1.0 - uv[1]]),
+                ..Vertex::default()
+            }).collect();
+            if source.normals.is_empty() {
+                mesh.compute_normals();
+            }
+            mesh.compute_tangents();
+            model.nodes.push(Node {
+                name: Some(object.name),
+                parent: None,
+                transform: Transform::IDENTITY,
+                mesh: Some(index),
+                skin: None,
+                weights: Vec::new(),
+            });
+            model.meshes.push(mesh);
+        }
+        Ok(model)
+    }
+
+    pub fn load_gltf(path: impl AsRef<Path>) -> Result<Self, gltf::Error> {
+        // embedded gltf files need their buffers in the file, .glb or data uris
+        let (document, buffers, _images) = match embedded::fallback(&path) {
+            Some(bytes) => gltf::import_slice(bytes)?,
+            None => gltf::import(path)?,
+        };
+        let buffer_data = |buffer: gltf::Buffer| Some(&*buffers[buffer.index()].0);
+
+        let mut nodes: Vec<Node> = document.nodes().map(|node| {
+            let (translation, rotation, scale) = node.transform().decomposed();
+            Node {
+                name: node.name().map(String::from),
+                parent: None,
+                transform: Transform {
+                    translation: Vec3::from(translation),
+                    rotation: Quat::from_array(rotation),
+                    scale: Vec3::from(scale),
+                },
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                skin: node.skin().map(|skin| skin.index()),
+                weights: node.weights()
+                    .or_else(|| node.mesh().and_then(|mesh| mesh.weights()))
+                    .map(Vec::from)
+                    .unwrap_or_default(),
+            }
+        }).collect();
+        for node in document.nodes() {
+            for child in node.children() {
+                nodes[child.index()].parent = Some(node.index());
+            }
+        }
+
+        // all primitives of a glTF mesh are merged into a single mesh
+        let mut meshes: Vec<Mesh> = document.meshes().map(|mesh| {
+            let mut merged = Mesh {
+                name: mesh.name().map(str::to_owned),
+                ..Mesh::default()
+            };
+            for primitive in mesh.primitives() {
+                let reader = primitive.reader(buffer_data);
+                let base = merged.vertices.len() as u32;
+                let positions = match reader.read_positions() {
+                    Some(positions) => positions,
+                    None => continue,
+                };
+                let start = merged.vertices.len();
+                merged.vertices.extend(positions.map(|position| Vertex {
+                    position,
+                    ..Vertex::default()
+                }));
+                let vertices = &mut merged.vertices[start..];
+                let normals = reader.read_normals();
+                let has_normals = normals.is_some();
+                if let Some(normals) = normals {
+                    vertices.iter_mut().zip(normals).for_each(|(v, n)| v.normal = n);
+                }
+                let tangents = reader.read_tangents();
+                let has_tangents = tangents.is_some();
+                if let Some(tangents) = tangents {
+                    vertices.iter_mut().zip(tangents).for_each(|(v, t)| v.tangent = t);
+                }
+                if let Some(uvs) = reader.read_tex_coords(0) {
+                    vertices.iter_mut().zip(uvs.into_f32()).for_each(|(v, uv)| v.uv = uv);
+                }
+                if let Some(joints) = reader.read_joints(0) {
+                    vertices.iter_mut().zip(joints.into_u16()).for_each(|(v, j)| v.joints = j.map(u32::from));
+                }
+                if let Some(weights) = reader.read_weights(0) {
+                    vertices.iter_mut().zip(weights.into_f32()).for_each(|(v, w)| v.weights = w);
+                }
+                let first_index = merged.indices.len();
+                match reader.read_indices() {
+                    Some(indices) => merged.indices.extend(indices.into_u32().map(|i| base + i)),
+                    None => merged.indices.extend(base..merged.vertices.len() as u32),
+                }
+                // the spec asks for flat normals where they're missing, smooth ones look better on
+                // anything exported from an editor
+                if !has_normals || !has_tangents {
+                    let indices: Vec<u32> = merged.indices[first_index..].iter().map(|i| i - base).collect();
+                    let vertices = &mut merged.vertices[start..];
+                    if !has_normals {
+                        process::smooth_normals(vertices, &indices);
+                    }
+                    if !has_tangents {
+                        process::tangents(vertices, &indices);
+                    }
+                }
+
+                // targets are padded with zero offsets for primitives that don't have them
+                let count = merged.vertices.len() - start;
+                for (index, (positions, normals, _tangents)) in reader.read_morph_targets().enumerate() {
+                    if merged.morph_targets.len() <= index {
+                        merged.morph_targets.push(MorphTarget {
+                            positions: vec![[0.0; 3]; start],
+                            normals: vec![[0.0; 3]; start],
+                        });
+                    }
+                    let target = &mut merged.morph_targets[index];
+                    target.positions.extend(positions.into_iter().flatten().chain(std::iter::repeat([0.0; 3])).take(count));
+                    target.normals.extend(normals.into_iter().flatten().chain(std::iter::repeat([0.0; 3])).take(count));
+                }
+                for target in &mut merged.morph_targets {
+                    target.positions.resize(merged.vertices.len(), [0.0; 3]);
+                    target.normals.resize(merged.vertices.len(), [0.0; 3]);
+                }
+            }
+            merged
+        }).collect();
+        merge_named_lods(&mut meshes, &mut nodes);
+
+        let skins = document.skins().map(|skin| {
+            let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+            let inverse_bind_matrices = match skin.reader(buffer_data).read_inverse_bind_matrices() {
+                Some(matrices) => matrices.map(|m| Mat4::from_cols_array_2d(&m)).collect(),
+                None => vec![Mat4::IDENTITY; joints.len()],
+            };
+            Skin { joints, inverse_bind_matrices }
+        }).collect();
+
+        let animations = document.animations().map(|animation| {
+            let mut duration: f32 = 0.0;
+            let channels = animation.channels().filter_map(|channel| {
+                let reader = channel.reader(buffer_data);
+                let times: Vec<f32> = reader.read_inputs()?.collect();
+                if times.is_empty() {
+                    return None;
+                }
+                let (property, values): (Property, Vec<f32>) = match reader.read_outputs()? {
+                    ReadOutputs::Translations(t) => (Property::Translation, t.flatten().collect()),
+                    ReadOutputs::Rotations(r) => (Property::Rotation, r.into_f32().flatten().collect()),
+                    ReadOutputs::Scales(s) => (Property::Scale, s.flatten().collect()),
+                    ReadOutputs::MorphTargetWeights(w) => (Property::MorphWeights, w.into_f32().collect()),
+                };
+                let interpolation = match channel.sampler().interpolation() {
+                    gltf::animation::Interpolation::Step => Interpolation::Step,
+                    gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                    gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                };
+                duration = duration.max(times.last().copied().unwrap_or(0.0));
+                Some(Channel {
+                    node: channel.target().node().index(),
+                    property,
+                    interpolation,
+                    times,
+                    values,
+                })
+            }).collect();
+            AnimationClip {
+                name: animation.name().map(String::from),
+                duration,
+                channels,
+            }
+        }).collect();
+
+        Ok(Self {
+            nodes,
+            meshes,
+            skins,
+            animations,
+        })
+    }
+
+    // a single unskinned node, used for procedural meshes
+    pub fn from_mesh(mesh: Mesh) -> Self {
+        Self {
+            nodes: vec![Node {
+                name: None,
+                parent: None,
+                transform: Transform::IDENTITY,
+                mesh: Some(0),
+                skin: None,
+                weights: Vec::new(),
+            }],
+            meshes: vec![mesh],
+            ..Self::default()
+        }
+    }
+
+    pub fn rest_pose(&self) -> Pose {
+        Pose {
+            transforms: self.nodes.iter().map(|node| node.transform).collect(),
+            weights: self.nodes.iter().map(|node| {
+                let target_count = node.mesh.map_or(0, |mesh| self.meshes[mesh].morph_targets.len());
+                let mut weights = node.weights.clone();
+                weights.resize(target_count, 0.0);
+                weights
+            }).collect(),
+        }
+    }
+
+    pub fn global_transforms(&self, pose: &[Transform]) -> Vec<Mat4> {
+        fn resolve(model: &Model, pose: &[Transform], globals: &mut [Option<Mat4>], index: usize) -> Mat4 {
+            if let Some(global) = globals[index] {
+                return global;
+            }
+            let local = pose[index].matrix();
+            let global = match model.nodes[index].parent {
+                Some(parent) => resolve(model, pose, globals, parent) * local,
+                None => local,
+            };
+            globals[index] = Some(global);
+            global
+        }
+
+        let mut globals = vec![None; self.nodes.len()];
+        (0..self.nodes.len()).map(|index| resolve(self, pose, &mut globals, index)).collect()
+    }
+
+    pub fn joint_matrices(&self, skin: usize, globals: &[Mat4]) -> Vec<Mat4> {
+        let skin = &self.skins[skin];
+        skin.joints.iter().zip(&skin.inverse_bind_matrices)
+            .map(|(&joint, inverse_bind)| globals[joint] * *inverse_bind)
+            .collect()
+    }
+
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        let globals = self.global_transforms(&self.rest_pose().transforms);
+        let mut bounds = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+        for (node, global) in self.nodes.iter().zip(&globals) {
+            let mesh = match node.mesh {
+                Some(mesh) => &self.meshes[mesh],
+                None => continue,
+            };
+            // skinned vertices are already in model space
+            let transform = if node.skin.is_some() { Mat4::IDENTITY } else { *global };
+            for vertex in &mesh.vertices {
+                let p = transform.transform_point3(Vec3::from(vertex.position));
+                bounds = (bounds.0.min(p), bounds.1.max(p));
+            }
+        }
+        bounds
+    }
+}
+
+// meshes named like "rock_LOD1" become lods of "rock" or "rock_LOD0", the usual export naming.
+// the nodes that showed them are left without a mesh
+fn merge_named_lods(meshes: &mut [Mesh], nodes: &mut [Node]) {
+    let level = |name: &str| {
+        let (base, level) = name.rsplit_once("_LOD")?;
+        Some((base.to_owned(), level.parse::<usize>().ok()?))
+    };
+    let mut lods = Vec::new();
+    for (index, mesh) in meshes.iter().enumerate() {
+        let Some((base, lod_level)) = mesh.name.as_deref().and_then(level).filter(|(_, level)| *level > 0) else {
+            continue;
+        };
+        let base_mesh = meshes.iter().position(|mesh| {
+            mesh.name.as_deref().is_some_and(|name| name == base || level(name) == Some((base.clone(), 0)))
+        });
+        if let Some(base_mesh) = base_mesh {
+            lods.push((base_mesh, lod_level, index));
+        }
+    }
+    lods.sort_unstable();
+    for &(base, lod_level, index) in &lods {
+        let lod = meshes[index].clone();
+        meshes[base].add_lod(&lod, Lod::default_screen_size(lod_level));
+    }
+    for node in nodes {
+        if node.mesh.is_some_and(|mesh| lods.iter().any(|&(_, _, index)| index == mesh)) {
+            node.mesh = None;
+        }
+    }
+}
+
+// steps to coarser lods while the size is below their threshold, and back to finer ones once it's
+// clearly above theirs. 0 is the base mesh, lods[0] is level 1
+fn select_lod(current: usize, size: f32, lods: &[Lod]) -> usize {
+    let mut lod = current.min(lods.len());
+    while lod < lods.len() && size < lods[lod].screen_size {
+        lod += 1;
+    }
+    while lod > 0 && size > lods[lod - 1].screen_size * (1.0 + LOD_HYSTERESIS) {
+        lod -= 1;
+    }
+    lod
+}
+
+struct NodeDraw {
+    node: usize,
+    mesh: usize,
+    // None draws through SharedObjects, at slot
+    binding: Option<ObjectBinding>,
+    uniform: ObjectUniform,
+    slot: u32,
+    // 0 is the base mesh, see select_lods
+    lod: usize,
+    // as of the last update
+    model: Mat4,
+}
+
+// the gpu side of an entity with a MeshRef, kept in sync by the renderer
+pub struct ModelInstance {
+    pub model: Arc<Model>,
+    // copied from the entity's components on every update
+    pub transform: Mat4,
+    pub material: Material,
+    // written into the id buffer when picking, 0 isn't pickable
+    pub pick_id: u32,
+    // the camera entity whose render target is bound as the texture, see set_texture
+    pub screen: Option<Entity>,
+    // the material texture bound the same way, when they can't be bound as an array
+    pub texture: Option<u32>,
+    bounds: (Vec3, Vec3),
+    encoding: VertexEncoding,
+    meshes: Vec<GpuMesh>,
+    draws: Vec<NodeDraw>,
+}
+
+impl ModelInstance {
+    pub fn new(context: &RenderContext, layouts: &LayoutRegistry, model: Arc<Model>, encoding: VertexEncoding, texture: &TextureView, sampler: &Sampler) -> Self {
+        let meshes: Vec<GpuMesh> = model.meshes.iter().map(|mesh| mesh.upload(context, layouts, encoding)).collect();
+        let draws = model.nodes.iter().enumerate().filter_map(|(index, node)| {
+            let mesh = node.mesh?;
+            let max_joints = node.skin.map_or(0, |skin| model.skins[skin].joints.len());
+            let shared = node.skin.is_none() && meshes[mesh].morph_target_count == 0;
+            Some(NodeDraw {
+                node: index,
+                mesh,
+                binding: (!shared).then(|| ObjectBinding::new(context, layouts, &meshes[mesh], max_joints, texture, sampler)),
+                uniform: ObjectUniform::zeroed(),
+                slot: 0,
+                model: Mat4::IDENTITY,
+                lod: 0,
+            })
+        }).collect();
+        Self {
+            bounds: model.bounds(),
+            encoding,
+            model,
+            transform: Mat4::IDENTITY,
+            material: Material::default(),
+            pick_id: 0,
+            screen: None,
+            texture: None,
+            meshes,
+            draws,
+        }
+    }
+
+    // what every mesh was uploaded as
+    pub fn encoding(&self) -> VertexEncoding {
+        self.encoding
+    }
+
+    // rest pose bounds in model space, see `transform` for placement
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        self.bounds
+    }
+
+    // tests against the rest pose, so animation and morphing are ignored
+    pub fn raycast(&self, ray: &Ray) -> Option<Hit> {
+        let local = ray.transform(self.transform.inverse());
+        local.intersect_aabb(self.bounds.0, self.bounds.1)?;
+
+        let globals = self.model.global_transforms(&self.model.rest_pose().transforms);
+        let mut closest: Option<Hit> = None;
+        for draw in &self.draws {
+            let transform = match self.model.nodes[draw.node].skin {
+                Some(_) => Mat4::IDENTITY,
+                None => globals[draw.node],
+            };
+            let node_ray = local.transform(transform.inverse());
+            let (min, max) = self.model.meshes[draw.mesh].bounds();
+            match node_ray.intersect_aabb(min, max) {
+                Some(distance) if closest.is_none_or(|hit| distance < hit.distance) => {}
+                _ => continue,
+            }
+
+            let mesh = &self.model.meshes[draw.mesh];
+            for (triangle, indices) in mesh.indices.chunks_exact(3).enumerate() {
+                let [a, b, c] = [0, 1, 2].map(|i| &mesh.vertices[indices[i] as usize]);
+                let hit = node_ray.intersect_triangle(a.position.into(), b.position.into(), c.position.into());
+                let (distance, barycentric) = match hit {
+                    Some(hit) if closest.is_none_or(|closest| hit.0 < closest.distance) => hit,
+                    _ => continue,
+                };
+                let uv = Vec2::from(a.uv) * (1.0 - barycentric.x - barycentric.y)
+                    + Vec2::from(b.uv) * barycentric.x
+                    + Vec2::from(c.uv) * barycentric.y;
+                closest = Some(Hit {
+                    distance,
+                    position: ray.at(distance),
+                    uv,
+                    node: draw.node,
+                    triangle,
+                });
+            }
+        }
+        closest
+    }
+
+    // poses the model where the player is, without a player it stays in its rest pose
+    pub fn update(&mut self, context: &RenderContext, player: Option<&AnimationPlayer>) {
+        let rest_pose = self.model.rest_pose();
+        let pose = match player {
+            Some(player) => player.pose(&self.model.animations, &rest_pose),
+            None => rest_pose,
+        };
+        let globals = self.model.global_transforms(&pose.transforms);
+        for draw in &mut self.draws {
+            let mesh = &self.meshes[draw.mesh];
+            let weights = &pose.weights[draw.node];
+            // joint matrices already contain the node hierarchy, so only the instance transform applies
+            let (model, joints) = match self.model.nodes[draw.node].skin {
+                Some(skin) => (self.transform, self.model.joint_matrices(skin, &globals)),
+                None => (self.transform * globals[draw.node], Vec::new()),
+            };
+            // a screen shows its render target rather than the material's texture
+            let material = Material { texture: self.material.texture.filter(|_| self.screen.is_none()), ..self.material };
+            let uniform = ObjectUniform::new(model, joints.len() as u32, mesh, self.pick_id, &material);
+            if let Some(binding) = &draw.binding {
+                binding.update(context, &uniform, &joints, weights);
+            }
+            draw.uniform = uniform;
+            draw.model = model;
+        }
+    }
+
+    // the same texture for every mesh in the model. shared meshes get a binding of their own,
+    // written on the next update
+    pub fn set_texture(&mut self, context: &RenderContext, layouts: &LayoutRegistry, texture: &TextureView, sampler: &Sampler) {
+        for draw in &mut self.draws {
+            let mesh = &self.meshes[draw.mesh];
+            match &mut draw.binding {
+                Some(binding) => binding.set_texture(context, layouts, mesh, texture, sampler),
+                None => draw.binding = Some(ObjectBinding::new(context, layouts, mesh, 0, texture, sampler)),
+            }
+        }
+    }
+
+    // hands out the next slots to meshes drawn through SharedObjects, and adds their uniforms as
+    // of the last update
+    pub fn assign_shared_slots(&mut self, uniforms: &mut Vec<ObjectUniform>) {
+        for draw in self.draws.iter_mut().filter(|draw| draw.binding.is_none()) {
+            draw.slot = uniforms.len() as u32;
+            uniforms.push(draw.uniform);
+        }
+    }
+
+    // what draw issues, one per mesh node
+    pub fn draw_count(&self) -> u32 {
+        self.draws.len() as u32
+    }
+
+    // what draw_dynamic issues
+    pub fn dynamic_draw_count(&self) -> u32 {
+        self.draws.iter().filter(|draw| !self.is_static(draw)).count() as u32
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, shared: &'a SharedObjects, pipelines: MeshPipelines<'a>) {
+        for draw in &self.draws {
+            self.draw_node(render_cmd, shared, pipelines, draw);
+        }
+    }
+
+    fn draw_node<'a>(&'a self, render_cmd: &mut impl RenderEncoder<'a>, shared: &'a SharedObjects, pipelines: MeshPipelines<'a>, draw: &'a NodeDraw) {
+        match &draw.binding {
+            Some(binding) => binding.bind(render_cmd),
+            None => shared.bind(render_cmd, draw.slot),
+        }
+        self.meshes[draw.mesh].draw(render_cmd, pipelines, draw.lod);
+    }
+
+    // what the meshes take up on the gpu, see VertexEncoding
+    pub fn vertex_bytes(&self) -> usize {
+        self.meshes.iter().map(GpuMesh::vertex_bytes).sum()
+    }
+
+    // meshes that are neither skinned nor morphed and show no screen or texture only need a transform, so
+    // gpu culling can draw them, see GpuCulling. yields the mesh index, lod and transform
+    pub fn static_draws(&self) -> impl Iterator<Item = (usize, usize, Mat4)> + '_ {
+        self.draws.iter()
+            .filter(|draw| self.is_static(draw))
+            .map(|draw| (draw.mesh, draw.lod, draw.model))
+    }
+
+    // from how much of the screen height each mesh's bounds cover as seen by the camera. a bias
+    // above 1 keeps detail for longer, 0 or less draws the base meshes
+    pub fn select_lods(&mut self, camera: &Camera, bias: f32) {
+        let tan = (camera.fovy * 0.5).tan();
+        for draw in &mut self.draws {
+            let lods = &self.model.meshes[draw.mesh].lods;
+            if bias <= 0.0 || lods.is_empty() {
+                draw.lod = 0;
+                continue;
+            }
+            let bounds = self.model.meshes[draw.mesh].bounding();
+            let center = draw.model.transform_point3(bounds.center);
+            let (scale, _, _) = draw.model.to_scale_rotation_translation();
+            let radius = bounds.radius * scale.abs().max_element();
+            let distance = (center - camera.eye).length().max(f32::EPSILON);
+            draw.lod = select_lod(draw.lod, radius / (distance * tan) * bias, lods);
+        }
+    }
+
+    // the lod each mesh drew with, as of the last select_lods
+    pub fn lods(&self) -> impl Iterator<Item = usize> + '_ {
+        self.draws.iter().map(|draw| draw.lod)
+    }
+
+    // everything static_draws leaves out
+    pub fn draw_dynamic<'a>(&'a self, render_cmd: &mut RenderPass<'a>, shared: &'a SharedObjects, pipelines: MeshPipelines<'a>) {
+        for draw in self.draws.iter().filter(|draw| !self.is_static(draw)) {
+            self.draw_node(render_cmd, shared, pipelines, draw);
+        }
+    }
+
+    // what draw_dynamic leaves out, e.g. into a render bundle, see StaticBundles
+    pub fn draw_static<'a>(&'a self, render_cmd: &mut impl RenderEncoder<'a>, shared: &'a SharedObjects, pipelines: MeshPipelines<'a>) {
+        for draw in self.draws.iter().filter(|draw| self.is_static(draw)) {
+            self.draw_node(render_cmd, shared, pipelines, draw);
+        }
+    }
+
+    // what draw_static records, without the transforms, which are read from buffers when drawn
+    pub fn hash_static_draws(&self, hasher: &mut impl Hasher) {
+        Arc::as_ptr(&self.model).hash(hasher);
+        for draw in self.draws.iter().filter(|draw| self.is_static(draw)) {
+            (draw.mesh, draw.lod, draw.slot, draw.binding.is_some(), self.meshes[draw.mesh].encoding).hash(hasher);
+        }
+    }
+
+    fn is_static(&self, draw: &NodeDraw) -> bool {
+        self.screen.is_none() && self.material.texture.is_none() && self.model.nodes[draw.node].skin.is_none() && self.meshes[draw.mesh].morph_target_count == 0
+    }
+}
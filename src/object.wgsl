@@ -0,0 +1,175 @@
+#include "camera.wgsl"
+
+// the object bind group and vertex transform, shared by mesh.wgsl and outline.wgsl
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    joint_count: u32,
+    morph_target_count: u32,
+    vertex_count: u32,
+    id: u32,
+    base_color: vec4<f32>,
+    texture: u32,
+}
+
+struct MorphDelta {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+}
+
+@group(1) @binding(0) var<uniform> object: Object;
+@group(1) @binding(1) var<storage, read> joints: array<mat4x4<f32>>;
+@group(1) @binding(2) var<storage, read> morph_deltas: array<MorphDelta>;
+@group(1) @binding(3) var<storage, read> morph_weights: array<f32>;
+// multiplies the base color, white unless the object is a screen
+@group(1) @binding(4) var base_color_map: texture_2d<f32>;
+@group(1) @binding(5) var base_color_sampler: sampler;
+// Vertex in mesh.rs as plain words, for vertex_pulled. CompressedVertex with COMPRESSED_VERTICES
+@group(3) @binding(0) var<storage, read> vertex_words: array<u32>;
+
+// QuantizationUniform in vertex_compression.rs, only read with COMPRESSED_VERTICES
+struct Quantization {
+    min: vec4<f32>,
+    extent: vec4<f32>,
+}
+
+@group(3) @binding(1) var<uniform> quantization: Quantization;
+
+struct Attributes {
+    position: vec3<f32>,
+    normal: vec3<f32>,
+    uv: vec2<f32>,
+    joints: vec4<u32>,
+    weights: vec4<f32>,
+}
+
+#ifdef COMPRESSED_VERTICES
+// CompressedVertex.LAYOUT, position in 0..1 of the bounds with the bitangent sign in w
+struct VertexIn {
+    @location(0) position: vec4<f32>,
+    @location(1) normal: vec2<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) joints: vec4<u32>,
+    @location(4) weights: vec4<f32>,
+    @location(5) tangent: vec2<f32>,
+    @builtin(vertex_index) index: u32,
+}
+#else
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) joints: vec4<u32>,
+    @location(4) weights: vec4<f32>,
+    @location(5) tangent: vec4<f32>,
+    @builtin(vertex_index) index: u32,
+}
+#endif
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+}
+
+// morphs, skins and projects a vertex however it was read
+fn transform_vertex(in: Attributes, index: u32) -> VertexOut {
+    var position = in.position;
+    var normal = in.normal;
+    for (var i = 0u; i < object.morph_target_count; i = i + 1u) {
+        let weight = morph_weights[i];
+        if (weight != 0.0) {
+            let delta = morph_deltas[i * object.vertex_count + index];
+            position = position + delta.position.xyz * weight;
+            normal = normal + delta.normal.xyz * weight;
+        }
+    }
+
+    var skin = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    if (object.joint_count > 0u) {
+        skin = joints[in.joints.x] * in.weights.x
+            + joints[in.joints.y] * in.weights.y
+            + joints[in.joints.z] * in.weights.z
+            + joints[in.joints.w] * in.weights.w;
+    }
+
+    let world = object.model * skin * vec4<f32>(position, 1.0);
+    let world_normal = object.normal * skin * vec4<f32>(normal, 0.0);
+
+    var out: VertexOut;
+    out.pos = camera.view_proj * world;
+    out.world_position = world.xyz;
+    out.normal = world_normal.xyz;
+    out.uv = in.uv;
+    return out;
+}
+
+// undoes octahedral in vertex_compression.rs
+fn octahedral_decode(encoded: vec2<f32>) -> vec3<f32> {
+    var n = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let t = max(-n.z, 0.0);
+    n.x = n.x + select(t, -t, n.x >= 0.0);
+    n.y = n.y + select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
+
+fn dequantize(position: vec3<f32>) -> vec3<f32> {
+    return quantization.min.xyz + position * quantization.extent.xyz;
+}
+
+fn read_attributes(in: VertexIn) -> Attributes {
+#ifdef COMPRESSED_VERTICES
+    return Attributes(dequantize(in.position.xyz), octahedral_decode(in.normal), in.uv, in.joints, in.weights);
+#else
+    return Attributes(in.position, in.normal, in.uv, in.joints, in.weights);
+#endif
+}
+
+@vertex
+fn vertex(in: VertexIn) -> VertexOut {
+    return transform_vertex(read_attributes(in), in.index);
+}
+
+fn read_f32(word: u32) -> f32 {
+    return bitcast<f32>(vertex_words[word]);
+}
+
+fn read_vec3(word: u32) -> vec3<f32> {
+    return vec3<f32>(read_f32(word), read_f32(word + 1u), read_f32(word + 2u));
+}
+
+// no vertex buffers, the index picks the vertex out of the storage buffer
+#ifdef COMPRESSED_VERTICES
+@vertex
+fn vertex_pulled(@builtin(vertex_index) index: u32) -> VertexOut {
+    let word = index * u32(COMPRESSED_VERTEX_WORDS);
+    let position = vec3<f32>(unpack2x16unorm(vertex_words[word]), unpack2x16unorm(vertex_words[word + 1u]).x);
+    let joints_xy = vertex_words[word + 5u];
+    let joints_zw = vertex_words[word + 6u];
+    var attributes: Attributes;
+    attributes.position = dequantize(position);
+    attributes.normal = octahedral_decode(unpack2x16snorm(vertex_words[word + 2u]));
+    attributes.uv = unpack2x16float(vertex_words[word + 3u]);
+    attributes.joints = vec4<u32>(joints_xy & 0xffffu, joints_xy >> 16u, joints_zw & 0xffffu, joints_zw >> 16u);
+    attributes.weights = unpack4x8unorm(vertex_words[word + 7u]);
+    return transform_vertex(attributes, index);
+}
+#else
+@vertex
+fn vertex_pulled(@builtin(vertex_index) index: u32) -> VertexOut {
+    let word = index * u32(VERTEX_WORDS);
+    var attributes: Attributes;
+    attributes.position = read_vec3(word);
+    attributes.normal = read_vec3(word + 3u);
+    attributes.uv = vec2<f32>(read_f32(word + 6u), read_f32(word + 7u));
+    attributes.joints = vec4<u32>(vertex_words[word + 8u], vertex_words[word + 9u], vertex_words[word + 10u], vertex_words[word + 11u]);
+    attributes.weights = vec4<f32>(read_vec3(word + 12u), read_f32(word + 15u));
+    return transform_vertex(attributes, index);
+}
+#endif
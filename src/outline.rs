@@ -0,0 +1,195 @@
+use std::mem::size_of;
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::{CameraBinding, FRAME_LAYOUT};
+use crate::context::RenderContext;
+use crate::mesh::{MeshPipelineIds, SharedObjects, Vertex, OBJECT_LAYOUT, VERTICES_LAYOUT};
+use crate::model::ModelInstance;
+use crate::pipeline_cache::{DepthKey, PipelineCache, PipelineKey};
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+pub const OUTLINE_LAYOUT: &str = "outline";
+
+#[derive(Copy, Clone, Debug)]
+pub struct OutlineSettings {
+    pub enabled: bool,
+    // linear, before tonemapping
+    pub color: [f32; 4],
+    // in pixels of the scene target
+    pub width: f32,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            color: [1.0, 0.6, 0.05, 1.0],
+            width: 3.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct OutlineUniform {
+    color: [f32; 4],
+    viewport_size: [f32; 2],
+    width: f32,
+    _padding: f32,
+}
+
+const OUTLINE_UNIFORM_SIZE: BufferAddress = size_of::<OutlineUniform>() as BufferAddress;
+
+// selection outlines in two draws per object: the first marks the object's pixels in the
+// stencil buffer without writing color, the second draws it again pushed out along its normals,
+// only where the stencil isn't marked. both ignore depth, so outlines show through whatever
+// covers the object
+pub struct Outline {
+    mark_pipeline: MeshPipelineIds,
+    outline_pipeline: MeshPipelineIds,
+    // one uniform per view, picked by dynamic offset
+    buffer: Buffer,
+    bind_group: BindGroup,
+    stride: BufferAddress,
+    capacity: u32,
+}
+
+impl Outline {
+    // after mesh::register_pipeline, the object layouts are shared
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, cache: &mut PipelineCache) -> Self {
+        layouts.register(context, OUTLINE_LAYOUT, &[
+            (Binding::DynamicUniform { size: OUTLINE_UNIFORM_SIZE }, ShaderStages::VERTEX | ShaderStages::FRAGMENT),
+        ]);
+        let preprocessor = Preprocessor::new().target(HDR_FORMAT);
+        cache.add_shader("outline", preprocessor.create_module(context, "outline.wgsl"));
+        cache.add_shader("outline_compressed", preprocessor.define("COMPRESSED_VERTICES", 1).create_module(context, "outline.wgsl"));
+        cache.add_layout("outline", context.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("outline"),
+            bind_group_layouts: &[
+                layouts.get(FRAME_LAYOUT),
+                layouts.get(OBJECT_LAYOUT),
+                layouts.get(OUTLINE_LAYOUT),
+                layouts.get(VERTICES_LAYOUT),
+            ],
+            push_constant_ranges: &[],
+        }));
+
+        let mark = StencilFaceState {
+            compare: CompareFunction::Always,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op: StencilOperation::Replace,
+        };
+        let mark_pipeline = MeshPipelineIds::get_or_create(context, cache, pipeline_key("vertex", ColorWrites::empty(), mark, 0xff), "outline_compressed");
+        let unmarked = StencilFaceState {
+            compare: CompareFunction::NotEqual,
+            ..StencilFaceState::IGNORE
+        };
+        let outline_pipeline = MeshPipelineIds::get_or_create(context, cache, pipeline_key("vertex_outline", ColorWrites::ALL, unmarked, 0), "outline_compressed");
+
+        let alignment = context.limits().min_uniform_buffer_offset_alignment as BufferAddress;
+        let stride = OUTLINE_UNIFORM_SIZE.next_multiple_of(alignment);
+        let capacity = 4;
+        let buffer = create_buffer(context, stride, capacity);
+        let bind_group = create_bind_group(context, layouts, &buffer);
+        Self {
+            mark_pipeline,
+            outline_pipeline,
+            buffer,
+            bind_group,
+            stride,
+            capacity,
+        }
+    }
+
+    // sizes are each view's viewport in pixels, in the order the views are drawn
+    pub fn update(&mut self, context: &RenderContext, layouts: &LayoutRegistry, settings: &OutlineSettings, sizes: &[(u32, u32)]) {
+        if sizes.len() as u32 > self.capacity {
+            self.capacity = (sizes.len() as u32).next_power_of_two();
+            self.buffer = create_buffer(context, self.stride, self.capacity);
+            self.bind_group = create_bind_group(context, layouts, &self.buffer);
+        }
+        let mut data = vec![0; sizes.len() * self.stride as usize];
+        for (slice, &(width, height)) in data.chunks_exact_mut(self.stride as usize).zip(sizes) {
+            let uniform = OutlineUniform {
+                color: settings.color,
+                viewport_size: [width as f32, height as f32],
+                width: settings.width,
+                _padding: 0.0,
+            };
+            slice[..OUTLINE_UNIFORM_SIZE as usize].copy_from_slice(bytemuck::bytes_of(&uniform));
+        }
+        context.queue.write_buffer(&self.buffer, 0, &data);
+    }
+
+    // each view marks its own stencil value, so a view drawn over another isn't masked by the
+    // marks left below it. the depth attachment has to have been cleared with a stencil of 0
+    pub fn draw<'a>(
+        &'a self,
+        render_cmd: &mut RenderPass<'a>,
+        cache: &'a PipelineCache,
+        camera_binding: &'a CameraBinding,
+        view: usize,
+        selected: &[&'a ModelInstance],
+        shared: &'a SharedObjects,
+    ) {
+        if selected.is_empty() {
+            return;
+        }
+        render_cmd.set_stencil_reference(view as u32 % 255 + 1);
+        render_cmd.set_bind_group(0, &camera_binding.bind_group, &[]);
+        render_cmd.set_bind_group(2, &self.bind_group, &[(view as BufferAddress * self.stride) as DynamicOffset]);
+        for pipelines in [self.mark_pipeline, self.outline_pipeline] {
+            for instance in selected {
+                instance.draw(render_cmd, shared, pipelines.get(cache));
+            }
+        }
+    }
+}
+
+fn pipeline_key(vertex_entry: &'static str, write_mask: ColorWrites, face: StencilFaceState, stencil_write_mask: u32) -> PipelineKey {
+    PipelineKey {
+        shader: "outline",
+        layout: "outline",
+        vertex_entry,
+        fragment_entry: Some("fragment_outline"),
+        vertex_layouts: vec![(&Vertex::LAYOUT).into()],
+        targets: vec![ColorTargetState {
+            format: HDR_FORMAT,
+            blend: Some(BlendState::ALPHA_BLENDING),
+            write_mask,
+        }],
+        primitive: PrimitiveState {
+            cull_mode: Some(Face::Back),
+            ..PrimitiveState::default()
+        },
+        depth: Some(DepthKey {
+            format: DEPTH_FORMAT,
+            write_enabled: false,
+            compare: CompareFunction::Always,
+            stencil: StencilState {
+                front: face,
+                back: face,
+                read_mask: 0xff,
+                write_mask: stencil_write_mask,
+            },
+        }),
+        multisample: MultisampleState::default(),
+    }
+}
+
+fn create_buffer(context: &RenderContext, stride: BufferAddress, capacity: u32) -> Buffer {
+    context.device.create_buffer(&BufferDescriptor {
+        label: Some("outline"),
+        size: stride * capacity as BufferAddress,
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_bind_group(context: &RenderContext, layouts: &LayoutRegistry, buffer: &Buffer) -> BindGroup {
+    BindGroupBuilder::new()
+        .buffer(buffer)
+        .build(context, layouts, OUTLINE_LAYOUT)
+}
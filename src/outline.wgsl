@@ -0,0 +1,29 @@
+#include "camera.wgsl"
+#include "object.wgsl"
+
+struct Outline {
+    color: vec4<f32>,
+    viewport_size: vec2<f32>,
+    // in pixels
+    width: f32,
+}
+
+@group(2) @binding(0) var<uniform> outline: Outline;
+
+// pushes the vertex out along its normal in screen space, so the outline is as wide at any distance
+@vertex
+fn vertex_outline(in: VertexIn) -> VertexOut {
+    var out = transform_vertex(read_attributes(in), in.index);
+    let clip_normal = (camera.view_proj * vec4<f32>(out.normal, 0.0)).xy;
+    if (length(clip_normal) > 0.0) {
+        let offset = normalize(clip_normal) * outline.width * 2.0 / outline.viewport_size;
+        out.pos = vec4<f32>(out.pos.xy + offset * out.pos.w, out.pos.zw);
+    }
+    return out;
+}
+
+// the stencil marking pass writes no color, so it can share this
+@fragment
+fn fragment_outline(in: VertexOut) -> @location(0) vec4<f32> {
+    return outline.color;
+}
@@ -0,0 +1,208 @@
+use std::mem::size_of;
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::color_grading::{ColorLut, LutData, DEFAULT_LUT_SIZE};
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+
+pub const OUTPUT_LAYOUT: &str = "output";
+pub const OUTPUT_LUT_LAYOUT: &str = "output lut";
+// what captures are rendered to when the surface is hdr, readback only handles 8 bit formats
+pub const CAPTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Antialiasing {
+    #[default]
+    None,
+    // a post-process on the final image, cheap and works whatever the scene was drawn with
+    Fxaa,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct OutputSettings {
+    // scales the scene before anything else
+    pub exposure: f32,
+    // hdr only, the nits that a scene value of 1.0 is shown at
+    pub paper_white: f32,
+    // hdr only, the display's peak brightness
+    pub max_nits: f32,
+    pub antialiasing: Antialiasing,
+    // sdr only, how much of the color lut set with OutputPass::set_lut is applied, 0 to 1
+    pub grading: f32,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            paper_white: 200.0,
+            max_nits: 1000.0,
+            antialiasing: Antialiasing::None,
+            grading: 1.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct OutputUniform {
+    exposure: f32,
+    paper_white: f32,
+    max_nits: f32,
+    grading: f32,
+}
+
+// the last pass of a frame: maps the linear hdr scene to the surface. hdr surfaces get it scaled
+// to nits, sdr ones get it tonemapped and then color graded through a 3d lut
+pub struct OutputPass {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    sampler: Sampler,
+    // an identity lut until set_lut is given one
+    lut: ColorLut,
+    lut_bind_group: BindGroup,
+    pipelines: Vec<(TextureFormat, Antialiasing, RenderPipeline)>,
+}
+
+impl OutputPass {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, scene: &TextureView) -> Self {
+        let buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("output"),
+            size: size_of::<OutputUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, OUTPUT_LAYOUT, &[
+            (Binding::Texture, ShaderStages::FRAGMENT),
+            (Binding::Uniform, ShaderStages::FRAGMENT),
+            (Binding::Sampler, ShaderStages::FRAGMENT),
+        ]);
+        layouts.register(context, OUTPUT_LUT_LAYOUT, &[
+            (Binding::Texture3d, ShaderStages::FRAGMENT),
+        ]);
+        // filters when the scene is scaled, and fxaa samples between texels
+        let sampler = context.device.create_sampler(&SamplerDescriptor {
+            label: Some("output"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        let bind_group = create_bind_group(context, layouts, scene, &buffer, &sampler);
+        let lut = ColorLut::new(context, "identity lut", &LutData::identity(DEFAULT_LUT_SIZE));
+        let lut_bind_group = create_lut_bind_group(context, layouts, &lut);
+
+        let mut formats = vec![context.format];
+        if context.is_hdr() {
+            formats.push(CAPTURE_FORMAT);
+        }
+        let pipelines = formats.into_iter()
+            .flat_map(|format| [Antialiasing::None, Antialiasing::Fxaa].map(|antialiasing| {
+                (format, antialiasing, create_pipeline(context, layouts, format, antialiasing))
+            }))
+            .collect();
+        Self {
+            buffer,
+            bind_group,
+            sampler,
+            lut,
+            lut_bind_group,
+            pipelines,
+        }
+    }
+
+    // the scene target is recreated on resize
+    pub fn set_scene(&mut self, context: &RenderContext, layouts: &LayoutRegistry, scene: &TextureView) {
+        self.bind_group = create_bind_group(context, layouts, scene, &self.buffer, &self.sampler);
+    }
+
+    // switches what sdr output is graded with, None goes back to the identity lut
+    pub fn set_lut(&mut self, context: &RenderContext, layouts: &LayoutRegistry, lut: Option<ColorLut>) {
+        self.lut = lut.unwrap_or_else(|| ColorLut::new(context, "identity lut", &LutData::identity(DEFAULT_LUT_SIZE)));
+        self.lut_bind_group = create_lut_bind_group(context, layouts, &self.lut);
+    }
+
+    pub fn update(&self, context: &RenderContext, settings: &OutputSettings) {
+        let uniform = OutputUniform {
+            exposure: settings.exposure,
+            paper_white: settings.paper_white,
+            max_nits: settings.max_nits,
+            grading: settings.grading.clamp(0.0, 1.0),
+        };
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    // format is the target's, either the surface format or CAPTURE_FORMAT
+    pub fn draw(&self, cmd: &mut CommandEncoder, target: &TextureView, format: TextureFormat, antialiasing: Antialiasing) {
+        let pipeline = self.pipelines.iter()
+            .find(|(pipeline_format, pipeline_antialiasing, _)| *pipeline_format == format && *pipeline_antialiasing == antialiasing)
+            .map(|(_, _, pipeline)| pipeline)
+            .unwrap_or_else(|| panic!("no output pipeline for {format:?}"));
+        let mut render_cmd = cmd.begin_render_pass(&RenderPassDescriptor {
+            label: Some("output"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                    view: target,
+                    resolve_target: None,
+                })
+            ],
+            depth_stencil_attachment: None,
+        });
+        render_cmd.set_pipeline(pipeline);
+        render_cmd.set_bind_group(0, &self.bind_group, &[]);
+        render_cmd.set_bind_group(1, &self.lut_bind_group, &[]);
+        render_cmd.draw(0..3, 0..1);
+    }
+}
+
+fn create_bind_group(context: &RenderContext, layouts: &LayoutRegistry, scene: &TextureView, buffer: &Buffer, sampler: &Sampler) -> BindGroup {
+    BindGroupBuilder::new()
+        .texture(scene)
+        .buffer(buffer)
+        .sampler(sampler)
+        .build(context, layouts, OUTPUT_LAYOUT)
+}
+
+fn create_lut_bind_group(context: &RenderContext, layouts: &LayoutRegistry, lut: &ColorLut) -> BindGroup {
+    BindGroupBuilder::new()
+        .texture(&lut.view)
+        .build(context, layouts, OUTPUT_LUT_LAYOUT)
+}
+
+fn create_pipeline(context: &RenderContext, layouts: &LayoutRegistry, format: TextureFormat, antialiasing: Antialiasing) -> RenderPipeline {
+    let device = &context.device;
+    let mut preprocessor = Preprocessor::new().target(format);
+    if antialiasing == Antialiasing::Fxaa {
+        preprocessor = preprocessor.define("FXAA", 1);
+    }
+    let shader_module = preprocessor.create_module(context, "output.wgsl");
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("output"),
+        bind_group_layouts: &[layouts.get(OUTPUT_LAYOUT), layouts.get(OUTPUT_LUT_LAYOUT)],
+        push_constant_ranges: &[],
+    });
+    let label = format!("output {format:?} {antialiasing:?}");
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(&label),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            entry_point: "vertex",
+            module: &shader_module,
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            entry_point: "fragment",
+            module: &shader_module,
+            targets: &[
+                Some(format.into())
+            ],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
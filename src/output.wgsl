@@ -0,0 +1,130 @@
+#include "color.wgsl"
+
+struct Settings {
+    exposure: f32,
+    // nits that scene white maps to on an hdr display
+    paper_white: f32,
+    // brightest the display can show, anything above is scaled down to it
+    max_nits: f32,
+    // how much of the color lut is applied, sdr only
+    grading: f32,
+}
+
+@group(0) @binding(0)
+var scene: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> settings: Settings;
+@group(0) @binding(2)
+var scene_sampler: sampler;
+// srgb encoded color in, srgb encoded color out, see color_grading.rs
+@group(1) @binding(0)
+var lut: texture_3d<f32>;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    // the scene can be smaller or larger than the target, see Renderer::render_scale
+    @location(0) uv: vec2<f32>,
+}
+
+// a single triangle that covers the screen
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    var out: VertexOut;
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.pos = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+// narkowicz's fit of the aces filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// maps an exposed scene color to what gets written to the target, before any encoding
+fn display(color: vec3<f32>) -> vec3<f32> {
+#ifdef HDR_TARGET
+    // scrgb is linear with 1.0 at 80 nits. scaling the whole color keeps the hue of highlights
+    // that go past what the display can do
+    let nits = color * settings.paper_white;
+    let brightest = max(max(nits.r, nits.g), max(nits.b, 0.0001));
+    return nits * min(1.0, settings.max_nits / brightest) / 80.0;
+#else
+    return aces(color);
+#endif
+}
+
+// luts are made for display colors, so this comes after tonemapping
+fn grade(color: vec3<f32>) -> vec3<f32> {
+    let size = f32(textureDimensions(lut).x);
+    // the outermost texels sit half a texel in from the edges
+    let coord = srgb_encode(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0))) * ((size - 1.0) / size) + 0.5 / size;
+    let graded = srgb_decode(textureSampleLevel(lut, scene_sampler, coord, 0.0).rgb);
+    return mix(color, graded, settings.grading);
+}
+
+fn exposed(uv: vec2<f32>) -> vec3<f32> {
+    return max(textureSampleLevel(scene, scene_sampler, uv, 0.0).rgb * settings.exposure, vec3<f32>(0.0));
+}
+
+#ifdef FXAA
+// edges are found on tonemapped luma even for hdr targets, raw scene values are too far from
+// what the eye sees
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(aces(color), vec3<f32>(0.299, 0.587, 0.114));
+}
+
+// the console variant of fxaa 3: four diagonal taps find the edge direction, then it blends
+// along the edge with two or four taps
+fn fxaa(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(scene));
+    let center = exposed(uv);
+    let luma_m = luma(center);
+    let luma_nw = luma(exposed(uv + vec2<f32>(-0.5, -0.5) * texel));
+    let luma_ne = luma(exposed(uv + vec2<f32>(0.5, -0.5) * texel));
+    let luma_sw = luma(exposed(uv + vec2<f32>(-0.5, 0.5) * texel));
+    let luma_se = luma(exposed(uv + vec2<f32>(0.5, 0.5) * texel));
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    // flat areas and faint edges are left alone
+    if (luma_max - luma_min < max(0.0312, luma_max * 0.125)) {
+        return center;
+    }
+
+    var dir = vec2<f32>(
+        (luma_sw + luma_se) - (luma_nw + luma_ne),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * 0.125, 1.0 / 128.0);
+    let scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2<f32>(-8.0), vec2<f32>(8.0)) * texel;
+
+    let near = 0.5 * (exposed(uv - dir / 6.0) + exposed(uv + dir / 6.0));
+    let far = near * 0.5 + 0.25 * (exposed(uv - dir * 0.5) + exposed(uv + dir * 0.5));
+    // the wider blend crossed another edge
+    let luma_far = luma(far);
+    if (luma_far < luma_min || luma_far > luma_max) {
+        return near;
+    }
+    return far;
+}
+#endif
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+#ifdef FXAA
+    let color = fxaa(in.uv);
+#else
+    let color = exposed(in.uv);
+#endif
+#ifdef HDR_TARGET
+    return output_color(vec4<f32>(display(color), 1.0));
+#else
+    return output_color(vec4<f32>(grade(display(color)), 1.0));
+#endif
+}
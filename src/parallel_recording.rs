@@ -0,0 +1,173 @@
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use wgpu::*;
+use crate::mesh::{MeshPipelines, SharedObjects};
+use crate::model::ModelInstance;
+
+// how much the averages move towards each new frame's time
+const SMOOTHING: f32 = 0.05;
+
+// the main views' meshes split across threads, each recording its share into a command encoder
+// of its own. only used without gpu culling, whose few indirect draws aren't worth splitting
+#[derive(Copy, Clone, Debug)]
+pub struct ParallelRecording {
+    pub enabled: bool,
+    // 0 is one per core
+    pub threads: usize,
+    // a thread records at least this many meshes, fewer aren't worth the extra passes
+    pub min_per_thread: usize,
+}
+
+impl Default for ParallelRecording {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threads: 0,
+            min_per_thread: 256,
+        }
+    }
+}
+
+impl ParallelRecording {
+    // what the pool is built with
+    pub fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        }
+    }
+
+    // how many chunks count meshes are split into, 1 records them on the calling thread
+    pub fn chunks(&self, count: usize) -> usize {
+        if !self.enabled {
+            return 1;
+        }
+        self.thread_count().min(count / self.min_per_thread.max(1)).max(1)
+    }
+}
+
+// the threads meshes are recorded on, kept across frames so a frame recorded in parallel doesn't
+// pay for starting them and the two ways of recording compare fairly
+pub struct RecordingPool {
+    pool: ThreadPool,
+}
+
+impl RecordingPool {
+    pub fn new(threads: usize) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("mesh recording {index}"))
+            .build()
+            .expect("failed to start the mesh recording threads");
+        Self { pool }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    // records the instances into `chunks` passes on the pool, returned in order. the passes load
+    // and store their targets, so they're submitted between whatever is drawn before and after
+    pub fn record_meshes(&self, device: &Device, pass: &MeshPass, instances: &[&ModelInstance], chunks: usize) -> Vec<CommandBuffer> {
+        let chunk_size = instances.len().div_ceil(chunks.max(1)).max(1);
+        self.pool.install(|| {
+            instances.par_chunks(chunk_size).enumerate()
+                .map(|(index, chunk)| record_chunk(device, pass, chunk, index))
+                .collect()
+        })
+    }
+}
+
+// cpu time spent recording the main views' meshes, smoothed over frames so the two ways of
+// recording can be compared on the same scene by switching between them
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RecordingStats {
+    // milliseconds a frame, 0 until measured
+    pub single: f32,
+    pub parallel: f32,
+    // what the last frame recorded in parallel was split into
+    pub chunks: usize,
+    // meshes recorded in the last frame, in every view
+    pub meshes: usize,
+}
+
+impl RecordingStats {
+    pub fn measure_single(&mut self, elapsed: Duration, meshes: usize) {
+        self.single = smooth(self.single, elapsed.as_secs_f32() * 1000.0);
+        self.meshes = meshes;
+    }
+
+    pub fn measure_parallel(&mut self, elapsed: Duration, meshes: usize, chunks: usize) {
+        self.parallel = smooth(self.parallel, elapsed.as_secs_f32() * 1000.0);
+        self.meshes = meshes;
+        self.chunks = chunks;
+    }
+}
+
+impl fmt::Display for RecordingStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} meshes recorded in {:.3} ms on one thread, {:.3} ms on {}", self.meshes, self.single, self.parallel, self.chunks)
+    }
+}
+
+fn smooth(average: f32, sample: f32) -> f32 {
+    if average == 0.0 {
+        sample
+    } else {
+        average + (sample - average) * SMOOTHING
+    }
+}
+
+// what every chunk's pass is begun and bound with, shared by the recording threads
+pub struct MeshPass<'a> {
+    pub color: &'a TextureView,
+    pub depth: &'a TextureView,
+    // x, y, width and height in pixels
+    pub viewport: (u32, u32, u32, u32),
+    pub pipelines: MeshPipelines<'a>,
+    pub camera: &'a BindGroup,
+    pub material_textures: &'a BindGroup,
+    pub shared: &'a SharedObjects,
+}
+
+fn record_chunk(device: &Device, pass: &MeshPass, instances: &[&ModelInstance], index: usize) -> CommandBuffer {
+    let label = format!("meshes {index}");
+    let mut cmd = device.create_command_encoder(&CommandEncoderDescriptor { label: Some(&label) });
+    let mut render_cmd = cmd.begin_render_pass(&RenderPassDescriptor {
+        label: Some(&label),
+        color_attachments: &[
+            Some(RenderPassColorAttachment {
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+                view: pass.color,
+                resolve_target: None,
+            })
+        ],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+            view: pass.depth,
+            depth_ops: Some(Operations {
+                load: LoadOp::Load,
+                store: true,
+            }),
+            stencil_ops: Some(Operations {
+                load: LoadOp::Load,
+                store: true,
+            }),
+        }),
+    });
+    let (x, y, width, height) = pass.viewport;
+    render_cmd.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+    render_cmd.set_scissor_rect(x, y, width, height);
+    render_cmd.set_bind_group(0, pass.camera, &[]);
+    render_cmd.set_bind_group(2, pass.material_textures, &[]);
+    for instance in instances {
+        instance.draw(&mut render_cmd, pass.shared, pass.pipelines);
+    }
+    drop(render_cmd);
+    cmd.finish()
+}
@@ -0,0 +1,338 @@
+use std::mem::size_of;
+use glam::{Mat4, Vec3};
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::{Camera, FRAME_LAYOUT};
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+// matches `Particle` in particles.wgsl
+const PARTICLE_SIZE: BufferAddress = 64;
+const MAX_EMITTERS: usize = 16;
+const WORKGROUP_SIZE: u32 = 64;
+
+const SIMULATION_LAYOUT: &str = "particle simulation";
+const RENDER_LAYOUT: &str = "particle render";
+const COLLISION_LAYOUT: &str = "particle collision";
+
+pub struct Emitter {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub spread: f32,
+    // particles per second
+    pub rate: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            velocity: Vec3::Y * 4.0,
+            spread: 1.0,
+            rate: 200.0,
+            lifetime: 2.0,
+            size: 0.05,
+            color: [1.0, 0.6, 0.2, 1.0],
+        }
+    }
+}
+
+// particles bouncing off what the main view sees, read from the ssao prepass in the simulation.
+// anything hidden or off screen can't be hit, so particles fall through it
+#[derive(Copy, Clone, Debug)]
+pub struct ParticleCollision {
+    pub enabled: bool,
+    // of the speed into the surface that's kept, bouncing back out
+    pub restitution: f32,
+    // of the speed along the surface that's kept
+    pub friction: f32,
+    // how far behind a surface a particle still counts as hitting it, in world units. further
+    // back it's taken to be behind the object instead of inside it
+    pub thickness: f32,
+}
+
+impl Default for ParticleCollision {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            restitution: 0.4,
+            friction: 0.8,
+            thickness: 0.5,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GpuEmitter {
+    position: [f32; 3],
+    spawn_count: u32,
+    velocity: [f32; 3],
+    spread: f32,
+    color: [f32; 4],
+    lifetime: f32,
+    size: f32,
+    first: u32,
+    _pad: u32,
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Params {
+    gravity: [f32; 3],
+    dt: f32,
+    seed: u32,
+    spawn_total: u32,
+    emitter_count: u32,
+    capacity: u32,
+    // the main view's, which the prepass was drawn from
+    view_proj: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    eye: [f32; 3],
+    restitution: f32,
+    friction: f32,
+    thickness: f32,
+    collide: u32,
+    _padding: u32,
+}
+
+pub struct ParticleSystem {
+    pub emitters: Vec<Emitter>,
+    pub gravity: Vec3,
+    pub collision: ParticleCollision,
+    capacity: u32,
+    carry: Vec<f32>,
+    spawn_total: u32,
+    frame: u32,
+
+    emitter_buffer: Buffer,
+    params_buffer: Buffer,
+    compute_bind_group: BindGroup,
+    collision_bind_group: BindGroup,
+    render_bind_group: BindGroup,
+    spawn_pipeline: ComputePipeline,
+    update_pipeline: ComputePipeline,
+    render_pipeline: RenderPipeline,
+}
+
+impl ParticleSystem {
+    // normal_depth is the ssao prepass, replaced with set_normal_depth when it's recreated
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, capacity: u32, normal_depth: &TextureView) -> Self {
+        let device = &context.device;
+        let particle_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("particles"),
+            size: capacity as BufferAddress * PARTICLE_SIZE,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // every slot starts out free
+        let mut free_list = vec![capacity];
+        free_list.extend(0..capacity);
+        let free_list_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("particle free list"),
+            usage: BufferUsages::STORAGE,
+            contents: bytemuck::cast_slice(&free_list),
+        });
+
+        let emitter_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("particle emitters"),
+            size: (MAX_EMITTERS * size_of::<GpuEmitter>()) as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("particle params"),
+            size: size_of::<Params>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        layouts.register(context, SIMULATION_LAYOUT, &[
+            (Binding::Storage { read_only: false }, ShaderStages::COMPUTE),
+            (Binding::Storage { read_only: false }, ShaderStages::COMPUTE),
+            (Binding::Storage { read_only: true }, ShaderStages::COMPUTE),
+            (Binding::Uniform, ShaderStages::COMPUTE),
+        ]);
+        let compute_bind_group = BindGroupBuilder::new()
+            .buffer(&particle_buffer)
+            .buffer(&free_list_buffer)
+            .buffer(&emitter_buffer)
+            .buffer(&params_buffer)
+            .build(context, layouts, SIMULATION_LAYOUT);
+        layouts.register(context, COLLISION_LAYOUT, &[(Binding::Texture, ShaderStages::COMPUTE)]);
+        let collision_bind_group = create_collision_bind_group(context, layouts, normal_depth);
+
+        layouts.register(context, RENDER_LAYOUT, &[(Binding::Storage { read_only: true }, ShaderStages::VERTEX)]);
+        let render_bind_group = BindGroupBuilder::new()
+            .buffer(&particle_buffer)
+            .build(context, layouts, RENDER_LAYOUT);
+
+        let compute_module = Preprocessor::new().create_module(context, "particles_compute.wgsl");
+        let compute_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("particle simulation"),
+            bind_group_layouts: &[layouts.get(SIMULATION_LAYOUT), layouts.get(COLLISION_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = |entry_point| device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&compute_pipeline_layout),
+            module: &compute_module,
+            entry_point,
+        });
+        let spawn_pipeline = compute_pipeline("spawn");
+        let update_pipeline = compute_pipeline("update");
+
+        let render_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "particles.wgsl");
+        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("particle render"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT), layouts.get(RENDER_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("particles"),
+            layout: Some(&render_pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &render_module,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &render_module,
+                targets: &[
+                    Some(ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(BlendState {
+                            color: BlendComponent {
+                                src_factor: BlendFactor::One,
+                                dst_factor: BlendFactor::One,
+                                operation: BlendOperation::Add,
+                            },
+                            alpha: BlendComponent::OVER,
+                        }),
+                        write_mask: ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            emitters: Vec::new(),
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            collision: ParticleCollision::default(),
+            capacity,
+            carry: Vec::new(),
+            spawn_total: 0,
+            frame: 0,
+
+            emitter_buffer,
+            params_buffer,
+            compute_bind_group,
+            collision_bind_group,
+            render_bind_group,
+            spawn_pipeline,
+            update_pipeline,
+            render_pipeline,
+        }
+    }
+
+    pub fn set_normal_depth(&mut self, context: &RenderContext, layouts: &LayoutRegistry, normal_depth: &TextureView) {
+        self.collision_bind_group = create_collision_bind_group(context, layouts, normal_depth);
+    }
+
+    // view is the camera and aspect the prepass is drawn with, None when it isn't drawn, which
+    // leaves collisions out
+    pub fn update(&mut self, context: &RenderContext, dt: f32, view: Option<(&Camera, f32)>) {
+        assert!(self.emitters.len() <= MAX_EMITTERS, "too many particle emitters");
+        self.carry.resize(self.emitters.len(), 0.0);
+        self.frame = self.frame.wrapping_add(1);
+
+        let mut gpu_emitters = [GpuEmitter::default(); MAX_EMITTERS];
+        let mut first = 0;
+        for ((emitter, carry), gpu) in self.emitters.iter().zip(&mut self.carry).zip(&mut gpu_emitters) {
+            *carry += emitter.rate * dt;
+            let spawn_count = carry.floor();
+            *carry -= spawn_count;
+
+            *gpu = GpuEmitter {
+                position: emitter.position.to_array(),
+                spawn_count: spawn_count as u32,
+                velocity: emitter.velocity.to_array(),
+                spread: emitter.spread,
+                color: emitter.color,
+                lifetime: emitter.lifetime,
+                size: emitter.size,
+                first,
+                _pad: 0,
+            };
+            first += spawn_count as u32;
+        }
+        self.spawn_total = first;
+
+        let params = Params {
+            gravity: self.gravity.to_array(),
+            dt,
+            seed: self.frame.wrapping_mul(0x9E37_79B9),
+            spawn_total: self.spawn_total,
+            emitter_count: self.emitters.len() as u32,
+            capacity: self.capacity,
+            view_proj: view.map_or(Mat4::IDENTITY, |(camera, aspect)| camera.view_projection(aspect)).to_cols_array_2d(),
+            view: view.map_or(Mat4::IDENTITY, |(camera, _)| camera.view()).to_cols_array_2d(),
+            eye: view.map_or(Vec3::ZERO, |(camera, _)| camera.eye).to_array(),
+            restitution: self.collision.restitution.max(0.0),
+            friction: self.collision.friction.clamp(0.0, 1.0),
+            thickness: self.collision.thickness.max(0.0),
+            collide: (self.collision.enabled && view.is_some()) as u32,
+            _padding: 0,
+        };
+        context.queue.write_buffer(&self.emitter_buffer, 0, bytemuck::cast_slice(&gpu_emitters));
+        context.queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    // with collisions on, after the prepass of the frame they collide with
+    pub fn simulate(&self, cmd: &mut CommandEncoder) {
+        let mut compute_cmd = cmd.begin_compute_pass(&ComputePassDescriptor { label: Some("particles") });
+        compute_cmd.set_bind_group(0, &self.compute_bind_group, &[]);
+        compute_cmd.set_bind_group(1, &self.collision_bind_group, &[]);
+        // update first so particles freed this frame can be respawned right away
+        compute_cmd.set_pipeline(&self.update_pipeline);
+        compute_cmd.dispatch_workgroups(workgroups(self.capacity), 1, 1);
+        if self.spawn_total > 0 {
+            compute_cmd.set_pipeline(&self.spawn_pipeline);
+            compute_cmd.dispatch_workgroups(workgroups(self.spawn_total), 1, 1);
+        }
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        render_cmd.set_pipeline(&self.render_pipeline);
+        render_cmd.set_bind_group(0, camera_bind_group, &[]);
+        render_cmd.set_bind_group(1, &self.render_bind_group, &[]);
+        render_cmd.draw(0..6, 0..self.capacity);
+    }
+}
+
+fn create_collision_bind_group(context: &RenderContext, layouts: &LayoutRegistry, normal_depth: &TextureView) -> BindGroup {
+    BindGroupBuilder::new()
+        .texture(normal_depth)
+        .build(context, layouts, COLLISION_LAYOUT)
+}
+
+fn workgroups(count: u32) -> u32 {
+    count.div_ceil(WORKGROUP_SIZE)
+}
@@ -0,0 +1,51 @@
+#include "camera.wgsl"
+#include "color.wgsl"
+
+struct Particle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    lifetime: f32,
+    color: vec4<f32>,
+    size: f32,
+    alive: u32,
+}
+
+@group(1) @binding(0) var<storage, read> particles: array<Particle>;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex: u32, @builtin(instance_index) instance: u32) -> VertexOut {
+    var out: VertexOut;
+    let p = particles[instance];
+    if (p.alive == 0u) {
+        out.pos = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        return out;
+    }
+
+    // two triangles: (0,1,2) (2,1,3) over the corners of a quad
+    var corners = array<u32, 6>(0u, 1u, 2u, 2u, 1u, 3u);
+    let corner_index = corners[vertex];
+    let corner = vec2<f32>(f32(corner_index & 1u), f32(corner_index >> 1u)) * 2.0 - 1.0;
+
+    let right = vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]);
+    let up = vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]);
+    let t = p.age / p.lifetime;
+    let world = p.position + (right * corner.x + up * corner.y) * p.size * (1.0 - 0.5 * t);
+
+    out.pos = camera.view_proj * vec4<f32>(world, 1.0);
+    out.uv = corner;
+    out.color = vec4<f32>(p.color.rgb, p.color.a * (1.0 - t));
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let falloff = clamp(1.0 - dot(in.uv, in.uv), 0.0, 1.0);
+    return output_color(vec4<f32>(in.color.rgb * in.color.a * falloff, 1.0));
+}
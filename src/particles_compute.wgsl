@@ -0,0 +1,153 @@
+struct Particle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    lifetime: f32,
+    color: vec4<f32>,
+    size: f32,
+    alive: u32,
+}
+
+struct FreeList {
+    count: atomic<i32>,
+    indices: array<u32>,
+}
+
+struct Emitter {
+    position: vec3<f32>,
+    spawn_count: u32,
+    velocity: vec3<f32>,
+    spread: f32,
+    color: vec4<f32>,
+    lifetime: f32,
+    size: f32,
+    first: u32,
+}
+
+struct Params {
+    gravity: vec3<f32>,
+    dt: f32,
+    seed: u32,
+    spawn_total: u32,
+    emitter_count: u32,
+    capacity: u32,
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    eye: vec3<f32>,
+    restitution: f32,
+    friction: f32,
+    thickness: f32,
+    collide: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(1) var<storage, read_write> free_list: FreeList;
+@group(0) @binding(2) var<storage, read> emitters: array<Emitter>;
+@group(0) @binding(3) var<uniform> params: Params;
+// the ssao prepass, view space normals and linear depth. see view_normal_depth in camera.wgsl
+@group(1) @binding(0) var normal_depth_map: texture_2d<f32>;
+
+fn hash(value: u32) -> u32 {
+    var state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return f32(*seed) / 4294967295.0;
+}
+
+@compute @workgroup_size(64)
+fn spawn(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.spawn_total) {
+        return;
+    }
+
+    var emitter_index = 0u;
+    for (var i = 0u; i < params.emitter_count; i = i + 1u) {
+        let e = emitters[i];
+        if (id.x >= e.first && id.x < e.first + e.spawn_count) {
+            emitter_index = i;
+        }
+    }
+    let emitter = emitters[emitter_index];
+
+    let slot = atomicSub(&free_list.count, 1) - 1;
+    if (slot < 0) {
+        atomicAdd(&free_list.count, 1);
+        return;
+    }
+    let index = free_list.indices[slot];
+
+    var seed = hash(id.x ^ params.seed);
+    let dir = vec3<f32>(random(&seed), random(&seed), random(&seed)) * 2.0 - 1.0;
+
+    var p: Particle;
+    p.position = emitter.position;
+    p.age = 0.0;
+    p.velocity = emitter.velocity + dir * emitter.spread;
+    p.lifetime = emitter.lifetime * (0.75 + 0.5 * random(&seed));
+    p.color = emitter.color;
+    p.size = emitter.size;
+    p.alive = 1u;
+    particles[index] = p;
+}
+
+// bounces p off the surface the prepass saw where it is on screen, if it's just behind it and
+// moving into it
+fn collide(p: ptr<function, Particle>) {
+    let clip = params.view_proj * vec4<f32>((*p).position, 1.0);
+    if (clip.w <= 0.0 || any(abs(clip.xy) > vec2<f32>(clip.w))) {
+        return;
+    }
+    let ndc = clip.xy / clip.w;
+    let size = vec2<f32>(textureDimensions(normal_depth_map));
+    let texel = vec2<i32>(clamp(vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * size, vec2<f32>(0.0), size - 1.0));
+    let surface = textureLoad(normal_depth_map, texel, 0);
+    // cleared pixels, nothing was drawn there
+    if (surface.w <= 0.0) {
+        return;
+    }
+    let depth = -(params.view * vec4<f32>((*p).position, 1.0)).z;
+    if (depth < surface.w || depth > surface.w + params.thickness) {
+        return;
+    }
+    // the view matrix is a rotation and a translation, its transpose takes normals back to world space
+    let rotation = mat3x3<f32>(params.view[0].xyz, params.view[1].xyz, params.view[2].xyz);
+    let normal = normalize(transpose(rotation) * surface.xyz);
+    let speed = dot((*p).velocity, normal);
+    if (speed >= 0.0) {
+        return;
+    }
+    let along = (*p).velocity - normal * speed;
+    (*p).velocity = along * params.friction - normal * speed * params.restitution;
+    // back out onto the surface along the line of sight
+    (*p).position = params.eye + ((*p).position - params.eye) * (surface.w / depth);
+}
+
+@compute @workgroup_size(64)
+fn update(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.capacity) {
+        return;
+    }
+    var p = particles[id.x];
+    if (p.alive == 0u) {
+        return;
+    }
+
+    p.age = p.age + params.dt;
+    if (p.age >= p.lifetime) {
+        p.alive = 0u;
+        let slot = atomicAdd(&free_list.count, 1);
+        free_list.indices[slot] = id.x;
+    } else {
+        p.velocity = p.velocity + params.gravity * params.dt;
+        p.position = p.position + p.velocity * params.dt;
+        if (params.collide != 0u) {
+            collide(&p);
+        }
+    }
+    particles[id.x] = p;
+}
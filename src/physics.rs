@@ -0,0 +1,276 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use crate::world::World;
+
+#[cfg(feature = "physics")]
+use std::collections::HashMap;
+#[cfg(feature = "physics")]
+use std::sync::Arc;
+#[cfg(feature = "physics")]
+use glam::Quat;
+#[cfg(feature = "physics")]
+use rapier3d::{na, prelude as rapier};
+#[cfg(feature = "physics")]
+use crate::model::Model;
+#[cfg(feature = "physics")]
+use crate::transform::Transform;
+#[cfg(feature = "physics")]
+use crate::world::{Entity, MeshRef};
+
+pub const GRAVITY: Vec3 = Vec3::new(0.0, -9.81, 0.0);
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyKind {
+    // moved by the simulation, its Transform is overwritten every step
+    #[default]
+    Dynamic,
+    // never moves, e.g. the ground
+    Fixed,
+    // follows its Transform and pushes dynamic bodies out of the way
+    Kinematic,
+}
+
+// sizes are before the entity's scale is applied
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ColliderShape {
+    // a box around the model's bounds
+    #[default]
+    Auto,
+    Cuboid { half_extents: Vec3 },
+    Ball { radius: f32 },
+    // along y, like primitives::cylinder
+    Cylinder { radius: f32, height: f32 },
+}
+
+// makes an entity with a Transform part of the simulation. changing it, or the scale, rebuilds the
+// body. setting the Transform from outside teleports the body there
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsBody {
+    pub kind: BodyKind,
+    pub shape: ColliderShape,
+    // mass is density times volume
+    pub density: f32,
+    pub friction: f32,
+    // bounciness, 0..1
+    pub restitution: f32,
+}
+
+impl Default for PhysicsBody {
+    fn default() -> Self {
+        Self {
+            kind: BodyKind::Dynamic,
+            shape: ColliderShape::Auto,
+            density: 1.0,
+            friction: 0.5,
+            restitution: 0.0,
+        }
+    }
+}
+
+#[cfg(feature = "physics")]
+struct Tracked {
+    handle: rapier::RigidBodyHandle,
+    body: PhysicsBody,
+    // as of the last step, anything else means it was moved from outside
+    transform: Transform,
+    // Auto shapes are fitted to it, e.g. a loading placeholder gets replaced
+    model: Option<Arc<Model>>,
+}
+
+#[cfg(feature = "physics")]
+struct Simulation {
+    params: rapier::IntegrationParameters,
+    pipeline: rapier::PhysicsPipeline,
+    islands: rapier::IslandManager,
+    broad_phase: rapier::BroadPhase,
+    narrow_phase: rapier::NarrowPhase,
+    bodies: rapier::RigidBodySet,
+    colliders: rapier::ColliderSet,
+    impulse_joints: rapier::ImpulseJointSet,
+    multibody_joints: rapier::MultibodyJointSet,
+    ccd: rapier::CCDSolver,
+    tracked: HashMap<Entity, Tracked>,
+}
+
+// steps rigid bodies for every entity with a PhysicsBody. call step from App::fixed_update so
+// the written back transforms get interpolated like everything else. without the physics feature
+// step does nothing and bodies stay where they are
+pub struct Physics {
+    pub gravity: Vec3,
+    #[cfg(feature = "physics")]
+    simulation: Simulation,
+}
+
+impl Physics {
+    pub fn new() -> Self {
+        Self {
+            gravity: GRAVITY,
+            #[cfg(feature = "physics")]
+            simulation: Simulation {
+                params: rapier::IntegrationParameters::default(),
+                pipeline: rapier::PhysicsPipeline::new(),
+                islands: rapier::IslandManager::new(),
+                broad_phase: rapier::BroadPhase::new(),
+                narrow_phase: rapier::NarrowPhase::new(),
+                bodies: rapier::RigidBodySet::new(),
+                colliders: rapier::ColliderSet::new(),
+                impulse_joints: rapier::ImpulseJointSet::new(),
+                multibody_joints: rapier::MultibodyJointSet::new(),
+                ccd: rapier::CCDSolver::new(),
+                tracked: HashMap::new(),
+            },
+        }
+    }
+
+    pub fn is_available() -> bool {
+        cfg!(feature = "physics")
+    }
+
+    // syncs bodies with the world, advances by step seconds and writes transforms back
+    pub fn step(&mut self, world: &mut World, step: f32) {
+        #[cfg(feature = "physics")]
+        self.simulation.step(world, step, self.gravity);
+        let _ = (world, step);
+    }
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "physics")]
+impl Simulation {
+    fn step(&mut self, world: &mut World, step: f32, gravity: Vec3) {
+        // bodies whose entity is gone, or that need rebuilding
+        let stale: Vec<_> = self.tracked.iter()
+            .filter(|(&entity, tracked)| {
+                match world.query_one_mut::<(&PhysicsBody, &Transform, Option<&MeshRef>)>(entity) {
+                    Ok((body, transform, mesh)) => {
+                        let model_changed = match (mesh, &tracked.model) {
+                            (Some(mesh), Some(model)) => !Arc::ptr_eq(&mesh.0, model),
+                            (mesh, model) => mesh.is_some() != model.is_some(),
+                        };
+                        *body != tracked.body
+                            || transform.scale != tracked.transform.scale
+                            || (body.shape == ColliderShape::Auto && model_changed)
+                    }
+                    Err(_) => true,
+                }
+            })
+            .map(|(&entity, _)| entity)
+            .collect();
+        for entity in stale {
+            let tracked = self.tracked.remove(&entity).unwrap();
+            self.bodies.remove(
+                tracked.handle,
+                &mut self.islands,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                true,
+            );
+        }
+
+        for (entity, (&body, &transform, mesh)) in world.query_mut::<(&PhysicsBody, &Transform, Option<&MeshRef>)>() {
+            match self.tracked.get_mut(&entity) {
+                Some(tracked) if tracked.transform != transform => {
+                    let rigid_body = &mut self.bodies[tracked.handle];
+                    let position = isometry(&transform);
+                    if body.kind == BodyKind::Kinematic {
+                        rigid_body.set_next_kinematic_position(position);
+                    } else {
+                        rigid_body.set_position(position, true);
+                        rigid_body.set_linvel(na::Vector3::zeros(), true);
+                        rigid_body.set_angvel(na::Vector3::zeros(), true);
+                    }
+                    tracked.transform = transform;
+                }
+                Some(_) => {}
+                None => {
+                    let handle = self.insert(&body, &transform, mesh);
+                    let model = mesh.map(|mesh| mesh.0.clone());
+                    self.tracked.insert(entity, Tracked { handle, body, transform, model });
+                }
+            }
+        }
+
+        self.params.dt = step;
+        self.pipeline.step(
+            &na::Vector3::new(gravity.x, gravity.y, gravity.z),
+            &self.params,
+            &mut self.islands,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            &mut self.ccd,
+            None,
+            &(),
+            &(),
+        );
+
+        for (&entity, tracked) in &mut self.tracked {
+            if tracked.body.kind != BodyKind::Dynamic {
+                continue;
+            }
+            let position = self.bodies[tracked.handle].position();
+            let (translation, rotation) = (position.translation.vector, position.rotation);
+            tracked.transform.translation = Vec3::new(translation.x, translation.y, translation.z);
+            tracked.transform.rotation = Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w);
+            if let Ok(transform) = world.query_one_mut::<&mut Transform>(entity) {
+                *transform = tracked.transform;
+            }
+        }
+    }
+
+    fn insert(&mut self, body: &PhysicsBody, transform: &Transform, mesh: Option<&MeshRef>) -> rapier::RigidBodyHandle {
+        let builder = match body.kind {
+            BodyKind::Dynamic => rapier::RigidBodyBuilder::dynamic(),
+            BodyKind::Fixed => rapier::RigidBodyBuilder::fixed(),
+            BodyKind::Kinematic => rapier::RigidBodyBuilder::kinematic_position_based(),
+        };
+        let handle = self.bodies.insert(builder.position(isometry(transform)).build());
+
+        // colliders can't be scaled, so the scale is baked into their size
+        let scale = transform.scale.abs();
+        let (collider, center) = match body.shape {
+            ColliderShape::Auto => {
+                let (min, max) = mesh.map_or((Vec3::splat(-0.5), Vec3::splat(0.5)), |mesh| mesh.0.bounds());
+                // flat meshes like planes still need some thickness to collide with
+                let half = ((max - min) * 0.5 * scale).max(Vec3::splat(0.01));
+                (rapier::ColliderBuilder::cuboid(half.x, half.y, half.z), (min + max) * 0.5 * scale)
+            }
+            ColliderShape::Cuboid { half_extents } => {
+                let half = half_extents * scale;
+                (rapier::ColliderBuilder::cuboid(half.x, half.y, half.z), Vec3::ZERO)
+            }
+            ColliderShape::Ball { radius } => (rapier::ColliderBuilder::ball(radius * scale.max_element()), Vec3::ZERO),
+            ColliderShape::Cylinder { radius, height } => {
+                let collider = rapier::ColliderBuilder::cylinder(height * 0.5 * scale.y, radius * scale.x.max(scale.z));
+                (collider, Vec3::ZERO)
+            }
+        };
+        let collider = collider
+            .translation(na::Vector3::new(center.x, center.y, center.z))
+            .density(body.density)
+            .friction(body.friction)
+            .restitution(body.restitution)
+            .build();
+        self.colliders.insert_with_parent(collider, handle, &mut self.bodies);
+        handle
+    }
+}
+
+#[cfg(feature = "physics")]
+fn isometry(transform: &Transform) -> rapier::Isometry<rapier::Real> {
+    let (t, r) = (transform.translation, transform.rotation);
+    rapier::Isometry::from_parts(
+        na::Translation3::new(t.x, t.y, t.z),
+        na::UnitQuaternion::from_quaternion(na::Quaternion::new(r.w, r.x, r.y, r.z)),
+    )
+}
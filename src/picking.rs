@@ -0,0 +1,129 @@
+use wgpu::*;
+use crate::context::RenderContext;
+use crate::readback::Readback;
+
+pub const ID_FORMAT: TextureFormat = TextureFormat::R32Uint;
+
+// renders object ids into an offscreen target and reads back the one under the cursor.
+// id 0 is the background, objects use index + 1
+pub struct Picker {
+    id_view: TextureView,
+    id_texture: Texture,
+    request: Option<(u32, u32)>,
+    in_flight: Option<Readback>,
+    size: (u32, u32),
+}
+
+impl Picker {
+    pub fn new(context: &RenderContext) -> Self {
+        let size = context.physical_size();
+        let (id_texture, id_view) = create_id_target(context, size.width, size.height);
+        Self {
+            id_view,
+            id_texture,
+            request: None,
+            in_flight: None,
+            size: (size.width, size.height),
+        }
+    }
+
+    pub fn resize(&mut self, context: &RenderContext, width: u32, height: u32) {
+        (self.id_texture, self.id_view) = create_id_target(context, width, height);
+        self.size = (width, height);
+    }
+
+    // picks the pixel at the given physical position, replacing any request that hasn't been rendered yet
+    pub fn request(&mut self, x: u32, y: u32) {
+        if x < self.size.0 && y < self.size.1 {
+            self.request = Some((x, y));
+        }
+    }
+
+    // the pending request, unless the previous one is still being read back
+    pub fn pending(&self) -> Option<(u32, u32)> {
+        match self.in_flight {
+            Some(_) => None,
+            None => self.request,
+        }
+    }
+
+    pub fn begin_pass<'a>(&'a self, cmd: &'a mut CommandEncoder, depth_view: &'a TextureView) -> RenderPass<'a> {
+        let mut pick_cmd = cmd.begin_render_pass(&RenderPassDescriptor {
+            label: Some("picking"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: true,
+                    },
+                    view: &self.id_view,
+                    resolve_target: None,
+                })
+            ],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        if let Some((x, y)) = self.request {
+            pick_cmd.set_scissor_rect(x, y, 1, 1);
+        }
+        pick_cmd
+    }
+
+    // copies the picked texel out and starts mapping it, the result shows up in `poll`
+    pub fn submit(&mut self, context: &RenderContext, mut cmd: CommandEncoder) {
+        let (x, y) = match self.request.take() {
+            Some(request) => request,
+            None => return,
+        };
+        context.queue.submit([cmd.finish()]);
+        let texel = ImageCopyTexture {
+            texture: &self.id_texture,
+            mip_level: 0,
+            origin: Origin3d { x, y, z: 0 },
+            aspect: TextureAspect::All,
+        };
+        self.in_flight = Some(context.read_texture(texel, ID_FORMAT, Extent3d { width: 1, height: 1, depth_or_array_layers: 1 }));
+    }
+
+    // returns the picked id once its readback has completed
+    pub fn poll(&mut self, context: &RenderContext) -> Option<u32> {
+        let readback = self.in_flight.as_mut()?;
+        context.device.poll(Maintain::Poll);
+        let result = readback.try_take()?;
+        self.in_flight = None;
+        match result {
+            Ok(data) => Some(u32::from_ne_bytes([data[0], data[1], data[2], data[3]])),
+            Err(error) => {
+                tracing::error!("pick readback failed: {error}");
+                None
+            }
+        }
+    }
+}
+
+fn create_id_target(context: &RenderContext, width: u32, height: u32) -> (Texture, TextureView) {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some("object ids"),
+        size: Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: ID_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+    });
+    let view = texture.create_view(&TextureViewDescriptor {
+        label: Some("object ids"),
+        ..TextureViewDescriptor::default()
+    });
+    (texture, view)
+}
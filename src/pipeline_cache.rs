@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use wgpu::*;
+use crate::context::RenderContext;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayoutKey {
+    pub array_stride: BufferAddress,
+    pub step_mode: VertexStepMode,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl From<&VertexBufferLayout<'_>> for VertexLayoutKey {
+    fn from(layout: &VertexBufferLayout) -> Self {
+        Self {
+            array_stride: layout.array_stride,
+            step_mode: layout.step_mode,
+            attributes: layout.attributes.to_vec(),
+        }
+    }
+}
+
+// DepthStencilState isn't hashable because of the float bias, so only the parts we vary are keyed
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DepthKey {
+    pub format: TextureFormat,
+    pub write_enabled: bool,
+    pub compare: CompareFunction,
+    pub stencil: StencilState,
+}
+
+impl DepthKey {
+    pub fn state(&self) -> DepthStencilState {
+        DepthStencilState {
+            format: self.format,
+            depth_write_enabled: self.write_enabled,
+            depth_compare: self.compare,
+            stencil: self.stencil.clone(),
+            bias: DepthBiasState::default(),
+        }
+    }
+}
+
+// everything that makes two render pipelines different. shaders and layouts are referred to
+// by the name they were registered under
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub shader: &'static str,
+    pub layout: &'static str,
+    pub vertex_entry: &'static str,
+    pub fragment_entry: Option<&'static str>,
+    pub vertex_layouts: Vec<VertexLayoutKey>,
+    pub targets: Vec<ColorTargetState>,
+    pub primitive: PrimitiveState,
+    pub depth: Option<DepthKey>,
+    pub multisample: MultisampleState,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineId(usize);
+
+// creates render pipelines on first use and hands out the same one for identical state
+#[derive(Default)]
+pub struct PipelineCache {
+    shaders: HashMap<&'static str, ShaderModule>,
+    layouts: HashMap<&'static str, PipelineLayout>,
+    ids: HashMap<PipelineKey, PipelineId>,
+    pipelines: Vec<RenderPipeline>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_shader(&mut self, name: &'static str, module: ShaderModule) {
+        self.shaders.insert(name, module);
+    }
+
+    pub fn add_layout(&mut self, name: &'static str, layout: PipelineLayout) {
+        self.layouts.insert(name, layout);
+    }
+
+    pub fn get_or_create(&mut self, context: &RenderContext, key: &PipelineKey) -> PipelineId {
+        if let Some(&id) = self.ids.get(key) {
+            return id;
+        }
+
+        let module = self.shaders.get(key.shader).unwrap_or_else(|| panic!("unknown shader {}", key.shader));
+        let layout = self.layouts.get(key.layout).unwrap_or_else(|| panic!("unknown pipeline layout {}", key.layout));
+        let vertex_layouts: Vec<VertexBufferLayout> = key.vertex_layouts.iter().map(|layout| VertexBufferLayout {
+            array_stride: layout.array_stride,
+            step_mode: layout.step_mode,
+            attributes: &layout.attributes,
+        }).collect();
+        let targets: Vec<Option<ColorTargetState>> = key.targets.iter().cloned().map(Some).collect();
+        // variants of one shader differ by entry point, e.g. "mesh fragment_id"
+        let label = match key.fragment_entry {
+            Some(entry) => format!("{} {entry}", key.shader),
+            None => format!("{} {}", key.shader, key.vertex_entry),
+        };
+        let pipeline = context.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(&label),
+            layout: Some(layout),
+            vertex: VertexState {
+                entry_point: key.vertex_entry,
+                module,
+                buffers: &vertex_layouts,
+            },
+            fragment: key.fragment_entry.map(|entry_point| FragmentState {
+                entry_point,
+                module,
+                targets: &targets,
+            }),
+            primitive: key.primitive,
+            depth_stencil: key.depth.as_ref().map(DepthKey::state),
+            multisample: key.multisample,
+            multiview: None,
+        });
+
+        let id = PipelineId(self.pipelines.len());
+        self.pipelines.push(pipeline);
+        self.ids.insert(key.clone(), id);
+        id
+    }
+
+    pub fn get(&self, id: PipelineId) -> &RenderPipeline {
+        &self.pipelines[id.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+}
@@ -0,0 +1,248 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use wgpu::*;
+use crate::context::{self, RenderContext};
+use crate::light::{MAX_LIGHTS, MAX_POINT_LIGHTS};
+use crate::material_textures::{MAX_MATERIAL_TEXTURES, NO_TEXTURE};
+use crate::mesh::VERTEX_WORDS;
+use crate::probes::MAX_PROBES;
+use crate::shadows::{MAX_CASCADES, POINT_SHADOW_FACES};
+use crate::ssao::SSAO_KERNEL_SIZE;
+use crate::vertex_compression::COMPRESSED_VERTEX_WORDS;
+use crate::water::MAX_WAVES;
+
+// every shader and shared chunk, so includes resolve without touching the file system
+const SOURCES: &[(&str, &str)] = &[
+    ("atmosphere.wgsl", include_str!("atmosphere.wgsl")),
+    ("camera.wgsl", include_str!("camera.wgsl")),
+    ("canvas.wgsl", include_str!("canvas.wgsl")),
+    ("color.wgsl", include_str!("color.wgsl")),
+    ("culled_object.wgsl", include_str!("culled_object.wgsl")),
+    ("culling.wgsl", include_str!("culling.wgsl")),
+    ("debug.wgsl", include_str!("debug.wgsl")),
+    ("decals.wgsl", include_str!("decals.wgsl")),
+    ("grid.wgsl", include_str!("grid.wgsl")),
+    ("image_filter.wgsl", include_str!("image_filter.wgsl")),
+    ("image_view.wgsl", include_str!("image_view.wgsl")),
+    ("lights.wgsl", include_str!("lights.wgsl")),
+    ("loading.wgsl", include_str!("loading.wgsl")),
+    ("mesh.wgsl", include_str!("mesh.wgsl")),
+    ("mesh_culled.wgsl", include_str!("mesh_culled.wgsl")),
+    ("object.wgsl", include_str!("object.wgsl")),
+    ("outline.wgsl", include_str!("outline.wgsl")),
+    ("output.wgsl", include_str!("output.wgsl")),
+    ("particles.wgsl", include_str!("particles.wgsl")),
+    ("particles_compute.wgsl", include_str!("particles_compute.wgsl")),
+    ("sdf.wgsl", include_str!("sdf.wgsl")),
+    ("shader.wgsl", include_str!("shader.wgsl")),
+    ("sky.wgsl", include_str!("sky.wgsl")),
+    ("ssao.wgsl", include_str!("ssao.wgsl")),
+    ("ssao_blur.wgsl", include_str!("ssao_blur.wgsl")),
+    ("ssr.wgsl", include_str!("ssr.wgsl")),
+    ("ssr_composite.wgsl", include_str!("ssr_composite.wgsl")),
+    ("terrain.wgsl", include_str!("terrain.wgsl")),
+    ("text.wgsl", include_str!("text.wgsl")),
+    ("tilemap.wgsl", include_str!("tilemap.wgsl")),
+    ("viewport_clear.wgsl", include_str!("viewport_clear.wgsl")),
+    ("voxels.wgsl", include_str!("voxels.wgsl")),
+    ("water.wgsl", include_str!("water.wgsl")),
+];
+
+#[derive(Debug)]
+pub struct PreprocessError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+// preprocessed wgsl plus the file and line every output line came from
+pub struct Processed {
+    pub source: String,
+    files: Vec<String>,
+    // (index into files, line) per output line
+    origins: Vec<(usize, usize)>,
+}
+
+impl Processed {
+    // maps a 1-based line of the output back to its file and 1-based line
+    pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+        let &(file, line) = self.origins.get(line.checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+}
+
+// supports `#include "file"` (each file at most once), `#define NAME value`, `#ifdef`, `#ifndef`,
+// `#else` and `#endif`. defined names are substituted as whole words outside directives
+#[derive(Clone, Default)]
+pub struct Preprocessor {
+    defines: HashMap<String, String>,
+}
+
+impl Preprocessor {
+    // starts with the constants shared between rust and wgsl
+    pub fn new() -> Self {
+        Self::default()
+            .define("MAX_LIGHTS", MAX_LIGHTS)
+            .define("MAX_CASCADES", MAX_CASCADES)
+            .define("MAX_POINT_LIGHTS", MAX_POINT_LIGHTS)
+            .define("POINT_SHADOW_FACES", POINT_SHADOW_FACES)
+            .define("MAX_PROBES", MAX_PROBES)
+            .define("MAX_WAVES", MAX_WAVES)
+            .define("SSAO_KERNEL_SIZE", SSAO_KERNEL_SIZE)
+            .define("MAX_MATERIAL_TEXTURES", MAX_MATERIAL_TEXTURES)
+            .define("VERTEX_WORDS", VERTEX_WORDS)
+            .define("COMPRESSED_VERTEX_WORDS", COMPRESSED_VERTEX_WORDS)
+            .define("NO_TEXTURE", format!("{NO_TEXTURE}u"))
+    }
+
+    pub fn define(mut self, name: &str, value: impl ToString) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // shaders writing to a target that doesn't encode srgb itself do it in output_color. float
+    // targets hold linear values, so nothing is encoded and HDR_TARGET is defined instead
+    pub fn target(self, format: TextureFormat) -> Self {
+        if context::is_hdr_format(format) {
+            self.define("HDR_TARGET", 1)
+        } else if format.describe().srgb {
+            self
+        } else {
+            self.define("ENCODE_SRGB", 1)
+        }
+    }
+
+    pub fn process(&self, name: &str) -> Result<String, PreprocessError> {
+        Ok(self.process_mapped(name)?.source)
+    }
+
+    pub fn process_mapped(&self, name: &str) -> Result<Processed, PreprocessError> {
+        let text = source(name).ok_or_else(|| PreprocessError {
+            file: name.to_string(),
+            line: 0,
+            message: "no such shader".to_string(),
+        })?;
+        self.process_source(name, text)
+    }
+
+    // preprocesses wgsl that isn't built in, e.g. loaded from disk. it can still include the built in chunks
+    pub fn process_source(&self, name: &str, text: &str) -> Result<Processed, PreprocessError> {
+        let mut defines = self.defines.clone();
+        let mut included = HashSet::new();
+        let mut output = Processed {
+            source: String::new(),
+            files: Vec::new(),
+            origins: Vec::new(),
+        };
+        expand(name, text, &mut defines, &mut included, &mut output)?;
+        Ok(output)
+    }
+
+    // panics with the preprocessor error, shaders are part of the binary so this is a bug
+    pub fn create_module(&self, context: &RenderContext, name: &str) -> ShaderModule {
+        let source = self.process(name).unwrap_or_else(|error| panic!("{error}"));
+        context.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(name),
+            source: ShaderSource::Wgsl(Cow::Owned(source)),
+        })
+    }
+}
+
+pub fn source(name: &str) -> Option<&'static str> {
+    SOURCES.iter().find(|(file, _)| *file == name).map(|(_, source)| *source)
+}
+
+pub fn sources() -> impl Iterator<Item = (&'static str, &'static str)> {
+    SOURCES.iter().copied()
+}
+
+fn expand(
+    name: &str,
+    text: &str,
+    defines: &mut HashMap<String, String>,
+    included: &mut HashSet<String>,
+    output: &mut Processed,
+) -> Result<(), PreprocessError> {
+    if !included.insert(name.to_string()) {
+        return Ok(());
+    }
+    let file_index = output.files.len();
+    output.files.push(name.to_string());
+
+    // one entry per open #ifdef: (condition, currently in the #else branch)
+    let mut conditions: Vec<(bool, bool)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| PreprocessError { file: name.to_string(), line: index + 1, message };
+        let active = conditions.iter().all(|&(condition, in_else)| condition != in_else);
+        let trimmed = line.trim();
+        let mut words = trimmed.split_whitespace();
+        match words.next() {
+            Some("#ifdef") | Some("#ifndef") => {
+                let symbol = words.next().ok_or_else(|| error("missing name".to_string()))?;
+                let defined = defines.contains_key(symbol);
+                conditions.push((defined == trimmed.starts_with("#ifdef"), false));
+            }
+            Some("#else") => match conditions.last_mut() {
+                Some((_, in_else @ false)) => *in_else = true,
+                _ => return Err(error("#else without #ifdef".to_string())),
+            },
+            Some("#endif") => {
+                conditions.pop().ok_or_else(|| error("#endif without #ifdef".to_string()))?;
+            }
+            Some("#define") if active => {
+                let symbol = words.next().ok_or_else(|| error("missing name".to_string()))?;
+                let value: Vec<&str> = words.collect();
+                defines.insert(symbol.to_string(), value.join(" "));
+            }
+            Some("#include") if active => {
+                let file = trimmed["#include".len()..].trim().trim_matches('"');
+                let text = source(file).ok_or_else(|| error(format!("can't include {file}")))?;
+                expand(file, text, defines, included, output)?;
+            }
+            Some(directive) if directive.starts_with('#') && active => {
+                return Err(error(format!("unknown directive {directive}")));
+            }
+            _ if active => {
+                output.source.push_str(&substitute(line, defines));
+                output.source.push('\n');
+                output.origins.push((file_index, index + 1));
+            }
+            _ => {}
+        }
+    }
+    if !conditions.is_empty() {
+        return Err(PreprocessError { file: name.to_string(), line: text.lines().count(), message: "missing #endif".to_string() });
+    }
+    Ok(())
+}
+
+fn substitute(line: &str, defines: &HashMap<String, String>) -> String {
+    if defines.is_empty() {
+        return line.to_string();
+    }
+    let mut result = String::with_capacity(line.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, result: &mut String| {
+        result.push_str(defines.get(word.as_str()).map_or(word.as_str(), String::as_str));
+        word.clear();
+    };
+    for c in line.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut result);
+            result.push(c);
+        }
+    }
+    flush(&mut word, &mut result);
+    result
+}
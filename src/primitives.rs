@@ -0,0 +1,128 @@
+use std::f32::consts::{PI, TAU};
+use glam::{Vec2, Vec3};
+use crate::mesh::{Mesh, Vertex};
+
+// all primitives are centered on the origin with counter-clockwise front faces.
+// tangents point along +u with the bitangent sign in w
+
+fn vertex(position: Vec3, normal: Vec3, uv: Vec2, tangent: Vec3) -> Vertex {
+    Vertex {
+        position: position.to_array(),
+        normal: normal.to_array(),
+        uv: uv.to_array(),
+        tangent: tangent.extend(1.0).to_array(),
+        ..Vertex::default()
+    }
+}
+
+// a (columns + 1) x (rows + 1) vertex grid, `point` maps uv to (position, normal, tangent)
+fn grid(mesh: &mut Mesh, columns: u32, rows: u32, point: impl Fn(Vec2) -> (Vec3, Vec3, Vec3)) {
+    let base = mesh.vertices.len() as u32;
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let uv = Vec2::new(column as f32 / columns as f32, row as f32 / rows as f32);
+            let (position, normal, tangent) = point(uv);
+            mesh.vertices.push(vertex(position, normal, uv, tangent));
+        }
+    }
+    let index = |column: u32, row: u32| base + row * (columns + 1) + column;
+    for row in 0..rows {
+        for column in 0..columns {
+            let (a, b) = (index(column, row), index(column + 1, row));
+            let (c, d) = (index(column, row + 1), index(column + 1, row + 1));
+            mesh.indices.extend([a, c, b, b, c, d]);
+        }
+    }
+}
+
+// flat on the xz plane facing +y
+pub fn plane(size: f32, subdivisions: u32) -> Mesh {
+    let mut mesh = Mesh::default();
+    let subdivisions = subdivisions.max(1);
+    grid(&mut mesh, subdivisions, subdivisions, |uv| {
+        let position = Vec3::new(uv.x - 0.5, 0.0, uv.y - 0.5) * size;
+        (position, Vec3::Y, Vec3::X)
+    });
+    mesh
+}
+
+pub fn cube(size: f32) -> Mesh {
+    let mut mesh = Mesh::default();
+    let half = size * 0.5;
+    // (normal, u axis, v axis) per face, with v x u == normal so the grid faces outwards
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::NEG_Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::NEG_Y),
+        (Vec3::Y, Vec3::X, Vec3::Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::Z, Vec3::X, Vec3::NEG_Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::NEG_Y),
+    ];
+    for (normal, u, v) in faces {
+        grid(&mut mesh, 1, 1, |uv| {
+            let position = (normal + u * (uv.x * 2.0 - 1.0) + v * (uv.y * 2.0 - 1.0)) * half;
+            (position, normal, u)
+        });
+    }
+    mesh
+}
+
+pub fn sphere(radius: f32, sectors: u32, stacks: u32) -> Mesh {
+    let mut mesh = Mesh::default();
+    grid(&mut mesh, sectors.max(3), stacks.max(2), |uv| {
+        let theta = uv.x * TAU;
+        let phi = uv.y * PI;
+        let normal = Vec3::new(-theta.cos() * phi.sin(), phi.cos(), theta.sin() * phi.sin());
+        let tangent = Vec3::new(theta.sin(), 0.0, theta.cos());
+        (normal * radius, normal, tangent)
+    });
+    mesh
+}
+
+// along the y axis, with caps
+pub fn cylinder(radius: f32, height: f32, sectors: u32) -> Mesh {
+    let mut mesh = Mesh::default();
+    let sectors = sectors.max(3);
+    let half = height * 0.5;
+    grid(&mut mesh, sectors, 1, |uv| {
+        let theta = uv.x * TAU;
+        let normal = Vec3::new(-theta.cos(), 0.0, theta.sin());
+        let tangent = Vec3::new(theta.sin(), 0.0, theta.cos());
+        (normal * radius + Vec3::Y * (half - uv.y * height), normal, tangent)
+    });
+
+    for (y, normal) in [(half, Vec3::Y), (-half, Vec3::NEG_Y)] {
+        let center = mesh.vertices.len() as u32;
+        mesh.vertices.push(vertex(Vec3::Y * y, normal, Vec2::splat(0.5), Vec3::X));
+        for sector in 0..=sectors {
+            let theta = sector as f32 / sectors as f32 * TAU;
+            let direction = Vec3::new(-theta.cos(), 0.0, theta.sin());
+            // v is mirrored on the bottom cap so the tangent frame keeps the same handedness
+            let uv = Vec2::new(direction.x, direction.z * normal.y) * 0.5 + 0.5;
+            mesh.vertices.push(vertex(direction * radius + Vec3::Y * y, normal, uv, Vec3::X));
+        }
+        for sector in 0..sectors {
+            let (a, b) = (center + 1 + sector, center + 2 + sector);
+            if normal.y > 0.0 {
+                mesh.indices.extend([center, a, b]);
+            } else {
+                mesh.indices.extend([center, b, a]);
+            }
+        }
+    }
+    mesh
+}
+
+// lies in the xz plane around the y axis
+pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> Mesh {
+    let mut mesh = Mesh::default();
+    grid(&mut mesh, major_segments.max(3), minor_segments.max(3), |uv| {
+        let theta = uv.x * TAU;
+        let phi = uv.y * TAU;
+        let ring = Vec3::new(-theta.cos(), 0.0, theta.sin());
+        let normal = ring * phi.cos() - Vec3::Y * phi.sin();
+        let tangent = Vec3::new(theta.sin(), 0.0, theta.cos());
+        (ring * major_radius + normal * minor_radius, normal, tangent)
+    });
+    mesh
+}
@@ -0,0 +1,328 @@
+use std::cell::Cell;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+use wgpu::*;
+use crate::bindings::LayoutRegistry;
+use crate::camera::{CameraBinding, CameraUniform, FrameTextures};
+use crate::context::RenderContext;
+use crate::light::SceneLights;
+use crate::renderer::{create_depth_view, HDR_FORMAT};
+use crate::shadows::{ShadowTextures, ShadowUniform};
+
+// matches the array size in lights.wgsl
+pub const MAX_PROBES: usize = 4;
+// the near plane of the faces, world units
+const CAPTURE_NEAR: f32 = 0.05;
+
+// captures what's around position into a cube, which surfaces inside the box of extents around
+// it reflect. the reflections are projected onto the box's walls, so they line up best when
+// the box matches the room it's in
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReflectionProbe {
+    pub position: Vec3,
+    // half the box's size along each axis
+    pub extents: Vec3,
+}
+
+impl Default for ReflectionProbe {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            extents: Vec3::splat(5.0),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ProbeSettings {
+    pub enabled: bool,
+    // width and height of each cube face
+    pub resolution: u32,
+    // reflectivity looking straight at a surface, grazing angles go towards 1
+    pub strength: f32,
+    // how far the faces see, world units
+    pub distance: f32,
+}
+
+impl Default for ProbeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resolution: 128,
+            strength: 0.1,
+            distance: 100.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GpuProbe {
+    position: [f32; 4],
+    extents: [f32; 4],
+}
+
+// part of the lights uniform, see lights.wgsl
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ProbeUniform {
+    probes: [GpuProbe; MAX_PROBES],
+    count: u32,
+    strength: f32,
+    _padding: [u32; 2],
+}
+
+impl ProbeUniform {
+    // no probes to sample, for cameras bound with ReflectionProbes::empty_textures
+    pub const NONE: ProbeUniform = ProbeUniform {
+        probes: [GpuProbe { position: [0.0; 4], extents: [0.0; 4] }; MAX_PROBES],
+        count: 0,
+        strength: 0.0,
+        _padding: [0; 2],
+    };
+}
+
+// what FrameTextures binds of the probes
+#[derive(Copy, Clone)]
+pub struct ProbeTextures<'a> {
+    pub maps: &'a TextureView,
+    pub sampler: &'a Sampler,
+}
+
+// a cube per reflection probe in the world, up to MAX_PROBES, as layers of one cube array. the
+// faces are drawn like a view, without grid, particles or debug lines, when the probes change
+// and when capture is called, not every frame. the faces are drawn without probes themselves
+pub struct ReflectionProbes {
+    resolution: u32,
+    maps: TextureView,
+    faces: Vec<TextureView>,
+    depth: TextureView,
+    // bound where there are no probes, and while the faces are drawn
+    empty: TextureView,
+    sampler: Sampler,
+    // one per face, made by bind. captures are rare, so there's no ring of them
+    camera_bindings: Vec<CameraBinding>,
+    // the probes the cubes were last asked to capture, in layer order
+    probes: Vec<ReflectionProbe>,
+    // by capture, taken up by the next update
+    requested: bool,
+    // set by update, cleared once encoded
+    pending: Cell<bool>,
+    // probes whose cubes hold a capture, sampled from the next update on
+    captured: Cell<usize>,
+    uniform: ProbeUniform,
+}
+
+impl ReflectionProbes {
+    pub fn new(context: &RenderContext) -> Self {
+        let resolution = ProbeSettings::default().resolution.min(context.limits().max_texture_dimension_2d);
+        let (maps, faces) = create_cubes(context, "reflection probes", resolution, MAX_PROBES);
+        let (empty, _) = create_cubes(context, "no reflection probes", 1, 1);
+        let sampler = context.device.create_sampler(&SamplerDescriptor {
+            label: Some("reflection probes"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        Self {
+            resolution,
+            maps,
+            faces,
+            depth: create_depth_view(context, "reflection probe depth", resolution, resolution),
+            empty,
+            sampler,
+            camera_bindings: Vec::new(),
+            probes: Vec::new(),
+            requested: false,
+            pending: Cell::new(false),
+            captured: Cell::new(0),
+            uniform: ProbeUniform::NONE,
+        }
+    }
+
+    pub fn textures(&self) -> ProbeTextures {
+        ProbeTextures {
+            maps: &self.maps,
+            sampler: &self.sampler,
+        }
+    }
+
+    // for everything else, with ProbeUniform::NONE
+    pub fn empty_textures(&self) -> ProbeTextures {
+        ProbeTextures {
+            maps: &self.empty,
+            sampler: &self.sampler,
+        }
+    }
+
+    // what the cameras bound with textures get as their probes, as of the last update
+    pub fn uniform(&self) -> &ProbeUniform {
+        &self.uniform
+    }
+
+    // captures every probe again after the next update, e.g. once the scene around them changed
+    pub fn capture(&mut self) {
+        self.requested = true;
+    }
+
+    // whether the next frame draws the faces, see face_count
+    pub fn capture_pending(&self) -> bool {
+        self.pending.get()
+    }
+
+    // the faces to draw while a capture is pending, six per probe
+    pub fn face_count(&self) -> usize {
+        self.probes.len() * 6
+    }
+
+    pub fn camera_binding(&self, face: usize) -> &CameraBinding {
+        &self.camera_bindings[face]
+    }
+
+    // what the faces are lit with, again whenever the shadow maps are recreated
+    pub fn bind(&mut self, context: &RenderContext, layouts: &mut LayoutRegistry, occlusion: &TextureView, shadows: ShadowTextures) {
+        let textures = FrameTextures {
+            occlusion,
+            shadows,
+            probes: ProbeTextures {
+                maps: &self.empty,
+                sampler: &self.sampler,
+            },
+        };
+        if self.camera_bindings.is_empty() {
+            self.camera_bindings = (0..MAX_PROBES * 6).map(|_| CameraBinding::new(context, layouts, textures)).collect();
+        }
+        for camera_binding in &mut self.camera_bindings {
+            camera_binding.set_textures(context, layouts, textures);
+        }
+    }
+
+    // probes past MAX_PROBES are left out. a different set of probes, or a new resolution,
+    // captures them again, lit by lights and the main view's shadows. returns true when the
+    // cubes were recreated, bindings made from textures have to be rebuilt
+    pub fn update(&mut self, context: &RenderContext, settings: &ProbeSettings, probes: &[ReflectionProbe], lights: &SceneLights, shadows: &ShadowUniform) -> bool {
+        let resolution = settings.resolution.clamp(1, context.limits().max_texture_dimension_2d);
+        let resized = resolution != self.resolution;
+        if resized {
+            (self.maps, self.faces) = create_cubes(context, "reflection probes", resolution, MAX_PROBES);
+            self.depth = create_depth_view(context, "reflection probe depth", resolution, resolution);
+            self.resolution = resolution;
+            self.captured.set(0);
+        }
+        if !settings.enabled {
+            self.uniform = ProbeUniform::NONE;
+            return resized;
+        }
+        let probes = &probes[..probes.len().min(MAX_PROBES)];
+        if resized || probes != self.probes {
+            self.probes = probes.to_vec();
+            self.captured.set(0);
+            self.requested = true;
+        }
+        if std::mem::take(&mut self.requested) {
+            self.pending.set(true);
+        }
+
+        if self.pending.get() {
+            // looking down each axis, with -z and +z swapped. cube lookups are left handed, so
+            // faces drawn like any other view come out mirrored, lights.wgsl flips z back
+            let faces = [
+                (Vec3::X, Vec3::Y),
+                (Vec3::NEG_X, Vec3::Y),
+                (Vec3::Y, Vec3::Z),
+                (Vec3::NEG_Y, Vec3::NEG_Z),
+                (Vec3::NEG_Z, Vec3::Y),
+                (Vec3::Z, Vec3::Y),
+            ];
+            let far = settings.distance.max(CAPTURE_NEAR * 2.0);
+            let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, CAPTURE_NEAR, far);
+            for (index, probe) in self.probes.iter().enumerate() {
+                for (face, &(forward, up)) in faces.iter().enumerate() {
+                    let view = Mat4::look_at_rh(probe.position, probe.position + forward, up);
+                    let camera_binding = &self.camera_bindings[index * 6 + face];
+                    camera_binding.update_uniform(context, &CameraUniform::from_matrices(view, projection, probe.position, far));
+                    camera_binding.update_lights(context, lights, shadows, &ProbeUniform::NONE);
+                }
+            }
+        }
+
+        let mut uniform = ProbeUniform {
+            count: self.captured.get() as u32,
+            strength: settings.strength.clamp(0.0, 1.0),
+            ..ProbeUniform::NONE
+        };
+        for (probe, gpu) in self.probes.iter().zip(&mut uniform.probes) {
+            *gpu = GpuProbe {
+                position: probe.position.extend(1.0).to_array(),
+                extents: probe.extents.abs().extend(0.0).to_array(),
+            };
+        }
+        self.uniform = uniform;
+        resized
+    }
+
+    // into one face, cleared to clear. marks the probes captured once the last face is begun
+    pub fn begin_pass<'a>(&'a self, cmd: &'a mut CommandEncoder, face: usize, clear: Color) -> RenderPass<'a> {
+        if face + 1 == self.face_count() {
+            self.pending.set(false);
+            self.captured.set(self.probes.len());
+        }
+        cmd.begin_render_pass(&RenderPassDescriptor {
+            label: Some("reflection probe face"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    ops: Operations {
+                        load: LoadOp::Clear(clear),
+                        store: true,
+                    },
+                    view: &self.faces[face],
+                    resolve_target: None,
+                })
+            ],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: false,
+                }),
+                stencil_ops: Some(Operations {
+                    load: LoadOp::Clear(0),
+                    store: false,
+                }),
+            }),
+        })
+    }
+}
+
+// the cube array view for sampling, and one view per face to draw into
+fn create_cubes(context: &RenderContext, label: &str, resolution: u32, cubes: usize) -> (TextureView, Vec<TextureView>) {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: cubes as u32 * 6,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: HDR_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    let maps = texture.create_view(&TextureViewDescriptor {
+        label: Some(label),
+        dimension: Some(TextureViewDimension::CubeArray),
+        ..TextureViewDescriptor::default()
+    });
+    let faces = (0..cubes as u32 * 6)
+        .map(|layer| texture.create_view(&TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: std::num::NonZeroU32::new(1),
+            ..TextureViewDescriptor::default()
+        }))
+        .collect();
+    (maps, faces)
+}
@@ -0,0 +1,79 @@
+use glam::{Mat4, Vec2, Vec3};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+    // along the ray, in units of the ray direction
+    pub distance: f32,
+    pub position: Vec3,
+    pub uv: Vec2,
+    pub node: usize,
+    pub triangle: usize,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    // the direction is left unnormalized so distances stay comparable across spaces
+    pub fn transform(&self, matrix: Mat4) -> Self {
+        Self {
+            origin: matrix.transform_point3(self.origin),
+            direction: matrix.transform_vector3(self.direction),
+        }
+    }
+
+    // in front of the ray only, None when it runs along the plane
+    pub fn intersect_plane(&self, origin: Vec3, normal: Vec3) -> Option<f32> {
+        let facing = self.direction.dot(normal);
+        if facing.abs() < 1e-6 {
+            return None;
+        }
+        let distance = (origin - self.origin).dot(normal) / facing;
+        (distance >= 0.0).then_some(distance)
+    }
+
+    // slab test, returns the entry distance (0 when starting inside)
+    pub fn intersect_aabb(&self, min: Vec3, max: Vec3) -> Option<f32> {
+        let inverse = self.direction.recip();
+        let t0 = (min - self.origin) * inverse;
+        let t1 = (max - self.origin) * inverse;
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element();
+        (near <= far).then_some(near)
+    }
+
+    // möller-trumbore, double sided. returns the distance and the barycentric weights of b and c
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<(f32, Vec2)> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let det = ab.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inverse_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * inverse_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(ab);
+        let v = self.direction.dot(q) * inverse_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = ac.dot(q) * inverse_det;
+        (t >= 0.0).then_some((t, Vec2::new(u, v)))
+    }
+}
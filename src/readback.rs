@@ -0,0 +1,170 @@
+use std::fmt;
+use std::future::Future;
+use std::num::NonZeroU32;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use wgpu::*;
+use crate::context::RenderContext;
+
+#[derive(Debug)]
+pub enum ReadbackError {
+    Map(BufferAsyncError),
+}
+
+impl fmt::Display for ReadbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadbackError::Map(error) => write!(f, "failed to map readback buffer: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadbackError {}
+
+impl From<BufferAsyncError> for ReadbackError {
+    fn from(error: BufferAsyncError) -> Self {
+        ReadbackError::Map(error)
+    }
+}
+
+// how texture rows sit in the staging buffer, each padded to COPY_BYTES_PER_ROW_ALIGNMENT
+#[derive(Copy, Clone, Debug)]
+struct Rows {
+    bytes: u32,
+    padded_bytes: u32,
+}
+
+#[derive(Default)]
+struct State {
+    mapped: Option<Result<(), BufferAsyncError>>,
+    waker: Option<Waker>,
+    taken: bool,
+}
+
+// a copy of gpu memory on its way back, started by RenderContext::read_buffer or read_texture.
+// it's a future that resolves once the device has been polled past the copy, which the event
+// loop does every frame. try_take checks without waiting, wait blocks until it's there
+pub struct Readback {
+    staging: Buffer,
+    // none for buffers, which come back as they are
+    rows: Option<Rows>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Readback {
+    // offsets and sizes have to be multiples of COPY_BUFFER_ALIGNMENT, the source needs COPY_SRC
+    pub fn buffer(context: &RenderContext, source: &Buffer, range: Range<BufferAddress>) -> Self {
+        let size = range.end - range.start;
+        assert!(
+            range.start % COPY_BUFFER_ALIGNMENT == 0 && size % COPY_BUFFER_ALIGNMENT == 0,
+            "buffer readback of {range:?} isn't {COPY_BUFFER_ALIGNMENT} byte aligned",
+        );
+        let staging = create_staging(context, size);
+        let mut cmd = context.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("buffer readback") });
+        cmd.copy_buffer_to_buffer(source, range.start, &staging, 0, size);
+        Self::start(context, cmd, staging, None)
+    }
+
+    // a region of one mip level, layers come back one after another. the format can't be block
+    // compressed, the texture needs COPY_SRC
+    pub fn texture(context: &RenderContext, source: ImageCopyTexture, format: TextureFormat, size: Extent3d) -> Self {
+        let info = format.describe();
+        assert_eq!(info.block_dimensions, (1, 1), "can't read back compressed {format:?}");
+        let bytes = size.width * info.block_size as u32;
+        let padded_bytes = bytes.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging = create_staging(context, (padded_bytes * size.height * size.depth_or_array_layers) as BufferAddress);
+        let mut cmd = context.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("texture readback") });
+        cmd.copy_texture_to_buffer(
+            source,
+            ImageCopyBuffer {
+                buffer: &staging,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes),
+                    rows_per_image: NonZeroU32::new(size.height),
+                },
+            },
+            size,
+        );
+        Self::start(context, cmd, staging, Some(Rows { bytes, padded_bytes }))
+    }
+
+    fn start(context: &RenderContext, cmd: CommandEncoder, staging: Buffer, rows: Option<Rows>) -> Self {
+        context.queue.submit([cmd.finish()]);
+        let state = Arc::new(Mutex::new(State::default()));
+        let mapped = state.clone();
+        staging.slice(..).map_async(MapMode::Read, move |result| {
+            let mut state = mapped.lock().unwrap();
+            state.mapped = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        Self {
+            staging,
+            rows,
+            state,
+        }
+    }
+
+    // the data once it's back, at most once. doesn't poll the device itself
+    pub fn try_take(&mut self) -> Option<Result<Vec<u8>, ReadbackError>> {
+        let mut state = self.state.lock().unwrap();
+        self.take(&mut state)
+    }
+
+    // blocks until the gpu has caught up with the copy
+    pub fn wait(mut self, context: &RenderContext) -> Result<Vec<u8>, ReadbackError> {
+        context.device.poll(Maintain::Wait);
+        self.try_take().expect("readback not mapped after waiting for the device")
+    }
+
+    fn take(&self, state: &mut State) -> Option<Result<Vec<u8>, ReadbackError>> {
+        if state.taken {
+            return None;
+        }
+        let mapped = state.mapped.take()?;
+        state.taken = true;
+        if let Err(error) = mapped {
+            return Some(Err(error.into()));
+        }
+        let data = {
+            let mapped = self.staging.slice(..).get_mapped_range();
+            match self.rows {
+                Some(rows) => mapped.chunks_exact(rows.padded_bytes as usize)
+                    .flat_map(|row| &row[..rows.bytes as usize])
+                    .copied()
+                    .collect(),
+                None => mapped.to_vec(),
+            }
+        };
+        self.staging.unmap();
+        Some(Ok(data))
+    }
+}
+
+impl Future for Readback {
+    type Output = Result<Vec<u8>, ReadbackError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match self.take(&mut state) {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn create_staging(context: &RenderContext, size: BufferAddress) -> Buffer {
+    context.device.create_buffer(&BufferDescriptor {
+        label: Some("readback"),
+        size,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,91 @@
+// the scene is drawn at the window size times this and filtered to the window by the output pass
+#[derive(Copy, Clone, Debug)]
+pub struct RenderScale {
+    // below 1 renders fewer pixels, above 1 supersamples
+    pub scale: f32,
+    // when set the scale is driven by the measured gpu time instead, between min and max
+    pub dynamic: bool,
+    pub target_frame_time: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for RenderScale {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            dynamic: false,
+            target_frame_time: 1.0 / 60.0,
+            min: 0.5,
+            max: 1.0,
+        }
+    }
+}
+
+// how far the scale moves per adjustment, also what it's rounded to so targets aren't recreated
+// for tiny changes
+const STEP: f32 = 0.05;
+// frames to wait after a change, so the timings measured are for the new size
+const COOLDOWN: u32 = 30;
+
+// picks a scale from gpu frame times: down when over the target, back up when well under it
+pub struct DynamicScale {
+    scale: f32,
+    // smoothed seconds per frame, 0 until the first measurement
+    frame_time: f32,
+    cooldown: u32,
+}
+
+impl DynamicScale {
+    pub fn new() -> Self {
+        Self {
+            scale: 1.0,
+            frame_time: 0.0,
+            cooldown: COOLDOWN,
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn frame_time(&self) -> f32 {
+        self.frame_time
+    }
+
+    pub fn measure(&mut self, frame_time: f32) {
+        self.frame_time = if self.frame_time == 0.0 { frame_time } else { self.frame_time * 0.9 + frame_time * 0.1 };
+    }
+
+    // the scale to render the next frame at
+    pub fn update(&mut self, settings: &RenderScale) -> f32 {
+        if !settings.dynamic {
+            self.scale = settings.scale;
+            return self.scale;
+        }
+        self.cooldown = self.cooldown.saturating_sub(1);
+        if self.cooldown == 0 && self.frame_time > 0.0 {
+            let target = settings.target_frame_time;
+            let step = if self.frame_time > target * 1.05 {
+                -STEP
+            } else if self.frame_time < target * 0.8 {
+                STEP
+            } else {
+                0.0
+            };
+            if step != 0.0 {
+                self.scale = ((self.scale + step) / STEP).round() * STEP;
+                self.cooldown = COOLDOWN;
+                self.frame_time = 0.0;
+            }
+        }
+        self.scale = self.scale.clamp(settings.min, settings.max);
+        self.scale
+    }
+}
+
+impl Default for DynamicScale {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use wgpu::*;
+use crate::bindings::LayoutRegistry;
+use crate::camera::{Camera, CameraBinding, FrameTextures};
+use crate::context::RenderContext;
+use crate::frames::FrameRing;
+use crate::light::SceneLights;
+use crate::renderer::{create_depth_view, create_hdr_view};
+use crate::probes::ProbeUniform;
+use crate::shadows::ShadowUniform;
+use crate::world::Entity;
+
+// on a camera entity, draws what it sees into a texture of this size instead of the window, for
+// screens in the scene like a security camera feed. the size doesn't follow the window or the
+// render scale
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderTarget {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for RenderTarget {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 512,
+        }
+    }
+}
+
+// on an entity with a MeshRef, shows the texture of the camera entity's RenderTarget, tinted by
+// the base color. a screen isn't drawn into its own target, it'd be sampled while drawn into
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Screen(pub Entity);
+
+// the gpu side of a RenderTarget, kept by the renderer. targets are drawn before the scene, in
+// no particular order, so a screen showing another target may be a frame behind
+pub struct OffscreenTarget {
+    pub size: RenderTarget,
+    pub camera: Camera,
+    color: TextureView,
+    depth: TextureView,
+    camera_bindings: FrameRing<CameraBinding>,
+}
+
+impl OffscreenTarget {
+    // nothing is occluded or shadowed, ssao and shadows only run for the window
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, size: RenderTarget, textures: FrameTextures) -> Self {
+        let (width, height) = (size.width.max(1), size.height.max(1));
+        Self {
+            size,
+            camera: Camera::default(),
+            color: create_hdr_view(context, "render target", width, height),
+            depth: create_depth_view(context, "render target depth", width, height),
+            camera_bindings: FrameRing::new(|| CameraBinding::new(context, layouts, textures)),
+        }
+    }
+
+    // what screens sample, in linear hdr like the scene
+    pub fn view(&self) -> &TextureView {
+        &self.color
+    }
+
+    pub fn camera_binding(&self, slot: usize) -> &CameraBinding {
+        self.camera_bindings.get(slot)
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.size.width.max(1) as f32 / self.size.height.max(1) as f32
+    }
+
+    pub fn update(&self, context: &RenderContext, slot: usize, lights: &SceneLights) {
+        let camera_binding = self.camera_bindings.get(slot);
+        camera_binding.update(context, &self.camera, self.aspect());
+        camera_binding.update_lights(context, lights, &ShadowUniform::NONE, &ProbeUniform::NONE);
+    }
+
+    pub fn begin_pass<'a>(&'a self, cmd: &'a mut CommandEncoder, clear: Color) -> RenderPass<'a> {
+        cmd.begin_render_pass(&RenderPassDescriptor {
+            label: Some("render target"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    ops: Operations {
+                        load: LoadOp::Clear(clear),
+                        store: true,
+                    },
+                    view: &self.color,
+                    resolve_target: None,
+                })
+            ],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+}
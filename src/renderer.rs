@@ -0,0 +1,1535 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+use glam::{Mat4, Vec3};
+use image::RgbaImage;
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::bindings::LayoutRegistry;
+use crate::bundles::{BundleInputs, StaticBundles};
+use crate::camera::{Camera, CameraBinding, FrameTextures};
+use crate::canvas::{Canvas, CanvasImage};
+use crate::capture;
+use crate::color_grading::ColorLut;
+use crate::context::RenderContext;
+use crate::culling::GpuCulling;
+use crate::debug::DebugDraw;
+use crate::decals::{Decal, Decals};
+use crate::frame_history::{FrameDump, FrameHistory, FrameHistorySettings};
+use crate::frames::{FramePacer, FrameRing};
+use crate::grid::{Grid, GridSettings};
+use crate::animation::AnimationPlayer;
+use crate::app::FrameTime;
+use crate::atmosphere::{AtmosphereSettings, Sky};
+use crate::light::{DirectionalLight, PointLight, SceneLights};
+use crate::image_filter::{ImageFilter, ImageFilters};
+use crate::image_view::ImageView;
+use crate::loading::LoadingScreen;
+use crate::material_textures::MaterialTextures;
+use crate::mesh::{self, Material, MeshPipelineIds, SharedObjects};
+use crate::outline::{Outline, OutlineSettings};
+use crate::output::{OutputPass, OutputSettings, CAPTURE_FORMAT};
+use crate::parallel_recording::{MeshPass, ParallelRecording, RecordingPool, RecordingStats};
+use crate::model::ModelInstance;
+use crate::particles::{Emitter, ParticleSystem};
+use crate::picking::{Picker, ID_FORMAT};
+use crate::pipeline_cache::PipelineCache;
+use crate::preprocessor::Preprocessor;
+use crate::raycast::{Hit, Ray};
+use crate::render_scale::{DynamicScale, RenderScale};
+use crate::render_target::{OffscreenTarget, RenderTarget, Screen};
+use crate::probes::{ProbeSettings, ReflectionProbe, ReflectionProbes};
+use crate::shadows::{ShadowMaps, ShadowSettings, ShadowUniform};
+use crate::ssao::{Ssao, SsaoSettings, NORMAL_DEPTH_FORMAT};
+use crate::ssr::{Ssr, SsrSettings};
+use crate::viewport::{View, Viewport, ViewportClear};
+use crate::voxels::{VoxelSettings, Voxels};
+use crate::water::{Water, WaterSettings};
+use crate::terrain::{Heightmap, Terrain, TerrainConfig};
+use crate::tilemap::{TiledMap, Tilemap, TilemapError};
+use crate::text::TextRenderer;
+use crate::texture::Texture;
+use crate::timestamps::FrameTimestamps;
+use crate::transform::Transform;
+use crate::vertex_compression::VertexEncoding;
+use crate::world::{Entity, MeshRef, PreviousTransform, Without, World};
+
+// the stencil marks selected objects, see Outline
+pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+// the scene is drawn in linear light into a target of this format, the output pass then maps it
+// to whatever the surface wants
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+// what the scene target and every view start out as, until clear_color is changed
+pub const CLEAR_COLOR: Color = Color::RED;
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Vertex {
+    pos: [f32; 2],
+}
+
+const VERTEX_SIZE: BufferAddress = size_of::<Vertex>() as BufferAddress;
+
+// compute passes of a frame, see Renderer::async_compute
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct ComputeWork {
+    culling: bool,
+    particles: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RenderEvent {
+    // None when the background was picked
+    Picked(Option<Entity>),
+}
+
+// draws what's in a World: entities with a MeshRef, DirectionalLights, PointLights,
+// ReflectionProbes and every Camera, see
+// Viewport and RenderTarget. terrain, particles and debug lines aren't entities and are owned here
+pub struct Renderer {
+    pub particles: ParticleSystem,
+    pub terrain: Option<Terrain>,
+    // a 2d map behind the main view, see set_tilemap
+    pub tilemap: Option<Tilemap>,
+    pub debug: DebugDraw,
+    // applied by the output pass on the next update
+    pub output: OutputSettings,
+    pub ssao: SsaoSettings,
+    // reflections blended over the scene, see Ssr
+    pub ssr: SsrSettings,
+    // cascaded shadows from the first light and cubes for point lights, see ShadowMaps
+    pub shadows: ShadowSettings,
+    // reflections of the surroundings captured around each ReflectionProbe, see ReflectionProbes
+    pub probes: ProbeSettings,
+    // fog over everything lit and a sky in place of the clear color, see AtmosphereSettings
+    pub atmosphere: AtmosphereSettings,
+    // an animated plane of waves, see Water
+    pub water: WaterSettings,
+    // a generated block landscape streamed in around the main camera, see Voxels
+    pub voxels: VoxelSettings,
+    // static meshes are culled and drawn indirectly on the gpu, where supported. see GpuCulling
+    pub gpu_culling: bool,
+    // scales the screen size meshes pick their lod by, as seen from the main view. above 1 keeps
+    // detail longer, 0 always draws the base meshes
+    pub lod_bias: f32,
+    // meshes drawn one by one read their vertices from a storage buffer by vertex index instead
+    // of through vertex buffers. gpu culled draws always use vertex buffers
+    pub vertex_pulling: bool,
+    pub render_scale: RenderScale,
+    pub pacing: FramePacer,
+    // outlined in every view, e.g. what was last picked
+    pub selected: Vec<Entity>,
+    pub outline: OutlineSettings,
+    pub grid: GridSettings,
+    // gpu time of every frame while Some, see FrameTimestamps
+    pub timestamps: Option<FrameTimestamps>,
+    // queued text and rectangles, drawn over the finished frame
+    pub text: TextRenderer,
+    // queued 2d shapes and images, drawn over the finished frame under the text
+    pub canvas: Canvas,
+    pub clear_color: Color,
+    // meshes drawn one by one show their triangle edges, where Features::POLYGON_MODE_LINE is
+    // supported. gpu culled draws stay solid
+    pub wireframe: bool,
+    // the last seconds of output kept around to save, see save_frame_history
+    pub frame_history: FrameHistorySettings,
+    // records the main views' meshes on several threads in big scenes, see recording_stats
+    pub parallel_recording: ParallelRecording,
+    // static meshes are recorded into render bundles once and replayed every frame while gpu
+    // culling is off, see StaticBundles. takes the place of parallel recording
+    pub render_bundles: bool,
+    // meshes are uploaded with quantized positions, octahedral normals and half float uvs, see
+    // CompressedVertex. switching uploads them all again on the next update. gpu culling keeps
+    // its own full copy of what it draws
+    pub vertex_compression: bool,
+    // gpu culling and the particle simulation are submitted on their own at the end of update,
+    // so the gpu starts on them behind the previous frame while this one is still being encoded.
+    // wgpu has one queue per device, so the overlap is whatever the driver finds between
+    // submissions. colliding particles read the normal prepass and stay in the frame
+    pub async_compute: bool,
+
+    render_pipeline: RenderPipeline,
+    vertex_buffer: Buffer,
+    // the frame group is written every frame, so there's one per frame in flight, and one per
+    // view in each
+    camera_bindings: FrameRing<Vec<CameraBinding>>,
+    // bumped when the camera bindings' textures are rebound
+    frame_textures_generation: u32,
+    // recorded with the camera bindings of the same slot
+    static_bundles: FrameRing<StaticBundles>,
+    // what submit_compute already submitted this frame, encode leaves it out
+    compute_submitted: ComputeWork,
+    viewport_clear: ViewportClear,
+    layouts: LayoutRegistry,
+    pipelines: PipelineCache,
+    // with vertex buffers, then pulled
+    mesh_render_pipeline: [MeshPipelineIds; 2],
+    mesh_normal_pipeline: [MeshPipelineIds; 2],
+    // none without POLYGON_MODE_LINE
+    mesh_wireframe_pipeline: Option<[MeshPipelineIds; 2]>,
+    mesh_shadow_pipeline: [MeshPipelineIds; 2],
+    mesh_point_shadow_pipeline: [MeshPipelineIds; 2],
+    // created when gpu_culling is first turned on
+    culling: Option<GpuCulling>,
+    material_textures: MaterialTextures,
+    loading_screen: LoadingScreen,
+    image_view: ImageView,
+    image_filters: ImageFilters,
+    // while Some only the loading screen is drawn
+    loading: Option<f32>,
+    depth_view: TextureView,
+    hdr_view: TextureView,
+    output_pass: OutputPass,
+    history: FrameHistory,
+    ssao_pass: Ssao,
+    ssr_pass: Ssr,
+    shadow_maps: ShadowMaps,
+    reflection_probes: ReflectionProbes,
+    sky: Sky,
+    decals: Decals,
+    water_pass: Water,
+    voxel_pass: Voxels,
+    outline_pass: Outline,
+    grid_pass: Grid,
+    picker: Picker,
+    // physical window size, and the size the scene targets were created at
+    window_size: (u32, u32),
+    internal_size: (u32, u32),
+    dynamic_scale: DynamicScale,
+    // seconds from submitting a frame to the gpu finishing it
+    frame_times: (Sender<f32>, Receiver<f32>),
+    events: Vec<RenderEvent>,
+    // the cameras found in the world on the last update, the main view first
+    views: Vec<View>,
+    // by camera entity, drawn before the views
+    targets: HashMap<Entity, OffscreenTarget>,
+    // bound to objects that aren't screens, and as the occlusion of render targets
+    white: Texture,
+    object_sampler: Sampler,
+    instances: HashMap<Entity, ModelInstance>,
+    shared_objects: SharedObjects,
+    next_pick_id: u32,
+    // issued while encoding the last frame, see draw_calls
+    draw_calls: Cell<u32>,
+    recording_stats: Cell<RecordingStats>,
+    // built once parallel recording is first enabled, and again when its thread count changes
+    recording_pool: Option<RecordingPool>,
+}
+
+impl Renderer {
+    pub fn new(context: &RenderContext) -> Self {
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "shader.wgsl");
+
+        let pipeline_layout = context.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("background"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let render_pipeline = context.device.create_render_pipeline(
+            &RenderPipelineDescriptor {
+                label: Some("background"),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    entry_point: "vertex",
+                    module: &shader_module,
+                    buffers: &[
+                        VertexBufferLayout {
+                            array_stride: VERTEX_SIZE,
+                            step_mode: VertexStepMode::Vertex,
+                            attributes: &vertex_attr_array![
+                                0 => Float32x2,
+                            ],
+                        },
+                    ],
+                },
+                fragment: Some(FragmentState {
+                    entry_point: "fragment",
+                    module: &shader_module,
+                    targets: &[
+                        Some(HDR_FORMAT.into())
+                    ],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: Some(DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: CompareFunction::Always,
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState::default(),
+                multiview: None,
+            }
+        );
+
+        let vertex_buffer = context.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("background triangle"),
+            usage: BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&[
+                Vertex { pos: [-1.0, -1.0] },
+                Vertex { pos: [0.0, 1.0] },
+                Vertex { pos: [1.0, -1.0] },
+            ]),
+        });
+
+        let mut layouts = LayoutRegistry::new();
+        let size = context.physical_size();
+        let white = Texture::solid(context, "white", [255; 4], false);
+        let ssao_pass = Ssao::new(context, &mut layouts, size.width, size.height);
+        let mut reflection_probes = ReflectionProbes::new(context);
+        let shadow_maps = ShadowMaps::new(context, &mut layouts, &white.view, reflection_probes.empty_textures());
+        reflection_probes.bind(context, &mut layouts, &white.view, shadow_maps.textures());
+        let camera_bindings = FrameRing::new(|| vec![CameraBinding::new(context, &mut layouts, frame_textures(&ssao_pass, &shadow_maps, &reflection_probes))]);
+        let viewport_clear = ViewportClear::new(context, &mut layouts, CLEAR_COLOR);
+        let sky = Sky::new(context, &layouts);
+        let decals = Decals::new(context, &mut layouts, ssao_pass.normal_depth_view());
+        let water_pass = Water::new(context, &mut layouts);
+        let voxel_pass = Voxels::new(context, &layouts);
+        let mut particles = ParticleSystem::new(context, &mut layouts, 16384, ssao_pass.normal_depth_view());
+        particles.emitters.push(Emitter::default());
+        particles.emitters.push(Emitter {
+            position: Vec3::new(1.5, 0.0, 0.0),
+            velocity: Vec3::new(-1.0, 5.0, 0.0),
+            color: [0.2, 0.5, 1.0, 1.0],
+            ..Emitter::default()
+        });
+
+        let mut pipelines = PipelineCache::new();
+        let material_textures = MaterialTextures::new(context, &mut layouts, &white.view);
+        mesh::register_pipeline(context, &mut layouts, &mut pipelines, material_textures.is_bindless());
+        let [mesh_render_pipeline, mesh_normal_pipeline] = [("fragment", HDR_FORMAT), ("fragment_normal", NORMAL_DEPTH_FORMAT)].map(|(entry, format)| {
+            [false, true].map(|pulled| mesh::pipeline_ids(context, &mut pipelines, mesh::pipeline_key(entry, format.into(), pulled)))
+        });
+        let mesh_wireframe_pipeline = context.features().contains(Features::POLYGON_MODE_LINE).then(|| {
+            [false, true].map(|pulled| {
+                let mut key = mesh::pipeline_key("fragment", HDR_FORMAT.into(), pulled);
+                key.primitive.polygon_mode = PolygonMode::Line;
+                key.primitive.cull_mode = None;
+                mesh::pipeline_ids(context, &mut pipelines, key)
+            })
+        });
+        let mesh_shadow_pipeline = [false, true].map(|pulled| mesh::pipeline_ids(context, &mut pipelines, mesh::shadow_pipeline_key(None, pulled)));
+        let mesh_point_shadow_pipeline = [false, true].map(|pulled| mesh::pipeline_ids(context, &mut pipelines, mesh::shadow_pipeline_key(Some("fragment_point_shadow"), pulled)));
+        let outline_pass = Outline::new(context, &mut layouts, &mut pipelines);
+        let grid_pass = Grid::new(context, &mut layouts);
+        let debug = DebugDraw::new(context, &layouts);
+        let loading_screen = LoadingScreen::new(context, &mut layouts);
+        let image_view = ImageView::new(context, &mut layouts);
+        let image_filters = ImageFilters::new(context, &mut layouts);
+        let text = TextRenderer::new(context, &mut layouts);
+        let canvas = Canvas::new(context, &mut layouts);
+        let depth_view = create_depth_view(context, "depth", size.width, size.height);
+        let hdr_view = create_hdr_view(context, "hdr scene", size.width, size.height);
+        let output_pass = OutputPass::new(context, &mut layouts, &hdr_view);
+        let ssr_pass = Ssr::new(context, &mut layouts, &hdr_view, ssao_pass.normal_depth_view(), size.width, size.height);
+        let object_sampler = context.device.create_sampler(&SamplerDescriptor {
+            label: Some("object"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        let shared_objects = SharedObjects::new(context, &layouts, &white.view, &object_sampler);
+
+        Self {
+            particles,
+            terrain: None,
+            tilemap: None,
+            debug,
+            output: OutputSettings::default(),
+            ssao: SsaoSettings::default(),
+            ssr: SsrSettings::default(),
+            shadows: ShadowSettings::default(),
+            probes: ProbeSettings::default(),
+            atmosphere: AtmosphereSettings::default(),
+            water: WaterSettings::default(),
+            voxels: VoxelSettings::default(),
+            gpu_culling: false,
+            lod_bias: 1.0,
+            vertex_pulling: false,
+            render_scale: RenderScale::default(),
+            pacing: FramePacer::new(),
+            selected: Vec::new(),
+            outline: OutlineSettings::default(),
+            grid: GridSettings::default(),
+            timestamps: None,
+            text,
+            canvas,
+            clear_color: CLEAR_COLOR,
+            wireframe: false,
+            frame_history: FrameHistorySettings::default(),
+            parallel_recording: ParallelRecording::default(),
+            render_bundles: false,
+            vertex_compression: false,
+            async_compute: false,
+
+            render_pipeline,
+            vertex_buffer,
+            camera_bindings,
+            frame_textures_generation: 0,
+            static_bundles: FrameRing::new(StaticBundles::new),
+            compute_submitted: ComputeWork::default(),
+            viewport_clear,
+            layouts,
+            pipelines,
+            mesh_render_pipeline,
+            mesh_normal_pipeline,
+            mesh_wireframe_pipeline,
+            mesh_shadow_pipeline,
+            mesh_point_shadow_pipeline,
+            culling: None,
+            material_textures,
+            loading_screen,
+            image_view,
+            image_filters,
+            loading: None,
+            depth_view,
+            hdr_view,
+            output_pass,
+            history: FrameHistory::new(if context.is_hdr() { CAPTURE_FORMAT } else { context.format }),
+            ssao_pass,
+            ssr_pass,
+            shadow_maps,
+            reflection_probes,
+            sky,
+            decals,
+            water_pass,
+            voxel_pass,
+            outline_pass,
+            grid_pass,
+            picker: Picker::new(context),
+            window_size: (size.width, size.height),
+            internal_size: (size.width, size.height),
+            dynamic_scale: DynamicScale::new(),
+            frame_times: channel(),
+            events: Vec::new(),
+            views: vec![View { camera: Camera::default(), viewport: Viewport::FULL }],
+            targets: HashMap::new(),
+            white,
+            object_sampler,
+            instances: HashMap::new(),
+            shared_objects,
+            next_pick_id: 1,
+            draw_calls: Cell::new(0),
+            recording_stats: Cell::new(RecordingStats::default()),
+            recording_pool: None,
+        }
+    }
+
+    pub fn resize(&mut self, context: &RenderContext, width: u32, height: u32) {
+        // minimized, the old targets are kept until there's something to draw again
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.window_size = (width, height);
+        self.resize_targets(context, self.scaled_size(self.dynamic_scale.scale()));
+    }
+
+    fn scaled_size(&self, scale: f32) -> (u32, u32) {
+        let (width, height) = self.window_size;
+        let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+        (scaled(width), scaled(height))
+    }
+
+    // everything the scene is drawn into, at the internal size
+    fn resize_targets(&mut self, context: &RenderContext, (width, height): (u32, u32)) {
+        self.internal_size = (width, height);
+        self.depth_view = create_depth_view(context, "depth", width, height);
+        self.hdr_view = create_hdr_view(context, "hdr scene", width, height);
+        self.output_pass.set_scene(context, &self.layouts, &self.hdr_view);
+        self.ssao_pass.resize(context, &self.layouts, width, height);
+        self.ssr_pass.set_targets(context, &self.layouts, &self.hdr_view, self.ssao_pass.normal_depth_view(), width, height);
+        self.decals.set_normal_depth(context, &self.layouts, self.ssao_pass.normal_depth_view());
+        self.particles.set_normal_depth(context, &self.layouts, self.ssao_pass.normal_depth_view());
+        self.rebind_frame_textures(context);
+        self.picker.resize(context, width, height);
+    }
+
+    // after the occlusion, the shadow maps or the probes' cubes were recreated
+    fn rebind_frame_textures(&mut self, context: &RenderContext) {
+        let textures = frame_textures(&self.ssao_pass, &self.shadow_maps, &self.reflection_probes);
+        for camera_binding in self.camera_bindings.iter_mut().flatten() {
+            camera_binding.set_textures(context, &self.layouts, textures);
+        }
+        self.frame_textures_generation += 1;
+        self.reflection_probes.bind(context, &mut self.layouts, &self.white.view, self.shadow_maps.textures());
+    }
+
+    // the probes are captured again on the next update, e.g. once what's around them has loaded
+    pub fn capture_probes(&mut self) {
+        self.reflection_probes.capture();
+    }
+
+    // lined up with views
+    fn camera_bindings(&self) -> &[CameraBinding] {
+        self.camera_bindings.get(self.pacing.slot())
+    }
+
+    // as of the last update, in the order they're drawn
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    // ssao is screen space with a single projection, so it only runs with one view
+    fn ssao_active(&self) -> bool {
+        self.ssao.enabled && self.views.len() == 1
+    }
+
+    // reads the ssao prepass, so the same goes for reflections
+    fn ssr_active(&self) -> bool {
+        self.ssr.enabled && self.views.len() == 1
+    }
+
+    // decals look up surfaces in the ssao prepass too
+    fn decals_active(&self) -> bool {
+        !self.decals.is_empty() && self.views.len() == 1
+    }
+
+    // and so do colliding particles
+    fn particle_collision_active(&self) -> bool {
+        self.particles.collision.enabled && self.views.len() == 1
+    }
+
+    // the cascades split up a single view's depth, point lights shadow any number of views
+    fn cascades_active(&self) -> bool {
+        self.shadows.enabled && self.views.len() == 1
+    }
+
+    // the size the scene is currently drawn at, see render_scale
+    pub fn internal_size(&self) -> (u32, u32) {
+        self.internal_size
+    }
+
+    // smoothed seconds the gpu took per frame, what the dynamic render scale goes by
+    pub fn gpu_frame_time(&self) -> f32 {
+        self.dynamic_scale.frame_time()
+    }
+
+    // the gpu side of an entity, as of the last update
+    pub fn instance(&self, entity: Entity) -> Option<&ModelInstance> {
+        self.instances.get(&entity)
+    }
+
+    pub fn instances(&self) -> impl Iterator<Item = (Entity, &ModelInstance)> {
+        self.instances.iter().map(|(&entity, instance)| (entity, instance))
+    }
+
+    // picks the model under a pixel, the result arrives as `RenderEvent::Picked` a frame or two later
+    pub fn pick(&mut self, x: u32, y: u32) {
+        let scale = |position: u32, internal: u32, window: u32| (position as u64 * internal as u64 / window.max(1) as u64) as u32;
+        self.picker.request(
+            scale(x, self.internal_size.0, self.window_size.0),
+            scale(y, self.internal_size.1, self.window_size.1),
+        );
+    }
+
+    // cpu alternative to `pick`, returns the closest model hit and where
+    pub fn raycast(&self, ray: &Ray) -> Option<(Entity, Hit)> {
+        self.instances()
+            .filter_map(|(entity, instance)| Some((entity, instance.raycast(ray)?)))
+            .min_by(|a, b| a.1.distance.total_cmp(&b.1.distance))
+    }
+
+    pub fn drain_events(&mut self) -> impl Iterator<Item = RenderEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    // shows a progress bar instead of the scene until set back to None, see Assets::progress
+    pub fn set_loading(&mut self, progress: Option<f32>) {
+        self.loading = progress;
+    }
+
+    pub fn set_terrain(&mut self, context: &RenderContext, heightmap: &Heightmap, blend_map: Option<&Texture>, config: TerrainConfig) {
+        self.terrain = Some(Terrain::new(context, &mut self.layouts, heightmap, blend_map, None, config));
+    }
+
+    // None takes the map away. the view starts out centered on it, see Tilemap::view
+    pub fn set_tilemap(&mut self, context: &RenderContext, map: Option<&TiledMap>) -> Result<(), TilemapError> {
+        self.tilemap = map.map(|map| Tilemap::new(context, &mut self.layouts, map)).transpose()?;
+        Ok(())
+    }
+
+    // a copy of the frames kept so far, see FrameHistorySettings. empty while it's off
+    pub fn save_frame_history(&self) -> FrameDump {
+        self.history.snapshot(&self.frame_history)
+    }
+
+    // for Canvas::image
+    pub fn add_canvas_image(&mut self, context: &RenderContext, texture: &Texture) -> CanvasImage {
+        self.canvas.add_image(context, &self.layouts, texture)
+    }
+
+    // returns the id to put in Material::texture
+    pub fn add_material_texture(&mut self, texture: Arc<Texture>) -> u32 {
+        self.material_textures.add(texture)
+    }
+
+    // e.g. once the asset it came from has been reloaded
+    pub fn set_material_texture(&mut self, id: u32, texture: Arc<Texture>) {
+        self.material_textures.set(id, texture);
+    }
+
+    // a filtered copy of the texture, e.g. to add as a material texture. see ImageFilters
+    pub fn filter_image(&self, context: &RenderContext, texture: &Texture, filter: ImageFilter) -> Texture {
+        self.image_filters.apply(context, &self.layouts, texture, filter)
+    }
+
+    // shows a texture over the scene, e.g. one dropped onto the window. None hides it
+    pub fn set_image(&mut self, context: &RenderContext, texture: Option<&Texture>) {
+        self.image_view.set_texture(context, &self.layouts, texture);
+    }
+
+    // what the output is color graded with, None for no grading. output.grading blends it in
+    pub fn set_color_lut(&mut self, context: &RenderContext, lut: Option<ColorLut>) {
+        self.output_pass.set_lut(context, &self.layouts, lut);
+    }
+
+    // brings gpu resources in line with the world, transforms are interpolated by time.alpha
+    // waits for a free frame slot first, see pacing
+    pub fn update(&mut self, context: &RenderContext, world: &mut World, time: &FrameTime) {
+        self.pacing.begin_frame(&context.device);
+        for frame_time in self.frame_times.1.try_iter() {
+            self.dynamic_scale.measure(frame_time);
+        }
+        let scale = self.dynamic_scale.update(&self.render_scale);
+        let size = self.scaled_size(scale);
+        if size != self.internal_size {
+            self.resize_targets(context, size);
+        }
+
+        let mut views: Vec<View> = world.query_mut::<Without<(&Camera, Option<&Viewport>), &RenderTarget>>().into_iter()
+            .map(|(_, (camera, viewport))| View { camera: *camera, viewport: viewport.copied().unwrap_or_default() })
+            .collect();
+        // without any cameras the last views are kept
+        if !views.is_empty() {
+            views.sort_by_key(|view| view.viewport.order);
+            self.views = views;
+        }
+        let mut lights = SceneLights {
+            directional: world.query_mut::<&DirectionalLight>().into_iter().map(|(_, light)| *light).collect(),
+            point: world.query_mut::<&PointLight>().into_iter().map(|(_, light)| *light).collect(),
+            shadowed: Vec::new(),
+            atmosphere: self.atmosphere,
+        };
+        if lights.directional.is_empty() {
+            lights.directional.push(DirectionalLight::default());
+        }
+        let (width, height) = self.internal_size;
+        let shadows = if self.shadows.enabled {
+            let main = self.views[0];
+            let cascades = self.cascades_active();
+            if self.shadow_maps.update(context, &self.shadows, &main.camera, main.aspect(width, height), cascades, &mut lights, self.pacing.slot()) {
+                self.rebind_frame_textures(context);
+            }
+            *self.shadow_maps.uniform()
+        } else {
+            ShadowUniform::NONE
+        };
+        let probes: Vec<ReflectionProbe> = world.query_mut::<&ReflectionProbe>().into_iter().map(|(_, probe)| *probe).collect();
+        if self.reflection_probes.update(context, &self.probes, &probes, &lights, &shadows) {
+            self.rebind_frame_textures(context);
+        }
+        let probes = *self.reflection_probes.uniform();
+        let camera_bindings = self.camera_bindings.get_mut(self.pacing.slot());
+        while camera_bindings.len() < self.views.len() {
+            camera_bindings.push(CameraBinding::new(context, &mut self.layouts, frame_textures(&self.ssao_pass, &self.shadow_maps, &self.reflection_probes)));
+        }
+        for (camera_binding, view) in camera_bindings.iter().zip(&self.views) {
+            camera_binding.update(context, &view.camera, view.aspect(width, height));
+            camera_binding.update_lights(context, &lights, &shadows, &probes);
+        }
+        let retargeted = self.update_targets(context, world);
+        let retextured = self.material_textures.update(context, &self.layouts, &self.white.view);
+        for target in self.targets.values() {
+            target.update(context, self.pacing.slot(), &lights);
+        }
+        let main = self.views[0];
+        let main_aspect = main.aspect(width, height);
+        let decals: Vec<Decal> = world.query_mut::<&Decal>().into_iter().map(|(_, decal)| *decal).collect();
+        self.decals.update(context, &self.layouts, &decals, &main.camera, &self.material_textures, &retextured, &self.white.view);
+        if let Some(terrain) = &mut self.terrain {
+            terrain.update(&main.camera, &main.camera.frustum(main_aspect));
+        }
+        self.voxel_pass.update(context, &self.voxels, &main.camera, &main.camera.frustum(main_aspect));
+        if let Some(tilemap) = &mut self.tilemap {
+            let window = context.physical_size();
+            let (_, _, viewport_width, viewport_height) = main.viewport.rect(window.width.max(1), window.height.max(1));
+            tilemap.update(context, time.delta, (viewport_width, viewport_height));
+        }
+        let collision_view = (self.particle_collision_active() && self.loading.is_none()).then_some((&main.camera, main_aspect));
+        self.particles.update(context, time.delta, collision_view);
+        if self.water.enabled {
+            self.water_pass.update(context, &self.water, time.delta, self.clear_color);
+        }
+        self.update_instances(context, world, time.alpha, &retargeted, &retextured);
+        self.update_culling(context);
+        self.update_bundles(context);
+        self.update_recording_pool();
+        self.debug.update(context);
+        self.text.update(context);
+        self.canvas.update(context);
+        self.history.update(context, &self.frame_history, self.window_size, time.delta);
+        self.viewport_clear.set_color(context, self.clear_color);
+        self.output_pass.update(context, &self.output);
+        let viewport_sizes: Vec<(u32, u32)> = self.views.iter()
+            .map(|view| view.viewport.rect(width, height))
+            .map(|(_, _, width, height)| (width, height))
+            .collect();
+        self.outline_pass.update(context, &self.layouts, &self.outline, &viewport_sizes);
+        self.grid_pass.update(context, &self.grid);
+        if let Some(timestamps) = &mut self.timestamps {
+            timestamps.next_frame();
+        }
+        if self.ssao_active() {
+            self.ssao_pass.update(context, &self.ssao, &main.camera, main_aspect);
+        }
+        if self.ssr_active() {
+            self.ssr_pass.update(context, &self.ssr, &main.camera, main_aspect, self.clear_color);
+        }
+        if let Some(progress) = self.loading {
+            self.loading_screen.update(context, progress);
+        }
+        self.image_view.update(context);
+
+        if let Some(id) = self.picker.poll(context) {
+            let picked = self.instances().find(|(_, instance)| instance.pick_id == id).map(|(entity, _)| entity);
+            self.events.push(RenderEvent::Picked(picked));
+        }
+        if self.picker.pending().is_some() {
+            self.render_picking(context);
+        }
+        self.submit_compute(context);
+    }
+
+    // the frame's compute work that doesn't wait on any of its passes
+    fn independent_compute(&self) -> ComputeWork {
+        ComputeWork {
+            culling: self.culling.is_some() && self.loading.is_none(),
+            particles: !(self.particle_collision_active() && self.loading.is_none()),
+        }
+    }
+
+    // see async_compute. the queue runs submissions in order, so this is done before the frame
+    // that follows it and the frame pacer only counts the frame
+    fn submit_compute(&mut self, context: &RenderContext) {
+        self.compute_submitted = ComputeWork::default();
+        if !self.async_compute {
+            return;
+        }
+        let work = self.independent_compute();
+        if !work.culling && !work.particles {
+            return;
+        }
+        let _span = tracing::info_span!("async compute").entered();
+        let mut cmd = context.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("compute") });
+        if let Some(culling) = self.culling.as_ref().filter(|_| work.culling) {
+            cmd.push_debug_group("culling");
+            tracing::info_span!("culling").in_scope(|| culling.cull(&mut cmd));
+            cmd.pop_debug_group();
+        }
+        if work.particles {
+            cmd.push_debug_group("particles");
+            tracing::info_span!("particles").in_scope(|| self.particles.simulate(&mut cmd));
+            cmd.pop_debug_group();
+        }
+        context.queue.submit([cmd.finish()]);
+        self.compute_submitted = work;
+    }
+
+    // returns the cameras whose target was created, resized or removed, screens showing them
+    // need their texture bound again
+    fn update_targets(&mut self, context: &RenderContext, world: &mut World) -> Vec<Entity> {
+        let mut retargeted = Vec::new();
+        self.targets.retain(|&entity, _| {
+            let kept = world.get::<&RenderTarget>(entity).is_ok() && world.get::<&Camera>(entity).is_ok();
+            if !kept {
+                retargeted.push(entity);
+            }
+            kept
+        });
+        for (entity, (camera, size)) in world.query_mut::<(&Camera, &RenderTarget)>() {
+            if self.targets.get(&entity).is_none_or(|target| target.size != *size) {
+                let textures = FrameTextures {
+                    occlusion: &self.white.view,
+                    shadows: self.shadow_maps.empty_textures(),
+                    probes: self.reflection_probes.empty_textures(),
+                };
+                self.targets.insert(entity, OffscreenTarget::new(context, &mut self.layouts, *size, textures));
+                retargeted.push(entity);
+            }
+            if let Some(target) = self.targets.get_mut(&entity) {
+                target.camera = *camera;
+            }
+        }
+        retargeted
+    }
+
+    fn update_instances(&mut self, context: &RenderContext, world: &mut World, alpha: f32, retargeted: &[Entity], retextured: &[u32]) {
+        // despawned entities, or ones that lost their MeshRef, give up their gpu resources
+        self.instances.retain(|&entity, _| world.get::<&MeshRef>(entity).is_ok());
+        let main_camera = self.views[0].camera;
+        let encoding = if self.vertex_compression { VertexEncoding::Compressed } else { VertexEncoding::Full };
+
+        let query = world.query_mut::<(&MeshRef, Option<&Transform>, Option<&PreviousTransform>, Option<&Material>, Option<&AnimationPlayer>, Option<&Screen>)>();
+        for (entity, (mesh, transform, previous, material, player, screen)) in query {
+            if self.instances.get(&entity).is_some_and(|instance| !Arc::ptr_eq(&instance.model, &mesh.0) || instance.encoding() != encoding) {
+                self.instances.remove(&entity);
+            }
+            let instance = self.instances.entry(entity).or_insert_with(|| {
+                let mut instance = ModelInstance::new(context, &self.layouts, mesh.0.clone(), encoding, &self.white.view, &self.object_sampler);
+                // ids aren't reused so a pick that arrives late can't hit the wrong entity
+                instance.pick_id = self.next_pick_id;
+                self.next_pick_id += 1;
+                instance
+            });
+            let screen = screen.map(|screen| screen.0);
+            let material = material.copied().unwrap_or_default();
+            // bound as an array the shader picks the material's texture itself
+            let texture = material.texture.filter(|_| !self.material_textures.is_bindless());
+            let rebind = instance.screen.is_some_and(|camera| retargeted.contains(&camera))
+                || instance.texture.is_some_and(|id| retextured.contains(&id));
+            if instance.screen != screen || instance.texture != texture || rebind {
+                // white until the camera has a target
+                let view = match (screen, texture) {
+                    (Some(camera), _) => self.targets.get(&camera).map_or(&self.white.view, OffscreenTarget::view),
+                    (None, Some(id)) => self.material_textures.get(id).map_or(&self.white.view, |texture| &texture.view),
+                    (None, None) => &self.white.view,
+                };
+                instance.set_texture(context, &self.layouts, view, &self.object_sampler);
+                instance.screen = screen;
+                instance.texture = texture;
+            }
+            instance.transform = match (previous, transform) {
+                (Some(previous), Some(transform)) => previous.0.lerp(transform, alpha).matrix(),
+                (_, transform) => transform.map_or(Mat4::IDENTITY, Transform::matrix),
+            };
+            instance.material = material;
+            instance.update(context, player);
+            instance.select_lods(&main_camera, self.lod_bias);
+        }
+
+        let mut uniforms = Vec::new();
+        for instance in self.instances.values_mut() {
+            instance.assign_shared_slots(&mut uniforms);
+        }
+        self.shared_objects.update(context, &self.layouts, &uniforms, &self.white.view, &self.object_sampler);
+    }
+
+    // what the meshes' vertex buffers take up, see vertex_compression
+    pub fn vertex_bytes(&self) -> usize {
+        self.instances.values().map(ModelInstance::vertex_bytes).sum()
+    }
+
+    // how many meshes drew with each lod on the last update, the base meshes first
+    pub fn lod_counts(&self) -> Vec<usize> {
+        let mut counts = Vec::new();
+        for lod in self.instances.values().flat_map(ModelInstance::lods) {
+            if counts.len() <= lod {
+                counts.resize(lod + 1, 0);
+            }
+            counts[lod] += 1;
+        }
+        counts
+    }
+
+    fn update_culling(&mut self, context: &RenderContext) {
+        if !self.gpu_culling {
+            self.culling = None;
+            return;
+        }
+        if self.culling.is_none() {
+            if !GpuCulling::is_supported(context) {
+                tracing::warn!("gpu culling needs indirect first instance support, drawing without it");
+                self.gpu_culling = false;
+                return;
+            }
+            self.culling = Some(GpuCulling::new(context, &mut self.layouts, &mut self.pipelines));
+        }
+        // views first, then targets in the order encode draws them
+        let (width, height) = self.internal_size;
+        let frustums: Vec<_> = self.views.iter().map(|view| view.camera.frustum(view.aspect(width, height)))
+            .chain(self.targets.values().map(|target| target.camera.frustum(target.aspect())))
+            .collect();
+        if let Some(culling) = &mut self.culling {
+            culling.update(context, &self.layouts, self.instances.values(), &frustums);
+        }
+    }
+
+    // after the instances and culling, the bundles hold the slots and lods of this update
+    fn update_bundles(&mut self, context: &RenderContext) {
+        if !self.render_bundles || self.culling.is_some() {
+            self.static_bundles.iter_mut().for_each(StaticBundles::clear);
+            return;
+        }
+        let slot = self.pacing.slot();
+        let pipelines = self.mesh_pipeline();
+        let inputs = BundleInputs {
+            pipelines,
+            camera: self.frame_textures_generation,
+            shared: self.shared_objects.generation(),
+            material_textures: self.material_textures.generation(),
+        };
+        let recorded = self.static_bundles.get_mut(slot).update(
+            context,
+            inputs,
+            pipelines.get(&self.pipelines),
+            self.camera_bindings.get(slot),
+            self.material_textures.bind_group(),
+            self.instances.values(),
+            &self.shared_objects,
+        );
+        if recorded {
+            tracing::debug!("recorded the static mesh bundles for frame slot {slot}");
+        }
+    }
+
+    // kept while parallel recording is off, so switching back and forth doesn't restart threads
+    fn update_recording_pool(&mut self) {
+        if !self.parallel_recording.enabled {
+            return;
+        }
+        let threads = self.parallel_recording.thread_count();
+        if self.recording_pool.as_ref().map(RecordingPool::threads) != Some(threads) {
+            self.recording_pool = Some(RecordingPool::new(threads));
+        }
+    }
+
+    // what the views draw meshes one by one with
+    fn mesh_pipeline(&self) -> MeshPipelineIds {
+        let pipelines = match self.mesh_wireframe_pipeline {
+            Some(wireframe) if self.wireframe => wireframe,
+            _ => self.mesh_render_pipeline,
+        };
+        pipelines[self.vertex_pulling as usize]
+    }
+
+    // only meshes are pickable, everything else is treated as background
+    fn render_picking(&mut self, context: &RenderContext) {
+        let id_pipeline = mesh::pipeline_ids(context, &mut self.pipelines, mesh::pipeline_key("fragment_id", ID_FORMAT.into(), self.vertex_pulling));
+        let mut cmd = context.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("picking") });
+        // drawn as seen through the topmost view under the pixel. the picker's scissor stays
+        let (width, height) = self.internal_size;
+        let (x, y) = self.picker.pending().unwrap_or_default();
+        let (x, y) = ((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+        let index = self.views.iter().rposition(|view| view.viewport.contains(x, y)).unwrap_or(0);
+        let (view_x, view_y, view_width, view_height) = self.views[index].viewport.rect(width, height);
+        let mut pick_cmd = self.picker.begin_pass(&mut cmd, &self.depth_view);
+        pick_cmd.set_viewport(view_x as f32, view_y as f32, view_width as f32, view_height as f32, 0.0, 1.0);
+        pick_cmd.set_bind_group(0, &self.camera_bindings()[index].bind_group, &[]);
+        pick_cmd.set_bind_group(2, self.material_textures.bind_group(), &[]);
+        for instance in self.instances.values() {
+            instance.draw(&mut pick_cmd, &self.shared_objects, id_pipeline.get(&self.pipelines));
+        }
+        drop(pick_cmd);
+        self.picker.submit(context, cmd);
+    }
+
+    pub async fn draw(&self, context: &RenderContext) -> Option<Error> {
+        // suspended, there's nowhere to draw to
+        let surface = context.surface()?;
+        let span = tracing::info_span!("draw").entered();
+        // the frame is skipped when there's no texture to draw to, the next one tries again
+        let surface_texture = match surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(error @ (SurfaceError::Lost | SurfaceError::Outdated)) => {
+                tracing::debug!("reconfiguring the surface: {error}");
+                context.reconfigure();
+                return None;
+            }
+            Err(SurfaceError::Timeout) => {
+                tracing::debug!("timed out waiting for a surface texture");
+                return None;
+            }
+            Err(SurfaceError::OutOfMemory) => panic!("out of memory getting the next surface texture"),
+        };
+        context.device.push_error_scope(ErrorFilter::Validation);
+        let surface_view = surface_texture.texture.create_view(&TextureViewDescriptor {
+            label: Some("surface"),
+            ..TextureViewDescriptor::default()
+        });
+        let buffers = self.encode(context, &surface_view, context.format);
+        self.submit(context, buffers);
+        surface_texture.present();
+        // the error scope is awaited outside the span, spans can't be held across an await
+        drop(span);
+
+        context.device.pop_error_scope().await
+    }
+
+    // like draw, into a texture of the given format instead of the surface, e.g. to benchmark
+    // without presenting
+    pub async fn draw_offscreen(&self, context: &RenderContext, target: &TextureView, format: TextureFormat) -> Option<Error> {
+        let span = tracing::info_span!("draw").entered();
+        context.device.push_error_scope(ErrorFilter::Validation);
+        let buffers = self.encode(context, target, format);
+        self.submit(context, buffers);
+        drop(span);
+        context.device.pop_error_scope().await
+    }
+
+    fn submit(&self, context: &RenderContext, buffers: Vec<CommandBuffer>) {
+        let submitted = Instant::now();
+        context.queue.submit(buffers);
+        self.pacing.submitted(&context.queue);
+        let sender = self.frame_times.0.clone();
+        context.queue.on_submitted_work_done(move || {
+            let _ = sender.send(submitted.elapsed().as_secs_f32());
+        });
+    }
+
+    // draws of scene content in the last encoded frame: background, tilemap, terrain, voxels, water, meshes,
+    // decals, grid, outlines, particles and debug lines, in every view, render target and the ssao prepass.
+    // the fullscreen passes around them aren't counted
+    pub fn draw_calls(&self) -> u32 {
+        self.draw_calls.get()
+    }
+
+    fn count_draws(&self, count: u32) {
+        self.draw_calls.set(self.draw_calls.get() + count);
+    }
+
+    // how long recording the main views' meshes took, on one thread and split across several
+    pub fn recording_stats(&self) -> RecordingStats {
+        self.recording_stats.get()
+    }
+
+    // how many chunks the main views' meshes are recorded in on this frame, 1 records them in the
+    // scene pass
+    fn mesh_chunks(&self) -> usize {
+        if self.culling.is_some() || self.render_bundles || self.loading.is_some() || self.recording_pool.is_none() {
+            return 1;
+        }
+        self.parallel_recording.chunks(self.instances.len())
+    }
+
+    // renders a frame offscreen at the window size and reads it back, for recording and screenshots.
+    // with an hdr surface the capture is tonemapped to sdr
+    pub fn capture(&self, context: &RenderContext) -> RgbaImage {
+        let size = context.physical_size();
+        let (width, height) = (size.width.max(1), size.height.max(1));
+        let format = if context.is_hdr() { CAPTURE_FORMAT } else { context.format };
+        let texture = context.device.create_texture(&TextureDescriptor {
+            label: Some("capture"),
+            size: Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some("capture"),
+            ..TextureViewDescriptor::default()
+        });
+        context.queue.submit(self.encode(context, &view, format));
+        capture::read_texture(context, &texture, format, width, height)
+    }
+
+    // the frame's command buffers in submission order, more than one when meshes are recorded in
+    // parallel
+    fn encode(&self, context: &RenderContext, target: &TextureView, format: TextureFormat) -> Vec<CommandBuffer> {
+        let mut cmd = context.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("frame") });
+        self.draw_calls.set(0);
+        if let Some(timestamps) = &self.timestamps {
+            timestamps.begin(&mut cmd);
+        }
+        // debug groups mirror the tracing spans so captures read the same as traces
+        if let Some(culling) = self.culling.as_ref().filter(|_| self.loading.is_none() && !self.compute_submitted.culling) {
+            cmd.push_debug_group("culling");
+            tracing::info_span!("culling").in_scope(|| culling.cull(&mut cmd));
+            cmd.pop_debug_group();
+        }
+        if self.loading.is_none() && !self.targets.is_empty() {
+            let _span = tracing::info_span!("render targets").entered();
+            cmd.push_debug_group("render targets");
+            for (index, (&entity, target)) in self.targets.iter().enumerate() {
+                let mut render_cmd = target.begin_pass(&mut cmd, self.clear_color);
+                self.draw_view(&mut render_cmd, target.camera_binding(self.pacing.slot()), Some(self.views.len() + index), Some(entity));
+            }
+            cmd.pop_debug_group();
+        }
+        if self.shadows.enabled && self.loading.is_none() {
+            let _span = tracing::info_span!("shadows").entered();
+            cmd.push_debug_group("shadows");
+            self.encode_shadows(&mut cmd);
+            cmd.pop_debug_group();
+        }
+        if self.probes.enabled && self.reflection_probes.capture_pending() && self.loading.is_none() {
+            let _span = tracing::info_span!("reflection probes").entered();
+            cmd.push_debug_group("reflection probes");
+            for face in 0..self.reflection_probes.face_count() {
+                let mut render_cmd = self.reflection_probes.begin_pass(&mut cmd, face, self.clear_color);
+                self.draw_view(&mut render_cmd, self.reflection_probes.camera_binding(face), None, None);
+            }
+            cmd.pop_debug_group();
+        }
+        cmd.push_debug_group("ssao");
+        let prepass = (self.ssao_active() || self.ssr_active() || self.decals_active() || self.particle_collision_active()) && self.loading.is_none();
+        if prepass {
+            let _span = tracing::info_span!("normals").entered();
+            self.encode_normals(&mut cmd);
+        }
+        if self.ssao_active() && prepass {
+            let _span = tracing::info_span!("ssao").entered();
+            self.ssao_pass.draw(&mut cmd);
+        } else {
+            self.ssao_pass.clear(&mut cmd);
+        }
+        cmd.pop_debug_group();
+        // after the prepass, which colliding particles read
+        if !self.compute_submitted.particles {
+            cmd.push_debug_group("particles");
+            tracing::info_span!("particles").in_scope(|| self.particles.simulate(&mut cmd));
+            cmd.pop_debug_group();
+        }
+        let scene_span = tracing::info_span!("scene").entered();
+        let chunks = self.mesh_chunks();
+        let mut buffers = Vec::new();
+        let mut render_cmd = self.begin_scene_pass(&mut cmd, true, chunks > 1);
+        if self.loading.is_some() {
+            render_cmd.push_debug_group("loading");
+            self.loading_screen.draw(&mut render_cmd);
+            render_cmd.pop_debug_group();
+            drop(render_cmd);
+            drop(scene_span);
+            self.encode_output(&mut cmd, target, format);
+            return vec![cmd.finish()];
+        }
+        if chunks > 1 {
+            // the pass only clears, the views are drawn in passes of their own around the meshes
+            drop(render_cmd);
+            self.encode_views_parallel(context, &mut cmd, &mut buffers, chunks);
+        } else {
+            self.encode_views(&mut render_cmd);
+            drop(render_cmd);
+        }
+        drop(scene_span);
+        if self.ssr_active() {
+            let _span = tracing::info_span!("ssr").entered();
+            cmd.push_debug_group("ssr");
+            self.ssr_pass.draw(&mut cmd, &self.hdr_view);
+            self.count_draws(2);
+            cmd.pop_debug_group();
+        }
+        self.encode_output(&mut cmd, target, format);
+        buffers.push(cmd.finish());
+        buffers
+    }
+
+    // the hdr and depth targets the views draw into. clear starts the frame, split keeps the
+    // stencil for the passes after it
+    fn begin_scene_pass<'a>(&'a self, cmd: &'a mut CommandEncoder, clear: bool, split: bool) -> RenderPass<'a> {
+        cmd.begin_render_pass(&RenderPassDescriptor {
+            label: Some("scene"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    ops: Operations {
+                        load: if clear { LoadOp::Clear(self.clear_color) } else { LoadOp::Load },
+                        store: true,
+                    },
+                    view: &self.hdr_view,
+                    resolve_target: None,
+                })
+            ],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(Operations {
+                    load: if clear { LoadOp::Clear(1.0) } else { LoadOp::Load },
+                    store: true,
+                }),
+                stencil_ops: Some(Operations {
+                    load: if clear { LoadOp::Clear(0) } else { LoadOp::Load },
+                    store: split,
+                }),
+            }),
+        })
+    }
+
+    // every view in the one scene pass, then the image view over them
+    fn encode_views<'a>(&'a self, render_cmd: &mut RenderPass<'a>) {
+        let (width, height) = self.internal_size;
+        let mut recording = Duration::ZERO;
+        for (index, (view, camera_binding)) in self.views.iter().zip(self.camera_bindings()).enumerate() {
+            render_cmd.push_debug_group(&format!("view {index}"));
+            set_viewport(render_cmd, view.viewport.rect(width, height));
+            // the first view starts from the pass clear
+            if index > 0 {
+                self.viewport_clear.draw(render_cmd);
+            }
+            self.draw_before_meshes(render_cmd, camera_binding, Some(index));
+            let start = Instant::now();
+            render_cmd.push_debug_group("meshes");
+            self.draw_meshes(render_cmd, camera_binding, false, Some(index), None);
+            render_cmd.pop_debug_group();
+            recording += start.elapsed();
+            self.draw_after_meshes(render_cmd, camera_binding, Some(index));
+            self.draw_outlines(render_cmd, camera_binding, index);
+            render_cmd.pop_debug_group();
+        }
+        // gpu culled draws cost next to nothing to record, they'd skew the comparison
+        if self.culling.is_none() {
+            let mut stats = self.recording_stats.get();
+            stats.measure_single(recording, self.instances.len() * self.views.len());
+            self.recording_stats.set(stats);
+        }
+        set_viewport(render_cmd, (0, 0, width, height));
+        render_cmd.push_debug_group("image");
+        self.image_view.draw(render_cmd);
+        render_cmd.pop_debug_group();
+    }
+
+    // like encode_views, with each view's meshes recorded on `chunks` threads. what's encoded so
+    // far is finished into buffers ahead of each view's meshes, and cmd carries on after them
+    fn encode_views_parallel(&self, context: &RenderContext, cmd: &mut CommandEncoder, buffers: &mut Vec<CommandBuffer>, chunks: usize) {
+        let pool = self.recording_pool.as_ref().expect("mesh_chunks only splits with a pool");
+        let (width, height) = self.internal_size;
+        let pipelines = self.mesh_pipeline().get(&self.pipelines);
+        let instances: Vec<&ModelInstance> = self.instances.values().collect();
+        let mut recording = Duration::ZERO;
+        for (index, (view, camera_binding)) in self.views.iter().zip(self.camera_bindings()).enumerate() {
+            let rect = view.viewport.rect(width, height);
+            let mut render_cmd = self.begin_scene_pass(cmd, false, true);
+            render_cmd.push_debug_group(&format!("view {index}"));
+            set_viewport(&mut render_cmd, rect);
+            if index > 0 {
+                self.viewport_clear.draw(&mut render_cmd);
+            }
+            self.draw_before_meshes(&mut render_cmd, camera_binding, Some(index));
+            render_cmd.pop_debug_group();
+            drop(render_cmd);
+            let before = std::mem::replace(cmd, context.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("frame") }));
+            buffers.push(before.finish());
+
+            let start = Instant::now();
+            let pass = MeshPass {
+                color: &self.hdr_view,
+                depth: &self.depth_view,
+                viewport: rect,
+                pipelines,
+                camera: &camera_binding.bind_group,
+                material_textures: self.material_textures.bind_group(),
+                shared: &self.shared_objects,
+            };
+            buffers.extend(pool.record_meshes(&context.device, &pass, &instances, chunks));
+            recording += start.elapsed();
+            self.count_draws(instances.iter().map(|instance| instance.draw_count()).sum());
+
+            let mut render_cmd = self.begin_scene_pass(cmd, false, true);
+            render_cmd.push_debug_group(&format!("view {index}"));
+            set_viewport(&mut render_cmd, rect);
+            self.draw_after_meshes(&mut render_cmd, camera_binding, Some(index));
+            self.draw_outlines(&mut render_cmd, camera_binding, index);
+            render_cmd.pop_debug_group();
+        }
+        let mut stats = self.recording_stats.get();
+        stats.measure_parallel(recording, instances.len() * self.views.len(), chunks);
+        self.recording_stats.set(stats);
+        let mut render_cmd = self.begin_scene_pass(cmd, false, false);
+        set_viewport(&mut render_cmd, (0, 0, width, height));
+        render_cmd.push_debug_group("image");
+        self.image_view.draw(&mut render_cmd);
+        render_cmd.pop_debug_group();
+    }
+
+    fn draw_outlines<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_binding: &'a CameraBinding, index: usize) {
+        if !self.outline.enabled {
+            return;
+        }
+        render_cmd.push_debug_group("outlines");
+        let selected: Vec<&ModelInstance> = self.selected.iter().filter_map(|entity| self.instances.get(entity)).collect();
+        self.count_draws(selected.iter().map(|instance| instance.draw_count() * 2).sum());
+        self.outline_pass.draw(render_cmd, &self.pipelines, camera_binding, index, &selected, &self.shared_objects);
+        render_cmd.pop_debug_group();
+    }
+
+    // cull_view is the view's index in the frustums culling was updated with. None for probe
+    // faces, which draw every mesh and leave out the grid, particles and debug lines
+    fn draw_view<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_binding: &'a CameraBinding, cull_view: Option<usize>, target: Option<Entity>) {
+        self.draw_before_meshes(render_cmd, camera_binding, cull_view);
+        render_cmd.push_debug_group("meshes");
+        self.draw_meshes(render_cmd, camera_binding, false, cull_view, target);
+        render_cmd.pop_debug_group();
+        self.draw_after_meshes(render_cmd, camera_binding, cull_view);
+    }
+
+    // the background, tilemap, terrain, voxels and water
+    fn draw_before_meshes<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_binding: &'a CameraBinding, cull_view: Option<usize>) {
+        render_cmd.push_debug_group("background");
+        if self.atmosphere.sky {
+            self.sky.draw(render_cmd, &camera_binding.bind_group);
+        } else {
+            render_cmd.set_pipeline(&self.render_pipeline);
+            render_cmd.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_cmd.draw(0..3, 0..1);
+        }
+        render_cmd.pop_debug_group();
+        self.count_draws(1);
+        // only the main view's screen is the map's
+        if let Some(tilemap) = self.tilemap.as_ref().filter(|_| cull_view == Some(0)) {
+            render_cmd.push_debug_group("tilemap");
+            self.count_draws(tilemap.draw_count());
+            tilemap.draw(render_cmd);
+            render_cmd.pop_debug_group();
+        }
+        if let Some(terrain) = &self.terrain {
+            render_cmd.push_debug_group("terrain");
+            self.count_draws(terrain.visible_chunks() as u32);
+            terrain.draw(render_cmd, &camera_binding.bind_group);
+            render_cmd.pop_debug_group();
+        }
+        if self.voxels.enabled {
+            render_cmd.push_debug_group("voxels");
+            self.count_draws(self.voxel_pass.draw_count());
+            self.voxel_pass.draw(render_cmd, &camera_binding.bind_group);
+            render_cmd.pop_debug_group();
+        }
+        if self.water.enabled {
+            render_cmd.push_debug_group("water");
+            self.water_pass.draw(render_cmd, &camera_binding.bind_group);
+            render_cmd.pop_debug_group();
+            self.count_draws(1);
+        }
+    }
+
+    // decals, the grid, particles and debug lines
+    fn draw_after_meshes<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_binding: &'a CameraBinding, cull_view: Option<usize>) {
+        if cull_view.is_none() {
+            return;
+        }
+        // the prepass is only drawn for the first view
+        if cull_view == Some(0) && self.decals_active() {
+            render_cmd.push_debug_group("decals");
+            self.decals.draw(render_cmd, &camera_binding.bind_group);
+            render_cmd.pop_debug_group();
+            self.count_draws(self.decals.draw_count());
+        }
+        if self.grid.enabled {
+            render_cmd.push_debug_group("grid");
+            self.grid_pass.draw(render_cmd, &camera_binding.bind_group);
+            render_cmd.pop_debug_group();
+            self.count_draws(1);
+        }
+        render_cmd.push_debug_group("particles");
+        self.particles.draw(render_cmd, &camera_binding.bind_group);
+        render_cmd.pop_debug_group();
+        render_cmd.push_debug_group("debug lines");
+        self.debug.draw(render_cmd, &camera_binding.bind_group);
+        render_cmd.pop_debug_group();
+        self.count_draws(1 + self.debug.draw_count());
+    }
+
+    // one by one, or only what gpu culling can't draw followed by the culled draws. screens
+    // showing `target` are left out, they'd sample the texture being drawn into
+    fn draw_meshes<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_binding: &'a CameraBinding, normals: bool, cull_view: Option<usize>, target: Option<Entity>) {
+        let pipelines = match self.mesh_wireframe_pipeline {
+            _ if normals => self.mesh_normal_pipeline,
+            Some(wireframe) if self.wireframe => wireframe,
+            _ => self.mesh_render_pipeline,
+        };
+        let pipelines = pipelines[self.vertex_pulling as usize].get(&self.pipelines);
+        render_cmd.set_bind_group(0, &camera_binding.bind_group, &[]);
+        render_cmd.set_bind_group(2, self.material_textures.bind_group(), &[]);
+        let instances = self.instances.values().filter(|instance| target.is_none() || instance.screen != target);
+        match self.culling.as_ref().zip(cull_view) {
+            Some((culling, cull_view)) => {
+                for instance in instances {
+                    self.count_draws(instance.dynamic_draw_count());
+                    instance.draw_dynamic(render_cmd, &self.shared_objects, pipelines);
+                }
+                let pipeline = if normals { culling.normal_pipeline } else { culling.render_pipeline };
+                self.count_draws(culling.draw_count(cull_view));
+                culling.draw(render_cmd, self.pipelines.get(pipeline), cull_view);
+            }
+            None => {
+                // recorded for the views' color pass only
+                let bundles = self.static_bundles.get(self.pacing.slot());
+                match cull_view.filter(|_| !normals && target.is_none()).and_then(|view| bundles.get(view)) {
+                    Some(bundle) => {
+                        for instance in instances {
+                            self.count_draws(instance.dynamic_draw_count());
+                            instance.draw_dynamic(render_cmd, &self.shared_objects, pipelines);
+                        }
+                        self.count_draws(bundles.draw_count());
+                        render_cmd.execute_bundles([bundle]);
+                    }
+                    None => {
+                        for instance in instances {
+                            self.count_draws(instance.draw_count());
+                            instance.draw(render_cmd, &self.shared_objects, pipelines);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode_output(&self, cmd: &mut CommandEncoder, target: &TextureView, format: TextureFormat) {
+        let _span = tracing::info_span!("output").entered();
+        cmd.push_debug_group("output");
+        self.output_pass.draw(cmd, target, format, self.output.antialiasing);
+        cmd.pop_debug_group();
+        cmd.push_debug_group("canvas");
+        self.count_draws(self.canvas.draw_count());
+        self.canvas.draw(cmd, target, format);
+        cmd.pop_debug_group();
+        cmd.push_debug_group("text");
+        self.count_draws(self.text.draw_count());
+        self.text.draw(cmd, target, format);
+        cmd.pop_debug_group();
+        // the same again, smaller. canvas and text are laid out in window pixels and scale down
+        if let Some((history_target, history_format)) = self.history.target() {
+            cmd.push_debug_group("frame history");
+            self.output_pass.draw(cmd, history_target, history_format, self.output.antialiasing);
+            self.canvas.draw(cmd, history_target, history_format);
+            self.text.draw(cmd, history_target, history_format);
+            cmd.pop_debug_group();
+        }
+        // the last thing either way a frame is encoded
+        if let Some(timestamps) = &self.timestamps {
+            timestamps.end(cmd);
+        }
+    }
+
+    // every mesh and the visible terrain from each cascade's light and each point light face. gpu
+    // culling only culls for the views, so the meshes are drawn one by one
+    fn encode_shadows(&self, cmd: &mut CommandEncoder) {
+        let slot = self.pacing.slot();
+        for cascade in 0..self.shadow_maps.cascade_count() {
+            let mut render_cmd = self.shadow_maps.begin_pass(cmd, cascade);
+            self.draw_shadow_casters(&mut render_cmd, self.shadow_maps.camera_binding(slot, cascade), false);
+        }
+        for face in 0..self.shadow_maps.point_face_count() {
+            let mut render_cmd = self.shadow_maps.begin_point_pass(cmd, face);
+            self.draw_shadow_casters(&mut render_cmd, self.shadow_maps.point_camera_binding(slot, face), true);
+        }
+    }
+
+    // point writes the distance to the light instead of depth
+    fn draw_shadow_casters<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_binding: &'a CameraBinding, point: bool) {
+        if let Some(terrain) = &self.terrain {
+            render_cmd.push_debug_group("terrain");
+            self.count_draws(terrain.visible_chunks() as u32);
+            if point {
+                terrain.draw_point_shadows(render_cmd, &camera_binding.bind_group);
+            } else {
+                terrain.draw_shadows(render_cmd, &camera_binding.bind_group);
+            }
+            render_cmd.pop_debug_group();
+        }
+        render_cmd.push_debug_group("meshes");
+        let pipelines = if point { &self.mesh_point_shadow_pipeline } else { &self.mesh_shadow_pipeline };
+        let pipelines = pipelines[self.vertex_pulling as usize].get(&self.pipelines);
+        render_cmd.set_bind_group(0, &camera_binding.bind_group, &[]);
+        render_cmd.set_bind_group(2, self.material_textures.bind_group(), &[]);
+        for instance in self.instances.values() {
+            self.count_draws(instance.draw_count());
+            instance.draw(render_cmd, &self.shared_objects, pipelines);
+        }
+        render_cmd.pop_debug_group();
+    }
+
+    // the ssao, ssr, decal and particle collision prepass, only meshes, terrain, voxels and water write normals. depth is cleared again by the main pass
+    fn encode_normals(&self, cmd: &mut CommandEncoder) {
+        let mut render_cmd = cmd.begin_render_pass(&RenderPassDescriptor {
+            label: Some("normals"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: true,
+                    },
+                    view: self.ssao_pass.normal_depth_view(),
+                    resolve_target: None,
+                })
+            ],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        if let Some(terrain) = &self.terrain {
+            render_cmd.push_debug_group("terrain");
+            self.count_draws(terrain.visible_chunks() as u32);
+            terrain.draw_normals(&mut render_cmd, &self.camera_bindings()[0].bind_group);
+            render_cmd.pop_debug_group();
+        }
+        if self.voxels.enabled {
+            render_cmd.push_debug_group("voxels");
+            self.count_draws(self.voxel_pass.draw_count());
+            self.voxel_pass.draw_normals(&mut render_cmd, &self.camera_bindings()[0].bind_group);
+            render_cmd.pop_debug_group();
+        }
+        if self.water.enabled {
+            render_cmd.push_debug_group("water");
+            self.count_draws(1);
+            self.water_pass.draw_normals(&mut render_cmd, &self.camera_bindings()[0].bind_group);
+            render_cmd.pop_debug_group();
+        }
+        render_cmd.push_debug_group("meshes");
+        self.draw_meshes(&mut render_cmd, &self.camera_bindings()[0], true, Some(0), None);
+        render_cmd.pop_debug_group();
+    }
+}
+
+// what the views are bound with
+fn frame_textures<'a>(ssao: &'a Ssao, shadow_maps: &'a ShadowMaps, probes: &'a ReflectionProbes) -> FrameTextures<'a> {
+    FrameTextures {
+        occlusion: ssao.occlusion_view(),
+        shadows: shadow_maps.textures(),
+        probes: probes.textures(),
+    }
+}
+
+// the scissor matches so nothing spills into the neighbouring views
+fn set_viewport(render_cmd: &mut RenderPass, (x, y, width, height): (u32, u32, u32, u32)) {
+    render_cmd.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+    render_cmd.set_scissor_rect(x, y, width, height);
+}
+
+pub(crate) fn create_depth_view(context: &RenderContext, label: &str, width: u32, height: u32) -> TextureView {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+    });
+    texture.create_view(&TextureViewDescriptor {
+        label: Some(label),
+        ..TextureViewDescriptor::default()
+    })
+}
+
+pub(crate) fn create_hdr_view(context: &RenderContext, label: &str, width: u32, height: u32) -> TextureView {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: HDR_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    texture.create_view(&TextureViewDescriptor {
+        label: Some(label),
+        ..TextureViewDescriptor::default()
+    })
+}
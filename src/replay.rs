@@ -0,0 +1,221 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use winit::dpi::PhysicalPosition;
+use winit::event::{DeviceEvent, DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, TouchPhase, VirtualKeyCode, WindowEvent};
+use crate::app::{App, Engine};
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => error.fmt(f),
+            ReplayError::Parse(error) => error.fmt(f),
+            ReplayError::Serialize(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for ReplayError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        ReplayError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for ReplayError {
+    fn from(error: ron::Error) -> Self {
+        ReplayError::Serialize(error)
+    }
+}
+
+// the winit events apps take input from, without the device ids that don't survive a restart.
+// positions are physical pixels
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key { scancode: u32, key: Option<VirtualKeyCode>, state: ElementState },
+    Modifiers(ModifiersState),
+    Character(char),
+    Focused(bool),
+    CursorMoved { x: f64, y: f64 },
+    MouseButton { button: MouseButton, state: ElementState },
+    MouseWheel(MouseScrollDelta),
+    // relative motion, what mouse look uses while the pointer is locked
+    MouseMotion { x: f64, y: f64 },
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match *event {
+            WindowEvent::KeyboardInput { input, .. } => InputEvent::Key { scancode: input.scancode, key: input.virtual_keycode, state: input.state },
+            WindowEvent::ModifiersChanged(modifiers) => InputEvent::Modifiers(modifiers),
+            WindowEvent::ReceivedCharacter(character) => InputEvent::Character(character),
+            WindowEvent::Focused(focused) => InputEvent::Focused(focused),
+            WindowEvent::CursorMoved { position, .. } => InputEvent::CursorMoved { x: position.x, y: position.y },
+            WindowEvent::MouseInput { button, state, .. } => InputEvent::MouseButton { button, state },
+            WindowEvent::MouseWheel { delta, .. } => InputEvent::MouseWheel(delta),
+            _ => return None,
+        })
+    }
+
+    pub fn from_device_event(event: &DeviceEvent) -> Option<Self> {
+        match *event {
+            DeviceEvent::MouseMotion { delta: (x, y) } => Some(InputEvent::MouseMotion { x, y }),
+            _ => None,
+        }
+    }
+
+    // as the event loop would hand it to the app
+    #[allow(deprecated)]
+    pub fn dispatch(&self, engine: &mut Engine, app: &mut impl App) {
+        // only used to tell devices apart, which nothing does
+        let device_id = unsafe { DeviceId::dummy() };
+        let modifiers = ModifiersState::empty();
+        let event = match *self {
+            InputEvent::Key { scancode, key, state } => WindowEvent::KeyboardInput {
+                device_id,
+                input: KeyboardInput { scancode, state, virtual_keycode: key, modifiers },
+                is_synthetic: false,
+            },
+            InputEvent::Modifiers(modifiers) => WindowEvent::ModifiersChanged(modifiers),
+            InputEvent::Character(character) => WindowEvent::ReceivedCharacter(character),
+            InputEvent::Focused(focused) => WindowEvent::Focused(focused),
+            InputEvent::CursorMoved { x, y } => WindowEvent::CursorMoved { device_id, position: PhysicalPosition::new(x, y), modifiers },
+            InputEvent::MouseButton { button, state } => WindowEvent::MouseInput { device_id, state, button, modifiers },
+            InputEvent::MouseWheel(delta) => WindowEvent::MouseWheel { device_id, delta, phase: TouchPhase::Moved, modifiers },
+            InputEvent::MouseMotion { x, y } => {
+                app.device_event(engine, &DeviceEvent::MouseMotion { delta: (x, y) });
+                return;
+            }
+        };
+        app.window_event(engine, &event);
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    // seconds since the recording started, to find your way around the file
+    pub time: f64,
+    // what the frame was advanced by, replayed as is so the fixed steps come out the same
+    pub delta: f64,
+    // what arrived since the frame before, in order
+    pub events: Vec<InputEvent>,
+}
+
+impl RecordedFrame {
+    pub fn dispatch(&self, engine: &mut Engine, app: &mut impl App) {
+        for event in &self.events {
+            event.dispatch(engine, app);
+        }
+    }
+}
+
+// a session's input frame by frame. replaying it into the same starting state gives the same
+// frames, as long as nothing else differs, e.g. assets still loading or a different window size
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InputRecording {
+    // physical pixels, cursor positions only line up in a window this size
+    pub size: (u32, u32),
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl InputRecording {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let source = fs::read_to_string(path)?;
+        Ok(ron::from_str(&source)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let source = ron::ser::to_string_pretty(self, PrettyConfig::default())?;
+        fs::write(path, source)?;
+        Ok(())
+    }
+}
+
+// collects events as they come in, see app::run
+pub struct InputRecorder {
+    pub path: PathBuf,
+    recording: InputRecording,
+    pending: Vec<InputEvent>,
+    time: f64,
+}
+
+impl InputRecorder {
+    pub fn new(path: impl Into<PathBuf>, size: (u32, u32)) -> Self {
+        Self {
+            path: path.into(),
+            recording: InputRecording { size, frames: Vec::new() },
+            pending: Vec::new(),
+            time: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, event: InputEvent) {
+        self.pending.push(event);
+    }
+
+    // before the frame is advanced by delta, the events since the last one go with it
+    pub fn frame(&mut self, delta: f64) {
+        self.time += delta;
+        self.recording.frames.push(RecordedFrame { time: self.time, delta, events: std::mem::take(&mut self.pending) });
+    }
+
+    pub fn save(&self) -> Result<(), ReplayError> {
+        self.recording.save(&self.path)
+    }
+}
+
+// hands out a recording's frames one at a time
+pub struct InputReplayer {
+    recording: InputRecording,
+    next: usize,
+}
+
+impl InputReplayer {
+    pub fn new(recording: InputRecording) -> Self {
+        Self { recording, next: 0 }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.recording.size
+    }
+
+    pub fn len(&self) -> usize {
+        self.recording.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recording.frames.is_empty()
+    }
+
+    // none once it's all been replayed
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        let frame = self.recording.frames.get(self.next)?.clone();
+        self.next += 1;
+        Some(frame)
+    }
+}
+
+// what app::run does with input besides handing it to the app
+pub enum InputLog {
+    // also written to the recorder's path when the loop exits
+    Record(InputRecorder),
+    // input from the window is dropped while the recording plays, then it's live again
+    Replay(InputReplayer),
+}
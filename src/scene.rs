@@ -0,0 +1,467 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use glam::{EulerRot, Quat, Vec3};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use crate::animation::AnimationPlayer;
+use crate::assets::Assets;
+use crate::camera::Camera;
+use crate::decals::Decal;
+use crate::embedded;
+use crate::light::{DirectionalLight, PointLight};
+use crate::mesh::Material;
+use crate::model::{Model, ModelError, GENERATED_LODS};
+use crate::physics::PhysicsBody;
+use crate::primitives;
+use crate::probes::ReflectionProbe;
+use crate::render_target::{RenderTarget, Screen};
+use crate::transform::Transform;
+use crate::viewport::Viewport;
+use crate::world::{self, Entity, MeshRef, Name, World};
+
+// also kept on the entities spawned from it, with gltf paths resolved, so a world can be saved
+// back out, see SceneDesc::capture
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MeshSource {
+    // relative to the scene file. .obj files load too
+    Gltf(PathBuf),
+    Plane { size: f32, subdivisions: u32 },
+    Cube { size: f32 },
+    Sphere { radius: f32, sectors: u32, stacks: u32 },
+    Cylinder { radius: f32, height: f32, sectors: u32 },
+    Torus { major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32 },
+}
+
+impl MeshSource {
+    pub fn build(&self, base_dir: &Path) -> Result<Model, ModelError> {
+        let mesh = match *self {
+            MeshSource::Gltf(ref path) => return Model::load(base_dir.join(path)),
+            MeshSource::Plane { size, subdivisions } => primitives::plane(size, subdivisions),
+            MeshSource::Cube { size } => primitives::cube(size),
+            MeshSource::Sphere { radius, sectors, stacks } => primitives::sphere(radius, sectors, stacks),
+            MeshSource::Cylinder { radius, height, sectors } => primitives::cylinder(radius, height, sectors),
+            MeshSource::Torus { major_radius, minor_radius, major_segments, minor_segments } => {
+                primitives::torus(major_radius, minor_radius, major_segments, minor_segments)
+            }
+        };
+        let mut model = Model::from_mesh(mesh);
+        model.generate_lods(GENERATED_LODS);
+        Ok(model)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformDesc {
+    pub translation: Vec3,
+    // euler angles in degrees, applied in xyz order
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl TransformDesc {
+    pub fn transform(&self) -> Transform {
+        let rotation = self.rotation * std::f32::consts::PI / 180.0;
+        Transform {
+            translation: self.translation,
+            rotation: Quat::from_euler(EulerRot::XYZ, rotation.x, rotation.y, rotation.z),
+            scale: self.scale,
+        }
+    }
+}
+
+impl From<Transform> for TransformDesc {
+    fn from(transform: Transform) -> Self {
+        let (x, y, z) = transform.rotation.to_euler(EulerRot::XYZ);
+        Self {
+            translation: transform.translation,
+            rotation: Vec3::new(x, y, z) * 180.0 / std::f32::consts::PI,
+            scale: transform.scale,
+        }
+    }
+}
+
+impl Default for TransformDesc {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntityDesc {
+    // entities are matched by name when reloading
+    pub name: String,
+    pub mesh: MeshSource,
+    #[serde(default)]
+    pub transform: TransformDesc,
+    #[serde(default)]
+    pub material: Material,
+    // clip to loop, if any
+    #[serde(default)]
+    pub animation: Option<usize>,
+    // simulated when Some, see Physics
+    #[serde(default)]
+    pub body: Option<PhysicsBody>,
+    // index into the scene's views, shows what that view's render target sees
+    #[serde(default)]
+    pub screen: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraDesc {
+    pub eye: Vec3,
+    pub target: Vec3,
+    // vertical field of view in degrees
+    #[serde(default = "CameraDesc::default_fovy")]
+    pub fovy: f32,
+    // fills the window when None
+    #[serde(default)]
+    pub viewport: Option<Viewport>,
+    // draws into a texture for screens instead of the window, the viewport is ignored then
+    #[serde(default)]
+    pub render_target: Option<RenderTarget>,
+}
+
+impl CameraDesc {
+    fn default_fovy() -> f32 {
+        60.0
+    }
+
+    // looking straight up or down has no usable y up, -z is up on screen instead
+    pub fn camera(&self) -> Camera {
+        let forward = (self.target - self.eye).normalize_or_zero();
+        let up = if forward.cross(Vec3::Y).length_squared() < 1e-6 { Vec3::NEG_Z } else { Vec3::Y };
+        Camera {
+            eye: self.eye,
+            target: self.target,
+            up,
+            fovy: self.fovy.to_radians(),
+            ..Camera::default()
+        }
+    }
+
+    fn from_entity(world: &World, entity: Entity) -> Option<Self> {
+        let camera = *world.get::<&Camera>(entity).ok()?;
+        Some(Self {
+            eye: camera.eye,
+            target: camera.target,
+            fovy: camera.fovy.to_degrees(),
+            viewport: world.get::<&Viewport>(entity).ok().map(|viewport| *viewport),
+            render_target: world.get::<&RenderTarget>(entity).ok().map(|render_target| *render_target),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneDesc {
+    pub camera: Option<CameraDesc>,
+    // extra cameras drawn after the main one, each into its own viewport
+    pub views: Vec<CameraDesc>,
+    pub lights: Vec<DirectionalLight>,
+    pub point_lights: Vec<PointLight>,
+    pub probes: Vec<ReflectionProbe>,
+    pub decals: Vec<Decal>,
+    pub entities: Vec<EntityDesc>,
+}
+
+impl SceneDesc {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let source = embedded::read_to_string(path)?;
+        Ok(ron::from_str(&source)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        let source = ron::ser::to_string_pretty(self, PrettyConfig::default())?;
+        fs::write(path, source)?;
+        Ok(())
+    }
+
+    // the world as a scene file at path would describe it: the main camera, the other cameras
+    // as views, directional and point lights, reflection probes, decals and every entity that knows its MeshSource. gltf paths are made relative
+    // to the file where they can be, so it can move along with its models. material textures
+    // and terrain aren't part of scenes and are left out
+    pub fn capture(world: &World, path: &Path) -> Self {
+        let base_dir = scene_dir(path);
+        let main = world::camera_entity(world);
+        let mut views: Vec<(Entity, i32)> = world.query::<(&Camera, Option<&Viewport>)>().iter()
+            .filter(|&(entity, _)| Some(entity) != main)
+            .map(|(entity, (_, viewport))| (entity, viewport.map_or(0, |viewport| viewport.order)))
+            .collect();
+        views.sort_by_key(|&(_, order)| order);
+        let views: Vec<Entity> = views.into_iter().map(|(entity, _)| entity).collect();
+
+        let mut query = world.query::<(&MeshSource, &Transform, Option<&Material>, Option<&Name>, Option<&AnimationPlayer>, Option<&PhysicsBody>, Option<&Screen>)>();
+        let mut entities: Vec<EntityDesc> = query.iter()
+            .map(|(entity, (mesh, transform, material, name, player, body, screen))| EntityDesc {
+                name: name.map_or_else(|| format!("entity {}", entity.id()), |name| name.0.clone()),
+                mesh: match mesh {
+                    MeshSource::Gltf(path) => MeshSource::Gltf(relative_path(path, &base_dir)),
+                    mesh => mesh.clone(),
+                },
+                transform: (*transform).into(),
+                material: material.copied().unwrap_or_default(),
+                animation: player.and_then(AnimationPlayer::current_clip),
+                body: body.copied(),
+                screen: screen.and_then(|screen| views.iter().position(|&view| view == screen.0)),
+            })
+            .collect();
+        entities.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            camera: main.and_then(|entity| CameraDesc::from_entity(world, entity)),
+            views: views.iter().filter_map(|&entity| CameraDesc::from_entity(world, entity)).collect(),
+            lights: world.query::<&DirectionalLight>().iter().map(|(_, light)| *light).collect(),
+            point_lights: world.query::<&PointLight>().iter().map(|(_, light)| *light).collect(),
+            probes: world.query::<&ReflectionProbe>().iter().map(|(_, probe)| *probe).collect(),
+            decals: world.query::<&Decal>().iter().map(|(_, decal)| *decal).collect(),
+            entities,
+        }
+    }
+}
+
+// despawns everything SceneDesc::capture would save except the main camera, so a loaded scene
+// replaces the world's contents instead of adding to them
+pub fn clear(world: &mut World) {
+    let main = world::camera_entity(world);
+    let mut despawned: Vec<Entity> = world.query::<&Camera>().iter()
+        .map(|(entity, _)| entity)
+        .filter(|&entity| Some(entity) != main)
+        .collect();
+    despawned.extend(world.query::<&DirectionalLight>().iter().map(|(entity, _)| entity));
+    despawned.extend(world.query::<&PointLight>().iter().map(|(entity, _)| entity));
+    despawned.extend(world.query::<&ReflectionProbe>().iter().map(|(entity, _)| entity));
+    despawned.extend(world.query::<&Decal>().iter().map(|(entity, _)| entity));
+    despawned.extend(world.query::<&MeshSource>().iter().map(|(entity, _)| entity));
+    for entity in despawned {
+        world::despawn(world, entity);
+    }
+}
+
+// where a file's relative paths start from
+fn scene_dir(path: &Path) -> PathBuf {
+    path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf()
+}
+
+// path as seen from base, both made absolute first. paths on another root, e.g. another windows
+// drive, stay absolute
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let (Ok(path), Ok(base)) = (std::path::absolute(path), std::path::absolute(base)) else {
+        return path.to_path_buf();
+    };
+    let common = path.components().zip(base.components()).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return path;
+    }
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    relative.extend(path.components().skip(common));
+    relative
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => error.fmt(f),
+            SceneError::Parse(error) => error.fmt(f),
+            SceneError::Serialize(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(error: io::Error) -> Self {
+        SceneError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for SceneError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        SceneError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for SceneError {
+    fn from(error: ron::Error) -> Self {
+        SceneError::Serialize(error)
+    }
+}
+
+// a scene file spawned into a world. the entities it spawned are tracked so a reload can update,
+// respawn or despawn them, anything else in the world is left alone
+pub struct Scene {
+    pub path: PathBuf,
+    desc: SceneDesc,
+    // spawned entity for every entity in desc, None if it failed to load
+    entities: Vec<Option<Entity>>,
+    views: Vec<Entity>,
+    lights: Vec<Entity>,
+    probes: Vec<Entity>,
+    decals: Vec<Entity>,
+}
+
+impl Scene {
+    // gltf models go through assets, so they load in the background, are shared between
+    // entities and hot reloaded
+    pub fn load(world: &mut World, assets: &mut Assets, path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref().to_path_buf();
+        let _span = tracing::info_span!("load scene", path = %path.display()).entered();
+        let desc = SceneDesc::load(&path)?;
+        Ok(Self::spawn(world, assets, path, desc))
+    }
+
+    // a scene already read from path, e.g. checked before the world was cleared for it
+    pub fn spawn(world: &mut World, assets: &mut Assets, path: impl AsRef<Path>, desc: SceneDesc) -> Self {
+        let mut scene = Self {
+            path: path.as_ref().to_path_buf(),
+            desc: SceneDesc::default(),
+            entities: Vec::new(),
+            views: Vec::new(),
+            lights: Vec::new(),
+            probes: Vec::new(),
+            decals: Vec::new(),
+        };
+        scene.apply(world, assets, desc);
+        scene
+    }
+
+    // re-reads the file, the current scene stays untouched if it doesn't parse
+    pub fn reload(&mut self, world: &mut World, assets: &mut Assets) -> Result<(), SceneError> {
+        let _span = tracing::info_span!("reload scene", path = %self.path.display()).entered();
+        let desc = SceneDesc::load(&self.path)?;
+        self.apply(world, assets, desc);
+        Ok(())
+    }
+
+    pub fn desc(&self) -> &SceneDesc {
+        &self.desc
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.iter().flatten().copied()
+    }
+
+    // only entities whose mesh source changed are respawned, the rest keep their gpu resources
+    fn apply(&mut self, world: &mut World, assets: &mut Assets, desc: SceneDesc) {
+        if desc.camera != self.desc.camera {
+            if let Some(desc) = &desc.camera {
+                let entity = world::camera_entity(world).unwrap_or_else(|| world.spawn((Camera::default(),)));
+                if let Ok(mut camera) = world.get::<&mut Camera>(entity) {
+                    let Camera { znear, zfar, .. } = *camera;
+                    *camera = Camera { znear, zfar, ..desc.camera() };
+                }
+                match desc.viewport {
+                    Some(viewport) => { let _ = world.insert_one(entity, viewport); }
+                    None => { let _ = world.remove_one::<Viewport>(entity); }
+                }
+            }
+        }
+        for view in self.views.drain(..) {
+            world::despawn(world, view);
+        }
+        // views without a viewport still draw after the main camera
+        self.views = desc.views.iter().enumerate()
+            .map(|(i, view)| {
+                let viewport = view.viewport.unwrap_or(Viewport { order: i as i32 + 1, ..Viewport::FULL });
+                let entity = world.spawn((view.camera(), viewport));
+                if let Some(render_target) = view.render_target {
+                    let _ = world.insert_one(entity, render_target);
+                }
+                entity
+            })
+            .collect();
+        for light in self.lights.drain(..) {
+            world::despawn(world, light);
+        }
+        self.lights = desc.lights.iter().map(|&light| world.spawn((light,)))
+            .chain(desc.point_lights.iter().map(|&light| world.spawn((light,))))
+            .collect();
+        for probe in self.probes.drain(..) {
+            world::despawn(world, probe);
+        }
+        self.probes = desc.probes.iter().map(|&probe| world.spawn((probe,))).collect();
+        for decal in self.decals.drain(..) {
+            world::despawn(world, decal);
+        }
+        self.decals = desc.decals.iter().map(|&decal| world.spawn((decal,))).collect();
+
+        let base_dir = scene_dir(&self.path);
+        let mut old_entities = std::mem::take(&mut self.entities);
+        let mut entities = Vec::with_capacity(desc.entities.len());
+        let mut rebuilt = 0;
+        for entity in &desc.entities {
+            let previous = self.desc.entities.iter().zip(&mut old_entities)
+                .find(|(old, spawned)| spawned.is_some() && old.name == entity.name && old.mesh == entity.mesh)
+                .and_then(|(old, spawned)| Some((old, spawned.take()?)));
+
+            let spawned = match previous {
+                Some((old, spawned)) => {
+                    if old.animation != entity.animation {
+                        world::play(world, spawned, entity.animation);
+                    }
+                    spawned
+                }
+                None => {
+                    let model = match &entity.mesh {
+                        MeshSource::Gltf(path) => Ok(assets.model(base_dir.join(path))),
+                        mesh => mesh.build(&base_dir).map(Arc::new),
+                    };
+                    let model = match model {
+                        Ok(model) => model,
+                        Err(error) => {
+                            tracing::error!("failed to load {}: {error}", entity.name);
+                            entities.push(None);
+                            continue;
+                        }
+                    };
+                    rebuilt += 1;
+                    let spawned = world::spawn_model(world, MeshRef(model), entity.transform.transform(), entity.material);
+                    world::play(world, spawned, entity.animation);
+                    spawned
+                }
+            };
+            let source = match &entity.mesh {
+                MeshSource::Gltf(path) => MeshSource::Gltf(base_dir.join(path)),
+                mesh => mesh.clone(),
+            };
+            let _ = world.insert(spawned, (entity.transform.transform(), entity.material, Name(entity.name.clone()), source));
+            if let Some(body) = entity.body {
+                let _ = world.insert_one(spawned, body);
+            } else {
+                let _ = world.remove_one::<PhysicsBody>(spawned);
+            }
+            match entity.screen.and_then(|view| self.views.get(view)) {
+                Some(&camera) => { let _ = world.insert_one(spawned, Screen(camera)); }
+                None => { let _ = world.remove_one::<Screen>(spawned); }
+            }
+            entities.push(Some(spawned));
+        }
+        for removed in old_entities.into_iter().flatten() {
+            world::despawn(world, removed);
+        }
+        if rebuilt > 0 {
+            tracing::info!("scene: rebuilt {rebuilt} of {} entities", desc.entities.len());
+        }
+
+        self.desc = desc;
+        self.entities = entities;
+    }
+}
@@ -0,0 +1,130 @@
+use image::{Rgba, RgbaImage};
+
+// how many pixels of distance the field holds on either side of the edge, which is also how
+// wide outlines and soft shadows can get. glyphs rendered at 32-64px hold up well with 4-8
+pub const DEFAULT_SPREAD: u32 = 8;
+// pixels at least this opaque are inside the shape
+const ALPHA_THRESHOLD: u8 = 128;
+// stands in for infinity in the distance transform, which has to subtract it from itself
+const FAR: f32 = 1e20;
+
+// turns an image's coverage into a signed distance field, so glyphs and icons stay crisp when
+// magnified and can be outlined or shadowed in the shader, see sdf.wgsl. alpha holds the
+// distance to the edge remapped so 0.5 is on the edge, 1 is spread pixels inside and 0 spread
+// pixels outside. the result has a border of spread pixels all around for the field to fade
+// out in. colors are kept and carried out into the border from the nearest opaque pixel, so
+// filtering along the edge doesn't bleed in black
+pub fn generate(image: &RgbaImage, spread: u32) -> RgbaImage {
+    let spread = spread.max(1);
+    let (width, height) = (image.width() + spread * 2, image.height() + spread * 2);
+    let pixel = |x: u32, y: u32| -> Option<&Rgba<u8>> {
+        let (x, y) = (x.checked_sub(spread)?, y.checked_sub(spread)?);
+        (x < image.width() && y < image.height()).then(|| image.get_pixel(x, y))
+    };
+    let inside: Vec<bool> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| pixel(x, y).is_some_and(|p| p[3] >= ALPHA_THRESHOLD))
+        .collect();
+    let outside: Vec<bool> = inside.iter().map(|inside| !inside).collect();
+    let (to_inside, nearest_inside) = distance_transform(&inside, width as usize, height as usize);
+    let (to_outside, _) = distance_transform(&outside, width as usize, height as usize);
+
+    let mut field = RgbaImage::new(width, height);
+    for (index, out) in field.pixels_mut().enumerate() {
+        // distances are between pixel centers, the edge lies halfway between
+        let distance = if inside[index] {
+            to_outside[index].sqrt() - 0.5
+        } else {
+            0.5 - to_inside[index].sqrt()
+        };
+        let alpha = (0.5 + distance / (spread as f32 * 2.0)).clamp(0.0, 1.0);
+        let nearest = nearest_inside[index];
+        let color = match pixel(nearest as u32 % width, nearest as u32 / width) {
+            Some(source) if to_inside[index] < FAR => [source[0], source[1], source[2]],
+            // nothing inside at all
+            _ => [255; 3],
+        };
+        *out = Rgba([color[0], color[1], color[2], (alpha * 255.0).round() as u8]);
+    }
+    field
+}
+
+// exact squared euclidean distances to the nearest site and which pixel that is, as an index
+// into the image. separable, columns then rows, after Felzenszwalb and Huttenlocher
+fn distance_transform(sites: &[bool], width: usize, height: usize) -> (Vec<f32>, Vec<usize>) {
+    let length = width.max(height);
+    let mut scratch = Scratch {
+        parabolas: vec![0; length],
+        boundaries: vec![0.0; length + 1],
+    };
+    let mut column = vec![0.0; height];
+    let mut distances = vec![0.0; length];
+    let mut nearest = vec![0; length];
+
+    // the distance to the nearest site in the same column, and its row
+    let mut vertical = vec![0.0; width * height];
+    let mut rows = vec![0; width * height];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = if sites[y * width + x] { 0.0 } else { FAR };
+        }
+        scratch.transform(&column, &mut distances[..height], &mut nearest[..height]);
+        for y in 0..height {
+            vertical[y * width + x] = distances[y];
+            rows[y * width + x] = nearest[y];
+        }
+    }
+
+    let mut squared = vec![0.0; width * height];
+    let mut indices = vec![0; width * height];
+    for y in 0..height {
+        let row = &vertical[y * width..(y + 1) * width];
+        scratch.transform(row, &mut distances[..width], &mut nearest[..width]);
+        for x in 0..width {
+            let column = nearest[x];
+            squared[y * width + x] = distances[x];
+            indices[y * width + x] = rows[y * width + column] * width + column;
+        }
+    }
+    (squared, indices)
+}
+
+// the lower envelope of the parabolas rooted at every sample
+struct Scratch {
+    parabolas: Vec<usize>,
+    boundaries: Vec<f32>,
+}
+
+impl Scratch {
+    fn transform(&mut self, f: &[f32], distances: &mut [f32], nearest: &mut [usize]) {
+        let (v, z) = (&mut self.parabolas, &mut self.boundaries);
+        let intersection = |q: usize, p: usize| {
+            let (qf, pf) = (q as f32, p as f32);
+            ((f[q] + qf * qf) - (f[p] + pf * pf)) / (2.0 * qf - 2.0 * pf)
+        };
+        let mut k = 0;
+        v[0] = 0;
+        z[0] = f32::NEG_INFINITY;
+        z[1] = f32::INFINITY;
+        for q in 1..f.len() {
+            let mut s = intersection(q, v[k]);
+            while k > 0 && s <= z[k] {
+                k -= 1;
+                s = intersection(q, v[k]);
+            }
+            k += 1;
+            v[k] = q;
+            z[k] = s;
+            z[k + 1] = f32::INFINITY;
+        }
+        k = 0;
+        for q in 0..f.len() {
+            while z[k + 1] < q as f32 {
+                k += 1;
+            }
+            let offset = q as f32 - v[k] as f32;
+            distances[q] = offset * offset + f[v[k]];
+            nearest[q] = v[k];
+        }
+    }
+}
@@ -0,0 +1,32 @@
+// for quads drawn from sdf atlas regions, see sdf.rs. alpha holds the distance to the edge,
+// 0.5 on it and growing inwards, in units of 1 / (2 * spread) atlas pixels
+
+// how much of the pixel the shape covers, antialiased over one screen pixel whatever the scale.
+// offset moves the edge out, positive grows the shape
+fn sdf_coverage(distance: f32, offset: f32) -> f32 {
+    let width = max(fwidth(distance), 0.0001) * 0.5;
+    let edge = 0.5 - offset;
+    return smoothstep(edge - width, edge + width, distance);
+}
+
+// fill with an outline of outline_width around it, both in distance units. width 0 is no outline
+fn sdf_outlined(distance: f32, fill: vec4<f32>, outline: vec4<f32>, outline_width: f32) -> vec4<f32> {
+    let inner = sdf_coverage(distance, 0.0);
+    let outer = sdf_coverage(distance, outline_width);
+    let color = mix(outline, fill, inner);
+    return vec4<f32>(color.rgb, color.a * outer);
+}
+
+// a soft shadow under the shape. shadow_distance is the field sampled at the uv offset by the
+// shadow's direction, softness spreads the falloff out in distance units
+fn sdf_shadow(shadow_distance: f32, color: vec4<f32>, softness: f32) -> vec4<f32> {
+    let alpha = smoothstep(0.5 - softness - 0.0001, 0.5 + softness, shadow_distance);
+    return vec4<f32>(color.rgb, color.a * alpha);
+}
+
+// straight alpha over straight alpha, for putting the shape on its shadow
+fn sdf_over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+    let rgb = (top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / max(alpha, 0.0001);
+    return vec4<f32>(rgb, alpha);
+}
@@ -0,0 +1,96 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use wgpu::*;
+use crate::context::RenderContext;
+use crate::preprocessor::{PreprocessError, Preprocessor};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+#[derive(Debug)]
+pub enum ShaderLoadError {
+    Io(io::Error),
+    Preprocess(PreprocessError),
+    InvalidSpirV(String),
+    UnknownExtension(String),
+}
+
+impl fmt::Display for ShaderLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderLoadError::Io(error) => error.fmt(f),
+            ShaderLoadError::Preprocess(error) => error.fmt(f),
+            ShaderLoadError::InvalidSpirV(message) => write!(f, "invalid spir-v: {message}"),
+            ShaderLoadError::UnknownExtension(extension) => write!(f, "unknown shader extension {extension:?}, expected wgsl or spv"),
+        }
+    }
+}
+
+impl std::error::Error for ShaderLoadError {}
+
+impl From<io::Error> for ShaderLoadError {
+    fn from(error: io::Error) -> Self {
+        ShaderLoadError::Io(error)
+    }
+}
+
+impl From<PreprocessError> for ShaderLoadError {
+    fn from(error: PreprocessError) -> Self {
+        ShaderLoadError::Preprocess(error)
+    }
+}
+
+// .wgsl is preprocessed (so it can include the built in chunks), .spv is precompiled spir-v,
+// e.g. from glslc or dxc. spir-v entry points keep the names the compiler gave them, usually "main"
+pub fn load(context: &RenderContext, path: impl AsRef<Path>) -> Result<ShaderModule, ShaderLoadError> {
+    let path = path.as_ref();
+    let label = path.to_string_lossy();
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("wgsl") => {
+            let text = fs::read_to_string(path)?;
+            let processed = Preprocessor::new().process_source(&label, &text)?;
+            Ok(context.device.create_shader_module(ShaderModuleDescriptor {
+                label: Some(&label),
+                source: ShaderSource::Wgsl(Cow::Owned(processed.source)),
+            }))
+        }
+        Some("spv") => {
+            let words = read_spirv(&fs::read(path)?)?;
+            if context.features().contains(Features::SPIRV_SHADER_PASSTHROUGH) {
+                // safety: the module goes to the driver unvalidated, which is the point of passthrough
+                Ok(unsafe {
+                    context.device.create_shader_module_spirv(&ShaderModuleDescriptorSpirV {
+                        label: Some(&label),
+                        source: Cow::Owned(words),
+                    })
+                })
+            } else {
+                Ok(context.device.create_shader_module(ShaderModuleDescriptor {
+                    label: Some(&label),
+                    source: ShaderSource::SpirV(Cow::Owned(words)),
+                }))
+            }
+        }
+        extension => Err(ShaderLoadError::UnknownExtension(extension.unwrap_or_default().to_string())),
+    }
+}
+
+// spir-v is a stream of u32 words, the magic number tells us the byte order
+pub fn read_spirv(bytes: &[u8]) -> Result<Vec<u32>, ShaderLoadError> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(ShaderLoadError::InvalidSpirV(format!("{} bytes isn't a whole number of words", bytes.len())));
+    }
+    let mut words: Vec<u32> = bytes.chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    match words.first() {
+        Some(&SPIRV_MAGIC) => {}
+        Some(&magic) if magic.swap_bytes() == SPIRV_MAGIC => {
+            words.iter_mut().for_each(|word| *word = word.swap_bytes());
+        }
+        _ => return Err(ShaderLoadError::InvalidSpirV("missing magic number".to_string())),
+    }
+    Ok(words)
+}
@@ -1,3 +1,5 @@
+#include "color.wgsl"
+
 struct VertexIn {
     @location(0) pos: vec2<f32>,
    @builtin(vertex_index) index: u32,
@@ -21,5 +23,5 @@ fn vertex(in: VertexIn) -> VertexOut {
 
 @fragment
 fn fragment() -> @location(0) vec4<f32> {
-    return vec4<f32>(1.0, 1.0, 0.0, 1.0);
+    return output_color(vec4<f32>(1.0, 1.0, 0.0, 1.0));
 }
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use crate::preprocessor::{self, Preprocessor, Processed};
+
+// a shader error pointing at the original file, not the preprocessed output
+#[derive(Debug)]
+pub struct ShaderError {
+    pub file: String,
+    // 0 if naga didn't report a location
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// preprocesses, parses and validates one shader, no device needed
+pub fn check(preprocessor: &Preprocessor, name: &str) -> Result<(), ShaderError> {
+    let processed = preprocessor.process_mapped(name).map_err(|error| ShaderError {
+        file: error.file,
+        line: error.line,
+        column: 0,
+        message: error.message,
+    })?;
+    let error = |location: Option<naga::SourceLocation>, message: String| {
+        locate(&processed, name, location, message)
+    };
+
+    let module = naga::front::wgsl::parse_str(&processed.source)
+        .map_err(|parse| error(parse.location(&processed.source), parse.to_string()))?;
+    validate(&module).map_err(|validation| {
+        error(validation.location(&processed.source), describe(validation.as_inner()))
+    })
+}
+
+// checks a shader on disk the same way shader::load would load it, .wgsl or .spv
+pub fn check_file(path: impl AsRef<Path>) -> Result<(), ShaderError> {
+    let path = path.as_ref();
+    let name = path.to_string_lossy();
+    let error = |message: String| ShaderError {
+        file: name.to_string(),
+        line: 0,
+        column: 0,
+        message,
+    };
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("wgsl") => {
+            let text = fs::read_to_string(path).map_err(|io| error(io.to_string()))?;
+            let processed = Preprocessor::new().process_source(&name, &text).map_err(|preprocess| ShaderError {
+                file: preprocess.file,
+                line: preprocess.line,
+                column: 0,
+                message: preprocess.message,
+            })?;
+            let module = naga::front::wgsl::parse_str(&processed.source).map_err(|parse| {
+                locate(&processed, &name, parse.location(&processed.source), parse.to_string())
+            })?;
+            validate(&module).map_err(|validation| {
+                locate(&processed, &name, validation.location(&processed.source), describe(validation.as_inner()))
+            })
+        }
+        Some("spv") => {
+            let bytes = fs::read(path).map_err(|io| error(io.to_string()))?;
+            let module = naga::front::spv::parse_u8_slice(&bytes, &Default::default())
+                .map_err(|parse| error(parse.to_string()))?;
+            // spir-v has no source lines to point at
+            validate(&module).map_err(|validation| error(describe(validation.as_inner())))
+        }
+        _ => Err(error("unknown shader extension, expected wgsl or spv".to_string())),
+    }
+}
+
+// checks every shader and chunk built into the binary, for srgb, linear and hdr targets, plus
+// the optional post-process and material texture defines
+pub fn check_all() -> Vec<ShaderError> {
+    let variants = [
+        Preprocessor::new(),
+        Preprocessor::new().define("ENCODE_SRGB", 1),
+        Preprocessor::new().define("HDR_TARGET", 1),
+        Preprocessor::new().define("FXAA", 1),
+        Preprocessor::new().define("HDR_TARGET", 1).define("FXAA", 1),
+        Preprocessor::new().define("MATERIAL_TEXTURES", 1),
+    ];
+    variants.iter()
+        .flat_map(|preprocessor| preprocessor::sources().map(move |(name, _)| check(preprocessor, name)))
+        .filter_map(Result::err)
+        .collect()
+}
+
+fn validate(module: &naga::Module) -> Result<(), naga::WithSpan<naga::valid::ValidationError>> {
+    Validator::new(ValidationFlags::all(), Capabilities::empty()).validate(module).map(drop)
+}
+
+// validation errors nest, the outer one alone rarely says what's wrong
+fn describe(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(inner) = source {
+        message += &format!(": {inner}");
+        source = inner.source();
+    }
+    message
+}
+
+fn locate(processed: &Processed, name: &str, location: Option<naga::SourceLocation>, message: String) -> ShaderError {
+    let origin = location.and_then(|location| {
+        let (file, line) = processed.origin(location.line_number as usize)?;
+        Some((file, line, location.line_position as usize))
+    });
+    let (file, line, column) = origin.unwrap_or((name, 0, 0));
+    ShaderError {
+        file: file.to_string(),
+        line,
+        column,
+        message,
+    }
+}
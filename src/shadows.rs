@@ -0,0 +1,404 @@
+use glam::{Mat4, Vec3};
+use wgpu::*;
+use crate::bindings::LayoutRegistry;
+use crate::camera::{Camera, CameraBinding, CameraUniform, FrameTextures};
+use crate::context::RenderContext;
+use crate::frames::FrameRing;
+use crate::light::{SceneLights, MAX_POINT_LIGHTS};
+use crate::probes::ProbeTextures;
+
+// match the array sizes in lights.wgsl
+pub const MAX_CASCADES: usize = 4;
+pub const MAX_POINT_SHADOWS: usize = 2;
+// a cube of them for every shadowed point light
+pub const POINT_SHADOW_FACES: usize = MAX_POINT_SHADOWS * 6;
+pub const SHADOW_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+// how far towards the light past a cascade's bounds things still cast into it, world units
+const CASTER_DISTANCE: f32 = 100.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    // 1 to MAX_CASCADES, each covering a further slice of the view
+    pub cascades: usize,
+    // width and height of each cascade's map
+    pub resolution: u32,
+    // view depth shadows end at, at most the camera's far plane
+    pub distance: f32,
+    // 0 splits the distance evenly, 1 logarithmically, which gives the near cascades more detail
+    pub split_lambda: f32,
+    // of each cascade at its far end, crossfaded into the next one so the seam doesn't show
+    pub blend: f32,
+    // against shadow acne, in the 0..1 depth of the cascade
+    pub bias: f32,
+    // moves the lookup out along the normal, in texels of the cascade
+    pub normal_bias: f32,
+    // tints what each cascade covers, red, green, blue and yellow from near to far
+    pub debug_cascades: bool,
+    // point lights with cast_shadows that get a cube, up to MAX_POINT_SHADOWS. the ones closest
+    // to the main camera win
+    pub point_lights: usize,
+    // width and height of each cube face
+    pub point_resolution: u32,
+    // against acne on point shadows, in fractions of the light's range
+    pub point_bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cascades: MAX_CASCADES,
+            resolution: 2048,
+            distance: 100.0,
+            split_lambda: 0.75,
+            blend: 0.1,
+            bias: 0.0005,
+            normal_bias: 1.5,
+            debug_cascades: false,
+            point_lights: MAX_POINT_SHADOWS,
+            point_resolution: 512,
+            point_bias: 0.005,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ShadowUniform {
+    // world to each cascade's light clip space
+    matrices: [[[f32; 4]; 4]; MAX_CASCADES],
+    // the view depth each cascade ends at
+    splits: [f32; MAX_CASCADES],
+    // world size of a texel in each cascade
+    texel_sizes: [f32; MAX_CASCADES],
+    count: u32,
+    bias: f32,
+    normal_bias: f32,
+    blend: f32,
+    debug: u32,
+    _padding: [u32; 3],
+    // world to clip space of every face, six per shadowed point light
+    point_matrices: [[[f32; 4]; 4]; POINT_SHADOW_FACES],
+    // shadowed point lights whose faces are up to date
+    point_count: u32,
+    point_bias: f32,
+    _point_padding: [u32; 2],
+}
+
+impl ShadowUniform {
+    // no cascades, everything is lit
+    pub const NONE: Self = Self {
+        matrices: [[[0.0; 4]; 4]; MAX_CASCADES],
+        splits: [0.0; MAX_CASCADES],
+        texel_sizes: [0.0; MAX_CASCADES],
+        count: 0,
+        bias: 0.0,
+        normal_bias: 0.0,
+        blend: 0.0,
+        debug: 0,
+        _padding: [0; 3],
+        point_matrices: [[[0.0; 4]; 4]; POINT_SHADOW_FACES],
+        point_count: 0,
+        point_bias: 0.0,
+        _point_padding: [0; 2],
+    };
+}
+
+// what FrameTextures binds of the shadows
+#[derive(Copy, Clone)]
+pub struct ShadowTextures<'a> {
+    pub maps: &'a TextureView,
+    pub point_maps: &'a TextureView,
+    pub sampler: &'a Sampler,
+}
+
+// cascaded shadow maps for the first directional light, fit to the main view every update, and
+// a cube of six faces for a few point lights. cascades and faces are layers of two depth
+// texture arrays, bound with every camera and sampled in lights.wgsl. meshes and visible terrain
+// chunks cast, drawn from each cascade and face. point light faces hold the distance to the
+// light over its range rather than projected depth, so it compares the same from every face
+pub struct ShadowMaps {
+    resolution: u32,
+    maps: TextureView,
+    layers: Vec<TextureView>,
+    point_resolution: u32,
+    point_maps: TextureView,
+    point_layers: Vec<TextureView>,
+    // bound where there are no shadows, and while drawing the maps, which can't be sampled then
+    empty: TextureView,
+    sampler: Sampler,
+    uniform: ShadowUniform,
+    // one per cascade and one per point light face, only the camera uniform is read
+    camera_bindings: FrameRing<Vec<CameraBinding>>,
+    point_camera_bindings: FrameRing<Vec<CameraBinding>>,
+}
+
+impl ShadowMaps {
+    // white and probes are bound with the cascades and faces, nothing reads them
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, white: &TextureView, probes: ProbeTextures) -> Self {
+        let max_size = context.limits().max_texture_dimension_2d;
+        let resolution = ShadowSettings::default().resolution.min(max_size);
+        let point_resolution = ShadowSettings::default().point_resolution.min(max_size);
+        let (maps, layers) = create_maps(context, "shadow cascades", resolution, MAX_CASCADES);
+        let (point_maps, point_layers) = create_maps(context, "point shadows", point_resolution, POINT_SHADOW_FACES);
+        // enough layers to stand in for either
+        let (empty, _) = create_maps(context, "no shadows", 1, MAX_CASCADES.max(POINT_SHADOW_FACES));
+        // linear with a comparison blends the four nearest results, smoothing edges a little for free
+        let sampler = context.device.create_sampler(&SamplerDescriptor {
+            label: Some("shadows"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            compare: Some(CompareFunction::LessEqual),
+            ..SamplerDescriptor::default()
+        });
+        let textures = FrameTextures {
+            occlusion: white,
+            shadows: ShadowTextures {
+                maps: &empty,
+                point_maps: &empty,
+                sampler: &sampler,
+            },
+            probes,
+        };
+        let camera_bindings = FrameRing::new(|| (0..MAX_CASCADES).map(|_| CameraBinding::new(context, layouts, textures)).collect());
+        let point_camera_bindings = FrameRing::new(|| (0..POINT_SHADOW_FACES).map(|_| CameraBinding::new(context, layouts, textures)).collect());
+        Self {
+            resolution,
+            maps,
+            layers,
+            point_resolution,
+            point_maps,
+            point_layers,
+            empty,
+            sampler,
+            uniform: ShadowUniform::NONE,
+            camera_bindings,
+            point_camera_bindings,
+        }
+    }
+
+    // for the cameras the cascades are fit to
+    pub fn textures(&self) -> ShadowTextures {
+        ShadowTextures {
+            maps: &self.maps,
+            point_maps: &self.point_maps,
+            sampler: &self.sampler,
+        }
+    }
+
+    // for everything else, with ShadowUniform::NONE
+    pub fn empty_textures(&self) -> ShadowTextures {
+        ShadowTextures {
+            maps: &self.empty,
+            point_maps: &self.empty,
+            sampler: &self.sampler,
+        }
+    }
+
+    // what the cameras bound with textures get as their shadows, as of the last update
+    pub fn uniform(&self) -> &ShadowUniform {
+        &self.uniform
+    }
+
+    pub fn cascade_count(&self) -> usize {
+        self.uniform.count as usize
+    }
+
+    // the faces of every shadowed point light, six in a row for each
+    pub fn point_face_count(&self) -> usize {
+        self.uniform.point_count as usize * 6
+    }
+
+    pub fn camera_binding(&self, slot: usize, cascade: usize) -> &CameraBinding {
+        &self.camera_bindings.get(slot)[cascade]
+    }
+
+    pub fn point_camera_binding(&self, slot: usize, face: usize) -> &CameraBinding {
+        &self.point_camera_bindings.get(slot)[face]
+    }
+
+    // fits the cascades to the camera when cascades is true, and picks the point lights that get
+    // a cube, filling in lights.shadowed. returns true when the maps were recreated for a new
+    // resolution, bindings made from textures have to be rebuilt
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(&mut self, context: &RenderContext, settings: &ShadowSettings, camera: &Camera, aspect: f32, cascades: bool, lights: &mut SceneLights, slot: usize) -> bool {
+        let max_size = context.limits().max_texture_dimension_2d;
+        let resolution = settings.resolution.clamp(1, max_size);
+        let point_resolution = settings.point_resolution.clamp(1, max_size);
+        let resized = resolution != self.resolution || point_resolution != self.point_resolution;
+        if resolution != self.resolution {
+            (self.maps, self.layers) = create_maps(context, "shadow cascades", resolution, MAX_CASCADES);
+            self.resolution = resolution;
+        }
+        if point_resolution != self.point_resolution {
+            (self.point_maps, self.point_layers) = create_maps(context, "point shadows", point_resolution, POINT_SHADOW_FACES);
+            self.point_resolution = point_resolution;
+        }
+
+        let mut uniform = ShadowUniform {
+            bias: settings.bias,
+            normal_bias: settings.normal_bias,
+            blend: settings.blend.clamp(0.0, 1.0),
+            debug: settings.debug_cascades as u32,
+            point_bias: settings.point_bias,
+            ..ShadowUniform::NONE
+        };
+        if cascades {
+            let direction = lights.directional.first().map_or(Vec3::NEG_Y, |light| light.direction);
+            self.fit_cascades(context, settings, camera, aspect, direction, slot, &mut uniform);
+        }
+        self.fit_point_lights(context, settings, camera.eye, lights, slot, &mut uniform);
+        self.uniform = uniform;
+        resized
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn fit_cascades(&self, context: &RenderContext, settings: &ShadowSettings, camera: &Camera, aspect: f32, direction: Vec3, slot: usize, uniform: &mut ShadowUniform) {
+        let count = settings.cascades.clamp(1, MAX_CASCADES);
+        let near = camera.znear;
+        let far = settings.distance.clamp(near + 0.001, camera.zfar.max(near + 0.001));
+        let direction = direction.try_normalize().unwrap_or(Vec3::NEG_Y);
+        uniform.count = count as u32;
+        let mut start = near;
+        for cascade in 0..count {
+            // between an even and a logarithmic split, see split_lambda
+            let t = (cascade + 1) as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(t);
+            let even = near + (far - near) * t;
+            let end = settings.split_lambda * logarithmic + (1.0 - settings.split_lambda) * even;
+
+            let (view, projection, position, texel_size) = fit_cascade(camera, aspect, start, end, direction, self.resolution);
+            uniform.matrices[cascade] = (projection * view).to_cols_array_2d();
+            uniform.splits[cascade] = end;
+            uniform.texel_sizes[cascade] = texel_size;
+            // the far plane is only read when drawing point light faces
+            self.camera_bindings.get(slot)[cascade].update_uniform(context, &CameraUniform::from_matrices(view, projection, position, 1.0));
+            start = end;
+        }
+    }
+
+    // a 90 degree view down each axis from the light, the faces' far plane is its range
+    fn fit_point_lights(&self, context: &RenderContext, settings: &ShadowSettings, eye: Vec3, lights: &mut SceneLights, slot: usize, uniform: &mut ShadowUniform) {
+        let mut candidates: Vec<usize> = (0..lights.point.len().min(MAX_POINT_LIGHTS))
+            .filter(|&index| lights.point[index].cast_shadows)
+            .collect();
+        candidates.sort_by(|&a, &b| lights.point[a].position.distance_squared(eye).total_cmp(&lights.point[b].position.distance_squared(eye)));
+        candidates.truncate(settings.point_lights.min(MAX_POINT_SHADOWS));
+        lights.shadowed = candidates;
+
+        let faces = [
+            (Vec3::X, Vec3::NEG_Y),
+            (Vec3::NEG_X, Vec3::NEG_Y),
+            (Vec3::Y, Vec3::Z),
+            (Vec3::NEG_Y, Vec3::NEG_Z),
+            (Vec3::Z, Vec3::NEG_Y),
+            (Vec3::NEG_Z, Vec3::NEG_Y),
+        ];
+        for (cube, &index) in lights.shadowed.iter().enumerate() {
+            let light = &lights.point[index];
+            let range = light.range.max(0.001);
+            let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, range * 0.001, range);
+            for (face, &(forward, up)) in faces.iter().enumerate() {
+                let view = Mat4::look_at_rh(light.position, light.position + forward, up);
+                let layer = cube * 6 + face;
+                uniform.point_matrices[layer] = (projection * view).to_cols_array_2d();
+                self.point_camera_bindings.get(slot)[layer].update_uniform(context, &CameraUniform::from_matrices(view, projection, light.position, range));
+            }
+        }
+        uniform.point_count = lights.shadowed.len() as u32;
+    }
+
+    // into the cascade's layer, cleared
+    pub fn begin_pass<'a>(&'a self, cmd: &'a mut CommandEncoder, cascade: usize) -> RenderPass<'a> {
+        begin_pass(cmd, "shadow cascade", &self.layers[cascade])
+    }
+
+    // into one face of a point light's cube, see point_face_count
+    pub fn begin_point_pass<'a>(&'a self, cmd: &'a mut CommandEncoder, face: usize) -> RenderPass<'a> {
+        begin_pass(cmd, "point shadow face", &self.point_layers[face])
+    }
+}
+
+fn begin_pass<'a>(cmd: &'a mut CommandEncoder, label: &str, target: &'a TextureView) -> RenderPass<'a> {
+    cmd.begin_render_pass(&RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+            view: target,
+            depth_ops: Some(Operations {
+                load: LoadOp::Clear(1.0),
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+    })
+}
+
+// a light space box around the bounding sphere of the view between near and far. the sphere
+// keeps the box the same size as the camera turns and the box moves in whole texels, so the
+// shadow edges don't crawl. returns the light's view, projection and position and the world
+// size of a texel
+fn fit_cascade(camera: &Camera, aspect: f32, near: f32, far: f32, direction: Vec3, resolution: u32) -> (Mat4, Mat4, Vec3, f32) {
+    let inverse_view = camera.view().inverse();
+    let tan_y = (camera.fovy * 0.5).tan();
+    let tan_x = tan_y * aspect;
+    let corners: Vec<Vec3> = [near, far].into_iter()
+        .flat_map(|depth| [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| Vec3::new(x * tan_x * depth, y * tan_y * depth, -depth)))
+        .map(|corner| inverse_view.transform_point3(corner))
+        .collect();
+    let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+    let radius = corners.iter().map(|corner| corner.distance(center)).fold(0.0, f32::max);
+    // rounded up so float noise doesn't change the size from frame to frame
+    let radius = ((radius * 16.0).ceil() / 16.0).max(0.01);
+
+    let up = if direction.dot(Vec3::Y).abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+    let view = Mat4::look_at_rh(Vec3::ZERO, direction, up);
+    let texel_size = radius * 2.0 / resolution as f32;
+    let mut light_center = view.transform_point3(center);
+    light_center.x = (light_center.x / texel_size).floor() * texel_size;
+    light_center.y = (light_center.y / texel_size).floor() * texel_size;
+    // the light looks down -z, depth is the distance along it
+    let projection = Mat4::orthographic_rh(
+        light_center.x - radius,
+        light_center.x + radius,
+        light_center.y - radius,
+        light_center.y + radius,
+        -light_center.z - radius - CASTER_DISTANCE,
+        -light_center.z + radius,
+    );
+    let position = center - direction * (radius + CASTER_DISTANCE);
+    (view, projection, position, texel_size)
+}
+
+// the array view for sampling, and one view per layer to draw into
+fn create_maps(context: &RenderContext, label: &str, resolution: u32, layers: usize) -> (TextureView, Vec<TextureView>) {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: layers as u32,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: SHADOW_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    let maps = texture.create_view(&TextureViewDescriptor {
+        label: Some(label),
+        dimension: Some(TextureViewDimension::D2Array),
+        ..TextureViewDescriptor::default()
+    });
+    let layers = (0..layers as u32)
+        .map(|layer| texture.create_view(&TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: std::num::NonZeroU32::new(1),
+            ..TextureViewDescriptor::default()
+        }))
+        .collect();
+    (maps, layers)
+}
@@ -0,0 +1,26 @@
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "color.wgsl"
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// a single triangle that covers the viewport
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOut;
+    out.ndc = corner * 2.0 - 1.0;
+    out.pos = vec4<f32>(out.ndc, 1.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    // any depth gives the same direction, halfway avoids an infinite far plane
+    let unprojected = camera.inverse_view_proj * vec4<f32>(in.ndc, 0.5, 1.0);
+    let direction = normalize(unprojected.xyz / unprojected.w - camera.position.xyz);
+    return output_color(vec4<f32>(sky(direction), 1.0));
+}
@@ -0,0 +1,248 @@
+use std::mem::size_of;
+use glam::Vec3;
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::Camera;
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+
+pub const SSAO_LAYOUT: &str = "ssao";
+pub const SSAO_BLUR_LAYOUT: &str = "ssao blur";
+// written by the prepass, see view_normal_depth in camera.wgsl
+pub const NORMAL_DEPTH_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const OCCLUSION_FORMAT: TextureFormat = TextureFormat::R8Unorm;
+pub const SSAO_KERNEL_SIZE: usize = 16;
+
+#[derive(Copy, Clone, Debug)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    // world units around a point that are searched for occluders
+    pub radius: f32,
+    // keeps flat surfaces from occluding themselves
+    pub bias: f32,
+    // 0 has no effect, above 1 darkens faster than the occluded fraction
+    pub intensity: f32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct SsaoUniform {
+    projection: [[f32; 4]; 4],
+    inverse_projection: [[f32; 4]; 4],
+    kernel: [[f32; 4]; SSAO_KERNEL_SIZE],
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    _padding: f32,
+}
+
+struct Targets {
+    normal_depth: TextureView,
+    raw: TextureView,
+    occlusion: TextureView,
+    ssao_bind_group: BindGroup,
+    blur_bind_group: BindGroup,
+}
+
+// ambient occlusion from a normal and depth prepass, blurred to hide the noise. the result is
+// bound with the lights and only darkens ambient light
+pub struct Ssao {
+    buffer: Buffer,
+    kernel: [[f32; 4]; SSAO_KERNEL_SIZE],
+    targets: Targets,
+    ssao_pipeline: RenderPipeline,
+    blur_pipeline: RenderPipeline,
+}
+
+impl Ssao {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, width: u32, height: u32) -> Self {
+        let buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("ssao"),
+            size: size_of::<SsaoUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, SSAO_LAYOUT, &[
+            (Binding::Uniform, ShaderStages::FRAGMENT),
+            (Binding::Texture, ShaderStages::FRAGMENT),
+        ]);
+        layouts.register(context, SSAO_BLUR_LAYOUT, &[
+            (Binding::Texture, ShaderStages::FRAGMENT),
+            (Binding::Texture, ShaderStages::FRAGMENT),
+        ]);
+        let targets = Targets::new(context, layouts, &buffer, width, height);
+        Self {
+            buffer,
+            kernel: kernel(),
+            targets,
+            ssao_pipeline: create_pipeline(context, layouts, "ssao.wgsl", SSAO_LAYOUT),
+            blur_pipeline: create_pipeline(context, layouts, "ssao_blur.wgsl", SSAO_BLUR_LAYOUT),
+        }
+    }
+
+    pub fn resize(&mut self, context: &RenderContext, layouts: &LayoutRegistry, width: u32, height: u32) {
+        self.targets = Targets::new(context, layouts, &self.buffer, width, height);
+    }
+
+    // what the prepass draws into
+    pub fn normal_depth_view(&self) -> &TextureView {
+        &self.targets.normal_depth
+    }
+
+    // the blurred result, recreated on resize
+    pub fn occlusion_view(&self) -> &TextureView {
+        &self.targets.occlusion
+    }
+
+    pub fn update(&self, context: &RenderContext, settings: &SsaoSettings, camera: &Camera, aspect: f32) {
+        let projection = camera.projection(aspect);
+        let uniform = SsaoUniform {
+            projection: projection.to_cols_array_2d(),
+            inverse_projection: projection.inverse().to_cols_array_2d(),
+            kernel: self.kernel,
+            radius: settings.radius,
+            bias: settings.bias,
+            intensity: settings.intensity,
+            _padding: 0.0,
+        };
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    // runs after the prepass and before anything samples the occlusion
+    pub fn draw(&self, cmd: &mut CommandEncoder) {
+        let passes = [
+            ("ssao", &self.ssao_pipeline, &self.targets.ssao_bind_group, &self.targets.raw),
+            ("ssao blur", &self.blur_pipeline, &self.targets.blur_bind_group, &self.targets.occlusion),
+        ];
+        for (label, pipeline, bind_group, target) in passes {
+            let mut render_cmd = begin_pass(cmd, label, target);
+            render_cmd.set_pipeline(pipeline);
+            render_cmd.set_bind_group(0, bind_group, &[]);
+            render_cmd.draw(0..3, 0..1);
+        }
+    }
+
+    // leaves nothing occluded, for frames drawn with ssao off
+    pub fn clear(&self, cmd: &mut CommandEncoder) {
+        let _ = begin_pass(cmd, "ssao clear", &self.targets.occlusion);
+    }
+}
+
+impl Targets {
+    fn new(context: &RenderContext, layouts: &LayoutRegistry, buffer: &Buffer, width: u32, height: u32) -> Self {
+        let normal_depth = create_target(context, "ssao normal depth", NORMAL_DEPTH_FORMAT, width, height);
+        let raw = create_target(context, "ssao raw", OCCLUSION_FORMAT, width, height);
+        let occlusion = create_target(context, "ssao", OCCLUSION_FORMAT, width, height);
+        let ssao_bind_group = BindGroupBuilder::new()
+            .buffer(buffer)
+            .texture(&normal_depth)
+            .build(context, layouts, SSAO_LAYOUT);
+        let blur_bind_group = BindGroupBuilder::new()
+            .texture(&raw)
+            .texture(&normal_depth)
+            .build(context, layouts, SSAO_BLUR_LAYOUT);
+        Self {
+            normal_depth,
+            raw,
+            occlusion,
+            ssao_bind_group,
+            blur_bind_group,
+        }
+    }
+}
+
+// points in the +z hemisphere, scaled so more of them land close to the center. a fixed
+// sequence, the per-pixel rotation in the shader provides the randomness
+fn kernel() -> [[f32; 4]; SSAO_KERNEL_SIZE] {
+    let mut state = 0x9e37_79b9u32;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32
+    };
+    std::array::from_fn(|i| {
+        let direction = Vec3::new(random() * 2.0 - 1.0, random() * 2.0 - 1.0, random()).normalize_or_zero();
+        let t = i as f32 / SSAO_KERNEL_SIZE as f32;
+        let scale = 0.1 + 0.9 * t * t;
+        (direction * random() * scale).extend(0.0).to_array()
+    })
+}
+
+fn begin_pass<'a>(cmd: &'a mut CommandEncoder, label: &str, target: &'a TextureView) -> RenderPass<'a> {
+    cmd.begin_render_pass(&RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[
+            Some(RenderPassColorAttachment {
+                ops: Operations {
+                    load: LoadOp::Clear(Color::WHITE),
+                    store: true,
+                },
+                view: target,
+                resolve_target: None,
+            })
+        ],
+        depth_stencil_attachment: None,
+    })
+}
+
+fn create_target(context: &RenderContext, label: &str, format: TextureFormat, width: u32, height: u32) -> TextureView {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    texture.create_view(&TextureViewDescriptor {
+        label: Some(label),
+        ..TextureViewDescriptor::default()
+    })
+}
+
+fn create_pipeline(context: &RenderContext, layouts: &LayoutRegistry, shader: &str, layout: &str) -> RenderPipeline {
+    let device = &context.device;
+    let shader_module = Preprocessor::new().create_module(context, shader);
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some(shader),
+        bind_group_layouts: &[layouts.get(layout)],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(shader),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            entry_point: "vertex",
+            module: &shader_module,
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            entry_point: "fragment",
+            module: &shader_module,
+            targets: &[
+                Some(OCCLUSION_FORMAT.into())
+            ],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,81 @@
+struct Ssao {
+    projection: mat4x4<f32>,
+    inverse_projection: mat4x4<f32>,
+    // view space offsets in a unit hemisphere around +z, denser towards the center
+    kernel: array<vec4<f32>, SSAO_KERNEL_SIZE>,
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> ssao: Ssao;
+@group(0) @binding(1)
+var normal_depth: texture_2d<f32>;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+}
+
+// a single triangle that covers the screen
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    var out: VertexOut;
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.pos = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fn view_position(pixel: vec2<f32>, size: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec2<f32>(pixel.x / size.x * 2.0 - 1.0, 1.0 - pixel.y / size.y * 2.0);
+    let far = ssao.inverse_projection * vec4<f32>(ndc, 1.0, 1.0);
+    let ray = far.xyz / far.w;
+    return ray * (depth / -ray.z);
+}
+
+// interleaved gradient noise, a different kernel rotation per pixel that the blur then evens out
+fn noise(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let center = textureLoad(normal_depth, vec2<i32>(in.pos.xy), 0);
+    if (center.w <= 0.0) {
+        return vec4<f32>(1.0);
+    }
+    let size = vec2<f32>(textureDimensions(normal_depth));
+    let normal = normalize(center.xyz);
+    let position = view_position(in.pos.xy, size, center.w);
+
+    let angle = noise(in.pos.xy) * 6.2831853;
+    var random = vec3<f32>(cos(angle), sin(angle), 0.0);
+    // normals pointing sideways can line up with the random vector
+    if (abs(dot(random, normal)) > 0.99) {
+        random = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(random - normal * dot(random, normal));
+    let basis = mat3x3<f32>(tangent, cross(normal, tangent), normal);
+
+    var occlusion = 0.0;
+    for (var i = 0; i < SSAO_KERNEL_SIZE; i = i + 1) {
+        let probe = position + basis * ssao.kernel[i].xyz * ssao.radius;
+        let clip = ssao.projection * vec4<f32>(probe, 1.0);
+        let ndc = clip.xy / clip.w;
+        let pixel = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * size;
+        if (any(pixel < vec2<f32>(0.0)) || any(pixel >= size)) {
+            continue;
+        }
+        let depth = textureLoad(normal_depth, vec2<i32>(pixel), 0).w;
+        if (depth <= 0.0) {
+            continue;
+        }
+        // geometry far in front of the point is something else, not an occluder
+        let range = smoothstep(0.0, 1.0, ssao.radius / abs(center.w - depth));
+        if (depth <= -probe.z - ssao.bias) {
+            occlusion = occlusion + range;
+        }
+    }
+    let visibility = 1.0 - ssao.intensity * occlusion / f32(SSAO_KERNEL_SIZE);
+    return vec4<f32>(clamp(visibility, 0.0, 1.0));
+}
@@ -0,0 +1,42 @@
+@group(0) @binding(0)
+var occlusion: texture_2d<f32>;
+@group(0) @binding(1)
+var normal_depth: texture_2d<f32>;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+}
+
+// a single triangle that covers the screen
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    var out: VertexOut;
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.pos = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// a 4x4 box, the size the noise repeats at. texels at a very different depth are skipped so
+// occlusion doesn't bleed across silhouettes
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let center = vec2<i32>(in.pos.xy);
+    let depth = textureLoad(normal_depth, center, 0).w;
+    let last = vec2<i32>(textureDimensions(occlusion)) - 1;
+    var total = 0.0;
+    var weight = 0.0;
+    for (var y = -2; y < 2; y = y + 1) {
+        for (var x = -2; x < 2; x = x + 1) {
+            let texel = clamp(center + vec2<i32>(x, y), vec2<i32>(0), last);
+            let sample_depth = textureLoad(normal_depth, texel, 0).w;
+            if (abs(sample_depth - depth) <= depth * 0.1) {
+                total = total + textureLoad(occlusion, texel, 0).r;
+                weight = weight + 1.0;
+            }
+        }
+    }
+    if (weight == 0.0) {
+        return vec4<f32>(1.0);
+    }
+    return vec4<f32>(total / weight);
+}
@@ -0,0 +1,230 @@
+use std::mem::size_of;
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::Camera;
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::HDR_FORMAT;
+
+pub const SSR_LAYOUT: &str = "ssr";
+pub const SSR_COMPOSITE_LAYOUT: &str = "ssr composite";
+// reflected color in rgb, how much of it replaces the scene in a
+const REFLECTION_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+#[derive(Copy, Clone, Debug)]
+pub struct SsrSettings {
+    pub enabled: bool,
+    // samples along each ray before it gives up, more find thinner things further away
+    pub steps: u32,
+    // how far behind the depth buffer a ray can be and still count as hitting it, world units
+    pub thickness: f32,
+    // world units a ray travels at most
+    pub max_distance: f32,
+    // of the screen at its edges and of max_distance at the end, where reflections fade out
+    // instead of stopping at a hard line
+    pub fade: f32,
+    // materials have no roughness, so this stands in for all of them. 0 is a mirror, 1 only
+    // reflects the fallback color
+    pub roughness: f32,
+    // reflectivity looking straight at a surface, grazing angles go towards 1
+    pub strength: f32,
+}
+
+impl Default for SsrSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            steps: 32,
+            thickness: 0.3,
+            max_distance: 20.0,
+            fade: 0.1,
+            roughness: 0.3,
+            strength: 0.1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct SsrUniform {
+    projection: [[f32; 4]; 4],
+    inverse_projection: [[f32; 4]; 4],
+    // what rays that leave the screen reflect, there's no environment map to fall back on
+    fallback: [f32; 4],
+    steps: u32,
+    thickness: f32,
+    max_distance: f32,
+    fade: f32,
+    roughness: f32,
+    strength: f32,
+    _padding: [f32; 2],
+}
+
+struct Targets {
+    reflection: TextureView,
+    ssr_bind_group: BindGroup,
+    composite_bind_group: BindGroup,
+}
+
+// reflections raymarched through the ssao prepass's view space normals and depth, picking up
+// the lit scene where a ray hits. drawn into a target of its own, as the scene can't be read
+// while it's drawn to, then blended over the scene before the output pass
+pub struct Ssr {
+    buffer: Buffer,
+    targets: Targets,
+    ssr_pipeline: RenderPipeline,
+    composite_pipeline: RenderPipeline,
+}
+
+impl Ssr {
+    // scene is the hdr target reflections are read from and blended into, normal_depth the
+    // prepass. both are replaced with set_targets when they're recreated
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, scene: &TextureView, normal_depth: &TextureView, width: u32, height: u32) -> Self {
+        let buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("ssr"),
+            size: size_of::<SsrUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, SSR_LAYOUT, &[
+            (Binding::Uniform, ShaderStages::FRAGMENT),
+            (Binding::Texture, ShaderStages::FRAGMENT),
+            (Binding::Texture, ShaderStages::FRAGMENT),
+        ]);
+        layouts.register(context, SSR_COMPOSITE_LAYOUT, &[
+            (Binding::Texture, ShaderStages::FRAGMENT),
+        ]);
+        let targets = Targets::new(context, layouts, &buffer, scene, normal_depth, width, height);
+        Self {
+            buffer,
+            targets,
+            ssr_pipeline: create_pipeline(context, layouts, "ssr.wgsl", SSR_LAYOUT, REFLECTION_FORMAT, None),
+            composite_pipeline: create_pipeline(context, layouts, "ssr_composite.wgsl", SSR_COMPOSITE_LAYOUT, HDR_FORMAT, Some(BlendState::ALPHA_BLENDING)),
+        }
+    }
+
+    pub fn set_targets(&mut self, context: &RenderContext, layouts: &LayoutRegistry, scene: &TextureView, normal_depth: &TextureView, width: u32, height: u32) {
+        self.targets = Targets::new(context, layouts, &self.buffer, scene, normal_depth, width, height);
+    }
+
+    pub fn update(&self, context: &RenderContext, settings: &SsrSettings, camera: &Camera, aspect: f32, fallback: Color) {
+        let projection = camera.projection(aspect);
+        let uniform = SsrUniform {
+            projection: projection.to_cols_array_2d(),
+            inverse_projection: projection.inverse().to_cols_array_2d(),
+            fallback: [fallback.r as f32, fallback.g as f32, fallback.b as f32, 1.0],
+            steps: settings.steps.max(1),
+            thickness: settings.thickness,
+            max_distance: settings.max_distance,
+            fade: settings.fade.clamp(0.0001, 1.0),
+            roughness: settings.roughness.clamp(0.0, 1.0),
+            strength: settings.strength.clamp(0.0, 1.0),
+            _padding: [0.0; 2],
+        };
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    // after the scene is drawn, into it. the prepass has to have run this frame
+    pub fn draw(&self, cmd: &mut CommandEncoder, scene: &TextureView) {
+        {
+            let mut render_cmd = begin_pass(cmd, "ssr", &self.targets.reflection, LoadOp::Clear(Color::TRANSPARENT));
+            render_cmd.set_pipeline(&self.ssr_pipeline);
+            render_cmd.set_bind_group(0, &self.targets.ssr_bind_group, &[]);
+            render_cmd.draw(0..3, 0..1);
+        }
+        let mut render_cmd = begin_pass(cmd, "ssr composite", scene, LoadOp::Load);
+        render_cmd.set_pipeline(&self.composite_pipeline);
+        render_cmd.set_bind_group(0, &self.targets.composite_bind_group, &[]);
+        render_cmd.draw(0..3, 0..1);
+    }
+}
+
+impl Targets {
+    fn new(context: &RenderContext, layouts: &LayoutRegistry, buffer: &Buffer, scene: &TextureView, normal_depth: &TextureView, width: u32, height: u32) -> Self {
+        let reflection = create_target(context, "ssr reflection", width, height);
+        let ssr_bind_group = BindGroupBuilder::new()
+            .buffer(buffer)
+            .texture(normal_depth)
+            .texture(scene)
+            .build(context, layouts, SSR_LAYOUT);
+        let composite_bind_group = BindGroupBuilder::new()
+            .texture(&reflection)
+            .build(context, layouts, SSR_COMPOSITE_LAYOUT);
+        Self {
+            reflection,
+            ssr_bind_group,
+            composite_bind_group,
+        }
+    }
+}
+
+fn begin_pass<'a>(cmd: &'a mut CommandEncoder, label: &str, target: &'a TextureView, load: LoadOp<Color>) -> RenderPass<'a> {
+    cmd.begin_render_pass(&RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[
+            Some(RenderPassColorAttachment {
+                ops: Operations {
+                    load,
+                    store: true,
+                },
+                view: target,
+                resolve_target: None,
+            })
+        ],
+        depth_stencil_attachment: None,
+    })
+}
+
+fn create_target(context: &RenderContext, label: &str, width: u32, height: u32) -> TextureView {
+    let texture = context.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: REFLECTION_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    texture.create_view(&TextureViewDescriptor {
+        label: Some(label),
+        ..TextureViewDescriptor::default()
+    })
+}
+
+fn create_pipeline(context: &RenderContext, layouts: &LayoutRegistry, shader: &str, layout: &str, format: TextureFormat, blend: Option<BlendState>) -> RenderPipeline {
+    let device = &context.device;
+    let shader_module = Preprocessor::new().create_module(context, shader);
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some(shader),
+        bind_group_layouts: &[layouts.get(layout)],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(shader),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            entry_point: "vertex",
+            module: &shader_module,
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            entry_point: "fragment",
+            module: &shader_module,
+            targets: &[
+                Some(ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: ColorWrites::ALL,
+                })
+            ],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
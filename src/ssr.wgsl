@@ -0,0 +1,132 @@
+struct Ssr {
+    projection: mat4x4<f32>,
+    inverse_projection: mat4x4<f32>,
+    fallback: vec4<f32>,
+    steps: u32,
+    thickness: f32,
+    max_distance: f32,
+    fade: f32,
+    roughness: f32,
+    strength: f32,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> ssr: Ssr;
+@group(0) @binding(1)
+var normal_depth: texture_2d<f32>;
+@group(0) @binding(2)
+var scene: texture_2d<f32>;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+}
+
+// a single triangle that covers the screen
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    var out: VertexOut;
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.pos = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fn view_position(pixel: vec2<f32>, size: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec2<f32>(pixel.x / size.x * 2.0 - 1.0, 1.0 - pixel.y / size.y * 2.0);
+    let far = ssr.inverse_projection * vec4<f32>(ndc, 1.0, 1.0);
+    let ray = far.xyz / far.w;
+    return ray * (depth / -ray.z);
+}
+
+fn screen_position(position: vec3<f32>, size: vec2<f32>) -> vec2<f32> {
+    let clip = ssr.projection * vec4<f32>(position, 1.0);
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * size;
+}
+
+// how far the point is behind what the depth buffer has there, negative in front of it. nothing
+// drawn counts as infinitely far away
+fn behind(position: vec3<f32>, size: vec2<f32>) -> f32 {
+    let depth = textureLoad(normal_depth, vec2<i32>(screen_position(position, size)), 0).w;
+    if (depth <= 0.0) {
+        return -1.0e9;
+    }
+    return -position.z - depth;
+}
+
+// interleaved gradient noise, staggers where each pixel's ray samples to turn banding into grain
+fn noise(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+// the reflected color, and in alpha how much of it to put over the scene
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let center = textureLoad(normal_depth, vec2<i32>(in.pos.xy), 0);
+    if (center.w <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+    let size = vec2<f32>(textureDimensions(normal_depth));
+    let normal = normalize(center.xyz);
+    let position = view_position(in.pos.xy, size, center.w);
+    let view_direction = normalize(position);
+    let direction = reflect(view_direction, normal);
+
+    // schlick's fresnel, strength being the reflectivity head on
+    let facing = clamp(dot(normal, -view_direction), 0.0, 1.0);
+    let reflectivity = ssr.strength + (1.0 - ssr.strength) * pow(1.0 - facing, 5.0);
+    if (reflectivity <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+
+    let stride = ssr.max_distance / f32(ssr.steps);
+    var travelled = stride * noise(in.pos.xy);
+    var hit = vec2<f32>(-1.0);
+    for (var i = 0u; i < ssr.steps; i = i + 1u) {
+        travelled = travelled + stride;
+        let probe = position + direction * travelled;
+        // gone behind the camera
+        if (probe.z >= 0.0) {
+            break;
+        }
+        let pixel = screen_position(probe, size);
+        if (any(pixel < vec2<f32>(0.0)) || any(pixel >= size)) {
+            break;
+        }
+        let depth = behind(probe, size);
+        if (depth > 0.0 && depth < ssr.thickness) {
+            // the last step went through the surface, narrow down where by halving it
+            var low = travelled - stride;
+            var high = travelled;
+            for (var j = 0; j < 5; j = j + 1) {
+                let middle = (low + high) * 0.5;
+                if (behind(position + direction * middle, size) > 0.0) {
+                    high = middle;
+                } else {
+                    low = middle;
+                }
+            }
+            travelled = high;
+            hit = screen_position(position + direction * high, size);
+            break;
+        }
+    }
+
+    var confidence = 0.0;
+    var color = ssr.fallback.rgb;
+    if (hit.x >= 0.0) {
+        let uv = hit / size;
+        let edge = min(min(uv.x, 1.0 - uv.x), min(uv.y, 1.0 - uv.y));
+        let edge_fade = clamp(edge / ssr.fade, 0.0, 1.0);
+        let distance_fade = clamp((1.0 - travelled / ssr.max_distance) / ssr.fade, 0.0, 1.0);
+        // rays coming back towards the camera mostly find back faces the depth buffer doesn't have
+        let camera_fade = 1.0 - smoothstep(0.0, 0.5, direction.z);
+        confidence = edge_fade * distance_fade * camera_fade;
+        let texel = clamp(vec2<i32>(hit), vec2<i32>(0), vec2<i32>(size) - 1);
+        color = textureLoad(scene, texel, 0).rgb;
+    }
+    // rough surfaces blur what they reflect into the surroundings' average, which the fallback
+    // stands in for
+    let reflected = mix(ssr.fallback.rgb, color, confidence * (1.0 - ssr.roughness));
+    return vec4<f32>(reflected, reflectivity);
+}
@@ -0,0 +1,21 @@
+@group(0) @binding(0)
+var reflection: texture_2d<f32>;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+}
+
+// a single triangle that covers the screen
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    var out: VertexOut;
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.pos = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// alpha blended over the scene, see ssr.wgsl for what's in it
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    return textureLoad(reflection, vec2<i32>(in.pos.xy), 0);
+}
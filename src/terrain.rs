@@ -0,0 +1,403 @@
+use std::path::Path;
+use glam::Vec3;
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::{Camera, Frustum, FRAME_LAYOUT};
+use crate::context::RenderContext;
+use crate::mesh::Vertex;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+use crate::shadows::SHADOW_FORMAT;
+use crate::ssao::NORMAL_DEPTH_FORMAT;
+use crate::texture::Texture;
+
+// quads along one side of a chunk at the finest level of detail
+const CHUNK_QUADS: u32 = 32;
+const CHUNK_VERTICES: u32 = CHUNK_QUADS + 1;
+// each level halves the resolution, so CHUNK_QUADS must be divisible by 2^(LOD_LEVELS - 1)
+const LOD_LEVELS: u32 = 5;
+
+pub const TERRAIN_LAYOUT: &str = "terrain";
+
+#[derive(Copy, Clone, Debug)]
+pub struct TerrainConfig {
+    // world space extent along x and z
+    pub size: f32,
+    pub height_scale: f32,
+    // a chunk drops one level of detail every time its distance doubles past this
+    pub lod_distance: f32,
+    pub layer_tiling: f32,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            size: 200.0,
+            height_scale: 30.0,
+            lod_distance: 40.0,
+            layer_tiling: 32.0,
+        }
+    }
+}
+
+pub struct Heightmap {
+    pub width: u32,
+    pub height: u32,
+    // normalized to 0..1
+    pub samples: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_luma16();
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            samples: image.pixels().map(|p| p.0[0] as f32 / u16::MAX as f32).collect(),
+        })
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as u32;
+        let y = y.clamp(0, self.height as i64 - 1) as u32;
+        self.samples[(y * self.width + x) as usize]
+    }
+}
+
+struct Chunk {
+    vertex_buffer: Buffer,
+    min: Vec3,
+    max: Vec3,
+}
+
+struct Lod {
+    index_buffer: Buffer,
+    index_count: u32,
+}
+
+pub struct Terrain {
+    pub config: TerrainConfig,
+    chunks: Vec<Chunk>,
+    lods: Vec<Lod>,
+    visible: Vec<(usize, usize)>,
+    bind_group: BindGroup,
+    render_pipeline: RenderPipeline,
+    // for the ssao prepass
+    normal_pipeline: RenderPipeline,
+    shadow_pipeline: RenderPipeline,
+    point_shadow_pipeline: RenderPipeline,
+}
+
+impl Terrain {
+    // without a blend map, layers are picked from height and slope: r = low, g = steep, b = mid, a = high
+    pub fn new(
+        context: &RenderContext,
+        layouts: &mut LayoutRegistry,
+        heightmap: &Heightmap,
+        blend_map: Option<&Texture>,
+        layers: Option<[&Texture; 4]>,
+        config: TerrainConfig,
+    ) -> Self {
+        let device = &context.device;
+        let chunks_x = (heightmap.width - 1).div_ceil(CHUNK_QUADS);
+        let chunks_z = (heightmap.height - 1).div_ceil(CHUNK_QUADS);
+        let grid_to_world = |gx: i64, gz: i64| {
+            let h = heightmap.get(gx, gz);
+            Vec3::new(
+                (gx as f32 / (heightmap.width - 1) as f32 - 0.5) * config.size,
+                h * config.height_scale,
+                (gz as f32 / (heightmap.height - 1) as f32 - 0.5) * config.size,
+            )
+        };
+        let normal_at = |gx: i64, gz: i64| {
+            let dx = grid_to_world(gx + 1, gz) - grid_to_world(gx - 1, gz);
+            let dz = grid_to_world(gx, gz + 1) - grid_to_world(gx, gz - 1);
+            dz.cross(dx).normalize()
+        };
+        let skirt_depth = config.height_scale * 0.05;
+
+        let mut chunks = Vec::new();
+        for cz in 0..chunks_z {
+            for cx in 0..chunks_x {
+                let mut vertices = Vec::with_capacity((CHUNK_VERTICES * CHUNK_VERTICES) as usize);
+                let mut min = Vec3::splat(f32::MAX);
+                let mut max = Vec3::splat(f32::MIN);
+                let mut vertex = |i: u32, j: u32, drop: f32| {
+                    // chunks hanging over the far edges collapse into degenerate triangles
+                    let gx = (cx * CHUNK_QUADS + i).min(heightmap.width - 1) as i64;
+                    let gz = (cz * CHUNK_QUADS + j).min(heightmap.height - 1) as i64;
+                    let position = grid_to_world(gx, gz) - Vec3::Y * drop;
+                    min = min.min(position);
+                    max = max.max(position);
+                    Vertex {
+                        position: position.to_array(),
+                        normal: normal_at(gx, gz).to_array(),
+                        uv: [
+                            gx as f32 / (heightmap.width - 1) as f32,
+                            gz as f32 / (heightmap.height - 1) as f32,
+                        ],
+                        ..Vertex::default()
+                    }
+                };
+                for j in 0..CHUNK_VERTICES {
+                    for i in 0..CHUNK_VERTICES {
+                        vertices.push(vertex(i, j, 0.0));
+                    }
+                }
+                // skirts hang below every edge to hide cracks between chunks of different detail
+                for (i, j) in edges() {
+                    vertices.push(vertex(i, j, skirt_depth));
+                }
+                chunks.push(Chunk {
+                    vertex_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                        label: Some("terrain chunk"),
+                        usage: BufferUsages::VERTEX,
+                        contents: bytemuck::cast_slice(&vertices),
+                    }),
+                    min,
+                    max,
+                });
+            }
+        }
+
+        let lods = (0..LOD_LEVELS).map(|lod| {
+            let indices = lod_indices(1 << lod);
+            Lod {
+                index_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("terrain lod"),
+                    usage: BufferUsages::INDEX,
+                    contents: bytemuck::cast_slice(&indices),
+                }),
+                index_count: indices.len() as u32,
+            }
+        }).collect();
+
+        let generated_blend_map;
+        let blend_map = match blend_map {
+            Some(blend_map) => blend_map,
+            None => {
+                let mut data = Vec::with_capacity((heightmap.width * heightmap.height * 4) as usize);
+                for gz in 0..heightmap.height as i64 {
+                    for gx in 0..heightmap.width as i64 {
+                        let height = heightmap.get(gx, gz);
+                        let steep = 1.0 - normal_at(gx, gz).y;
+                        let weights = [
+                            (1.0 - height * 3.0).max(0.0),
+                            (steep * 4.0).min(1.0),
+                            1.0 - (height * 2.0 - 1.0).abs(),
+                            (height * 3.0 - 2.0).max(0.0),
+                        ];
+                        data.extend(weights.map(|w| (w.clamp(0.0, 1.0) * 255.0) as u8));
+                    }
+                }
+                generated_blend_map = Texture::from_rgba8(context, "terrain blend map", heightmap.width, heightmap.height, &data, false);
+                &generated_blend_map
+            }
+        };
+        let default_layers;
+        let layers = match layers {
+            Some(layers) => layers,
+            None => {
+                default_layers = [
+                    [86, 125, 70, 255],
+                    [110, 104, 96, 255],
+                    [150, 120, 80, 255],
+                    [240, 240, 245, 255],
+                ].map(|color| Texture::solid(context, "terrain layer", color, true));
+                [&default_layers[0], &default_layers[1], &default_layers[2], &default_layers[3]]
+            }
+        };
+
+        let texture = (Binding::Texture, ShaderStages::FRAGMENT);
+        layouts.register(context, TERRAIN_LAYOUT, &[
+            (Binding::Uniform, ShaderStages::FRAGMENT),
+            texture,
+            texture,
+            texture,
+            texture,
+            texture,
+            (Binding::Sampler, ShaderStages::FRAGMENT),
+        ]);
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("terrain"),
+            usage: BufferUsages::UNIFORM,
+            contents: bytemuck::cast_slice(&[config.layer_tiling, 0.0, 0.0, 0.0]),
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("terrain"),
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        let bind_group = BindGroupBuilder::new()
+            .buffer(&params_buffer)
+            .texture(&blend_map.view)
+            .texture(&layers[0].view)
+            .texture(&layers[1].view)
+            .texture(&layers[2].view)
+            .texture(&layers[3].view)
+            .sampler(&sampler)
+            .build(context, layouts, TERRAIN_LAYOUT);
+
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "terrain.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("terrain"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT), layouts.get(TERRAIN_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label, fragment_entry, format: TextureFormat| device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[Vertex::LAYOUT],
+            },
+            fragment: Some(FragmentState {
+                entry_point: fragment_entry,
+                module: &shader_module,
+                targets: &[
+                    Some(format.into())
+                ],
+            }),
+            // skirts are seen from both sides
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        let render_pipeline = create_pipeline("terrain", "fragment", HDR_FORMAT);
+        let normal_pipeline = create_pipeline("terrain normals", "fragment_normal", NORMAL_DEPTH_FORMAT);
+        let create_shadow_pipeline = |label, fragment_entry: Option<&str>| device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[Vertex::LAYOUT],
+            },
+            fragment: fragment_entry.map(|entry_point| FragmentState {
+                entry_point,
+                module: &shader_module,
+                targets: &[],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        let shadow_pipeline = create_shadow_pipeline("terrain shadows", None);
+        let point_shadow_pipeline = create_shadow_pipeline("terrain point shadows", Some("fragment_point_shadow"));
+
+        Self {
+            config,
+            chunks,
+            lods,
+            visible: Vec::new(),
+            bind_group,
+            render_pipeline,
+            normal_pipeline,
+            shadow_pipeline,
+            point_shadow_pipeline,
+        }
+    }
+
+    // picks the visible chunks and their level of detail for this frame
+    pub fn update(&mut self, camera: &Camera, frustum: &Frustum) {
+        self.visible.clear();
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            if !frustum.intersects_aabb(chunk.min, chunk.max) {
+                continue;
+            }
+            let closest = camera.eye.clamp(chunk.min, chunk.max);
+            let distance = camera.eye.distance(closest) / self.config.lod_distance;
+            let lod = if distance <= 1.0 { 0 } else { distance.log2().ceil() as usize };
+            self.visible.push((index, lod.min(self.lods.len() - 1)));
+        }
+    }
+
+    pub fn visible_chunks(&self) -> usize {
+        self.visible.len()
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        self.draw_with(render_cmd, camera_bind_group, &self.render_pipeline);
+    }
+
+    // view space normals and depth for ssao, see Ssao::normal_depth_view
+    pub fn draw_normals<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        self.draw_with(render_cmd, camera_bind_group, &self.normal_pipeline);
+    }
+
+    // depth only, into a shadow cascade. only the chunks visible from the camera cast
+    pub fn draw_shadows<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        self.draw_with(render_cmd, camera_bind_group, &self.shadow_pipeline);
+    }
+
+    // distance to the light, into a point light's shadow face
+    pub fn draw_point_shadows<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        self.draw_with(render_cmd, camera_bind_group, &self.point_shadow_pipeline);
+    }
+
+    fn draw_with<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup, pipeline: &'a RenderPipeline) {
+        render_cmd.set_pipeline(pipeline);
+        render_cmd.set_bind_group(0, camera_bind_group, &[]);
+        render_cmd.set_bind_group(1, &self.bind_group, &[]);
+        for &(chunk, lod) in &self.visible {
+            let lod = &self.lods[lod];
+            render_cmd.set_vertex_buffer(0, self.chunks[chunk].vertex_buffer.slice(..));
+            render_cmd.set_index_buffer(lod.index_buffer.slice(..), IndexFormat::Uint32);
+            render_cmd.draw_indexed(0..lod.index_count, 0, 0..1);
+        }
+    }
+}
+
+// the four chunk borders as (i, j) grid coordinates, CHUNK_VERTICES each: bottom, top, left, right
+fn edges() -> impl Iterator<Item = (u32, u32)> {
+    let bottom = (0..CHUNK_VERTICES).map(|i| (i, 0));
+    let top = (0..CHUNK_VERTICES).map(|i| (i, CHUNK_QUADS));
+    let left = (0..CHUNK_VERTICES).map(|j| (0, j));
+    let right = (0..CHUNK_VERTICES).map(|j| (CHUNK_QUADS, j));
+    bottom.chain(top).chain(left).chain(right)
+}
+
+fn lod_indices(step: u32) -> Vec<u32> {
+    let grid = |i: u32, j: u32| j * CHUNK_VERTICES + i;
+    let mut indices = Vec::new();
+    for j in (0..CHUNK_QUADS).step_by(step as usize) {
+        for i in (0..CHUNK_QUADS).step_by(step as usize) {
+            let (a, b) = (grid(i, j), grid(i + step, j));
+            let (c, d) = (grid(i, j + step), grid(i + step, j + step));
+            indices.extend([a, c, b, b, c, d]);
+        }
+    }
+
+    let skirt_start = CHUNK_VERTICES * CHUNK_VERTICES;
+    let edges: Vec<(u32, u32)> = edges().collect();
+    for edge in 0..4 {
+        for k in (0..CHUNK_QUADS).step_by(step as usize) {
+            let base = edge * CHUNK_VERTICES;
+            let (i0, j0) = edges[(base + k) as usize];
+            let (i1, j1) = edges[(base + k + step) as usize];
+            let (top0, top1) = (grid(i0, j0), grid(i1, j1));
+            let (bottom0, bottom1) = (skirt_start + base + k, skirt_start + base + k + step);
+            indices.extend([top0, bottom0, top1, top1, bottom0, bottom1]);
+        }
+    }
+    indices
+}
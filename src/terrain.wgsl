@@ -0,0 +1,71 @@
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "color.wgsl"
+
+struct Terrain {
+    // how often the layer textures repeat across the whole terrain
+    layer_tiling: f32,
+}
+
+@group(1) @binding(0) var<uniform> terrain: Terrain;
+@group(1) @binding(1) var blend_map: texture_2d<f32>;
+@group(1) @binding(2) var layer0: texture_2d<f32>;
+@group(1) @binding(3) var layer1: texture_2d<f32>;
+@group(1) @binding(4) var layer2: texture_2d<f32>;
+@group(1) @binding(5) var layer3: texture_2d<f32>;
+@group(1) @binding(6) var terrain_sampler: sampler;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) world_position: vec3<f32>,
+}
+
+@vertex
+fn vertex(in: VertexIn) -> VertexOut {
+    var out: VertexOut;
+    out.pos = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.normal = in.normal;
+    out.uv = in.uv;
+    out.world_position = in.position;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    var weights = textureSample(blend_map, terrain_sampler, in.uv);
+    let total = weights.r + weights.g + weights.b + weights.a;
+    if (total <= 0.0) {
+        weights = vec4<f32>(1.0, 0.0, 0.0, 0.0);
+    } else {
+        weights = weights / total;
+    }
+
+    let tiled = in.uv * terrain.layer_tiling;
+    let albedo = textureSample(layer0, terrain_sampler, tiled).rgb * weights.r
+        + textureSample(layer1, terrain_sampler, tiled).rgb * weights.g
+        + textureSample(layer2, terrain_sampler, tiled).rgb * weights.b
+        + textureSample(layer3, terrain_sampler, tiled).rgb * weights.a;
+
+    let color = shade(albedo, normalize(in.normal), in.world_position, ambient_occlusion(in.pos.xy));
+    return output_color(vec4<f32>(color, 1.0));
+}
+
+@fragment
+fn fragment_normal(in: VertexOut) -> @location(0) vec4<f32> {
+    return view_normal_depth(in.normal, in.world_position);
+}
+
+// the distance to the light over its range, for a point light's shadow face. the face camera
+// holds the light's position and range, see ShadowMaps
+@fragment
+fn fragment_point_shadow(in: VertexOut) -> @builtin(frag_depth) f32 {
+    return distance(in.world_position, camera.position.xyz) / camera.position.w;
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use glam::Vec2;
+use image::{Rgba, RgbaImage};
+use wgpu::*;
+use crate::atlas::{Atlas, AtlasBuilder, AtlasRegion};
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::context::RenderContext;
+use crate::output::CAPTURE_FORMAT;
+use crate::preprocessor::Preprocessor;
+
+pub const TEXT_LAYOUT: &str = "text";
+// the built-in font: a `:` and the character, then its rows with X where the pixel is set.
+// printable ascii without lowercase letters, which are drawn as uppercase
+const FONT: &str = include_str!("font.txt");
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+// a pixel between glyphs and two between lines, before scaling
+const ADVANCE: f32 = 6.0;
+const LINE_HEIGHT: f32 = 9.0;
+// the atlas region rectangles are drawn with
+const SOLID: &str = "solid";
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TextQuad {
+    min: [f32; 2],
+    max: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    color: [f32; 4],
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct ScreenUniform {
+    size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+// immediate mode text and rectangles over the finished frame, in physical pixels from the top
+// left with linear colors. queued during the frame like DebugDraw and drawn after the output
+// pass, so they're neither tonemapped nor scaled along with the scene
+pub struct TextRenderer {
+    // keeps the texture the bind group samples
+    _atlas: Atlas,
+    glyphs: HashMap<char, AtlasRegion>,
+    solid: AtlasRegion,
+    quads: Vec<TextQuad>,
+    quad_buffer: Buffer,
+    quad_capacity: usize,
+    quad_count: u32,
+    screen_buffer: Buffer,
+    bind_group: BindGroup,
+    pipelines: Vec<(TextureFormat, RenderPipeline)>,
+}
+
+impl TextRenderer {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry) -> Self {
+        let atlas = font_atlas(context);
+        let glyphs = atlas.regions()
+            .filter_map(|(name, region)| {
+                let mut chars = name.chars();
+                let character = chars.next()?;
+                chars.next().is_none().then_some((character, region))
+            })
+            .collect();
+        let solid = atlas.region(SOLID).expect("the font atlas has a solid region");
+
+        let screen_buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("text screen"),
+            size: size_of::<ScreenUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, TEXT_LAYOUT, &[
+            (Binding::Texture, ShaderStages::FRAGMENT),
+            (Binding::Sampler, ShaderStages::FRAGMENT),
+            (Binding::Uniform, ShaderStages::VERTEX),
+        ]);
+        // glyphs are pixel art, scaled up they stay blocky rather than blurry
+        let sampler = context.device.create_sampler(&SamplerDescriptor {
+            label: Some("text"),
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..SamplerDescriptor::default()
+        });
+        let bind_group = BindGroupBuilder::new()
+            .texture(&atlas.texture.view)
+            .sampler(&sampler)
+            .buffer(&screen_buffer)
+            .build(context, layouts, TEXT_LAYOUT);
+
+        // the same targets the output pass draws to
+        let mut formats = vec![context.format];
+        if context.is_hdr() {
+            formats.push(CAPTURE_FORMAT);
+        }
+        let pipelines = formats.into_iter()
+            .map(|format| (format, create_pipeline(context, layouts, format)))
+            .collect();
+        Self {
+            _atlas: atlas,
+            glyphs,
+            solid,
+            quads: Vec::new(),
+            quad_buffer: create_quad_buffer(context, 1024),
+            quad_capacity: 1024,
+            quad_count: 0,
+            screen_buffer,
+            bind_group,
+            pipelines,
+        }
+    }
+
+    pub fn rect(&mut self, min: Vec2, max: Vec2, color: [f32; 4]) {
+        self.quads.push(TextQuad {
+            min: min.to_array(),
+            max: max.to_array(),
+            uv_min: self.solid.min.to_array(),
+            uv_max: self.solid.max.to_array(),
+            color,
+        });
+    }
+
+    // from the top left of the first line, each font pixel scale pixels big. newlines start
+    // over at position.x a line down. returns where the next character would go
+    pub fn text(&mut self, position: Vec2, scale: f32, color: [f32; 4], text: &str) -> Vec2 {
+        let mut cursor = position;
+        for character in text.chars() {
+            if character == '\n' {
+                cursor = Vec2::new(position.x, cursor.y + LINE_HEIGHT * scale);
+                continue;
+            }
+            if let Some(region) = self.glyph(character) {
+                let size = Vec2::new(GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32) * scale;
+                self.quads.push(TextQuad {
+                    min: cursor.to_array(),
+                    max: (cursor + size).to_array(),
+                    uv_min: region.min.to_array(),
+                    uv_max: region.max.to_array(),
+                    color,
+                });
+            }
+            cursor.x += ADVANCE * scale;
+        }
+        cursor
+    }
+
+    // the size text would take up, the longest line by the number of lines
+    pub fn measure(text: &str, scale: f32) -> Vec2 {
+        let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+        let lines = text.lines().count().max(1);
+        Vec2::new(columns as f32 * ADVANCE, lines as f32 * LINE_HEIGHT) * scale
+    }
+
+    pub fn line_height(scale: f32) -> f32 {
+        LINE_HEIGHT * scale
+    }
+
+    // from one character to the next, every glyph is as wide
+    pub fn advance(scale: f32) -> f32 {
+        ADVANCE * scale
+    }
+
+    // none for whitespace, characters the font doesn't have show as ?
+    fn glyph(&self, character: char) -> Option<AtlasRegion> {
+        if character.is_whitespace() {
+            return None;
+        }
+        let character = character.to_ascii_uppercase();
+        self.glyphs.get(&character).or_else(|| self.glyphs.get(&'?')).copied()
+    }
+
+    // uploads everything queued since the last update and starts a new batch
+    pub fn update(&mut self, context: &RenderContext) {
+        if self.quads.len() > self.quad_capacity {
+            self.quad_capacity = self.quads.len().next_power_of_two();
+            self.quad_buffer = create_quad_buffer(context, self.quad_capacity);
+        }
+        if !self.quads.is_empty() {
+            context.queue.write_buffer(&self.quad_buffer, 0, bytemuck::cast_slice(&self.quads));
+            let size = context.physical_size();
+            let screen = ScreenUniform {
+                size: [size.width.max(1) as f32, size.height.max(1) as f32],
+                _padding: [0.0; 2],
+            };
+            context.queue.write_buffer(&self.screen_buffer, 0, bytemuck::bytes_of(&screen));
+        }
+        self.quad_count = self.quads.len() as u32;
+        self.quads.clear();
+    }
+
+    // everything goes out in one draw, none when there's nothing queued
+    pub fn draw_count(&self) -> u32 {
+        (self.quad_count > 0) as u32
+    }
+
+    // over what's already in target, format is one the output pass draws to
+    pub fn draw(&self, cmd: &mut CommandEncoder, target: &TextureView, format: TextureFormat) {
+        if self.quad_count == 0 {
+            return;
+        }
+        let pipeline = self.pipelines.iter()
+            .find(|(pipeline_format, _)| *pipeline_format == format)
+            .map(|(_, pipeline)| pipeline)
+            .unwrap_or_else(|| panic!("no text pipeline for {format:?}"));
+        let mut render_cmd = cmd.begin_render_pass(&RenderPassDescriptor {
+            label: Some("text"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                    view: target,
+                    resolve_target: None,
+                })
+            ],
+            depth_stencil_attachment: None,
+        });
+        render_cmd.set_pipeline(pipeline);
+        render_cmd.set_bind_group(0, &self.bind_group, &[]);
+        render_cmd.set_vertex_buffer(0, self.quad_buffer.slice(..));
+        render_cmd.draw(0..4, 0..self.quad_count);
+    }
+}
+
+fn font_atlas(context: &RenderContext) -> Atlas {
+    let mut builder = AtlasBuilder::new();
+    let mut lines = FONT.lines();
+    while let Some(header) = lines.next() {
+        let Some(character) = header.strip_prefix(':').and_then(|rest| rest.chars().next()) else {
+            continue;
+        };
+        let mut image = RgbaImage::new(GLYPH_WIDTH, GLYPH_HEIGHT);
+        for (y, row) in lines.by_ref().take(GLYPH_HEIGHT as usize).enumerate() {
+            for (x, pixel) in row.chars().take(GLYPH_WIDTH as usize).enumerate() {
+                if pixel == 'X' {
+                    image.put_pixel(x as u32, y as u32, Rgba([255; 4]));
+                }
+            }
+        }
+        builder.add(character.to_string(), image);
+    }
+    builder.add(SOLID, RgbaImage::from_pixel(2, 2, Rgba([255; 4])));
+    builder.build(context, "font", false).expect("the built-in font fits into any atlas")
+}
+
+fn create_pipeline(context: &RenderContext, layouts: &LayoutRegistry, format: TextureFormat) -> RenderPipeline {
+    let device = &context.device;
+    let shader_module = Preprocessor::new().target(format).create_module(context, "text.wgsl");
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("text"),
+        bind_group_layouts: &[layouts.get(TEXT_LAYOUT)],
+        push_constant_ranges: &[],
+    });
+    let label = format!("text {format:?}");
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(&label),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            entry_point: "vertex",
+            module: &shader_module,
+            buffers: &[
+                VertexBufferLayout {
+                    array_stride: size_of::<TextQuad>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32x2,
+                        3 => Float32x2,
+                        4 => Float32x4,
+                    ],
+                },
+            ],
+        },
+        fragment: Some(FragmentState {
+            entry_point: "fragment",
+            module: &shader_module,
+            targets: &[
+                Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })
+            ],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleStrip,
+            ..PrimitiveState::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+fn create_quad_buffer(context: &RenderContext, capacity: usize) -> Buffer {
+    context.device.create_buffer(&BufferDescriptor {
+        label: Some("text quads"),
+        size: (capacity * size_of::<TextQuad>()) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,44 @@
+#include "color.wgsl"
+
+struct Screen {
+    // physical pixels
+    size: vec2<f32>,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0) var glyphs: texture_2d<f32>;
+@group(0) @binding(1) var glyph_sampler: sampler;
+@group(0) @binding(2) var<uniform> screen: Screen;
+
+// one glyph or rectangle, in pixels from the top left
+struct QuadIn {
+    @location(0) min: vec2<f32>,
+    @location(1) max: vec2<f32>,
+    @location(2) uv_min: vec2<f32>,
+    @location(3) uv_max: vec2<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+// a triangle strip of 4 vertices per quad
+@vertex
+fn vertex(@builtin(vertex_index) index: u32, quad: QuadIn) -> VertexOut {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    let pixel = mix(quad.min, quad.max, corner);
+    var out: VertexOut;
+    out.pos = vec4<f32>(pixel / screen.size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = mix(quad.uv_min, quad.uv_max, corner);
+    out.color = quad.color;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let coverage = textureSample(glyphs, glyph_sampler, in.uv).a;
+    return output_color(vec4<f32>(in.color.rgb, in.color.a * coverage));
+}
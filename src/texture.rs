@@ -0,0 +1,194 @@
+use std::fmt;
+use std::num::NonZeroU32;
+use std::path::Path;
+use wgpu::*;
+use crate::compressed::{self, CompressedError, CompressedImage};
+use crate::context::RenderContext;
+use crate::embedded;
+
+#[derive(Debug)]
+pub enum TextureError {
+    Image(image::ImageError),
+    Compressed(CompressedError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Image(error) => error.fmt(f),
+            TextureError::Compressed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<image::ImageError> for TextureError {
+    fn from(error: image::ImageError) -> Self {
+        TextureError::Image(error)
+    }
+}
+
+impl From<CompressedError> for TextureError {
+    fn from(error: CompressedError) -> Self {
+        TextureError::Compressed(error)
+    }
+}
+
+// a texture file read and decoded as far as possible without a device, so it can happen on any thread
+pub enum TextureData {
+    Image(image::DynamicImage),
+    Compressed(CompressedImage),
+}
+
+impl TextureData {
+    // .ktx2 and .dds stay compressed, anything else goes through the image crate
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+        Ok(match extension.as_deref() {
+            Some("ktx2" | "dds") => TextureData::Compressed(CompressedImage::load(path)?),
+            _ => match embedded::fallback(path) {
+                Some(bytes) => TextureData::Image(image::load_from_memory(bytes)?),
+                None => TextureData::Image(image::open(path)?),
+            },
+        })
+    }
+}
+
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: TextureView,
+    pub size: Extent3d,
+}
+
+impl Texture {
+    pub fn from_rgba8(context: &RenderContext, label: &str, width: u32, height: u32, data: &[u8], srgb: bool) -> Self {
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = context.device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: if srgb { TextureFormat::Rgba8UnormSrgb } else { TextureFormat::Rgba8Unorm },
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        context.queue.write_texture(
+            texture.as_image_copy(),
+            data,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(width * 4),
+                rows_per_image: None,
+            },
+            size,
+        );
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some(label),
+            ..TextureViewDescriptor::default()
+        });
+        Self {
+            texture,
+            view,
+            size,
+        }
+    }
+
+    pub fn from_image(context: &RenderContext, label: &str, image: &image::DynamicImage, srgb: bool) -> Self {
+        let rgba = image.to_rgba8();
+        Self::from_rgba8(context, label, rgba.width(), rgba.height(), &rgba, srgb)
+    }
+
+    // srgb only applies to plain images, compressed files say for themselves
+    pub fn load(context: &RenderContext, path: impl AsRef<Path>, srgb: bool) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        Self::from_data(context, &path.display().to_string(), &TextureData::read(path)?, srgb)
+    }
+
+    pub fn from_data(context: &RenderContext, label: &str, data: &TextureData, srgb: bool) -> Result<Self, TextureError> {
+        Ok(match data {
+            TextureData::Image(image) => Self::from_image(context, label, image, srgb),
+            TextureData::Compressed(image) => Self::from_compressed(context, label, image)?,
+        })
+    }
+
+    // uploads as is when the device supports the format, otherwise every level is decompressed
+    // to rgba8 first. bc is usually there on desktop, etc2 and astc on mobile
+    pub fn from_compressed(context: &RenderContext, label: &str, image: &CompressedImage) -> Result<Self, CompressedError> {
+        let info = image.format.describe();
+        let (block_width, block_height) = (info.block_dimensions.0 as u32, info.block_dimensions.1 as u32);
+        // wgpu wants the top level to be whole blocks
+        let supported = context.features().contains(info.required_features)
+            && image.width.is_multiple_of(block_width)
+            && image.height.is_multiple_of(block_height);
+        if supported {
+            return Ok(Self::from_levels(context, label, image.format, image.width, image.height, &image.levels));
+        }
+        let levels = image.decompress()?;
+        Ok(Self::from_levels(context, label, image.decompressed_format(), image.width, image.height, &levels))
+    }
+
+    // one mip level per entry, largest first, each tightly packed in whole blocks
+    fn from_levels(context: &RenderContext, label: &str, format: TextureFormat, width: u32, height: u32, levels: &[Vec<u8>]) -> Self {
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = context.device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        let info = format.describe();
+        let (block_width, block_height) = (info.block_dimensions.0 as u32, info.block_dimensions.1 as u32);
+        for (level, data) in levels.iter().enumerate() {
+            let level = level as u32;
+            debug_assert_eq!(data.len(), compressed::level_byte_size(format, width, height, level));
+            // copies of block compressed levels cover whole blocks, even past the edge of small levels
+            let blocks_wide = (width >> level).max(1).div_ceil(block_width);
+            let blocks_high = (height >> level).max(1).div_ceil(block_height);
+            context.queue.write_texture(
+                ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                data,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(blocks_wide * info.block_size as u32),
+                    rows_per_image: None,
+                },
+                Extent3d {
+                    width: blocks_wide * block_width,
+                    height: blocks_high * block_height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some(label),
+            ..TextureViewDescriptor::default()
+        });
+        Self {
+            texture,
+            view,
+            size,
+        }
+    }
+
+    pub fn solid(context: &RenderContext, label: &str, color: [u8; 4], srgb: bool) -> Self {
+        Self::from_rgba8(context, label, 1, 1, &color, srgb)
+    }
+}
@@ -0,0 +1,830 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem::size_of;
+use std::path::Path;
+use std::str::FromStr;
+use glam::Vec2;
+use image::RgbaImage;
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::atlas::{AtlasBuilder, AtlasError, AtlasRegion};
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+use crate::texture::Texture;
+
+pub const TILEMAP_LAYOUT: &str = "tilemap";
+pub const TILE_LAYER_LAYOUT: &str = "tile layer";
+// tiles along each side of the chunks a layer is split into, which are culled and drawn whole
+const CHUNK_TILES: i32 = 16;
+// the top bits of a gid flip the tile, the rest is the tile. 0x1000_0000 rotates hexagonal
+// tiles and is ignored
+const FLIP_HORIZONTAL: u32 = 0x8000_0000;
+const FLIP_VERTICAL: u32 = 0x4000_0000;
+const FLIP_DIAGONAL: u32 = 0x2000_0000;
+const GID_MASK: u32 = 0x0fff_ffff;
+
+#[derive(Debug)]
+pub enum TilemapError {
+    Io(io::Error),
+    Image(image::ImageError),
+    Atlas(AtlasError),
+    // xml that doesn't parse, or a map using something that isn't supported
+    Invalid(String),
+}
+
+impl fmt::Display for TilemapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TilemapError::Io(error) => error.fmt(f),
+            TilemapError::Image(error) => error.fmt(f),
+            TilemapError::Atlas(error) => error.fmt(f),
+            TilemapError::Invalid(message) => write!(f, "invalid map: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for TilemapError {}
+
+impl From<io::Error> for TilemapError {
+    fn from(error: io::Error) -> Self {
+        TilemapError::Io(error)
+    }
+}
+
+impl From<image::ImageError> for TilemapError {
+    fn from(error: image::ImageError) -> Self {
+        TilemapError::Image(error)
+    }
+}
+
+impl From<AtlasError> for TilemapError {
+    fn from(error: AtlasError) -> Self {
+        TilemapError::Atlas(error)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> TilemapError {
+    TilemapError::Invalid(message.into())
+}
+
+// tiles cut from one image, left to right and top to bottom
+pub struct Tileset {
+    pub name: String,
+    // the gid of the first tile, the map's tiles refer to this set from here up to the next set's
+    pub first_gid: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    pub tile_count: u32,
+    // pixels between tiles, and around them at the image's border
+    pub spacing: u32,
+    pub margin: u32,
+    pub image: RgbaImage,
+    // by tile id within the set, frames of tile ids and how long they show in milliseconds
+    pub animations: HashMap<u32, Vec<(u32, u32)>>,
+}
+
+impl Tileset {
+    // where the tile is in image, in pixels
+    fn tile_rect(&self, id: u32) -> (u32, u32, u32, u32) {
+        let columns = self.columns.max(1);
+        let x = self.margin + (id % columns) * (self.tile_width + self.spacing);
+        let y = self.margin + (id / columns) * (self.tile_height + self.spacing);
+        (x, y, self.tile_width, self.tile_height)
+    }
+}
+
+pub struct TileLayer {
+    pub name: String,
+    // the layer's tiles that aren't empty, in tiles from the map's top left and with their gid.
+    // infinite maps can go negative
+    pub tiles: Vec<(i32, i32, u32)>,
+    // in pixels, including the groups the layer is in
+    pub offset: Vec2,
+    // how fast the layer scrolls along with the view, 1 moves with the map and 0 stays put
+    pub parallax: Vec2,
+    // srgb color multiplied with the tiles, alpha includes the layer's opacity
+    pub tint: [f32; 4],
+}
+
+// an orthogonal map from Tiled, with its tilesets' images. object and image layers are left out,
+// as are hidden layers
+pub struct TiledMap {
+    pub tile_width: u32,
+    pub tile_height: u32,
+    // in tiles, of the map's fixed area. infinite maps can have tiles past it
+    pub width: u32,
+    pub height: u32,
+    // by first_gid
+    pub tilesets: Vec<Tileset>,
+    // back to front
+    pub layers: Vec<TileLayer>,
+}
+
+impl TiledMap {
+    // a .tmx file, tilesets and images are looked up next to it
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TilemapError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        Self::parse(&source, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+
+    pub fn parse(source: &str, directory: &Path) -> Result<Self, TilemapError> {
+        let map = parse_xml(source)?;
+        if map.name != "map" {
+            return Err(invalid(format!("expected a map, found {}", map.name)));
+        }
+        let orientation = map.attribute("orientation").unwrap_or("orthogonal");
+        if orientation != "orthogonal" {
+            return Err(invalid(format!("{orientation} maps aren't supported, only orthogonal ones")));
+        }
+
+        let mut tilesets = Vec::new();
+        for element in map.children("tileset") {
+            let first_gid = element.required("firstgid")?;
+            let tileset = match element.attribute("source") {
+                // an external .tsx, its image is relative to it
+                Some(source) => {
+                    let path = directory.join(source);
+                    let external = parse_xml(&fs::read_to_string(&path)?)?;
+                    parse_tileset(&external, first_gid, path.parent().unwrap_or(directory))?
+                }
+                None => parse_tileset(element, first_gid, directory)?,
+            };
+            tilesets.push(tileset);
+        }
+        tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+        let mut layers = Vec::new();
+        let root = Inherited { offset: Vec2::ZERO, parallax: Vec2::ONE, opacity: 1.0, visible: true };
+        parse_layers(&map, root, &mut layers)?;
+        Ok(Self {
+            tile_width: map.required("tilewidth")?,
+            tile_height: map.required("tileheight")?,
+            width: map.parse_or("width", 0)?,
+            height: map.parse_or("height", 0)?,
+            tilesets,
+            layers,
+        })
+    }
+
+    // the tileset a gid is from and the tile's id in it, None for empty tiles and gids past the
+    // last set
+    fn tile(&self, gid: u32) -> Option<(usize, u32)> {
+        let gid = gid & GID_MASK;
+        let index = self.tilesets.iter().rposition(|tileset| tileset.first_gid <= gid)?;
+        let id = gid - self.tilesets[index].first_gid;
+        (gid != 0 && id < self.tilesets[index].tile_count).then_some((index, id))
+    }
+}
+
+// what a layer takes from the groups it's in
+#[derive(Copy, Clone)]
+struct Inherited {
+    offset: Vec2,
+    parallax: Vec2,
+    opacity: f32,
+    visible: bool,
+}
+
+impl Inherited {
+    fn child(self, element: &Element) -> Result<Self, TilemapError> {
+        Ok(Self {
+            offset: self.offset + Vec2::new(element.parse_or("offsetx", 0.0)?, element.parse_or("offsety", 0.0)?),
+            parallax: self.parallax * Vec2::new(element.parse_or("parallaxx", 1.0)?, element.parse_or("parallaxy", 1.0)?),
+            opacity: self.opacity * element.parse_or("opacity", 1.0)?,
+            visible: self.visible && element.parse_or("visible", 1)? != 0,
+        })
+    }
+}
+
+fn parse_layers(parent: &Element, inherited: Inherited, layers: &mut Vec<TileLayer>) -> Result<(), TilemapError> {
+    for element in &parent.children {
+        match element.name.as_str() {
+            "group" => parse_layers(element, inherited.child(element)?, layers)?,
+            "layer" => {
+                let inherited = inherited.child(element)?;
+                if !inherited.visible {
+                    continue;
+                }
+                let data = element.child("data").ok_or_else(|| invalid("a layer has no data"))?;
+                let mut tiles = Vec::new();
+                if data.child("chunk").is_some() {
+                    for chunk in data.children("chunk") {
+                        let origin = (chunk.required("x")?, chunk.required("y")?);
+                        push_tiles(&mut tiles, parse_gids(data, chunk)?, origin, chunk.required("width")?);
+                    }
+                } else {
+                    push_tiles(&mut tiles, parse_gids(data, data)?, (0, 0), element.required("width")?);
+                }
+                let tint = match element.attribute("tintcolor") {
+                    Some(color) => parse_color(color)?,
+                    None => [1.0; 4],
+                };
+                layers.push(TileLayer {
+                    name: element.attribute("name").unwrap_or_default().to_string(),
+                    tiles,
+                    offset: inherited.offset,
+                    parallax: inherited.parallax,
+                    tint: [tint[0], tint[1], tint[2], tint[3] * inherited.opacity],
+                });
+            }
+            // object and image layers aren't drawn
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// gids row by row from content, in the encoding set on data. chunks of infinite maps are their
+// own content but share their layer's data element
+fn parse_gids(data: &Element, content: &Element) -> Result<Vec<u32>, TilemapError> {
+    match data.attribute("encoding") {
+        Some("csv") => content.text.split(',')
+            .map(str::trim)
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| gid.parse().map_err(|_| invalid(format!("{gid} isn't a tile"))))
+            .collect(),
+        Some("base64") => {
+            if let Some(compression) = data.attribute("compression") {
+                return Err(invalid(format!("{compression} compressed layers aren't supported, save the map as csv or uncompressed base64")));
+            }
+            Ok(decode_base64(&content.text)?
+                .chunks_exact(4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect())
+        }
+        None => content.children("tile").map(|tile| tile.parse_or("gid", 0)).collect(),
+        Some(encoding) => Err(invalid(format!("unknown layer encoding {encoding}"))),
+    }
+}
+
+fn push_tiles(tiles: &mut Vec<(i32, i32, u32)>, gids: Vec<u32>, origin: (i32, i32), width: u32) {
+    let width = width.max(1);
+    for (index, gid) in gids.into_iter().enumerate().filter(|(_, gid)| *gid != 0) {
+        let index = index as u32;
+        tiles.push((origin.0 + (index % width) as i32, origin.1 + (index / width) as i32, gid));
+    }
+}
+
+fn parse_tileset(element: &Element, first_gid: u32, directory: &Path) -> Result<Tileset, TilemapError> {
+    let name = element.attribute("name").unwrap_or_default().to_string();
+    let image_element = element.child("image")
+        .ok_or_else(|| invalid(format!("tileset {name} has no image, image collections aren't supported")))?;
+    let source: String = image_element.required("source")?;
+    let mut image = image::open(directory.join(source))?.to_rgba8();
+    // a color that's transparent, for images without alpha
+    if let Some(transparent) = image_element.attribute("trans") {
+        let [r, g, b, _] = parse_color(transparent)?.map(|channel| (channel * 255.0).round() as u8);
+        for pixel in image.pixels_mut().filter(|pixel| pixel.0[..3] == [r, g, b]) {
+            pixel.0[3] = 0;
+        }
+    }
+
+    let tile_width = element.required("tilewidth")?;
+    let tile_height: u32 = element.required("tileheight")?;
+    let spacing = element.parse_or("spacing", 0)?;
+    let margin = element.parse_or("margin", 0)?;
+    let fits = |size: u32, tile: u32| (size.saturating_sub(margin * 2) + spacing) / (tile + spacing).max(1);
+    let columns = element.parse_or("columns", fits(image.width(), tile_width))?;
+    let tile_count = element.parse_or("tilecount", columns * fits(image.height(), tile_height))?;
+    let mut animations = HashMap::new();
+    for tile in element.children("tile") {
+        let Some(animation) = tile.child("animation") else {
+            continue;
+        };
+        let frames = animation.children("frame")
+            .map(|frame| -> Result<(u32, u32), TilemapError> { Ok((frame.required("tileid")?, frame.required("duration")?)) })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !frames.is_empty() {
+            animations.insert(tile.required("id")?, frames);
+        }
+    }
+    Ok(Tileset { name, first_gid, tile_width, tile_height, columns, tile_count, spacing, margin, image, animations })
+}
+
+// #rrggbb or #aarrggbb as 0..1, the # is optional
+fn parse_color(text: &str) -> Result<[f32; 4], TilemapError> {
+    let hex = text.trim().trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16).map_err(|_| invalid(format!("{text} isn't a color")))?;
+    let channel = |shift: u32| ((value >> shift) & 0xff) as f32 / 255.0;
+    match hex.len() {
+        6 => Ok([channel(16), channel(8), channel(0), 1.0]),
+        8 => Ok([channel(16), channel(8), channel(0), channel(24)]),
+        _ => Err(invalid(format!("{text} isn't a color"))),
+    }
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, TilemapError> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in text.bytes().filter(|byte| !byte.is_ascii_whitespace()) {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => return Err(invalid("layer data isn't valid base64")),
+        };
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+// the part of xml Tiled writes: elements, attributes, text and the named entities. comments,
+// the declaration and doctypes are skipped
+#[derive(Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn parse_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, TilemapError> {
+        match self.attribute(name) {
+            Some(value) => value.trim().parse().map_err(|_| invalid(format!("{} has {name}=\"{value}\"", self.name))),
+            None => Ok(default),
+        }
+    }
+
+    fn required<T: FromStr>(&self, name: &str) -> Result<T, TilemapError> {
+        let value = self.attribute(name).ok_or_else(|| invalid(format!("{} has no {name}", self.name)))?;
+        value.trim().parse().map_err(|_| invalid(format!("{} has {name}=\"{value}\"", self.name)))
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children(name).next()
+    }
+}
+
+// the document's root element
+fn parse_xml(source: &str) -> Result<Element, TilemapError> {
+    // the bottom one collects the root
+    let mut stack = vec![Element::default()];
+    let mut rest = source;
+    loop {
+        let start = rest.find('<').unwrap_or(rest.len());
+        if let Some(open) = stack.last_mut() {
+            open.text.push_str(&unescape(&rest[..start]));
+        }
+        rest = &rest[start..];
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").ok_or_else(|| invalid("unterminated comment"))?;
+            rest = &comment[end + 3..];
+            continue;
+        }
+        let end = rest.find('>').ok_or_else(|| invalid("unterminated tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().filter(|_| !stack.is_empty()).ok_or_else(|| invalid(format!("unexpected </{name}>")))?;
+            if element.name != name.trim() {
+                return Err(invalid(format!("<{}> closed by </{}>", element.name, name.trim())));
+            }
+            stack.last_mut().unwrap().children.push(element);
+        } else if let Some(tag) = tag.strip_suffix('/') {
+            let element = parse_tag(tag)?;
+            stack.last_mut().unwrap().children.push(element);
+        } else {
+            stack.push(parse_tag(tag)?);
+        }
+    }
+    if stack.len() > 1 {
+        return Err(invalid(format!("<{}> isn't closed", stack.last().unwrap().name)));
+    }
+    stack.pop().and_then(|document| document.children.into_iter().next()).ok_or_else(|| invalid("empty document"))
+}
+
+// the inside of an opening tag, its name and attributes
+fn parse_tag(tag: &str) -> Result<Element, TilemapError> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut element = Element { name: tag[..name_end].to_string(), ..Element::default() };
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let malformed = || invalid(format!("malformed attributes in <{}>", element.name));
+        let equals = rest.find('=').ok_or_else(malformed)?;
+        let key = rest[..equals].trim();
+        let quoted = rest[equals + 1..].trim_start();
+        let quote = quoted.chars().next().filter(|quote| *quote == '"' || *quote == '\'').ok_or_else(malformed)?;
+        let length = quoted[1..].find(quote).ok_or_else(malformed)?;
+        let value = unescape(&quoted[1..1 + length]);
+        element.attributes.push((key.to_string(), value));
+        rest = quoted[length + 2..].trim_start();
+    }
+    Ok(element)
+}
+
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// which part of the map is on screen
+#[derive(Copy, Clone, Debug)]
+pub struct TilemapView {
+    // the map pixel in the middle of the main view
+    pub center: Vec2,
+    // screen pixels per map pixel
+    pub zoom: f32,
+}
+
+impl Default for TilemapView {
+    fn default() -> Self {
+        Self {
+            center: Vec2::ZERO,
+            // tiles are mostly pixel art, which reads better bigger
+            zoom: 2.0,
+        }
+    }
+}
+
+// per instance, see TileIn in tilemap.wgsl
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TileQuad {
+    // in map pixels, y down
+    min: [f32; 2],
+    max: [f32; 2],
+    // the unflipped tile in the atlas
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    // the gid's flip bits moved down to the bottom, horizontal 4, vertical 2 and diagonal 1
+    flips: u32,
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TilemapUniform {
+    center: [f32; 2],
+    // the main view's size in window pixels
+    viewport: [f32; 2],
+    zoom: f32,
+    _padding: [f32; 3],
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct LayerUniform {
+    offset: [f32; 2],
+    parallax: [f32; 2],
+    tint: [f32; 4],
+}
+
+// an animated tile's frames, as uvs in the atlas and the time each ends at in seconds
+struct TileAnimation {
+    frames: Vec<((Vec2, Vec2), f32)>,
+    // the frame showing
+    current: usize,
+}
+
+impl TileAnimation {
+    fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |(_, end)| *end)
+    }
+}
+
+struct TileChunk {
+    buffer: Buffer,
+    // kept to rewrite the buffer when animated tiles change frames
+    quads: Vec<TileQuad>,
+    // indices into quads, with the animation they show
+    animated: Vec<(usize, usize)>,
+    // in map pixels, before the layer's offset
+    min: Vec2,
+    max: Vec2,
+}
+
+struct GpuLayer {
+    bind_group: BindGroup,
+    offset: Vec2,
+    parallax: Vec2,
+    // by row, then column, so tiles taller than the map's overlap in the order Tiled draws them
+    chunks: Vec<TileChunk>,
+}
+
+// a TiledMap drawn in 2d behind everything in the main view, its tilesets packed into one atlas
+// and its layers split into chunks of instanced quads. only chunks on screen are drawn
+pub struct Tilemap {
+    pub view: TilemapView,
+    // keeps the texture the bind group samples
+    _atlas: Texture,
+    uniform_buffer: Buffer,
+    bind_group: BindGroup,
+    layers: Vec<GpuLayer>,
+    animations: Vec<TileAnimation>,
+    // seconds animations have run for
+    time: f32,
+    // layer and chunk indices, back to front
+    visible: Vec<(usize, usize)>,
+    pipeline: RenderPipeline,
+}
+
+impl Tilemap {
+    // the view starts out centered on the map
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, map: &TiledMap) -> Result<Self, TilemapError> {
+        let device = &context.device;
+        let mut builder = AtlasBuilder::new();
+        for (index, tileset) in map.tilesets.iter().enumerate() {
+            builder.add(index.to_string(), tileset.image.clone());
+        }
+        let (image, regions) = builder.pack(context.limits().max_texture_dimension_2d)?;
+        let atlas = Texture::from_rgba8(context, "tilemap", image.width(), image.height(), &image, true);
+        let regions: Vec<AtlasRegion> = (0..map.tilesets.len()).map(|index| regions[&index.to_string()]).collect();
+        let tile_uv = |tileset: usize, id: u32| {
+            let (x, y, width, height) = map.tilesets[tileset].tile_rect(id);
+            let region = regions[tileset];
+            let size = Vec2::new(region.width as f32, region.height as f32);
+            (region.remap(Vec2::new(x as f32, y as f32) / size), region.remap(Vec2::new((x + width) as f32, (y + height) as f32) / size))
+        };
+
+        let mut animations = Vec::new();
+        let mut animation_indices = HashMap::new();
+        for (index, tileset) in map.tilesets.iter().enumerate() {
+            for (&id, frames) in &tileset.animations {
+                let mut end = 0.0;
+                let frames = frames.iter()
+                    .map(|&(frame, milliseconds)| {
+                        end += milliseconds.max(1) as f32 / 1000.0;
+                        (tile_uv(index, frame.min(tileset.tile_count.saturating_sub(1))), end)
+                    })
+                    .collect();
+                animation_indices.insert((index, id), animations.len());
+                animations.push(TileAnimation { frames, current: 0 });
+            }
+        }
+
+        layouts.register(context, TILEMAP_LAYOUT, &[
+            (Binding::Texture, ShaderStages::FRAGMENT),
+            (Binding::Sampler, ShaderStages::FRAGMENT),
+            (Binding::Uniform, ShaderStages::VERTEX),
+        ]);
+        layouts.register(context, TILE_LAYER_LAYOUT, &[
+            (Binding::Uniform, ShaderStages::VERTEX_FRAGMENT),
+        ]);
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("tilemap"),
+            size: size_of::<TilemapUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // pixel art stays sharp scaled up
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("tilemap"),
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..SamplerDescriptor::default()
+        });
+        let bind_group = BindGroupBuilder::new()
+            .texture(&atlas.view)
+            .sampler(&sampler)
+            .buffer(&uniform_buffer)
+            .build(context, layouts, TILEMAP_LAYOUT);
+
+        let tile_size = Vec2::new(map.tile_width as f32, map.tile_height as f32);
+        let mut layers = Vec::new();
+        for layer in &map.layers {
+            let mut chunks: HashMap<(i32, i32), Vec<(i32, i32, u32)>> = HashMap::new();
+            for &tile in &layer.tiles {
+                chunks.entry((tile.1.div_euclid(CHUNK_TILES), tile.0.div_euclid(CHUNK_TILES))).or_default().push(tile);
+            }
+            let mut chunks: Vec<((i32, i32), Vec<(i32, i32, u32)>)> = chunks.into_iter().collect();
+            chunks.sort_by_key(|(key, _)| *key);
+            let chunks: Vec<TileChunk> = chunks.into_iter()
+                .filter_map(|(_, mut tiles)| {
+                    tiles.sort_by_key(|&(x, y, _)| (y, x));
+                    let mut quads = Vec::new();
+                    let mut animated = Vec::new();
+                    let (mut min, mut max) = (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN));
+                    for (x, y, gid) in tiles {
+                        let Some((tileset, id)) = map.tile(gid) else {
+                            continue;
+                        };
+                        let set = &map.tilesets[tileset];
+                        // tiles bigger than the map's grid stick out up and to the right
+                        let bottom_left = Vec2::new(x as f32, (y + 1) as f32) * tile_size;
+                        let quad_min = bottom_left - Vec2::new(0.0, set.tile_height as f32);
+                        let quad_max = quad_min + Vec2::new(set.tile_width as f32, set.tile_height as f32);
+                        let (uv_min, uv_max) = tile_uv(tileset, id);
+                        if let Some(&animation) = animation_indices.get(&(tileset, id)) {
+                            animated.push((quads.len(), animation));
+                        }
+                        quads.push(TileQuad {
+                            min: quad_min.to_array(),
+                            max: quad_max.to_array(),
+                            uv_min: uv_min.to_array(),
+                            uv_max: uv_max.to_array(),
+                            flips: [FLIP_HORIZONTAL, FLIP_VERTICAL, FLIP_DIAGONAL].iter().fold(0, |flips, &bit| flips << 1 | ((gid & bit) != 0) as u32),
+                        });
+                        min = min.min(quad_min);
+                        max = max.max(quad_max);
+                    }
+                    (!quads.is_empty()).then(|| TileChunk {
+                        buffer: device.create_buffer_init(&BufferInitDescriptor {
+                            label: Some("tile chunk"),
+                            contents: bytemuck::cast_slice(&quads),
+                            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                        }),
+                        quads,
+                        animated,
+                        min,
+                        max,
+                    })
+                })
+                .collect();
+            if chunks.is_empty() {
+                continue;
+            }
+            let uniform = LayerUniform {
+                offset: layer.offset.to_array(),
+                parallax: layer.parallax.to_array(),
+                tint: layer.tint,
+            };
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("tile layer"),
+                contents: bytemuck::bytes_of(&uniform),
+                usage: BufferUsages::UNIFORM,
+            });
+            layers.push(GpuLayer {
+                bind_group: BindGroupBuilder::new().buffer(&buffer).build(context, layouts, TILE_LAYER_LAYOUT),
+                offset: layer.offset,
+                parallax: layer.parallax,
+                chunks,
+            });
+        }
+
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "tilemap.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("tilemap"),
+            bind_group_layouts: &[layouts.get(TILEMAP_LAYOUT), layouts.get(TILE_LAYER_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("tilemap"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[
+                    VertexBufferLayout {
+                        array_stride: size_of::<TileQuad>() as BufferAddress,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &vertex_attr_array![
+                            0 => Float32x2,
+                            1 => Float32x2,
+                            2 => Float32x2,
+                            3 => Float32x2,
+                            4 => Uint32,
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &shader_module,
+                targets: &[
+                    Some(ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                ..PrimitiveState::default()
+            },
+            // a backdrop, the scene draws over it
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        let map_size = Vec2::new(map.width as f32, map.height as f32) * tile_size;
+        Ok(Self {
+            view: TilemapView { center: map_size * 0.5, ..TilemapView::default() },
+            _atlas: atlas,
+            uniform_buffer,
+            bind_group,
+            layers,
+            animations,
+            time: 0.0,
+            visible: Vec::new(),
+            pipeline,
+        })
+    }
+
+    // moves animated tiles on by delta seconds and picks the chunks on screen. viewport is the
+    // main view's size in window pixels
+    pub fn update(&mut self, context: &RenderContext, delta: f32, viewport: (u32, u32)) {
+        self.time += delta;
+        let mut changed = vec![false; self.animations.len()];
+        for (animation, changed) in self.animations.iter_mut().zip(&mut changed) {
+            let time = self.time % animation.duration();
+            let frame = animation.frames.iter().position(|(_, end)| time < *end).unwrap_or(animation.frames.len() - 1);
+            *changed = frame != animation.current;
+            animation.current = frame;
+        }
+        if changed.contains(&true) {
+            for chunk in self.layers.iter_mut().flat_map(|layer| &mut layer.chunks) {
+                if !chunk.animated.iter().any(|&(_, animation)| changed[animation]) {
+                    continue;
+                }
+                for &(quad, animation) in &chunk.animated {
+                    let animation = &self.animations[animation];
+                    let (uv_min, uv_max) = animation.frames[animation.current].0;
+                    chunk.quads[quad].uv_min = uv_min.to_array();
+                    chunk.quads[quad].uv_max = uv_max.to_array();
+                }
+                context.queue.write_buffer(&chunk.buffer, 0, bytemuck::cast_slice(&chunk.quads));
+            }
+        }
+
+        let viewport = Vec2::new(viewport.0.max(1) as f32, viewport.1.max(1) as f32);
+        let zoom = self.view.zoom.max(0.01);
+        let uniform = TilemapUniform {
+            center: self.view.center.to_array(),
+            viewport: viewport.to_array(),
+            zoom,
+            _padding: [0.0; 3],
+        };
+        context.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        // half the screen, in map pixels
+        let half = viewport * 0.5 / zoom;
+        self.visible.clear();
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            let shift = layer.offset - self.view.center * layer.parallax;
+            for (chunk_index, chunk) in layer.chunks.iter().enumerate() {
+                let (min, max) = (chunk.min + shift, chunk.max + shift);
+                if max.x > -half.x && max.y > -half.y && min.x < half.x && min.y < half.y {
+                    self.visible.push((layer_index, chunk_index));
+                }
+            }
+        }
+    }
+
+    // visible chunks, one draw each
+    pub fn draw_count(&self) -> u32 {
+        self.visible.len() as u32
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>) {
+        if self.visible.is_empty() {
+            return;
+        }
+        render_cmd.set_pipeline(&self.pipeline);
+        render_cmd.set_bind_group(0, &self.bind_group, &[]);
+        let mut bound_layer = None;
+        for &(layer_index, chunk_index) in &self.visible {
+            let layer = &self.layers[layer_index];
+            if bound_layer != Some(layer_index) {
+                render_cmd.set_bind_group(1, &layer.bind_group, &[]);
+                bound_layer = Some(layer_index);
+            }
+            let chunk = &layer.chunks[chunk_index];
+            render_cmd.set_vertex_buffer(0, chunk.buffer.slice(..));
+            render_cmd.draw(0..4, 0..chunk.quads.len() as u32);
+        }
+    }
+}
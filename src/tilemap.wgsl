@@ -0,0 +1,69 @@
+#include "color.wgsl"
+
+// see TilemapUniform
+struct Tilemap {
+    center: vec2<f32>,
+    // the main view's size in window pixels
+    viewport: vec2<f32>,
+    zoom: f32,
+    _padding: f32,
+    _padding_2: vec2<f32>,
+}
+
+// see LayerUniform
+struct Layer {
+    offset: vec2<f32>,
+    parallax: vec2<f32>,
+    // srgb, alpha includes the layer's opacity
+    tint: vec4<f32>,
+}
+
+@group(0) @binding(0) var atlas: texture_2d<f32>;
+@group(0) @binding(1) var atlas_sampler: sampler;
+@group(0) @binding(2) var<uniform> tilemap: Tilemap;
+@group(1) @binding(0) var<uniform> layer: Layer;
+
+// see TileQuad
+struct TileIn {
+    @location(0) min: vec2<f32>,
+    @location(1) max: vec2<f32>,
+    @location(2) uv_min: vec2<f32>,
+    @location(3) uv_max: vec2<f32>,
+    // horizontal 4, vertical 2, diagonal 1
+    @location(4) flips: u32,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a triangle strip of 4 vertices per tile. tiled flips diagonally first, then horizontally and
+// vertically, so the uv corner is flipped the other way around
+@vertex
+fn vertex(@builtin(vertex_index) index: u32, tile: TileIn) -> VertexOut {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    var uv_corner = corner;
+    if ((tile.flips & 2u) != 0u) {
+        uv_corner.y = 1.0 - uv_corner.y;
+    }
+    if ((tile.flips & 4u) != 0u) {
+        uv_corner.x = 1.0 - uv_corner.x;
+    }
+    if ((tile.flips & 1u) != 0u) {
+        uv_corner = uv_corner.yx;
+    }
+    let map_position = mix(tile.min, tile.max, corner) + layer.offset;
+    let screen = (map_position - tilemap.center * layer.parallax) * tilemap.zoom;
+    var out: VertexOut;
+    out.pos = vec4<f32>(screen / tilemap.viewport * vec2<f32>(2.0, -2.0), 0.0, 1.0);
+    out.uv = mix(tile.uv_min, tile.uv_max, uv_corner);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let texel = textureSample(atlas, atlas_sampler, in.uv);
+    let tint = vec4<f32>(srgb_decode(layer.tint.rgb), layer.tint.a);
+    return output_color(texel * tint);
+}
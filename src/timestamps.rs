@@ -0,0 +1,83 @@
+use wgpu::*;
+use crate::context::RenderContext;
+
+// wgpu won't create query sets past this many queries
+const MAX_QUERIES: u32 = 8192;
+
+// gpu timestamps at the start and end of every encoded frame, for up to a fixed number of
+// frames. nothing is read back until `read`, so measuring doesn't stall the frames it measures
+pub struct FrameTimestamps {
+    query_set: QuerySet,
+    capacity: u32,
+    // the frame being encoded, None before the first next_frame
+    frame: Option<u32>,
+    count: u32,
+}
+
+impl FrameTimestamps {
+    pub fn is_supported(context: &RenderContext) -> bool {
+        context.features().contains(Features::TIMESTAMP_QUERY)
+    }
+
+    // frames past the capacity aren't timed
+    pub fn new(context: &RenderContext, frames: u32) -> Self {
+        let capacity = frames.clamp(1, MAX_QUERIES / 2);
+        let query_set = context.device.create_query_set(&QuerySetDescriptor {
+            label: Some("frame timestamps"),
+            ty: QueryType::Timestamp,
+            count: capacity * 2,
+        });
+        Self {
+            query_set,
+            capacity,
+            frame: None,
+            count: 0,
+        }
+    }
+
+    // called once per frame before it's encoded
+    pub fn next_frame(&mut self) {
+        self.frame = (self.count < self.capacity).then_some(self.count);
+        self.count = (self.count + 1).min(self.capacity);
+    }
+
+    pub fn begin(&self, cmd: &mut CommandEncoder) {
+        if let Some(frame) = self.frame {
+            cmd.write_timestamp(&self.query_set, frame * 2);
+        }
+    }
+
+    pub fn end(&self, cmd: &mut CommandEncoder) {
+        if let Some(frame) = self.frame {
+            cmd.write_timestamp(&self.query_set, frame * 2 + 1);
+        }
+    }
+
+    // milliseconds each timed frame took on the gpu, blocking until they're all done
+    pub fn read(&self, context: &RenderContext) -> Vec<f32> {
+        if self.count == 0 {
+            return Vec::new();
+        }
+        let size = (self.count * 2) as BufferAddress * std::mem::size_of::<u64>() as BufferAddress;
+        let resolve = context.device.create_buffer(&BufferDescriptor {
+            label: Some("timestamp resolve"),
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let mut cmd = context.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("timestamp resolve") });
+        cmd.resolve_query_set(&self.query_set, 0..self.count * 2, &resolve, 0);
+        context.queue.submit([cmd.finish()]);
+
+        let data = context.read_buffer(&resolve, 0..size).wait(context).unwrap_or_else(|error| panic!("{error}"));
+        // nanoseconds per tick
+        let period = context.queue.get_timestamp_period() as f64;
+        data.chunks_exact(16)
+            .map(|pair| {
+                let start = u64::from_ne_bytes(pair[..8].try_into().unwrap());
+                let end = u64::from_ne_bytes(pair[8..].try_into().unwrap());
+                (end.saturating_sub(start) as f64 * period / 1e6) as f32
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,298 @@
+use std::f32::consts::PI;
+use glam::{Quat, Vec3, Vec4};
+use crate::camera::Camera;
+use crate::mesh::Material;
+use crate::transform::Transform;
+use crate::world::{Entity, World};
+
+// how a tween's progress is shaped over its duration, see easings.net for curves
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Ease {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineInOut,
+    // overshoots a little before settling
+    BackOut,
+    BounceOut,
+}
+
+impl Ease {
+    // t goes 0..1, the result starts at 0 and ends at 1 but may leave that range in between
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Ease::Linear => t,
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1.0 - (1.0 - t).powi(2),
+            Ease::QuadInOut if t < 0.5 => 2.0 * t * t,
+            Ease::QuadInOut => 1.0 - (2.0 - 2.0 * t).powi(2) * 0.5,
+            Ease::CubicIn => t * t * t,
+            Ease::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Ease::CubicInOut if t < 0.5 => 4.0 * t * t * t,
+            Ease::CubicInOut => 1.0 - (2.0 - 2.0 * t).powi(3) * 0.5,
+            Ease::SineInOut => (1.0 - (PI * t).cos()) * 0.5,
+            Ease::BackOut => {
+                let overshoot = 1.70158;
+                1.0 + (overshoot + 1.0) * (t - 1.0).powi(3) + overshoot * (t - 1.0).powi(2)
+            }
+            Ease::BounceOut => bounce_out(t),
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    let (n, d) = (7.5625, 2.75);
+    if t < 1.0 / d {
+        n * t * t
+    } else if t < 2.0 / d {
+        let t = t - 1.5 / d;
+        n * t * t + 0.75
+    } else if t < 2.5 / d {
+        let t = t - 2.25 / d;
+        n * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / d;
+        n * t * t + 0.984375
+    }
+}
+
+// something a tween can animate and the value it ends at. transforms and materials are the
+// entity's own components, camera properties need a Camera on the entity
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Property {
+    Translation(Vec3),
+    Rotation(Quat),
+    Scale(Vec3),
+    // the material's base color, linear
+    Color([f32; 4]),
+    CameraEye(Vec3),
+    CameraTarget(Vec3),
+    // vertical, in radians
+    CameraFov(f32),
+}
+
+impl Property {
+    // both have to be the same kind of property
+    fn lerp(self, end: Self, t: f32) -> Self {
+        match (self, end) {
+            (Property::Translation(a), Property::Translation(b)) => Property::Translation(a.lerp(b, t)),
+            (Property::Rotation(a), Property::Rotation(b)) => Property::Rotation(a.slerp(b, t)),
+            (Property::Scale(a), Property::Scale(b)) => Property::Scale(a.lerp(b, t)),
+            (Property::Color(a), Property::Color(b)) => Property::Color(Vec4::from(a).lerp(Vec4::from(b), t).into()),
+            (Property::CameraEye(a), Property::CameraEye(b)) => Property::CameraEye(a.lerp(b, t)),
+            (Property::CameraTarget(a), Property::CameraTarget(b)) => Property::CameraTarget(a.lerp(b, t)),
+            (Property::CameraFov(a), Property::CameraFov(b)) => Property::CameraFov(a + (b - a) * t),
+            _ => end,
+        }
+    }
+}
+
+// moves one property from wherever it is when the tween starts to a value
+#[derive(Copy, Clone, Debug)]
+pub struct Tween {
+    end: Property,
+    duration: f32,
+    ease: Ease,
+    // taken from the entity when the tween starts, none before that
+    start: Option<Property>,
+}
+
+impl Tween {
+    // duration in seconds
+    pub fn to(end: Property, duration: f32) -> Self {
+        Self {
+            end,
+            duration: duration.max(0.0),
+            ease: Ease::Linear,
+            start: None,
+        }
+    }
+
+    pub fn ease(mut self, ease: Ease) -> Self {
+        self.ease = ease;
+        self
+    }
+
+    fn value_at(&self, elapsed: f32) -> Option<Property> {
+        let t = if self.duration > 0.0 { (elapsed / self.duration).min(1.0) } else { 1.0 };
+        Some(self.start?.lerp(self.end, self.ease.apply(t)))
+    }
+}
+
+// tweens that run together, the group ends with the longest one or the wait
+#[derive(Clone, Debug, Default)]
+struct Group {
+    tweens: Vec<Tween>,
+    wait: f32,
+}
+
+impl Group {
+    fn duration(&self) -> f32 {
+        self.tweens.iter().map(|tween| tween.duration).fold(self.wait, f32::max)
+    }
+}
+
+// a component that plays tweens on its entity, chained one after another in fixed steps.
+// finished chains are removed and reported to App::tween_finished, looping ones never finish:
+//
+//   Tweens::new()
+//       .then(Tween::to(Property::Translation(up), 1.0).ease(Ease::QuadOut))
+//       .with(Tween::to(Property::Color(red), 1.0))
+//       .wait(0.5)
+//       .then(Tween::to(Property::Translation(down), 1.0).ease(Ease::BounceOut))
+#[derive(Clone, Debug, Default)]
+pub struct Tweens {
+    groups: Vec<Group>,
+    current: usize,
+    // into the current group
+    elapsed: f32,
+    looping: bool,
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // starts once everything before it is done
+    pub fn then(mut self, tween: Tween) -> Self {
+        self.groups.push(Group { tweens: vec![tween], wait: 0.0 });
+        self
+    }
+
+    // runs alongside the last tween added with then
+    pub fn with(mut self, tween: Tween) -> Self {
+        match self.groups.last_mut() {
+            Some(group) => group.tweens.push(tween),
+            None => self.groups.push(Group { tweens: vec![tween], wait: 0.0 }),
+        }
+        self
+    }
+
+    // seconds before the next tween starts
+    pub fn wait(mut self, seconds: f32) -> Self {
+        self.groups.push(Group { tweens: Vec::new(), wait: seconds.max(0.0) });
+        self
+    }
+
+    // starts over from the first tween when the last one ends. every tween starts from where
+    // the property is at the time, so a chain there and back again loops seamlessly
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current >= self.groups.len()
+    }
+
+    // returns whether the chain is finished
+    fn advance(&mut self, step: f32, targets: &mut Targets) -> bool {
+        let mut remaining = step;
+        loop {
+            let Some(group) = self.groups.get_mut(self.current) else {
+                return true;
+            };
+            for tween in &mut group.tweens {
+                if tween.start.is_none() {
+                    tween.start = targets.get(tween.end);
+                }
+            }
+            let duration = group.duration();
+            self.elapsed += remaining;
+            for tween in &group.tweens {
+                if let Some(value) = tween.value_at(self.elapsed) {
+                    targets.set(value);
+                }
+            }
+            if self.elapsed < duration {
+                return false;
+            }
+            // what's left of the step goes to the next group
+            remaining = self.elapsed - duration;
+            self.elapsed = 0.0;
+            self.current += 1;
+            if self.current == self.groups.len() && self.looping {
+                // a loop that takes no time would never let the step run out
+                if self.groups.iter().all(|group| group.duration() <= 0.0) {
+                    return true;
+                }
+                self.current = 0;
+                for tween in self.groups.iter_mut().flat_map(|group| &mut group.tweens) {
+                    tween.start = None;
+                }
+            }
+        }
+    }
+}
+
+// the components of one entity tweens can write to
+struct Targets<'a> {
+    transform: Option<&'a mut Transform>,
+    material: Option<&'a mut Material>,
+    camera: Option<&'a mut Camera>,
+}
+
+impl Targets<'_> {
+    // the current value of the same kind of property, none if the entity doesn't have it
+    fn get(&self, property: Property) -> Option<Property> {
+        Some(match property {
+            Property::Translation(_) => Property::Translation(self.transform.as_ref()?.translation),
+            Property::Rotation(_) => Property::Rotation(self.transform.as_ref()?.rotation),
+            Property::Scale(_) => Property::Scale(self.transform.as_ref()?.scale),
+            Property::Color(_) => Property::Color(self.material.as_ref()?.base_color),
+            Property::CameraEye(_) => Property::CameraEye(self.camera.as_ref()?.eye),
+            Property::CameraTarget(_) => Property::CameraTarget(self.camera.as_ref()?.target),
+            Property::CameraFov(_) => Property::CameraFov(self.camera.as_ref()?.fovy),
+        })
+    }
+
+    fn set(&mut self, property: Property) {
+        match property {
+            Property::Translation(value) => if let Some(transform) = &mut self.transform {
+                transform.translation = value;
+            },
+            Property::Rotation(value) => if let Some(transform) = &mut self.transform {
+                transform.rotation = value;
+            },
+            Property::Scale(value) => if let Some(transform) = &mut self.transform {
+                transform.scale = value;
+            },
+            Property::Color(value) => if let Some(material) = &mut self.material {
+                material.base_color = value;
+            },
+            Property::CameraEye(value) => if let Some(camera) = &mut self.camera {
+                camera.eye = value;
+            },
+            Property::CameraTarget(value) => if let Some(camera) = &mut self.camera {
+                camera.target = value;
+            },
+            Property::CameraFov(value) => if let Some(camera) = &mut self.camera {
+                camera.fovy = value;
+            },
+        }
+    }
+}
+
+// tweens are simulation like animations, so they advance in fixed steps. returns the entities
+// whose chains finished, their Tweens are removed
+pub fn advance(world: &mut World, step: f32) -> Vec<Entity> {
+    let mut finished = Vec::new();
+    let query = world.query_mut::<(&mut Tweens, Option<&mut Transform>, Option<&mut Material>, Option<&mut Camera>)>();
+    for (entity, (tweens, transform, material, camera)) in query {
+        let mut targets = Targets { transform, material, camera };
+        if tweens.advance(step, &mut targets) {
+            finished.push(entity);
+        }
+    }
+    for &entity in &finished {
+        let _ = world.remove_one::<Tweens>(entity);
+    }
+    finished
+}
@@ -0,0 +1,167 @@
+use std::mem::size_of;
+use glam::Vec3;
+use wgpu::*;
+use crate::mesh::Vertex;
+use crate::pipeline_cache::PipelineKey;
+
+// what the pulling vertex shader reads a compressed vertex as
+pub const COMPRESSED_VERTEX_WORDS: usize = size_of::<CompressedVertex>() / size_of::<u32>();
+
+// how a mesh's vertices are stored on the gpu. compressed ones take 32 bytes instead of 80 and
+// are drawn with pipelines built from the same shaders with COMPRESSED_VERTICES defined
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum VertexEncoding {
+    #[default]
+    Full,
+    Compressed,
+}
+
+impl VertexEncoding {
+    // bytes per vertex
+    pub fn stride(self) -> usize {
+        match self {
+            VertexEncoding::Full => size_of::<Vertex>(),
+            VertexEncoding::Compressed => size_of::<CompressedVertex>(),
+        }
+    }
+}
+
+// Vertex at 32 bytes. positions are 16 bit fractions of the mesh's bounds, see Quantization,
+// with the bitangent sign in w. normals and tangents are octahedral, uvs half floats, joints 16
+// bit and weights 8 bit
+#[derive(Copy, Clone, Default, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct CompressedVertex {
+    pub position: [u16; 4],
+    pub normal: [i16; 2],
+    pub uv: [u16; 2],
+    pub tangent: [i16; 2],
+    pub joints: [u16; 4],
+    pub weights: [u8; 4],
+}
+
+impl CompressedVertex {
+    // the same locations as Vertex::LAYOUT
+    pub const LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: size_of::<CompressedVertex>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &vertex_attr_array![
+            0 => Unorm16x4,
+            1 => Snorm16x2,
+            2 => Float16x2,
+            5 => Snorm16x2,
+            3 => Uint16x4,
+            4 => Unorm8x4,
+        ],
+    };
+
+    pub fn new(vertex: &Vertex, quantization: &Quantization) -> Self {
+        let position = (Vec3::from(vertex.position) - quantization.min) / quantization.extent;
+        let sign = if vertex.tangent[3] < 0.0 { 0 } else { u16::MAX };
+        Self {
+            position: [unorm16(position.x), unorm16(position.y), unorm16(position.z), sign],
+            normal: octahedral(Vec3::from(vertex.normal)),
+            uv: vertex.uv.map(f16_bits),
+            tangent: octahedral(Vec3::new(vertex.tangent[0], vertex.tangent[1], vertex.tangent[2])),
+            joints: vertex.joints.map(|joint| joint.min(u16::MAX as u32) as u16),
+            weights: vertex.weights.map(|weight| (weight.clamp(0.0, 1.0) * 255.0).round() as u8),
+        }
+    }
+}
+
+// matches Quantization in object.wgsl, group 3 of the mesh pipelines. a position of 0..1 in
+// each axis is min + position * extent. full meshes get the identity, which nothing reads
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct QuantizationUniform {
+    pub min: [f32; 4],
+    pub extent: [f32; 4],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quantization {
+    pub min: Vec3,
+    // never 0, flat meshes would divide by it
+    pub extent: Vec3,
+}
+
+impl Quantization {
+    pub const IDENTITY: Self = Self { min: Vec3::ZERO, extent: Vec3::ONE };
+
+    // the bounds of the base vertices. morph targets are added after decoding, so they can
+    // reach past them
+    pub fn new((min, max): (Vec3, Vec3)) -> Self {
+        Self { min, extent: (max - min).max(Vec3::splat(f32::MIN_POSITIVE)) }
+    }
+
+    pub fn uniform(&self) -> QuantizationUniform {
+        QuantizationUniform {
+            min: self.min.extend(0.0).into(),
+            extent: self.extent.extend(0.0).into(),
+        }
+    }
+}
+
+pub fn compress(vertices: &[Vertex], quantization: &Quantization) -> Vec<CompressedVertex> {
+    vertices.iter().map(|vertex| CompressedVertex::new(vertex, quantization)).collect()
+}
+
+// the variant of a mesh pipeline's key for compressed vertices. shader is the key's shader built
+// with COMPRESSED_VERTICES defined, pulled pipelines have no vertex layouts to swap
+pub fn compressed_key(mut key: PipelineKey, shader: &'static str) -> PipelineKey {
+    key.shader = shader;
+    if !key.vertex_layouts.is_empty() {
+        key.vertex_layouts = vec![(&CompressedVertex::LAYOUT).into()];
+    }
+    key
+}
+
+fn unorm16(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+fn snorm16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+// a unit vector folded onto the octahedron and flattened to two components, decoded by
+// octahedral_decode in object.wgsl
+fn octahedral(direction: Vec3) -> [i16; 2] {
+    let sign = |value: f32| if value >= 0.0 { 1.0 } else { -1.0 };
+    let n = direction / (direction.x.abs() + direction.y.abs() + direction.z.abs()).max(f32::EPSILON);
+    let (x, y) = if n.z >= 0.0 {
+        (n.x, n.y)
+    } else {
+        ((1.0 - n.y.abs()) * sign(n.x), (1.0 - n.x.abs()) * sign(n.y))
+    };
+    [snorm16(x), snorm16(y)]
+}
+
+// rounded to nearest. past 65504 is infinite, tiny values lose precision down to 0
+fn f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    if exponent == 0xff {
+        // infinity stays infinite and nan stays nan
+        return sign | 0x7c00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // subnormal, with the implicit bit shifted into the mantissa
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | ((mantissa >> shift) + round) as u16;
+    }
+    // a carry out of the mantissa moves up the exponent, which is what rounding up should do
+    let half = sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16;
+    half + ((mantissa >> 12) & 1) as u16
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, Frames, ImageDecoder, RgbaImage};
+use wgpu::*;
+use crate::context::RenderContext;
+use crate::texture::Texture;
+
+// frames decoded ahead of playback. the decoder blocks once they're all waiting
+const QUEUED_FRAMES: usize = 4;
+// browsers show gif frames with almost no delay this long, most such files count on it
+const MIN_FRAME_TIME: f32 = 0.02;
+const DEFAULT_FRAME_TIME: f32 = 0.1;
+
+#[derive(Debug)]
+pub enum VideoError {
+    Io(io::Error),
+    Image(image::ImageError),
+    // not an animation format we decode, only gif and apng are
+    Unsupported(PathBuf),
+}
+
+impl fmt::Display for VideoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VideoError::Io(error) => error.fmt(f),
+            VideoError::Image(error) => error.fmt(f),
+            VideoError::Unsupported(path) => write!(f, "{} isn't a gif or png", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for VideoError {}
+
+impl From<io::Error> for VideoError {
+    fn from(error: io::Error) -> Self {
+        VideoError::Io(error)
+    }
+}
+
+impl From<image::ImageError> for VideoError {
+    fn from(error: image::ImageError) -> Self {
+        VideoError::Image(error)
+    }
+}
+
+struct VideoFrame {
+    image: RgbaImage,
+    // seconds it stays up
+    duration: f32,
+}
+
+// an animated gif or png played into a texture. frames are decoded on their own thread and
+// written into the same texture as they come due, so a material holding the texture shows the
+// animation without being rebound. add `texture` to the renderer with add_material_texture
+pub struct VideoTexture {
+    pub texture: Arc<Texture>,
+    pub paused: bool,
+    frames: Receiver<VideoFrame>,
+    // until the frame showing is replaced
+    remaining: f32,
+    finished: bool,
+}
+
+impl VideoTexture {
+    // only reads the header here to size the texture, which stays transparent until the first
+    // frame is decoded. looping starts over from the first frame at the end, otherwise the last
+    // frame stays up
+    pub fn open(context: &RenderContext, path: impl AsRef<Path>, looping: bool) -> Result<Self, VideoError> {
+        let path = path.as_ref().to_path_buf();
+        let (width, height) = dimensions(&path)?;
+        let label = path.display().to_string();
+        let texture = Texture::from_rgba8(context, &label, width, height, &vec![0; (width * height * 4) as usize], true);
+
+        // the decoders aren't Send, so the thread opens its own
+        let (sender, receiver) = mpsc::sync_channel(QUEUED_FRAMES);
+        thread::Builder::new()
+            .name(format!("video {label}"))
+            .spawn(move || stream(path, sender, looping))
+            .expect("failed to spawn video thread");
+        Ok(Self {
+            texture: Arc::new(texture),
+            paused: false,
+            frames: receiver,
+            remaining: 0.0,
+            finished: false,
+        })
+    }
+
+    // the end of a video that doesn't loop
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // once per rendered frame with its delta. frames that came due while the last frame was
+    // up are skipped, only the newest is uploaded
+    pub fn update(&mut self, context: &RenderContext, delta: f32) {
+        if self.paused || self.finished {
+            return;
+        }
+        self.remaining -= delta;
+        let mut due = None;
+        while self.remaining <= 0.0 {
+            match self.frames.try_recv() {
+                Ok(frame) => {
+                    self.remaining += frame.duration;
+                    due = Some(frame);
+                }
+                // the decoder is behind, the next frame shows as soon as it's there
+                Err(TryRecvError::Empty) => {
+                    self.remaining = 0.0;
+                    break;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+        if let Some(frame) = due {
+            self.write(context, &frame.image);
+        }
+    }
+
+    fn write(&self, context: &RenderContext, image: &RgbaImage) {
+        let size = self.texture.size;
+        if image.dimensions() != (size.width, size.height) {
+            tracing::warn!("video frame is {:?}, not {}x{}", image.dimensions(), size.width, size.height);
+            return;
+        }
+        context.queue.write_texture(
+            self.texture.texture.as_image_copy(),
+            image,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(size.width * 4),
+                rows_per_image: None,
+            },
+            size,
+        );
+    }
+}
+
+impl From<image::Frame> for VideoFrame {
+    fn from(frame: image::Frame) -> Self {
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let duration = numerator as f32 / denominator.max(1) as f32 / 1000.0;
+        Self {
+            image: frame.into_buffer(),
+            duration: if duration < MIN_FRAME_TIME { DEFAULT_FRAME_TIME } else { duration },
+        }
+    }
+}
+
+fn is_gif(path: &Path) -> Result<bool, VideoError> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gif") => Ok(true),
+        Some("png" | "apng") => Ok(false),
+        _ => Err(VideoError::Unsupported(path.to_path_buf())),
+    }
+}
+
+// the size of the whole canvas, frames can be smaller but are composited onto it
+fn dimensions(path: &Path) -> Result<(u32, u32), VideoError> {
+    let gif = is_gif(path)?;
+    let reader = BufReader::new(File::open(path)?);
+    Ok(if gif { GifDecoder::new(reader)?.dimensions() } else { PngDecoder::new(reader)?.dimensions() })
+}
+
+// frames come out whole, already composited onto the ones before
+fn decode(path: &Path) -> Result<Frames<'static>, VideoError> {
+    let gif = is_gif(path)?;
+    let reader = BufReader::new(File::open(path)?);
+    Ok(if gif { GifDecoder::new(reader)?.into_frames() } else { PngDecoder::new(reader)?.apng().into_frames() })
+}
+
+// runs until the video ends or the VideoTexture is dropped. looping reopens the file each time
+// rather than keeping every frame in memory
+fn stream(path: PathBuf, sender: SyncSender<VideoFrame>, looping: bool) {
+    loop {
+        let frames = match decode(&path) {
+            Ok(frames) => frames,
+            Err(error) => {
+                tracing::error!("failed to open {}: {error}", path.display());
+                return;
+            }
+        };
+        let mut count = 0;
+        for frame in frames {
+            count += 1;
+            let frame = match frame {
+                Ok(frame) => VideoFrame::from(frame),
+                Err(error) => {
+                    tracing::error!("failed to decode {}: {error}", path.display());
+                    return;
+                }
+            };
+            if sender.send(frame).is_err() {
+                return;
+            }
+        }
+        // a still image has nothing to loop
+        if !looping || count <= 1 {
+            return;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::Camera;
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+
+pub const VIEWPORT_CLEAR_LAYOUT: &str = "viewport clear";
+
+// where a camera entity draws, as fractions of the window from the top left. cameras without
+// one fill the window. e.g. the quarters of a 2x2 split screen, or a small picture in picture
+// view with a higher order than the main one
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    // drawn in increasing order, so later views cover earlier ones where they overlap. the
+    // first is the main view, which terrain lods and ssao follow
+    pub order: i32,
+}
+
+impl Viewport {
+    pub const FULL: Self = Self { x: 0.0, y: 0.0, width: 1.0, height: 1.0, order: 0 };
+
+    // one quarter of a 2x2 split, 0 is top left, 1 top right, 2 bottom left, 3 bottom right
+    pub fn quarter(index: usize) -> Self {
+        Self {
+            x: (index % 2) as f32 * 0.5,
+            y: (index / 2 % 2) as f32 * 0.5,
+            width: 0.5,
+            height: 0.5,
+            order: index as i32,
+        }
+    }
+
+    // in pixels of a target this size, at least one pixel and inside the target
+    pub fn rect(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let x = ((self.x.clamp(0.0, 1.0) * width as f32).round() as u32).min(width - 1);
+        let y = ((self.y.clamp(0.0, 1.0) * height as f32).round() as u32).min(height - 1);
+        let right = (((self.x + self.width).clamp(0.0, 1.0) * width as f32).round() as u32).clamp(x + 1, width);
+        let bottom = (((self.y + self.height).clamp(0.0, 1.0) * height as f32).round() as u32).clamp(y + 1, height);
+        (x, y, right - x, bottom - y)
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+// a camera and where it draws, gathered from the world every update
+#[derive(Copy, Clone, Debug)]
+pub struct View {
+    pub camera: Camera,
+    pub viewport: Viewport,
+}
+
+impl View {
+    pub fn aspect(&self, width: u32, height: u32) -> f32 {
+        let (_, _, width, height) = self.viewport.rect(width, height);
+        width as f32 / height as f32
+    }
+}
+
+// fills the current viewport with the clear color at the far plane, so a view drawn over
+// another starts out as if the pass had just been cleared
+pub struct ViewportClear {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    render_pipeline: RenderPipeline,
+}
+
+impl ViewportClear {
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry, color: Color) -> Self {
+        let device = &context.device;
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("viewport clear"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            contents: bytemuck::cast_slice(&color_array(color)),
+        });
+        layouts.register(context, VIEWPORT_CLEAR_LAYOUT, &[(Binding::Uniform, ShaderStages::FRAGMENT)]);
+        let bind_group = BindGroupBuilder::new()
+            .buffer(&buffer)
+            .build(context, layouts, VIEWPORT_CLEAR_LAYOUT);
+
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "viewport_clear.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("viewport clear"),
+            bind_group_layouts: &[layouts.get(VIEWPORT_CLEAR_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("viewport clear"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                entry_point: "fragment",
+                module: &shader_module,
+                targets: &[
+                    Some(HDR_FORMAT.into())
+                ],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        Self {
+            buffer,
+            bind_group,
+            render_pipeline,
+        }
+    }
+
+    pub fn set_color(&self, context: &RenderContext, color: Color) {
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&color_array(color)));
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>) {
+        render_cmd.set_pipeline(&self.render_pipeline);
+        render_cmd.set_bind_group(0, &self.bind_group, &[]);
+        render_cmd.draw(0..3, 0..1);
+    }
+}
+
+fn color_array(color: Color) -> [f32; 4] {
+    [color.r as f32, color.g as f32, color.b as f32, color.a as f32]
+}
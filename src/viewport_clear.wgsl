@@ -0,0 +1,19 @@
+struct Clear {
+    color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> clear: Clear;
+
+// a single triangle that covers the viewport, at the far plane
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(corner * 2.0 - 1.0, 1.0, 1.0);
+}
+
+// already linear, so it matches the clear color of the pass
+@fragment
+fn fragment() -> @location(0) vec4<f32> {
+    return clear.color;
+}
@@ -0,0 +1,481 @@
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use glam::{IVec3, Vec3};
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::bindings::LayoutRegistry;
+use crate::camera::{Camera, Frustum, FRAME_LAYOUT};
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+use crate::ssao::NORMAL_DEPTH_FORMAT;
+
+// voxels along each side of a chunk
+pub const CHUNK_SIZE: i32 = 32;
+// chunk rows from the bottom of the world, y chunk coordinates go from -1 up
+const VERTICAL_CHUNKS: i32 = 2;
+// meshing is cpu bound, past a few threads it mostly fights the renderer
+const MAX_MESHER_THREADS: usize = 4;
+// chunks queued for the threads at once. the nearest missing ones go first, so keeping the
+// queue short lets a moving camera reprioritise
+const MAX_IN_FLIGHT: usize = 16;
+// meshes uploaded per update, the rest wait in the channel so a burst doesn't stall a frame
+const MAX_UPLOADS: usize = 8;
+// freed vertex buffers kept for reuse, past this they're dropped
+const MAX_POOLED_BUFFERS: usize = 64;
+
+#[derive(Copy, Clone, Debug)]
+pub struct VoxelSettings {
+    pub enabled: bool,
+    // chunks kept around the camera along x and z, anything further is unloaded
+    pub view_distance: u32,
+    // picks the generated landscape, changing it regenerates every chunk
+    pub seed: u32,
+}
+
+impl Default for VoxelSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            view_distance: 6,
+            seed: 1,
+        }
+    }
+}
+
+// what a voxel is made of, also indexes the colors in voxels.wgsl
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum Block {
+    Air,
+    Grass,
+    Dirt,
+    Stone,
+    Sand,
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct VoxelVertex {
+    position: [f32; 3],
+    // see VertexIn in voxels.wgsl
+    packed: u32,
+}
+
+impl VoxelVertex {
+    const LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: size_of::<VoxelVertex>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &vertex_attr_array![
+            0 => Float32x3,
+            1 => Uint32,
+        ],
+    };
+}
+
+// a meshed chunk's quads, four vertices each, in a buffer from the pool
+struct ChunkMesh {
+    vertex_buffer: Buffer,
+    capacity: BufferAddress,
+    quads: u32,
+    min: Vec3,
+    max: Vec3,
+}
+
+// vertex buffers of unloaded chunks, handed out again to chunks that fit in them instead of
+// allocating a buffer per chunk as the camera moves
+struct BufferPool {
+    free: Vec<(BufferAddress, Buffer)>,
+}
+
+impl BufferPool {
+    // the smallest free buffer that's big enough, or a new one rounded up to a power of two so
+    // it's likelier to fit another chunk later
+    fn take(&mut self, context: &RenderContext, size: BufferAddress) -> (BufferAddress, Buffer) {
+        let best = self.free.iter().enumerate()
+            .filter(|(_, (capacity, _))| *capacity >= size)
+            .min_by_key(|(_, (capacity, _))| *capacity)
+            .map(|(index, _)| index);
+        if let Some(index) = best {
+            return self.free.swap_remove(index);
+        }
+        let capacity = size.next_power_of_two();
+        let buffer = context.device.create_buffer(&BufferDescriptor {
+            label: Some("voxel chunk"),
+            size: capacity,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        (capacity, buffer)
+    }
+
+    fn give(&mut self, capacity: BufferAddress, buffer: Buffer) {
+        if self.free.len() < MAX_POOLED_BUFFERS {
+            self.free.push((capacity, buffer));
+        }
+    }
+}
+
+// threads that generate chunks and greedy mesh them. uploads need the context so they stay on
+// the main thread, see Voxels::update
+struct Mesher {
+    jobs: Sender<(IVec3, u32)>,
+    results: Receiver<(IVec3, u32, Vec<VoxelVertex>)>,
+}
+
+impl Mesher {
+    fn new() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<(IVec3, u32)>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, results) = mpsc::channel();
+        let threads = thread::available_parallelism().map_or(2, |threads| threads.get()).clamp(1, MAX_MESHER_THREADS);
+        for index in 0..threads {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            thread::Builder::new()
+                .name(format!("voxel mesher {index}"))
+                .spawn(move || loop {
+                    // both channels close when Voxels is dropped, which ends the thread
+                    let job = job_receiver.lock().unwrap().recv();
+                    let Ok((coord, seed)) = job else {
+                        break;
+                    };
+                    let vertices = tracing::info_span!("mesh chunk").in_scope(|| mesh_chunk(coord, seed));
+                    if result_sender.send((coord, seed, vertices)).is_err() {
+                        break;
+                    }
+                })
+                .expect("failed to start voxel mesher thread");
+        }
+        Self { jobs, results }
+    }
+}
+
+// a generated landscape streamed in chunks around the camera. chunks are generated and meshed
+// on worker threads, nearest first, and uploaded a few per frame as they come back. only chunks
+// in the main view's frustum are drawn. they're in the ssao prepass but don't cast shadows
+pub struct Voxels {
+    // None for chunks that are all air or all buried
+    chunks: HashMap<IVec3, Option<ChunkMesh>>,
+    // requested and not back from the threads yet
+    pending: HashSet<IVec3>,
+    mesher: Mesher,
+    // jobs the threads haven't answered, including ones whose chunk was unloaded since
+    in_flight: usize,
+    seed: u32,
+    pool: BufferPool,
+    // quads share one index buffer, 0 1 2 0 2 3 offset by 4 per quad
+    index_buffer: Buffer,
+    index_quads: u32,
+    visible: Vec<IVec3>,
+    render_pipeline: RenderPipeline,
+    normal_pipeline: RenderPipeline,
+}
+
+impl Voxels {
+    // FRAME_LAYOUT has to be registered already, see CameraBinding
+    pub fn new(context: &RenderContext, layouts: &LayoutRegistry) -> Self {
+        let device = &context.device;
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "voxels.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("voxels"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label, fragment_entry, format: TextureFormat| device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[VoxelVertex::LAYOUT],
+            },
+            fragment: Some(FragmentState {
+                entry_point: fragment_entry,
+                module: &shader_module,
+                targets: &[
+                    Some(format.into())
+                ],
+            }),
+            // faces only exist towards air, so their backs are never seen
+            primitive: PrimitiveState {
+                cull_mode: Some(Face::Back),
+                ..PrimitiveState::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        let index_quads = 4096;
+        Self {
+            chunks: HashMap::new(),
+            pending: HashSet::new(),
+            mesher: Mesher::new(),
+            in_flight: 0,
+            seed: VoxelSettings::default().seed,
+            pool: BufferPool { free: Vec::new() },
+            index_buffer: create_index_buffer(context, index_quads),
+            index_quads,
+            visible: Vec::new(),
+            render_pipeline: create_pipeline("voxels", "fragment", HDR_FORMAT),
+            normal_pipeline: create_pipeline("voxel normals", "fragment_normal", NORMAL_DEPTH_FORMAT),
+        }
+    }
+
+    // chunks with a mesh, and the chunks being meshed
+    pub fn chunk_counts(&self) -> (usize, usize) {
+        (self.chunks.values().flatten().count(), self.pending.len())
+    }
+
+    // visible chunks, one draw each
+    pub fn draw_count(&self) -> u32 {
+        self.visible.len() as u32
+    }
+
+    // streams chunks in and out around camera and picks the ones in frustum to draw
+    pub fn update(&mut self, context: &RenderContext, settings: &VoxelSettings, camera: &Camera, frustum: &Frustum) {
+        self.visible.clear();
+        if !settings.enabled || settings.seed != self.seed {
+            self.seed = settings.seed;
+            self.pending.clear();
+            for mesh in self.chunks.drain().filter_map(|(_, mesh)| mesh) {
+                self.pool.give(mesh.capacity, mesh.vertex_buffer);
+            }
+        }
+
+        // results of an old seed, or for chunks unloaded since, are thrown away
+        for (coord, seed, vertices) in self.mesher.results.try_iter().take(MAX_UPLOADS) {
+            self.in_flight -= 1;
+            if seed != self.seed || !self.pending.remove(&coord) {
+                continue;
+            }
+            let mesh = (!vertices.is_empty()).then(|| {
+                let quads = vertices.len() as u32 / 4;
+                if quads > self.index_quads {
+                    self.index_quads = quads.next_power_of_two();
+                    self.index_buffer = create_index_buffer(context, self.index_quads);
+                }
+                let bytes: &[u8] = bytemuck::cast_slice(&vertices);
+                let (capacity, vertex_buffer) = self.pool.take(context, bytes.len() as BufferAddress);
+                context.queue.write_buffer(&vertex_buffer, 0, bytes);
+                let min = (coord * CHUNK_SIZE).as_vec3();
+                ChunkMesh { vertex_buffer, capacity, quads, min, max: min + Vec3::splat(CHUNK_SIZE as f32) }
+            });
+            self.chunks.insert(coord, mesh);
+        }
+        if !settings.enabled {
+            return;
+        }
+
+        let center = (camera.eye / CHUNK_SIZE as f32).floor().as_ivec3();
+        let distance = settings.view_distance as i32;
+        let mut wanted: Vec<IVec3> = (-distance..=distance)
+            .flat_map(|x| (-distance..=distance).map(move |z| (x, z)))
+            .flat_map(|(x, z)| (-1..VERTICAL_CHUNKS - 1).map(move |y| IVec3::new(center.x + x, y, center.z + z)))
+            .collect();
+        // a chunk past the view distance is only unloaded one chunk further out, so it isn't
+        // dropped and requested again as the camera moves back and forth over a border
+        let near = |coord: &IVec3| (coord.x - center.x).abs() <= distance + 1 && (coord.z - center.z).abs() <= distance + 1;
+        let pool = &mut self.pool;
+        self.chunks.retain(|coord, mesh| {
+            let keep = near(coord);
+            if !keep {
+                if let Some(mesh) = mesh.take() {
+                    pool.give(mesh.capacity, mesh.vertex_buffer);
+                }
+            }
+            keep
+        });
+        self.pending.retain(near);
+        wanted.retain(|coord| !self.chunks.contains_key(coord) && !self.pending.contains(coord));
+        let eye = camera.eye;
+        wanted.sort_by(|a, b| chunk_distance(*a, eye).total_cmp(&chunk_distance(*b, eye)));
+        for coord in wanted.into_iter().take(MAX_IN_FLIGHT.saturating_sub(self.in_flight)) {
+            // the threads only stop once self is gone
+            let _ = self.mesher.jobs.send((coord, self.seed));
+            self.pending.insert(coord);
+            self.in_flight += 1;
+        }
+
+        self.visible = self.chunks.iter()
+            .filter(|(_, mesh)| mesh.as_ref().is_some_and(|mesh| frustum.intersects_aabb(mesh.min, mesh.max)))
+            .map(|(coord, _)| *coord)
+            .collect();
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        self.draw_with(render_cmd, camera_bind_group, &self.render_pipeline);
+    }
+
+    // view space normals and depth for ssao, see Ssao::normal_depth_view
+    pub fn draw_normals<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        self.draw_with(render_cmd, camera_bind_group, &self.normal_pipeline);
+    }
+
+    fn draw_with<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup, pipeline: &'a RenderPipeline) {
+        if self.visible.is_empty() {
+            return;
+        }
+        render_cmd.set_pipeline(pipeline);
+        render_cmd.set_bind_group(0, camera_bind_group, &[]);
+        render_cmd.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
+        for mesh in self.visible.iter().filter_map(|coord| self.chunks.get(coord)).flatten() {
+            let size = mesh.quads as BufferAddress * 4 * size_of::<VoxelVertex>() as BufferAddress;
+            render_cmd.set_vertex_buffer(0, mesh.vertex_buffer.slice(..size));
+            render_cmd.draw_indexed(0..mesh.quads * 6, 0, 0..1);
+        }
+    }
+}
+
+fn chunk_distance(coord: IVec3, eye: Vec3) -> f32 {
+    let center = (coord * CHUNK_SIZE).as_vec3() + Vec3::splat(CHUNK_SIZE as f32 * 0.5);
+    center.distance_squared(eye)
+}
+
+fn create_index_buffer(context: &RenderContext, quads: u32) -> Buffer {
+    let indices: Vec<u32> = (0..quads)
+        .flat_map(|quad| [0, 1, 2, 0, 2, 3].map(|corner| quad * 4 + corner))
+        .collect();
+    context.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("voxel quads"),
+        contents: bytemuck::cast_slice(&indices),
+        usage: BufferUsages::INDEX,
+    })
+}
+
+// 0 to 1, smoothly interpolated between random values at whole coordinates
+fn value_noise(seed: u32, x: f32, z: f32) -> f32 {
+    let hash = |x: i32, z: i32| {
+        let mut h = (x as u32).wrapping_mul(0x8da6_b343) ^ (z as u32).wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+        h ^= h >> 13;
+        h = h.wrapping_mul(0x5bd1_e995);
+        h ^= h >> 15;
+        h as f32 / u32::MAX as f32
+    };
+    let (x0, z0) = (x.floor(), z.floor());
+    let (tx, tz) = (x - x0, z - z0);
+    let (tx, tz) = (tx * tx * (3.0 - 2.0 * tx), tz * tz * (3.0 - 2.0 * tz));
+    let (ix, iz) = (x0 as i32, z0 as i32);
+    let top = hash(ix, iz) + (hash(ix + 1, iz) - hash(ix, iz)) * tx;
+    let bottom = hash(ix, iz + 1) + (hash(ix + 1, iz + 1) - hash(ix, iz + 1)) * tx;
+    top + (bottom - top) * tz
+}
+
+// the surface's height at a column, four octaves of value noise from -16 to 16
+fn surface_height(seed: u32, x: i32, z: i32) -> i32 {
+    let mut height = 0.0;
+    let mut amplitude = 0.5;
+    let mut scale = 1.0 / 64.0;
+    for octave in 0..4 {
+        height += value_noise(seed.wrapping_add(octave), x as f32 * scale, z as f32 * scale) * amplitude;
+        amplitude *= 0.5;
+        scale *= 2.0;
+    }
+    ((height / 0.9375 - 0.5) * 32.0) as i32
+}
+
+fn block_at(y: i32, height: i32) -> Block {
+    match height - y {
+        depth if depth < 0 => Block::Air,
+        // low ground is beach
+        0 if height < -8 => Block::Sand,
+        0 => Block::Grass,
+        1..=3 => Block::Dirt,
+        _ => Block::Stone,
+    }
+}
+
+// the chunk's faces towards air, neighbouring faces of the same block merged into one quad. the
+// chunk is generated with a voxel of its neighbours around it, so faces on its borders are only
+// there where the neighbour is air
+fn mesh_chunk(coord: IVec3, seed: u32) -> Vec<VoxelVertex> {
+    const PADDED: i32 = CHUNK_SIZE + 2;
+    let origin = coord * CHUNK_SIZE - IVec3::ONE;
+    let heights: Vec<i32> = (0..PADDED * PADDED)
+        .map(|index| surface_height(seed, origin.x + index % PADDED, origin.z + index / PADDED))
+        .collect();
+    let mut voxels = vec![Block::Air; (PADDED * PADDED * PADDED) as usize];
+    for z in 0..PADDED {
+        for y in 0..PADDED {
+            for x in 0..PADDED {
+                voxels[(x + PADDED * (y + PADDED * z)) as usize] = block_at(origin.y + y, heights[(x + PADDED * z) as usize]);
+            }
+        }
+    }
+    // chunk coordinates, -1 and CHUNK_SIZE are the neighbours' border
+    let get = |p: IVec3| voxels[((p.x + 1) + PADDED * ((p.y + 1) + PADDED * (p.z + 1))) as usize];
+
+    let mut vertices = Vec::new();
+    let mut mask = vec![Block::Air; (CHUNK_SIZE * CHUNK_SIZE) as usize];
+    for axis in 0..3 {
+        // u and v span the slices, in the order that makes u x v point along +axis
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for positive in [false, true] {
+            let mut step = IVec3::ZERO;
+            step[axis] = if positive { 1 } else { -1 };
+            for depth in 0..CHUNK_SIZE {
+                for j in 0..CHUNK_SIZE {
+                    for i in 0..CHUNK_SIZE {
+                        let mut p = IVec3::ZERO;
+                        p[axis] = depth;
+                        p[u] = i;
+                        p[v] = j;
+                        let block = get(p);
+                        mask[(i + j * CHUNK_SIZE) as usize] = if block != Block::Air && get(p + step) == Block::Air { block } else { Block::Air };
+                    }
+                }
+                for j in 0..CHUNK_SIZE {
+                    let mut i = 0;
+                    while i < CHUNK_SIZE {
+                        let block = mask[(i + j * CHUNK_SIZE) as usize];
+                        if block == Block::Air {
+                            i += 1;
+                            continue;
+                        }
+                        let row = |j: i32, i: i32| mask[(i + j * CHUNK_SIZE) as usize];
+                        let mut width = 1;
+                        while i + width < CHUNK_SIZE && row(j, i + width) == block {
+                            width += 1;
+                        }
+                        let mut height = 1;
+                        while j + height < CHUNK_SIZE && (i..i + width).all(|k| row(j + height, k) == block) {
+                            height += 1;
+                        }
+                        for dj in 0..height {
+                            for di in 0..width {
+                                mask[(i + di + (j + dj) * CHUNK_SIZE) as usize] = Block::Air;
+                            }
+                        }
+
+                        let mut base = coord * CHUNK_SIZE;
+                        base[axis] += depth + positive as i32;
+                        base[u] += i;
+                        base[v] += j;
+                        let (mut du, mut dv) = (IVec3::ZERO, IVec3::ZERO);
+                        du[u] = width;
+                        dv[v] = height;
+                        // counter clockwise seen from the side the face is on
+                        let corners = if positive {
+                            [base, base + du, base + du + dv, base + dv]
+                        } else {
+                            [base, base + dv, base + du + dv, base + du]
+                        };
+                        let packed = axis as u32 | (positive as u32) << 2 | (block as u32) << 8;
+                        vertices.extend(corners.map(|corner| VoxelVertex { position: corner.as_vec3().to_array(), packed }));
+                        i += width;
+                    }
+                }
+            }
+        }
+    }
+    vertices
+}
@@ -0,0 +1,48 @@
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "color.wgsl"
+
+// see VoxelVertex
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    // axis in bits 0-1, facing the positive side in bit 2, block from bit 8 up
+    @location(1) packed: u32,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+}
+
+@vertex
+fn vertex(in: VertexIn) -> VertexOut {
+    var normal = vec3<f32>(0.0);
+    normal[in.packed & 3u] = select(-1.0, 1.0, ((in.packed >> 2u) & 1u) == 1u);
+    // indexed by Block, air never gets a face
+    var colors = array<vec3<f32>, 5>(
+        vec3<f32>(1.0, 0.0, 1.0),
+        vec3<f32>(0.25, 0.5, 0.15),
+        vec3<f32>(0.4, 0.27, 0.17),
+        vec3<f32>(0.45, 0.45, 0.45),
+        vec3<f32>(0.8, 0.72, 0.5),
+    );
+    var out: VertexOut;
+    out.pos = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.world_position = in.position;
+    out.normal = normal;
+    out.color = colors[min(in.packed >> 8u, 4u)];
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let color = shade(in.color, in.normal, in.world_position, ambient_occlusion(in.pos.xy));
+    return output_color(vec4<f32>(color, 1.0));
+}
+
+@fragment
+fn fragment_normal(in: VertexOut) -> @location(0) vec4<f32> {
+    return view_normal_depth(in.normal, in.world_position);
+}
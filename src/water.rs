@@ -0,0 +1,219 @@
+use std::mem::size_of;
+use glam::Vec2;
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::bindings::{BindGroupBuilder, Binding, LayoutRegistry};
+use crate::camera::FRAME_LAYOUT;
+use crate::context::RenderContext;
+use crate::preprocessor::Preprocessor;
+use crate::renderer::{DEPTH_FORMAT, HDR_FORMAT};
+use crate::ssao::NORMAL_DEPTH_FORMAT;
+
+pub const WATER_LAYOUT: &str = "water";
+// matches the array size in water.wgsl
+pub const MAX_WAVES: usize = 4;
+
+// a gerstner wave, the surface moving in circles so crests sharpen and troughs flatten
+#[derive(Copy, Clone, Debug)]
+pub struct WaterWave {
+    // which way it travels, along the water's plane
+    pub direction: Vec2,
+    // 0 is a sine wave, 1 is as sharp as it gets. the waves' total above 1 makes crests loop
+    pub steepness: f32,
+    // crest to crest in world units, 0 turns the wave off. the speed follows from it
+    pub wavelength: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct WaterSettings {
+    pub enabled: bool,
+    // of the water at rest
+    pub height: f32,
+    // world units along each side of the square, centered on the origin
+    pub size: f32,
+    // quads along each side, the waves are displaced per vertex
+    pub resolution: u32,
+    pub waves: [WaterWave; MAX_WAVES],
+    // linear, what the water looks like where it doesn't reflect
+    pub color: [f32; 3],
+    // looking straight down, grazing angles go towards 1
+    pub reflectivity: f32,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        let wave = |x, z, steepness, wavelength| WaterWave {
+            direction: Vec2::new(x, z),
+            steepness,
+            wavelength,
+        };
+        Self {
+            enabled: false,
+            height: -0.5,
+            size: 200.0,
+            resolution: 256,
+            waves: [
+                wave(1.0, 0.0, 0.25, 12.0),
+                wave(0.7, 0.7, 0.2, 7.0),
+                wave(0.2, 1.0, 0.15, 4.0),
+                wave(-0.6, 0.8, 0.1, 2.5),
+            ],
+            color: [0.02, 0.08, 0.1],
+            reflectivity: 0.02,
+        }
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct WaterUniform {
+    // direction xy, steepness, wavelength
+    waves: [[f32; 4]; MAX_WAVES],
+    color: [f32; 4],
+    horizon: [f32; 4],
+    height: f32,
+    time: f32,
+    size: f32,
+    resolution: u32,
+    wave_count: u32,
+    reflectivity: f32,
+    _padding: [f32; 2],
+}
+
+// an animated plane of water, its waves displaced in the vertex shader. it reflects the sky, or
+// the clear color without it, more at grazing angles. it's in the ssao prepass like meshes, so
+// screen space reflections pick up the scene on it
+pub struct Water {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    index_buffer: Buffer,
+    resolution: u32,
+    // seconds the waves have moved for
+    time: f32,
+    render_pipeline: RenderPipeline,
+    normal_pipeline: RenderPipeline,
+}
+
+impl Water {
+    // FRAME_LAYOUT has to be registered already, see CameraBinding
+    pub fn new(context: &RenderContext, layouts: &mut LayoutRegistry) -> Self {
+        let device = &context.device;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("water"),
+            size: size_of::<WaterUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        layouts.register(context, WATER_LAYOUT, &[
+            (Binding::Uniform, ShaderStages::VERTEX_FRAGMENT),
+        ]);
+        let bind_group = BindGroupBuilder::new()
+            .buffer(&buffer)
+            .build(context, layouts, WATER_LAYOUT);
+
+        let shader_module = Preprocessor::new().target(HDR_FORMAT).create_module(context, "water.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("water"),
+            bind_group_layouts: &[layouts.get(FRAME_LAYOUT), layouts.get(WATER_LAYOUT)],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label, fragment_entry, format: TextureFormat| device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                entry_point: "vertex",
+                module: &shader_module,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                entry_point: fragment_entry,
+                module: &shader_module,
+                targets: &[
+                    Some(format.into())
+                ],
+            }),
+            // seen from below as well
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+        let render_pipeline = create_pipeline("water", "fragment", HDR_FORMAT);
+        let normal_pipeline = create_pipeline("water normals", "fragment_normal", NORMAL_DEPTH_FORMAT);
+        let resolution = WaterSettings::default().resolution;
+        Self {
+            buffer,
+            bind_group,
+            index_buffer: create_index_buffer(context, resolution),
+            resolution,
+            time: 0.0,
+            render_pipeline,
+            normal_pipeline,
+        }
+    }
+
+    // moves the waves on by delta seconds. horizon is reflected while the sky is off
+    pub fn update(&mut self, context: &RenderContext, settings: &WaterSettings, delta: f32, horizon: Color) {
+        let resolution = settings.resolution.clamp(1, 1024);
+        if resolution != self.resolution {
+            self.index_buffer = create_index_buffer(context, resolution);
+            self.resolution = resolution;
+        }
+        self.time += delta;
+        let mut uniform = WaterUniform {
+            waves: [[0.0; 4]; MAX_WAVES],
+            color: [settings.color[0], settings.color[1], settings.color[2], 1.0],
+            horizon: [horizon.r as f32, horizon.g as f32, horizon.b as f32, 1.0],
+            height: settings.height,
+            time: self.time,
+            size: settings.size,
+            resolution,
+            wave_count: 0,
+            reflectivity: settings.reflectivity.clamp(0.0, 1.0),
+            _padding: [0.0; 2],
+        };
+        let waves = settings.waves.iter().filter(|wave| wave.wavelength > 0.0 && wave.direction != Vec2::ZERO);
+        for (wave, gpu) in waves.zip(&mut uniform.waves) {
+            *gpu = [wave.direction.x, wave.direction.y, wave.steepness.clamp(0.0, 1.0), wave.wavelength];
+            uniform.wave_count += 1;
+        }
+        context.queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn draw<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        self.draw_with(render_cmd, camera_bind_group, &self.render_pipeline);
+    }
+
+    // view space normals and depth for ssao and ssr, see Ssao::normal_depth_view
+    pub fn draw_normals<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        self.draw_with(render_cmd, camera_bind_group, &self.normal_pipeline);
+    }
+
+    fn draw_with<'a>(&'a self, render_cmd: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup, pipeline: &'a RenderPipeline) {
+        render_cmd.set_pipeline(pipeline);
+        render_cmd.set_bind_group(0, camera_bind_group, &[]);
+        render_cmd.set_bind_group(1, &self.bind_group, &[]);
+        render_cmd.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
+        render_cmd.draw_indexed(0..self.resolution * self.resolution * 6, 0, 0..1);
+    }
+}
+
+// two triangles per quad of a grid with resolution + 1 vertices a side, row by row
+fn create_index_buffer(context: &RenderContext, resolution: u32) -> Buffer {
+    let side = resolution + 1;
+    let indices: Vec<u32> = (0..resolution)
+        .flat_map(|z| (0..resolution).map(move |x| z * side + x))
+        .flat_map(|corner| [corner, corner + side, corner + 1, corner + 1, corner + side, corner + side + 1])
+        .collect();
+    context.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("water"),
+        contents: bytemuck::cast_slice(&indices),
+        usage: BufferUsages::INDEX,
+    })
+}
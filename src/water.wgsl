@@ -0,0 +1,88 @@
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "color.wgsl"
+
+// one gerstner wave, see WaterWave
+struct Wave {
+    direction: vec2<f32>,
+    steepness: f32,
+    wavelength: f32,
+}
+
+// see WaterUniform
+struct Water {
+    waves: array<Wave, MAX_WAVES>,
+    color: vec4<f32>,
+    // what's reflected while the sky is off
+    horizon: vec4<f32>,
+    height: f32,
+    time: f32,
+    size: f32,
+    // quads along each side of the grid
+    resolution: u32,
+    wave_count: u32,
+    reflectivity: f32,
+    _padding: vec2<f32>,
+}
+
+@group(1) @binding(0) var<uniform> water: Water;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+// a grid of resolution quads a side, displaced by every wave. the normal comes from the
+// displaced surface's derivatives, summed up per wave
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOut {
+    let side = water.resolution + 1u;
+    let cell = vec2<f32>(f32(index % side), f32(index / side)) / f32(water.resolution);
+    let flat_position = (cell - 0.5) * water.size;
+    var position = vec3<f32>(flat_position.x, water.height, flat_position.y);
+    var normal = vec3<f32>(0.0, 1.0, 0.0);
+    for (var i = 0u; i < water.wave_count; i = i + 1u) {
+        let wave = water.waves[i];
+        let k = 6.28318530718 / wave.wavelength;
+        // deep water dispersion, longer waves travel faster
+        let speed = sqrt(9.8 / k);
+        let direction = normalize(wave.direction);
+        let phase = k * (dot(direction, flat_position) - speed * water.time);
+        let amplitude = wave.steepness / k;
+        position = position + vec3<f32>(direction.x * amplitude * cos(phase), amplitude * sin(phase), direction.y * amplitude * cos(phase));
+        normal = normal - vec3<f32>(direction.x * wave.steepness * cos(phase), wave.steepness * sin(phase), direction.y * wave.steepness * cos(phase));
+    }
+    var out: VertexOut;
+    out.pos = camera.view_proj * vec4<f32>(position, 1.0);
+    out.world_position = position;
+    out.normal = normal;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOut) -> @location(0) vec4<f32> {
+    let normal = normalize(in.normal);
+    let to_eye = normalize(camera.position.xyz - in.world_position);
+    let reflected = reflect(-to_eye, normal);
+    var environment = water.horizon.rgb;
+    if (lights.atmosphere.sky != 0u) {
+        // below the horizon would be the sky's dark ground
+        environment = sky(vec3<f32>(reflected.x, abs(reflected.y), reflected.z));
+    }
+    // schlick's approximation, water reflects a few percent looking straight down
+    let facing = clamp(dot(to_eye, normal), 0.0, 1.0);
+    let fresnel = mix(water.reflectivity, 1.0, pow(1.0 - facing, 5.0));
+    let body = water.color.rgb * surface_light(normal, in.world_position, ambient_occlusion(in.pos.xy));
+    // the sun's glint
+    let sun = lights.lights[0];
+    let half_vector = normalize(to_eye - sun.direction);
+    let glint = sun.color.rgb * sun.intensity * pow(max(dot(normal, half_vector), 0.0), 400.0) * 4.0;
+    let color = fog(mix(body, environment, fresnel) + glint, in.world_position);
+    return output_color(vec4<f32>(color, 1.0));
+}
+
+@fragment
+fn fragment_normal(in: VertexOut) -> @location(0) vec4<f32> {
+    return view_normal_depth(normalize(in.normal), in.world_position);
+}
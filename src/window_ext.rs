@@ -0,0 +1,308 @@
+use std::fmt;
+use std::str::FromStr;
+use winit::dpi::PhysicalSize;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::window::{BadIcon, Fullscreen, Icon, UserAttentionType, Window};
+
+// the default window icon, a 32x32 rgb triangle
+pub const ICON_PNG: &[u8] = include_bytes!("icon.png");
+// how often the fps in the title changes, any faster and it can't be read
+const TITLE_INTERVAL: f32 = 0.5;
+
+#[derive(Debug)]
+pub enum WindowExtError {
+    Image(image::ImageError),
+    Icon(BadIcon),
+    // a fullscreen mode that doesn't parse, see FullscreenRequest
+    Mode(String),
+}
+
+impl fmt::Display for WindowExtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowExtError::Image(error) => error.fmt(f),
+            WindowExtError::Icon(error) => error.fmt(f),
+            WindowExtError::Mode(mode) => write!(f, "{mode} isn't borderless, exclusive or <width>x<height>[@<hz>]"),
+        }
+    }
+}
+
+impl std::error::Error for WindowExtError {}
+
+impl From<image::ImageError> for WindowExtError {
+    fn from(error: image::ImageError) -> Self {
+        WindowExtError::Image(error)
+    }
+}
+
+impl From<BadIcon> for WindowExtError {
+    fn from(error: BadIcon) -> Self {
+        WindowExtError::Icon(error)
+    }
+}
+
+// decodes a png, or anything else the image crate was built with, into a window icon
+pub fn icon_from_png(png: &[u8]) -> Result<Icon, WindowExtError> {
+    let image = image::load_from_memory(png)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+}
+
+// ICON_PNG. platforms without window icons (macos, wayland, mobile) ignore it
+pub fn default_icon() -> Option<Icon> {
+    icon_from_png(ICON_PNG)
+        .map_err(|error| tracing::warn!("failed to load the window icon: {error}"))
+        .ok()
+}
+
+// keeps the window title as `<title> - <fps> fps (<ms> ms)`, averaged over the last half second
+pub struct FpsTitle {
+    pub title: String,
+    frames: u32,
+    elapsed: f32,
+}
+
+impl FpsTitle {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self { title: title.into(), frames: 0, elapsed: 0.0 }
+    }
+
+    // call once a frame with its delta in seconds
+    pub fn update(&mut self, window: &Window, delta: f32) {
+        self.frames += 1;
+        self.elapsed += delta;
+        if self.elapsed < TITLE_INTERVAL {
+            return;
+        }
+        let frame_time = self.elapsed / self.frames as f32;
+        window.set_title(&format!("{} - {:.0} fps ({:.2} ms)", self.title, 1.0 / frame_time, frame_time * 1000.0));
+        self.frames = 0;
+        self.elapsed = 0.0;
+    }
+}
+
+// what to ask for when going fullscreen. monitors are numbered in the order the platform lists
+// them, see monitor_report, with none meaning the primary one
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FullscreenRequest {
+    pub monitor: Option<usize>,
+    // switches the monitor's video mode. otherwise the window covers the monitor at the desktop
+    // resolution, which is also what's used when no mode matches
+    pub exclusive: bool,
+    // the mode to switch to, the biggest one when none
+    pub size: Option<PhysicalSize<u32>>,
+    // in hz, the fastest the size allows when none
+    pub refresh_rate: Option<u32>,
+}
+
+// borderless, exclusive, or a mode to go exclusive in as <width>x<height>[@<hz>]
+impl FromStr for FullscreenRequest {
+    type Err = WindowExtError;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        let invalid = || WindowExtError::Mode(mode.to_string());
+        match mode {
+            "borderless" => return Ok(Self::default()),
+            "exclusive" => return Ok(Self { exclusive: true, ..Self::default() }),
+            _ => {}
+        }
+        let (size, refresh_rate) = match mode.split_once('@') {
+            Some((size, rate)) => (size, Some(rate.trim_end_matches("hz").parse().map_err(|_| invalid())?)),
+            None => (mode, None),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let width = width.parse().map_err(|_| invalid())?;
+        let height = height.parse().map_err(|_| invalid())?;
+        Ok(Self { monitor: None, exclusive: true, size: Some(PhysicalSize::new(width, height)), refresh_rate })
+    }
+}
+
+impl fmt::Display for FullscreenRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.exclusive {
+            return write!(f, "borderless");
+        }
+        match self.size {
+            Some(size) => write!(f, "{}x{}", size.width, size.height)?,
+            None => write!(f, "exclusive")?,
+        }
+        if let Some(rate) = self.refresh_rate {
+            write!(f, "@{rate}")?;
+        }
+        Ok(())
+    }
+}
+
+// resolves a request against the monitors there are, falling back to the primary monitor when
+// the asked for one doesn't exist and to borderless when there's no matching mode. wayland has
+// no exclusive fullscreen and winit treats it as borderless there
+pub fn fullscreen(request: &FullscreenRequest, monitors: impl IntoIterator<Item = MonitorHandle>, primary: Option<MonitorHandle>) -> Fullscreen {
+    let monitors: Vec<_> = monitors.into_iter().collect();
+    // wayland has no primary monitor
+    let primary = primary.or_else(|| monitors.first().cloned());
+    let monitor = match request.monitor {
+        Some(index) => monitors.get(index).cloned().or_else(|| {
+            tracing::warn!("there's no monitor {index}, there are {}", monitors.len());
+            primary
+        }),
+        None => primary,
+    };
+    if !request.exclusive {
+        return Fullscreen::Borderless(monitor);
+    }
+    let Some(monitor) = monitor else {
+        tracing::warn!("no monitor to go {request} fullscreen on, going borderless");
+        return Fullscreen::Borderless(None);
+    };
+    match video_mode(&monitor, request) {
+        Some(mode) => {
+            tracing::info!("fullscreen at {}", describe_mode(&mode));
+            Fullscreen::Exclusive(mode)
+        }
+        None => {
+            tracing::warn!("{} has no {request} mode, going borderless", monitor.name().unwrap_or_default());
+            Fullscreen::Borderless(Some(monitor))
+        }
+    }
+}
+
+fn video_mode(monitor: &MonitorHandle, request: &FullscreenRequest) -> Option<VideoMode> {
+    monitor.video_modes()
+        .filter(|mode| request.size.is_none_or(|size| mode.size() == size))
+        // rounded so 59.94 counts as 60
+        .filter(|mode| request.refresh_rate.is_none_or(|rate| (mode.refresh_rate_millihertz() + 500) / 1000 == rate))
+        .max_by_key(|mode| (mode.size().width * mode.size().height, mode.refresh_rate_millihertz(), mode.bit_depth()))
+}
+
+fn describe_mode(mode: &VideoMode) -> String {
+    let size = mode.size();
+    format!("{}x{}@{:.2}, {} bit", size.width, size.height, mode.refresh_rate_millihertz() as f32 / 1000.0, mode.bit_depth())
+}
+
+// every monitor with its index and video modes, biggest and fastest first
+pub fn monitor_report<T>(target: &EventLoopWindowTarget<T>) -> String {
+    let primary = target.primary_monitor();
+    let mut report = String::new();
+    for (index, monitor) in target.available_monitors().enumerate() {
+        let size = monitor.size();
+        let primary = if primary.as_ref() == Some(&monitor) { " (primary)" } else { "" };
+        report += &format!("{index}: {}{primary}, {}x{}, scale {}\n", monitor.name().unwrap_or_default(), size.width, size.height, monitor.scale_factor());
+        let mut modes: Vec<_> = monitor.video_modes().collect();
+        modes.sort_by_key(|mode| std::cmp::Reverse((mode.size().width * mode.size().height, mode.refresh_rate_millihertz(), mode.bit_depth())));
+        for mode in modes {
+            report += &format!("    {}\n", describe_mode(&mode));
+        }
+    }
+    report
+}
+
+// values are 0..1
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TaskbarProgress {
+    None,
+    // busy without knowing for how long
+    Indeterminate,
+    Normal(f32),
+    Paused(f32),
+    Error(f32),
+}
+
+// the window's taskbar button. progress is only shown on windows with the taskbar feature,
+// everywhere else it's kept track of and nothing more. flashing goes through winit so it works
+// wherever the platform has something like it, e.g. bouncing the dock icon on macos
+pub struct Taskbar {
+    #[cfg(all(windows, feature = "taskbar"))]
+    list: Option<windows_taskbar::TaskbarList>,
+    progress: TaskbarProgress,
+}
+
+impl Taskbar {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(all(windows, feature = "taskbar"))]
+            list: windows_taskbar::TaskbarList::new()
+                .map_err(|error| tracing::warn!("taskbar unavailable: {error}"))
+                .ok(),
+            progress: TaskbarProgress::None,
+        }
+    }
+
+    pub fn progress(&self) -> TaskbarProgress {
+        self.progress
+    }
+
+    // cheap to call every frame, the shell is only told about changes
+    pub fn set_progress(&mut self, window: &Window, progress: TaskbarProgress) {
+        if progress == self.progress {
+            return;
+        }
+        self.progress = progress;
+        #[cfg(all(windows, feature = "taskbar"))]
+        if let Some(list) = &self.list {
+            if let Err(error) = list.set(window, progress) {
+                tracing::warn!("failed to set the taskbar progress: {error}");
+            }
+        }
+        #[cfg(not(all(windows, feature = "taskbar")))]
+        let _ = window;
+    }
+
+    // flashes the button until the window is focused. does nothing while it already is
+    pub fn flash(&self, window: &Window) {
+        window.request_user_attention(Some(UserAttentionType::Informational));
+    }
+}
+
+impl Default for Taskbar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(windows, feature = "taskbar"))]
+mod windows_taskbar {
+    use windows::core::Result;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED};
+    use windows::Win32::UI::Shell::{ITaskbarList3, TaskbarList as TASKBAR_LIST, TBPF_ERROR, TBPF_INDETERMINATE, TBPF_NOPROGRESS, TBPF_NORMAL, TBPF_PAUSED};
+    use winit::platform::windows::WindowExtWindows;
+    use winit::window::Window;
+    use super::TaskbarProgress;
+
+    // the shell takes whole numbers out of a total
+    const STEPS: u64 = 1000;
+
+    pub struct TaskbarList(ITaskbarList3);
+
+    impl TaskbarList {
+        pub fn new() -> Result<Self> {
+            unsafe {
+                // winit has usually initialized com on this thread already for drag and drop,
+                // which makes this a no-op
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                let list: ITaskbarList3 = CoCreateInstance(&TASKBAR_LIST, None, CLSCTX_INPROC_SERVER)?;
+                list.HrInit()?;
+                Ok(Self(list))
+            }
+        }
+
+        pub fn set(&self, window: &Window, progress: TaskbarProgress) -> Result<()> {
+            let hwnd = HWND(window.hwnd());
+            let (state, value) = match progress {
+                TaskbarProgress::None => (TBPF_NOPROGRESS, None),
+                TaskbarProgress::Indeterminate => (TBPF_INDETERMINATE, None),
+                TaskbarProgress::Normal(value) => (TBPF_NORMAL, Some(value)),
+                TaskbarProgress::Paused(value) => (TBPF_PAUSED, Some(value)),
+                TaskbarProgress::Error(value) => (TBPF_ERROR, Some(value)),
+            };
+            unsafe {
+                self.0.SetProgressState(hwnd, state)?;
+                if let Some(value) = value {
+                    self.0.SetProgressValue(hwnd, (value.clamp(0.0, 1.0) * STEPS as f32) as u64, STEPS)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
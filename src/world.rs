@@ -0,0 +1,86 @@
+use std::sync::Arc;
+use crate::animation::AnimationPlayer;
+use crate::camera::Camera;
+use crate::mesh::Material;
+use crate::model::Model;
+use crate::render_target::RenderTarget;
+use crate::transform::Transform;
+use crate::viewport::Viewport;
+
+pub use hecs::{Entity, Without, World};
+
+// the model an entity draws. entities can share a model, gpu resources are still per entity
+// since each one has its own transform and pose. pointing it at a different model rebuilds them
+#[derive(Clone)]
+pub struct MeshRef(pub Arc<Model>);
+
+impl MeshRef {
+    pub fn new(model: Model) -> Self {
+        Self(Arc::new(model))
+    }
+}
+
+// what an entity is called in scene files. scenes match entities by it when reloading
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Name(pub String);
+
+// the transform as of the previous fixed step. the renderer blends from it to Transform by the
+// frame's interpolation alpha, so movement done in fixed steps looks smooth at any frame rate
+#[derive(Copy, Clone, Debug)]
+pub struct PreviousTransform(pub Transform);
+
+// a model with the components the renderer looks for. add an AnimationPlayer to animate it
+pub fn spawn_model(world: &mut World, model: MeshRef, transform: Transform, material: Material) -> Entity {
+    world.spawn((model, transform, material))
+}
+
+// despawning is just removing the entity, the renderer frees its gpu resources on the next update
+pub fn despawn(world: &mut World, entity: Entity) {
+    let _ = world.despawn(entity);
+}
+
+// the main view's camera, the one with the lowest viewport order. cameras without a viewport
+// count as order 0, cameras drawing into a RenderTarget aren't views
+pub fn camera_entity(world: &World) -> Option<Entity> {
+    world.query::<Without<(&Camera, Option<&Viewport>), &RenderTarget>>().iter()
+        .min_by_key(|(_, (_, viewport))| viewport.map_or(0, |viewport| viewport.order))
+        .map(|(entity, _)| entity)
+}
+
+// starts a clip if the model has it, stops the player otherwise
+pub fn play(world: &mut World, entity: Entity, clip: Option<usize>) {
+    let clip_count = match world.get::<&MeshRef>(entity) {
+        Ok(mesh) => mesh.0.animations.len(),
+        Err(_) => return,
+    };
+    if world.get::<&AnimationPlayer>(entity).is_err() {
+        let _ = world.insert_one(entity, AnimationPlayer::new());
+    }
+    if let Ok(mut player) = world.get::<&mut AnimationPlayer>(entity) {
+        match clip {
+            Some(clip) if clip < clip_count => player.play(clip, true),
+            _ => player.stop(),
+        }
+    }
+}
+
+// run before every fixed step
+pub fn snapshot_transforms(world: &mut World) {
+    let mut missing = Vec::new();
+    for (entity, (transform, previous)) in world.query_mut::<(&Transform, Option<&mut PreviousTransform>)>() {
+        match previous {
+            Some(previous) => previous.0 = *transform,
+            None => missing.push((entity, *transform)),
+        }
+    }
+    for (entity, transform) in missing {
+        let _ = world.insert_one(entity, PreviousTransform(transform));
+    }
+}
+
+// animations are simulation, so they advance in fixed steps too
+pub fn advance_animations(world: &mut World, step: f32) {
+    for (_, (model, player)) in world.query_mut::<(&MeshRef, &mut AnimationPlayer)>() {
+        player.update(step, &model.0.animations);
+    }
+}
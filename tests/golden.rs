@@ -0,0 +1,25 @@
+use std::process::Command;
+use dumb_wgpu_example::golden;
+
+// renders every scene in scenes/ in a hidden window through the example's --golden mode and
+// checks it against golden/<name>.png. needs a gpu, a display and a backend context.rs picks for
+// the platform, so it only runs with `cargo test -- --ignored`. references are written with
+// --update-golden, failed frames and their diffs end up in target/golden
+#[test]
+#[ignore = "needs a gpu and a window"]
+fn scenes_match_references() {
+    let scenes = golden::scenes();
+    assert!(!scenes.is_empty(), "no scenes to check");
+    let failed: Vec<String> = scenes.iter()
+        .filter(|scene| {
+            let status = Command::new(env!("CARGO_BIN_EXE_dumb-wgpu-example"))
+                .arg("--golden-scene")
+                .arg(scene)
+                .status()
+                .expect("failed to run the example");
+            !status.success()
+        })
+        .map(|scene| scene.display().to_string())
+        .collect();
+    assert!(failed.is_empty(), "differ from their reference: {}", failed.join(", "));
+}